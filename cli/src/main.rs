@@ -0,0 +1,1105 @@
+//! Command line harness for running the strategies over corpus files.
+
+extern crate xi_der_corpus as corpus;
+extern crate xi_der_harness;
+extern crate xi_der_strategies;
+#[macro_use]
+extern crate tracing;
+extern crate tracing_subscriber;
+#[cfg(feature = "chrome-trace")]
+extern crate tracing_chrome;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{self, Command};
+use std::time::Duration;
+
+use corpus::Direction;
+use corpus::generate::{self, Preset};
+use xi_der_harness::{breakdown, builds, codegen, config, estimate, external, inspect, latency,
+                      opt_matrix, pgo, pipeline, report, runner, transport};
+use xi_der_harness::runner::{Budget, Cache};
+use xi_der_harness::transport::Transport;
+#[cfg(feature = "perf")]
+use xi_der_harness::counters;
+#[cfg(feature = "history")]
+use xi_der_harness::history::{self, History, RunInfo};
+use xi_der_strategies::{coverage, diagnostics, mutate, strategy, validate, wire};
+
+#[cfg(feature = "history")]
+const DEFAULT_HISTORY: &str = "der_bench_history.sqlite";
+
+/// Event filter directives for logging to stderr, in `RUST_LOG` syntax.
+const LOG_ENV: &str = "DER_BENCH_LOG";
+
+const USAGE: &str = "usage: der_bench [--trace FILE] <command> [options]
+
+commands:
+    run <corpus> [--iterations N | --max-time SECONDS] [--strategy NAME]
+        [--cache hot|cold|flushed] [--output FILE] [--history PATH]
+        [--reruns N] [--external COMMAND]... [--opt-matrix LEVELS
+        [--features LIST]] [--framing lines|braces] [--by-method PASSES]
+        [--slowest N]
+        time each strategy (or just NAME) over <corpus>. With --max-time,
+        each strategy runs for that long instead of a fixed number of
+        passes. Defaults to 100 passes. With --cache cold, the CPU caches
+        are evicted before each pass; flushed also flushes the corpus
+        itself, on x86_64 Linux. On Linux, peak resident memory above
+        where each run started is reported too. With --output, a JSON report
+        including toolchain and machine details is written to FILE. With
+        the history feature, results are recorded in PATH (default
+        der_bench_history.sqlite). Each run is timed in batches, and the
+        spread of the batches is reported (±%), along with how much slower
+        the last were than the first (drift%). A run that slowed down by
+        more than 10% is warned about, and with --reruns, run again, up to
+        N times. The strategies are those for the direction in <corpus>'s
+        entry, if it has one that isn't client to core. Each --external
+        COMMAND is a parser in another language, run over the same corpus
+        for the same number of passes (so it needs --iterations, if
+        anything) and reported beside the strategies; see
+        harness/src/external.rs, and harness/external for runners using
+        Python's json and Node's JSON.parse. With --opt-matrix, der_bench
+        is rebuilt with cargo at each of the comma-separated opt-levels in
+        LEVELS (such as 1,2,3), with and without LTO, and with the cargo
+        features in LIST, and each build is run with the other options;
+        the results are collected into a table of ns/message and rank for
+        each build, or with --output, a JSON file. The source tree der_bench
+        was built from has to still be there. With --framing braces, the
+        messages in <corpus> can span lines, as a client that pretty-prints
+        them writes them, each ending where its braces balance; see
+        corpus/src/framing.rs. That can't be given to --external. With
+        --by-method, each strategy is then run over <corpus> PASSES more
+        times, timing each message on its own, and its time is broken down
+        by method, the most expensive first; see harness/src/breakdown.rs.
+        With --slowest, the N messages each strategy took longest over are
+        shown, from those timings, or from 10 passes without --by-method.
+        The timings are followed by what each strategy is like besides
+        fast: whether it uses unsafe code, parses without allocating, and
+        borrows from the input, and how much of it is written by hand
+        (maintainability), as the JSON report records too.
+
+    pgo <training corpus> <corpus> [--features LIST] [--output FILE]
+        [run options]
+        build der_bench with cargo instrumented for profile-guided
+        optimization, with the cargo features in LIST, and run it over
+        <training corpus>; then rebuild it with the profile, and run it and
+        a plain release build over <corpus>, with the other options, as
+        --opt-matrix does. Needs llvm-profdata from rustc's LLVM; see
+        harness/src/pgo.rs, and harness/scripts/pgo_bench.sh for the
+        benches.
+
+    inspect [--strategy NAME] [--features LIST] [--out DIR]
+        build the strategies crate with cargo, in release mode with the
+        cargo features in LIST, emitting assembly and LLVM IR, and write
+        the functions each strategy (or just NAME) was compiled to, its
+        parse_into and, for those that deserialize into one type, that
+        type's Deserialize impl, to STRATEGY.s and STRATEGY.ll in DIR
+        (default der_bench_inspect). See harness/src/inspect.rs.
+
+    dispatch <corpus> [--iterations N | --max-time SECONDS]
+        time the fastest strategies over <corpus> as run does, through a
+        trait object, and again with the harness monomorphized for each,
+        to show what the virtual calls in run cost them.
+
+    matrix [CONFIG]
+        run each strategy over each corpus described in CONFIG (default
+        bench.toml), with each cache mode and number of threads it lists,
+        and print and/or write a JSON report for each corpus. See
+        harness/src/config.rs for the format.
+
+    directions [--iterations N | --max-time SECONDS]
+        time serde's parsing of the built-in corpora of each protocol
+        direction: client to core (c2s), core to client (s2c), plugin to
+        core (p2c) and core to plugin (c2p), grouped by direction.
+
+    estimate <corpus> [--calibrate CORPUS] [--strategy NAME] [--passes N]
+        [--iterations N | --max-time SECONDS]
+        predict each strategy's (or just NAME's) time per pass over
+        <corpus> from how many messages of each type it has and how long
+        they are, with a cost per message and per byte for each type, fitted
+        by timing each line of CORPUS (default the built-in corpora of
+        <corpus>'s direction) N times on its own (default 100). Then time a
+        run over <corpus>, as run does, and show how far off the prediction
+        was.
+
+    trend [--history PATH] [--corpus CORPUS] [--strategy NAME]
+        print recorded results over time, optionally only those for CORPUS
+        and/or strategy NAME. Requires the history feature.
+
+    mutate <corpus> [--count N] [--seed N]
+        apply structured mutations to each line of <corpus>, and report how
+        each strategy handles them.
+
+    batch <corpus> <output> [--size N]
+        coalesce the messages in <corpus> into JSON-RPC batches of up to N
+        messages each (default 8), writing them to <output>.
+
+    corpus shuffle <corpus> <output> [--seed N]
+    corpus interleave <output> <corpus>... [--seed N]
+        reorder <corpus>, or merge several corpora, at random but
+        reproducibly (with --seed, default 1), writing the result to
+        <output>. Each view's messages stay in order, and after the
+        new_view that created the view; views that two corpora share are
+        renamed in the later one.
+
+    corpus generate <preset> <output> [--messages N] [--seed N] [--depth N]
+        generate N messages (default 5000) of the kind of traffic <preset>
+        models, reproducibly (with --seed, default 1), writing them to
+        <output>. Presets:
+            cursor_burst    long runs of move_* notifications, as from key
+                            auto-repeat
+            scroll_storm    a scroll and a request_lines per frame, as from
+                            momentum scrolling on a trackpad
+            file_session    new_view, save and close_view for files with
+                            long, often non-ASCII, absolute paths
+            deep_nesting    plugin RPCs with params nested --depth levels
+                            deep (default 200), past serde_json's limit
+            plugin_session  what core sends a plugin while a file is
+                            edited: initialize, then an update for each
+                            edit (validate with --direction c2p)
+
+    corpus chunk <corpus> <output> [--size KB]
+        split each insert in <corpus> longer than KB kilobytes (default 16)
+        into inserts of at most that many, sharing an undo group, writing
+        the result to <output>. The reassemble strategy puts them back
+        together when given several at once; pipeline over the result
+        shows the latency of each chunk next to that of the whole paste.
+
+    corpus validate <corpus> [--direction c2s|s2c|p2c|c2p] [--max N]
+        check that each line of <corpus> is a message sent in the given
+        direction (default c2s), or a batch of them, as rpc2's types have
+        it, and show where up to N lines (default 20) that aren't went
+        wrong: the line and column, the error, and the text around it.
+        Fails if any line is invalid, so that a corpus can be checked
+        before it's benchmarked.
+
+    stream <corpus> [--transport pipe|unix|tcp|shm] [--passes N] [--strategy NAME]
+        time each strategy (or just NAME) over N passes of <corpus>
+        (default 100), streamed from another process over a pipe (the
+        default, as xi uses), a Unix domain socket, or a localhost TCP
+        socket. With the shm feature, shm is a ring buffer in shared
+        memory, parsed in place.
+
+    pipeline <corpus> [--passes N] [--channel NAME]
+        parse N passes of <corpus> (default 100) on one thread and dispatch
+        on another, through a queue, and report the latency from reading
+        each message to dispatching it. rpc2's messages are parsed before
+        they're queued; the borrowing strategies are sent the line. This is
+        done with each kind of queue (or just NAME): std's sync_channel,
+        crossbeam's bounded and unbounded channels (crossbeam and
+        crossbeam_unbounded), and with the features of the same names,
+        flume's bounded channel and a ringbuf ring.
+
+    latency [--strategy NAME] [--message FILE] [--p99 MICROSECONDS]
+        [--samples N]
+        parse a keystroke (or the first line of FILE) N times (default
+        100000) with each strategy pipeline runs (or just NAME), timing
+        each parse on its own, and fail if the 99th percentile is over the
+        budget (default 5µs). Meant for release builds on CI machines; see
+        harness/src/latency.rs.
+
+    counters <corpus> [--passes N] [--strategy NAME]
+        count cycles, instructions, and instruction cache and iTLB misses
+        per message for each strategy (or just NAME) over N passes of
+        <corpus> (default 100). Requires the perf feature, and Linux.
+
+    coverage
+        parse one message of every rpc2 variant with each strategy, and
+        list the ones each strategy doesn't handle.
+
+    errors [--strategy NAME] [--output FILE]
+        feed a fixed set of malformed messages to each strategy (or just
+        NAME), and show the errors they produce side by side. With
+        --output, the comparison is also written to FILE as Markdown.
+
+    wire <corpus> [--examples N] [--compare exact|canonical]
+        write each notification in <corpus> back out with each module that
+        can serialize one, and report how each output differs from rpc2's:
+        members in another order, missing or extra, nulls left out or
+        written, and numbers or escapes written another way, with up to N
+        examples of each (default 3). With --compare canonical, both are
+        rewritten with sorted keys and one way of writing each number and
+        string first, leaving only the differences in what was written.
+
+    generate <description> [--out DIR]
+        write modules of serde types for the protocol in <description>,
+        with owned strings, borrowed ones and Cows, each with a strategy
+        that parses into them, as NAME_owned.rs, NAME_borrowed.rs and
+        NAME_cow.rs in DIR (default the current directory). They're meant
+        for strategies/src, with a `pub mod` in lib.rs, and their
+        `Generated` strategies added to strategy::all(). See
+        harness/src/codegen.rs for the format, and
+        strategies/protocols/core.toml.
+
+    export-schema <description> [--output FILE]
+        print a JSON Schema for what the types generated from the protocol
+        in <description> accept, or write it to FILE, for checking what a
+        frontend in another language sends. strategies/protocols has
+        descriptions of rpc2's notifications (core.toml) and requests
+        (core_requests.toml).
+
+a <corpus> of - is read from stdin, to the end, before anything is timed,
+so that traffic can be piped in as it's captured, as from xi-core
+--log-rpc. corpora ending in .gz, .zst or .lz4 are read and written
+compressed, with the gzip, zstd and lz4 features. A corpus with an entry in
+a corpus.toml beside it is checked against the entry when it's read, and
+reports include the entry.
+
+the harness logs a span for each strategy it runs, and each batch of
+passes. Set DER_BENCH_LOG (as RUST_LOG, e.g. info or xi_der_harness=trace)
+to print them to stderr. With the chrome-trace feature, --trace FILE writes
+them to FILE as a Chrome trace, for chrome://tracing or Perfetto.";
+
+fn main() {
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    let trace = if args.first().map(String::as_str) == Some("--trace") && args.len() > 1 {
+        args.drain(..2).nth(1)
+    } else {
+        None
+    };
+    let guard = match init_tracing(trace) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let result = match args.first().map(String::as_str) {
+        Some("run") => cmd_run(&args[1..]),
+        Some("pgo") => cmd_pgo(&args[1..]),
+        Some("inspect") => cmd_inspect(&args[1..]),
+        Some("dispatch") => cmd_dispatch(&args[1..]),
+        Some("directions") => cmd_directions(&args[1..]),
+        Some("matrix") => cmd_matrix(&args[1..]),
+        Some("estimate") => cmd_estimate(&args[1..]),
+        Some("mutate") => cmd_mutate(&args[1..]),
+        Some("batch") => cmd_batch(&args[1..]),
+        Some("corpus") => cmd_corpus(&args[1..]),
+        Some("coverage") => cmd_coverage(&args[1..]),
+        Some("errors") => cmd_errors(&args[1..]),
+        Some("wire") => cmd_wire(&args[1..]),
+        Some("generate") => cmd_generate(&args[1..]),
+        Some("export-schema") => cmd_export_schema(&args[1..]),
+        Some("stream") => cmd_stream(&args[1..]),
+        Some("pipeline") => cmd_pipeline(&args[1..]),
+        Some("latency") => cmd_latency(&args[1..]),
+        #[cfg(feature = "perf")]
+        Some("counters") => cmd_counters(&args[1..]),
+        // the other end of `stream`
+        Some("produce") => cmd_produce(&args[1..]),
+        #[cfg(feature = "history")]
+        Some("trend") => cmd_trend(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    // flushes the trace; exit doesn't run destructors
+    drop(guard);
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn cmd_run(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let max_time = opts.value("--max-time");
+    let iterations = opts.value("--iterations");
+    let strategy_name = opts.value("--strategy");
+    let cache = opts.value("--cache");
+    let output = opts.value("--output");
+    let reruns = opts.number("--reruns", 0)?;
+    let mut externals = Vec::new();
+    while let Some(command) = opts.value("--external") {
+        externals.push(command);
+    }
+    let opt_levels = opts.value("--opt-matrix");
+    let features = opts.value("--features");
+    let by_method = opts.number("--by-method", 0)?;
+    let slowest = opts.number("--slowest", 0)?;
+    let framing = match opts.value("--framing") {
+        Some(f) => corpus::Framing::from_name(&f)
+            .ok_or_else(|| format!("invalid value for --framing: '{}'", f))?,
+        None => corpus::Framing::Lines,
+    };
+    #[cfg(feature = "history")]
+    let history_path = opts.value("--history").unwrap_or_else(|| DEFAULT_HISTORY.to_owned());
+    opts.finish()?;
+
+    let budget = budget(max_time, iterations)?;
+    if !externals.is_empty() && matches!(budget, Budget::Time(_)) {
+        return Err("--external needs --iterations".into());
+    }
+    // the external runners read a message a line
+    if !externals.is_empty() && framing != corpus::Framing::Lines {
+        return Err("--external needs one message per line".into());
+    }
+    match (opt_levels, features) {
+        (Some(levels), features) => {
+            return run_opt_matrix(&path, args, &levels, features, output);
+        }
+        (None, Some(_)) => return Err("--features needs --opt-matrix".into()),
+        (None, None) => {}
+    }
+
+    let cache = match cache {
+        Some(c) => Cache::from_name(&c).ok_or_else(|| format!("invalid value for --cache: '{}'", c))?,
+        None => Cache::Hot,
+    };
+
+    let _span = info_span!("run", corpus = path.as_str()).entered();
+    let (lines, entry) = corpus::read_framed(&path, framing)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let strategies = match strategy_name {
+        Some(name) => vec![strategy::by_name(&name)
+                           .ok_or_else(|| format!("unknown strategy '{}'", name))?],
+        None => strategy::for_direction(entry.as_ref().map(|e| e.direction).unwrap_or_default()),
+    };
+    // one buffer, faulted in before the first strategy runs, for all of them
+    let buffer = corpus::Buffer::new(&lines);
+    let mut measurements = strategies.iter()
+        .map(|s| runner::measure_steady(s.as_ref(), &buffer, budget, cache, reruns as u32))
+        .collect::<Vec<_>>();
+    if let Budget::Iterations(passes) = budget {
+        let direction = entry.as_ref().map(|e| e.direction).unwrap_or_default();
+        for command in &externals {
+            measurements.push(external::measure(command, &lines, direction, passes)?);
+        }
+    }
+    runner::print_measurements(&measurements);
+    println!();
+    runner::print_traits(&measurements);
+    for m in measurements.iter().filter(|m| m.slowed()) {
+        let drift = m.spread.map_or(0.0, |s| s.drift * 100.0);
+        eprintln!("warning: {} slowed down by {:.0}% over its run; the CPU may be throttling, \
+                   or something else was running (try --reruns N)", m.strategy, drift);
+    }
+    if by_method > 0 || slowest > 0 {
+        println!();
+        let passes = if by_method > 0 { by_method } else { 10 };
+        let mut breakdowns = Vec::new();
+        for s in &strategies {
+            let times = breakdown::time_lines(s.as_ref(), &buffer, passes);
+            if slowest > 0 {
+                let slow = breakdown::slowest(&buffer, &times, slowest as usize);
+                breakdown::print_slowest(s.name(), &slow);
+            }
+            breakdowns.push(breakdown::Breakdown::of(s.name(), &buffer, &times));
+        }
+        if by_method > 0 {
+            breakdown::print_breakdowns(&breakdowns);
+        }
+    }
+    // timings of strategies that didn't do the same work aren't worth saving
+    runner::verify_checksums(&measurements)?;
+
+    if let Some(output) = output {
+        let report = report::Report::new(&path, corpus::hash(&lines), entry, &measurements);
+        File::create(&output)
+            .and_then(|f| report.write_json(f))
+            .map_err(|e| format!("{}: {}", output, e))?;
+    }
+
+    #[cfg(feature = "history")]
+    {
+        History::open(&history_path)
+            .and_then(|mut h| h.record(&RunInfo::current(&lines), &measurements))
+            .map_err(|e| format!("{}: {}", history_path, e))?;
+    }
+    Ok(())
+}
+
+/// Runs `run` over `path`, with the other options in `args`, built at each
+/// of `levels`, with and without LTO; see harness/src/opt_matrix.rs.
+fn run_opt_matrix(path: &str, args: &[String], levels: &str, features: Option<String>,
+                  output: Option<String>) -> Result<(), String> {
+    let profiles = opt_matrix::Profile::matrix(&opt_matrix::Profile::parse_levels(levels)?);
+    let mut run_args = vec!["run".to_owned(), path.to_owned()];
+    run_args.extend(forwarded(args, &["--opt-matrix", "--features", "--output"]));
+    let comparison = opt_matrix::run(&profiles, &run_args, features.as_deref())?;
+    write_comparison(&comparison, output)
+}
+
+/// The options in `args`, with their values, other than those in `skip`.
+fn forwarded(args: &[String], skip: &[&str]) -> Vec<String> {
+    let mut forwarded = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with("--") {
+            continue;
+        }
+        let value = iter.next();
+        if !skip.contains(&arg.as_str()) {
+            forwarded.push(arg.clone());
+            forwarded.extend(value.cloned());
+        }
+    }
+    forwarded
+}
+
+/// Prints `comparison`, and writes it to `output` if given.
+fn write_comparison(comparison: &builds::Comparison, output: Option<String>)
+    -> Result<(), String>
+{
+    comparison.print();
+    if let Some(output) = output {
+        fs::write(&output, format!("{:#}\n", comparison.to_json()))
+            .map_err(|e| format!("{}: {}", output, e))?;
+    }
+    Ok(())
+}
+
+/// The budget given by `--max-time` or `--iterations`, defaulting to 100
+/// passes.
+fn budget(max_time: Option<String>, iterations: Option<String>) -> Result<Budget, String> {
+    Ok(match (max_time, iterations) {
+        (Some(_), Some(_)) => return Err("--max-time and --iterations are exclusive".into()),
+        (Some(secs), None) => {
+            let time = secs.parse::<f64>().ok().filter(|s| *s > 0.0)
+                .and_then(|s| Duration::try_from_secs_f64(s).ok())
+                .ok_or_else(|| format!("invalid value for --max-time: '{}'", secs))?;
+            Budget::Time(time)
+        }
+        (None, Some(n)) => Budget::Iterations(n.parse()
+            .map_err(|_| format!("invalid value for --iterations: '{}'", n))?),
+        (None, None) => Budget::Iterations(100),
+    })
+}
+
+fn cmd_pgo(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let training = opts.positional("training corpus")?;
+    let path = opts.positional("corpus")?;
+    let features = opts.value("--features");
+    let output = opts.value("--output");
+    // the rest are run's, and it checks them
+    let options = forwarded(args, &["--features", "--output"]);
+    if options.iter().any(|o| o == "--opt-matrix") {
+        return Err("--opt-matrix can't be combined with pgo".into());
+    }
+
+    let mut train_args = vec!["run".to_owned(), training];
+    train_args.extend(options.iter().cloned());
+    let mut measure_args = vec!["run".to_owned(), path];
+    measure_args.extend(options);
+    let comparison = pgo::run(&train_args, &measure_args, features.as_deref())?;
+    write_comparison(&comparison, output)
+}
+
+fn cmd_inspect(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let strategy_name = opts.value("--strategy");
+    let features = opts.value("--features");
+    let out = opts.value("--out").unwrap_or_else(|| "der_bench_inspect".into());
+    opts.finish()?;
+
+    let names = match strategy_name {
+        Some(name) => {
+            strategy::by_name(&name).ok_or_else(|| format!("unknown strategy '{}'", name))?;
+            vec![name]
+        }
+        None => strategy::all().iter().map(|s| s.name().to_owned()).collect(),
+    };
+    let listings = inspect::run(&names, features.as_deref(), Path::new(&out))?;
+    println!("{:<20} {:>10} {:>14}", "strategy", "functions", "instructions");
+    for listing in &listings {
+        if listing.functions == 0 {
+            println!("{:<20} {:>10} {:>14}", listing.strategy, "-", "-");
+        } else {
+            println!("{:<20} {:>10} {:>14}", listing.strategy, listing.functions,
+                     listing.instructions);
+        }
+    }
+    println!("written to {}", out);
+    Ok(())
+}
+
+fn cmd_dispatch(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let max_time = opts.value("--max-time");
+    let iterations = opts.value("--iterations");
+    opts.finish()?;
+    let budget = budget(max_time, iterations)?;
+
+    let lines = corpus::read_jsonl(&path).map_err(|e| format!("{}: {}", path, e))?;
+    runner::print_dispatch(&runner::compare_dispatch(&corpus::Buffer::new(&lines), budget));
+    Ok(())
+}
+
+fn cmd_matrix(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("config").unwrap_or_else(|_| config::CONFIG.to_owned());
+    opts.finish()?;
+    let config = config::Config::load(&path)?;
+
+    for runs in &config.corpora {
+        let _span = info_span!("matrix", corpus = runs.path.as_str()).entered();
+        let (lines, entry) = corpus::read_described(&runs.path)
+            .map_err(|e| format!("{}: {}", runs.path, e))?;
+        let names = match config.strategies {
+            Some(ref names) => names.clone(),
+            None => {
+                let direction = entry.as_ref().map(|e| e.direction).unwrap_or_default();
+                strategy::for_direction(direction).iter().map(|s| s.name().to_owned()).collect()
+            }
+        };
+        // validated when the config was loaded
+        let budget = runs.budget()?;
+        let buffer = corpus::Buffer::new(&lines);
+        let mut measurements = Vec::new();
+        for &cache in &runs.cache {
+            for &threads in &runs.threads {
+                for name in &names {
+                    measurements.extend(
+                        runner::measure_contended(name, &buffer, budget, cache, threads));
+                }
+            }
+        }
+
+        if config.reports.contains(&config::ReportFormat::Table) {
+            println!("{}:", runs.path);
+            runner::print_measurements(&measurements);
+            println!();
+        }
+        runner::verify_checksums(&measurements)?;
+        if config.reports.contains(&config::ReportFormat::Json) {
+            let dir = config.output_dir.as_ref().map_or(".", String::as_str);
+            let stem = Path::new(&runs.path).file_stem()
+                .and_then(|s| s.to_str())
+                .filter(|_| runs.path != corpus::STDIN)
+                .unwrap_or("stdin");
+            let output = Path::new(dir).join(format!("{}.json", stem));
+            let report = report::Report::new(&runs.path, corpus::hash(&lines), entry,
+                                             &measurements);
+            fs::create_dir_all(dir)
+                .and_then(|()| File::create(&output))
+                .and_then(|f| report.write_json(f))
+                .map_err(|e| format!("{}: {}", output.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+fn cmd_directions(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let max_time = opts.value("--max-time");
+    let iterations = opts.value("--iterations");
+    opts.finish()?;
+    let budget = budget(max_time, iterations)?;
+
+    let measurements = Direction::ALL.iter().map(|&direction| {
+        let buffer = corpus::Buffer::new(direction.corpora().iter().flat_map(|c| c.lines()));
+        // one strategy a direction, and the same approach for each
+        let strategy: Box<dyn strategy::Strategy> = match direction {
+            Direction::C2S => Box::new(strategy::Serde),
+            other => strategy::for_direction(other).remove(0),
+        };
+        runner::measure(strategy.as_ref(), &buffer, budget, Cache::Hot)
+    }).collect::<Vec<_>>();
+    runner::print_measurements(&measurements);
+    Ok(())
+}
+
+fn cmd_estimate(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let calibration = opts.value("--calibrate");
+    let strategy_name = opts.value("--strategy");
+    let passes = opts.number("--passes", 100)?;
+    let max_time = opts.value("--max-time");
+    let iterations = opts.value("--iterations");
+    opts.finish()?;
+    let budget = budget(max_time, iterations)?;
+    if passes == 0 {
+        return Err("invalid value for --passes: '0'".into());
+    }
+
+    let (lines, entry) = corpus::read_described(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let direction = entry.as_ref().map(|e| e.direction).unwrap_or_default();
+    let calibration = match calibration {
+        Some(p) => corpus::read_jsonl(&p).map_err(|e| format!("{}: {}", p, e))?,
+        None => direction.corpora().iter().flat_map(|c| c.lines()).map(String::from).collect(),
+    };
+    let strategies = match strategy_name {
+        Some(name) => vec![strategy::by_name(&name)
+                           .ok_or_else(|| format!("unknown strategy '{}'", name))?],
+        None => strategy::for_direction(direction),
+    };
+
+    let stats = estimate::Stats::of(lines.iter().map(String::as_str));
+    let buffer = corpus::Buffer::new(&lines);
+    let mut estimates = Vec::new();
+    for s in &strategies {
+        let model = estimate::calibrate(s.as_ref(), &calibration, passes);
+        let uncovered = model.uncovered(&stats);
+        if !uncovered.is_empty() {
+            eprintln!("warning: the calibration corpus has no {}; {} costs them as an average \
+                       message", uncovered.join(", "), s.name());
+        }
+        let actual = runner::measure(s.as_ref(), &buffer, budget, Cache::Hot);
+        estimates.push(estimate::Estimate {
+            strategy: s.name(),
+            predicted_ns: model.predict(&stats),
+            actual_ns: actual.ns_per_iter(),
+        });
+    }
+    estimate::print_estimates(&estimates);
+    Ok(())
+}
+
+#[cfg(feature = "history")]
+fn cmd_trend(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.value("--history").unwrap_or_else(|| DEFAULT_HISTORY.to_owned());
+    let corpus_path = opts.value("--corpus");
+    let strategy = opts.value("--strategy");
+    opts.finish()?;
+
+    let corpus_hash = match corpus_path {
+        Some(p) => Some(corpus::hash(&corpus::read_jsonl(&p).map_err(|e| format!("{}: {}", p, e))?)),
+        None => None,
+    };
+    let rows = History::open(&path)
+        .and_then(|h| h.trend(corpus_hash.as_ref().map(String::as_str),
+                              strategy.as_ref().map(String::as_str)))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    history::print_trend(&rows);
+    Ok(())
+}
+
+fn cmd_mutate(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let count = opts.number("--count", 10)?;
+    let seed = opts.number("--seed", 1)?;
+    opts.finish()?;
+
+    let lines = corpus::read_jsonl(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let report = mutate::run(&lines, &strategy::all(), count as usize, seed);
+    report.print();
+    Ok(())
+}
+
+fn cmd_batch(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let output = opts.positional("output")?;
+    let size = opts.number("--size", 8)?;
+    opts.finish()?;
+
+    if size == 0 {
+        return Err("--size must be at least 1".into());
+    }
+    let lines = corpus::read_jsonl(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let batches = corpus::batch(&lines, size as usize);
+    File::create(&output)
+        .and_then(|f| corpus::Compression::from_path(&output).encoder(f))
+        .and_then(|mut f| batches.iter().try_for_each(|b| writeln!(f, "{}", b)))
+        .map_err(|e| format!("{}: {}", output, e))
+}
+
+fn cmd_corpus(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let subcommand = opts.positional("shuffle|interleave|generate|chunk|validate")?;
+    let (output, corpora) = match subcommand.as_str() {
+        "validate" => {
+            let path = opts.positional("corpus")?;
+            let direction = match opts.value("--direction") {
+                Some(d) => Direction::from_name(&d)
+                    .ok_or_else(|| format!("invalid value for --direction: '{}'", d))?,
+                None => Direction::C2S,
+            };
+            let max = opts.number("--max", 20)?;
+            opts.finish()?;
+            return validate_corpus(&path, direction, max as usize);
+        }
+        "generate" => {
+            let name = opts.positional("preset")?;
+            let output = opts.positional("output")?;
+            let messages = opts.number("--messages", 5000)?;
+            let seed = opts.number("--seed", 1)?;
+            let depth = opts.number("--depth", 200)?;
+            opts.finish()?;
+            let lines = if name == "deep_nesting" {
+                generate::deep_nesting(depth as usize, messages as usize, seed)
+            } else if name == "plugin_session" {
+                generate::plugin_session(messages as usize, seed)
+            } else {
+                Preset::from_name(&name).ok_or_else(|| format!("unknown preset '{}'", name))?
+                    .generate(messages as usize, seed)
+            };
+            return write_corpus(&output, &lines);
+        }
+        "chunk" => {
+            let path = opts.positional("corpus")?;
+            let output = opts.positional("output")?;
+            let size = opts.number("--size", 16)?;
+            opts.finish()?;
+            if size == 0 {
+                return Err("--size must be at least 1".into());
+            }
+            let lines = corpus::read_jsonl(&path).map_err(|e| format!("{}: {}", path, e))?;
+            return write_corpus(&output, &corpus::paste::chunk(&lines, size as usize * 1024));
+        }
+        "shuffle" => {
+            let path = opts.positional("corpus")?;
+            (opts.positional("output")?, vec![path])
+        }
+        "interleave" => {
+            let output = opts.positional("output")?;
+            let mut corpora = vec![opts.positional("corpus")?];
+            corpora.extend(opts.rest());
+            (output, corpora)
+        }
+        other => return Err(format!("unknown corpus command '{}'", other)),
+    };
+    let seed = opts.number("--seed", 1)?;
+    opts.finish()?;
+
+    let corpora = corpora.iter()
+        .map(|path| corpus::read_jsonl(path).map_err(|e| format!("{}: {}", path, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let lines = corpus::interleave::interleave(&corpora, seed);
+    write_corpus(&output, &lines)
+}
+
+/// Checks each line of the corpus at `path`, read as `corpus::read_jsonl`
+/// reads it, but without skipping blank lines in the count.
+fn validate_corpus(path: &str, direction: Direction, max: usize) -> Result<(), String> {
+    let report = if path == corpus::STDIN {
+        let stdin = io::stdin();
+        validate::check(stdin.lock(), direction)
+    } else {
+        File::open(path)
+            .and_then(|f| corpus::Compression::from_path(path).decoder(f))
+            .and_then(|reader| validate::check(reader, direction))
+    };
+    let report = report.map_err(|e| format!("{}: {}", path, e))?;
+    report.print(max);
+    if !report.is_valid() {
+        return Err(format!("{}: {} invalid messages", path, report.invalid.len()));
+    }
+    Ok(())
+}
+
+/// Writes `lines` to `output`, compressed according to the extension.
+fn write_corpus(output: &str, lines: &[String]) -> Result<(), String> {
+    File::create(output)
+        .and_then(|f| corpus::Compression::from_path(output).encoder(f))
+        .and_then(|mut f| lines.iter().try_for_each(|l| writeln!(f, "{}", l)))
+        .map_err(|e| format!("{}: {}", output, e))
+}
+
+fn cmd_coverage(args: &[String]) -> Result<(), String> {
+    Options::parse(args)?.finish()?;
+    let strategies = strategy::all();
+    let gaps = coverage::check(&strategies);
+    coverage::print_gaps(&strategies, &gaps);
+    Ok(())
+}
+
+fn cmd_errors(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let strategy_name = opts.value("--strategy");
+    let output = opts.value("--output");
+    opts.finish()?;
+
+    let strategies = match strategy_name {
+        Some(name) => vec![strategy::by_name(&name)
+                           .ok_or_else(|| format!("unknown strategy '{}'", name))?],
+        None => strategy::all(),
+    };
+    let report = diagnostics::run(&strategies);
+    report.print();
+    if let Some(output) = output {
+        fs::write(&output, report.to_markdown()).map_err(|e| format!("{}: {}", output, e))?;
+    }
+    Ok(())
+}
+
+fn cmd_wire(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let examples = opts.number("--examples", 3)?;
+    let canonical = match opts.value("--compare").as_ref().map(String::as_str) {
+        None | Some("exact") => false,
+        Some("canonical") => true,
+        Some(other) => return Err(format!("unknown comparison '{}'", other)),
+    };
+    opts.finish()?;
+
+    let lines = corpus::read_jsonl(&path).map_err(|e| format!("{}: {}", path, e))?;
+    wire::run(&lines, canonical).print(&lines, examples as usize);
+    Ok(())
+}
+
+fn cmd_generate(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("description")?;
+    let out = opts.value("--out").unwrap_or_else(|| ".".to_owned());
+    opts.finish()?;
+
+    let protocol = codegen::Protocol::load(&path)?;
+    let source = Path::new(&path).file_name()
+        .map_or_else(|| path.clone(), |f| f.to_string_lossy().into_owned());
+    for &flavour in &codegen::Flavour::ALL {
+        let file = Path::new(&out).join(protocol.module_file(flavour));
+        fs::write(&file, protocol.generate(flavour, &source))
+            .map_err(|e| format!("{}: {}", file.display(), e))?;
+        println!("wrote {}", file.display());
+    }
+    Ok(())
+}
+
+fn cmd_export_schema(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("description")?;
+    let output = opts.value("--output");
+    opts.finish()?;
+
+    let schema = format!("{:#}\n", codegen::Protocol::load(&path)?.schema());
+    match output {
+        Some(output) => fs::write(&output, schema).map_err(|e| format!("{}: {}", output, e)),
+        None => {
+            print!("{}", schema);
+            Ok(())
+        }
+    }
+}
+
+fn cmd_stream(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let transport = opts.value("--transport").unwrap_or_else(|| "pipe".to_owned());
+    let passes = opts.number("--passes", 100)?;
+    let strategy_name = opts.value("--strategy");
+    opts.finish()?;
+    // each strategy's producer reads the corpus afresh
+    if path == corpus::STDIN {
+        return Err("stream can't read its corpus from stdin".into());
+    }
+
+    let transport = Transport::from_name(&transport)
+        .ok_or_else(|| format!("invalid value for --transport: '{}'", transport))?;
+    let strategies = match strategy_name {
+        Some(name) => vec![strategy::by_name(&name)
+                           .ok_or_else(|| format!("unknown strategy '{}'", name))?],
+        None => strategy::all(),
+    };
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+
+    let _span = info_span!("stream", corpus = path.as_str()).entered();
+    let mut measurements = Vec::new();
+    for s in &strategies {
+        let mut producer = Command::new(&exe);
+        producer.arg("produce").arg(&path);
+        let m = transport::measure(s.as_ref(), transport, passes, producer)
+            .map_err(|e| format!("{}: {}", s.name(), e))?;
+        measurements.push(m);
+    }
+    runner::print_measurements(&measurements);
+    Ok(())
+}
+
+fn cmd_pipeline(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let passes = opts.number("--passes", 100)?;
+    let channel = opts.value("--channel");
+    opts.finish()?;
+
+    let channels = match channel {
+        Some(name) => vec![pipeline::Channel::from_name(&name)
+                           .ok_or_else(|| format!("unknown channel '{}'", name))?],
+        None => pipeline::Channel::all(),
+    };
+    let lines = corpus::read_jsonl(&path).map_err(|e| format!("{}: {}", path, e))?;
+    for channel in channels {
+        println!("{}:", channel.name());
+        pipeline::print_latencies(&pipeline::run_all(&lines, passes, channel));
+        println!();
+    }
+    Ok(())
+}
+
+fn cmd_latency(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let strategy_name = opts.value("--strategy");
+    let message = opts.value("--message");
+    let p99 = opts.value("--p99");
+    let samples = opts.number("--samples", 100_000)?;
+    opts.finish()?;
+
+    let budget = match p99 {
+        Some(us) => us.parse::<f64>().ok().filter(|us| *us > 0.0)
+            .and_then(|us| Duration::try_from_secs_f64(us / 1e6).ok())
+            .ok_or_else(|| format!("invalid value for --p99: '{}'", us))?,
+        None => Duration::from_secs_f64(latency::DEFAULT_P99_US / 1e6),
+    };
+    let json = match message {
+        Some(path) => corpus::read_jsonl(&path).map_err(|e| format!("{}: {}", path, e))?
+            .into_iter().next().ok_or_else(|| format!("{}: no message", path))?,
+        None => latency::KEYSTROKE.to_owned(),
+    };
+    let names = match strategy_name {
+        Some(name) => vec![name],
+        None => latency::STRATEGIES.iter().map(|&s| s.into()).collect(),
+    };
+    let mut all = Vec::new();
+    for name in names {
+        let strategy = strategy::by_name(&name)
+            .ok_or_else(|| format!("unknown strategy '{}'", name))?;
+        all.push(latency::sample(&*strategy, &json, samples as usize));
+    }
+    pipeline::print_latencies(&all);
+    let failures = all.iter().filter_map(|l| latency::check(l, budget).err()).collect::<Vec<_>>();
+    if !failures.is_empty() {
+        return Err(failures.join("\n"));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "perf")]
+fn cmd_counters(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let passes = opts.number("--passes", 100)?;
+    let strategy_name = opts.value("--strategy");
+    opts.finish()?;
+
+    let strategies = match strategy_name {
+        Some(name) => vec![strategy::by_name(&name)
+                           .ok_or_else(|| format!("unknown strategy '{}'", name))?],
+        None => strategy::all(),
+    };
+    let lines = corpus::read_jsonl(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let counts = strategies.iter()
+        .map(|s| counters::count(s.as_ref(), &lines, passes))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("perf counters: {}", e))?;
+    counters::print_counts(&counts);
+    Ok(())
+}
+
+fn cmd_produce(args: &[String]) -> Result<(), String> {
+    let mut opts = Options::parse(args)?;
+    let path = opts.positional("corpus")?;
+    let transport = opts.positional("transport")?;
+    let addr = opts.positional("addr")?;
+    let passes = opts.positional("passes")?;
+    opts.finish()?;
+
+    let transport = Transport::from_name(&transport)
+        .ok_or_else(|| format!("unknown transport '{}'", transport))?;
+    let passes = passes.parse().map_err(|_| format!("invalid passes '{}'", passes))?;
+    let lines = corpus::read_jsonl(&path).map_err(|e| format!("{}: {}", path, e))?;
+    transport::connect_and_produce(transport, &addr, &lines, passes)
+        .map_err(|e| format!("{}: {}", addr, e))
+}
+
+#[cfg(feature = "chrome-trace")]
+type TraceGuard = tracing_chrome::FlushGuard;
+#[cfg(not(feature = "chrome-trace"))]
+type TraceGuard = ();
+
+/// Logs events to stderr as `LOG_ENV` asks, and writes spans to `trace`.
+/// The trace is complete once the returned guard is dropped.
+fn init_tracing(trace: Option<String>) -> Result<Option<TraceGuard>, String> {
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::{fmt, EnvFilter};
+
+    let log = match env::var(LOG_ENV) {
+        Ok(filter) => Some(fmt::layer().with_writer(io::stderr).with_filter(
+            EnvFilter::try_new(&filter).map_err(|e| format!("{}: {}", LOG_ENV, e))?)),
+        Err(_) => None,
+    };
+
+    #[cfg(feature = "chrome-trace")]
+    {
+        let (chrome, guard) = match trace {
+            Some(path) => {
+                let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new().file(path).build();
+                (Some(layer), Some(guard))
+            }
+            None => (None, None),
+        };
+        tracing_subscriber::registry().with(log).with(chrome).init();
+        Ok(guard)
+    }
+
+    #[cfg(not(feature = "chrome-trace"))]
+    {
+        if trace.is_some() {
+            return Err("--trace requires the chrome-trace feature".into());
+        }
+        tracing_subscriber::registry().with(log).init();
+        Ok(None)
+    }
+}
+
+/// A minimal argument parser: `--flag value` pairs and positional arguments.
+struct Options {
+    positional: Vec<String>,
+    flags: Vec<(String, String)>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut flags = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg.starts_with("--") {
+                let value = iter.next().ok_or_else(|| format!("missing value for {}", arg))?;
+                flags.push((arg.clone(), value.clone()));
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        positional.reverse();
+        Ok(Options { positional, flags })
+    }
+
+    /// Takes the next positional argument.
+    fn positional(&mut self, name: &str) -> Result<String, String> {
+        self.positional.pop().ok_or_else(|| format!("missing argument <{}>", name))
+    }
+
+    /// Takes the remaining positional arguments.
+    fn rest(&mut self) -> Vec<String> {
+        let mut rest = ::std::mem::replace(&mut self.positional, Vec::new());
+        rest.reverse();
+        rest
+    }
+
+    /// Takes the value of `flag`, if present.
+    fn value(&mut self, flag: &str) -> Option<String> {
+        let idx = self.flags.iter().position(|&(ref f, _)| f == flag)?;
+        Some(self.flags.remove(idx).1)
+    }
+
+    fn number(&mut self, flag: &str, default: u64) -> Result<u64, String> {
+        match self.value(flag) {
+            Some(v) => v.parse().map_err(|_| format!("invalid value for {}: '{}'", flag, v)),
+            None => Ok(default),
+        }
+    }
+
+    /// Errors if any arguments were not consumed.
+    fn finish(self) -> Result<(), String> {
+        if let Some(&(ref flag, _)) = self.flags.first() {
+            return Err(format!("unknown option {}", flag));
+        }
+        if let Some(arg) = self.positional.last() {
+            return Err(format!("unexpected argument '{}'", arg));
+        }
+        Ok(())
+    }
+}
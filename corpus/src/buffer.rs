@@ -0,0 +1,93 @@
+//! A corpus in a single allocation, for every strategy to parse in turn.
+//!
+//! Lines read one `String` at a time are scattered over the heap, and a
+//! built-in corpus sits in the binary's read-only pages until something
+//! first reads it. Either way, whichever strategy runs first pays for page
+//! faults and cold TLB entries the later ones don't. A `Buffer` is copied
+//! into once, up front, which writes, and so faults in, every page of it
+//! before anything is timed; then every strategy is given the same one.
+
+use std::slice;
+
+pub struct Buffer {
+    text: String,
+    /// The start and end of each line in `text`.
+    spans: Vec<(usize, usize)>,
+}
+
+impl Buffer {
+    /// Copies `lines` into a new buffer.
+    pub fn new<I, S>(lines: I) -> Buffer
+        where I: IntoIterator<Item = S>, S: AsRef<str>
+    {
+        let mut text = String::new();
+        let mut spans = Vec::new();
+        for line in lines {
+            let start = text.len();
+            text.push_str(line.as_ref());
+            spans.push((start, text.len()));
+            text.push('\n');
+        }
+        text.shrink_to_fit();
+        spans.shrink_to_fit();
+        Buffer { text, spans }
+    }
+
+    /// The lines, in order.
+    pub fn lines(&self) -> Lines<'_> {
+        Lines { text: &self.text, spans: self.spans.iter() }
+    }
+
+    /// The whole corpus, a line at a time, each ended by a newline.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The number of lines.
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+}
+
+/// An iterator over a `Buffer`'s lines.
+#[derive(Clone)]
+pub struct Lines<'a> {
+    text: &'a str,
+    spans: slice::Iter<'a, (usize, usize)>,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.spans.next().map(|&(start, end)| &self.text[start..end])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.spans.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Lines<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TEST_JSON;
+
+    #[test]
+    fn same_lines() {
+        let buffer = Buffer::new(TEST_JSON.lines());
+        assert!(buffer.lines().eq(TEST_JSON.lines()));
+        assert_eq!(buffer.len(), TEST_JSON.lines().count());
+        assert_eq!(buffer.as_str().lines().count(), buffer.len());
+
+        let owned = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        assert_eq!(Buffer::new(&owned).as_str(), buffer.as_str());
+        assert!(Buffer::new(Vec::<String>::new()).is_empty());
+    }
+}
@@ -0,0 +1,421 @@
+//! Simulating partial reads.
+//!
+//! The benches in xi-der-strategies all get to see a whole message at a
+//! time. In xi, RPCs arrive over a pipe, and a single `read` can return half
+//! a message, or three and a bit. This module provides a reader that hands out a corpus
+//! in random-sized chunks, and a few different ways of reassembling those
+//! chunks into lines.
+
+use std::cmp;
+use std::io::{self, Read};
+use std::str;
+
+use rng::XorShift;
+
+/// The size of the reads performed by the reassemblers, and the initial
+/// capacity of their buffers.
+const READ_SIZE: usize = 4096;
+
+/// A `Read` impl that returns at most a random number of bytes per call.
+pub struct ChunkedReader<'a> {
+    data: &'a [u8],
+    rng: XorShift,
+    max_chunk: usize,
+}
+
+impl<'a> ChunkedReader<'a> {
+    /// Creates a new reader over `data`. Each read returns between 1 and
+    /// `max_chunk` bytes; the sequence of sizes is determined by `seed`.
+    pub fn new(data: &'a [u8], seed: u64, max_chunk: usize) -> Self {
+        assert!(max_chunk > 0);
+        ChunkedReader { data, rng: XorShift::new(seed), max_chunk }
+    }
+}
+
+impl<'a> Read for ChunkedReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.data.is_empty() || buf.is_empty() {
+            return Ok(0);
+        }
+        let n = self.rng.range(1, self.max_chunk);
+        let n = cmp::min(n, cmp::min(buf.len(), self.data.len()));
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+/// A strategy for turning a stream of arbitrarily split reads back into
+/// newline-delimited messages.
+pub trait Reassembler {
+    fn name(&self) -> &'static str;
+
+    /// Reads `source` until EOF, calling `handler` with each complete line,
+    /// not including the newline. A final line without a trailing newline
+    /// is passed to `handler` at EOF.
+    ///
+    /// Buffers are retained between calls.
+    fn read_lines<R, F>(&mut self, source: R, handler: F) -> io::Result<()>
+        where R: Read, F: FnMut(&str);
+}
+
+/// Accumulates reads into a `String`, and drains complete lines off the front.
+///
+/// This is the obvious implementation, and what you'd probably write first.
+#[derive(Debug, Default)]
+pub struct StringAccumulator {
+    buf: String,
+    // the start of a utf-8 sequence that was split across reads
+    carry: Vec<u8>,
+}
+
+/// A fixed-size ring buffer, grown only when a single line doesn't fit.
+///
+/// Lines are passed to the handler in place, unless they wrap around the
+/// end of the buffer, in which case they're copied into a scratch buffer.
+#[derive(Debug, Default)]
+pub struct RingBuffer {
+    buf: Vec<u8>,
+    // index of the first unconsumed byte
+    head: usize,
+    // number of unconsumed bytes
+    len: usize,
+    // number of bytes after head known not to contain a newline
+    scanned: usize,
+    scratch: Vec<u8>,
+}
+
+/// A linear buffer that is scanned in place.
+///
+/// Consumed lines are never moved; the cursor is just reset once everything
+/// has been consumed. Remaining bytes are moved to the front only when a
+/// partial line reaches the end of the buffer.
+#[derive(Debug, Default)]
+pub struct ScanningBuffer {
+    buf: Vec<u8>,
+    // first unconsumed byte
+    start: usize,
+    // end of valid data
+    end: usize,
+    // bytes in start..scanned are known not to contain a newline
+    scanned: usize,
+}
+
+impl StringAccumulator {
+    pub fn new() -> Self {
+        StringAccumulator::default()
+    }
+
+    fn read_all<R, F>(&mut self, source: &mut R, handler: &mut F) -> io::Result<()>
+        where R: Read, F: FnMut(&str)
+    {
+        let mut chunk = [0u8; READ_SIZE];
+        loop {
+            let n = read_some(source, &mut chunk)?;
+            if n == 0 {
+                break;
+            }
+
+            if self.carry.is_empty() {
+                let used = push_utf8(&mut self.buf, &chunk[..n])?;
+                self.carry.extend_from_slice(&chunk[used..n]);
+            } else {
+                self.carry.extend_from_slice(&chunk[..n]);
+                let used = push_utf8(&mut self.buf, &self.carry)?;
+                self.carry.drain(..used);
+            }
+
+            let mut consumed = 0;
+            while let Some(idx) = self.buf[consumed..].find('\n') {
+                handler(&self.buf[consumed..consumed + idx]);
+                consumed += idx + 1;
+            }
+            self.buf.drain(..consumed);
+        }
+
+        if !self.carry.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "truncated utf-8 sequence at EOF"));
+        }
+        if !self.buf.is_empty() {
+            handler(&self.buf);
+        }
+        Ok(())
+    }
+}
+
+impl RingBuffer {
+    pub fn new() -> Self {
+        RingBuffer::default()
+    }
+
+    fn grow(&mut self) {
+        let cap = self.buf.len();
+        let mut new_buf = vec![0; cmp::max(cap * 2, READ_SIZE)];
+        let first = cmp::min(self.len, cap - self.head);
+        new_buf[..first].copy_from_slice(&self.buf[self.head..self.head + first]);
+        new_buf[first..self.len].copy_from_slice(&self.buf[..self.len - first]);
+        self.buf = new_buf;
+        self.head = 0;
+    }
+
+    /// Passes the `line_len` bytes at `head` to the handler.
+    fn emit<F: FnMut(&str)>(buf: &[u8], scratch: &mut Vec<u8>, head: usize,
+                            line_len: usize, handler: &mut F) -> io::Result<()> {
+        let cap = buf.len();
+        let bytes = if head + line_len <= cap {
+            &buf[head..head + line_len]
+        } else {
+            scratch.clear();
+            scratch.extend_from_slice(&buf[head..]);
+            scratch.extend_from_slice(&buf[..line_len - (cap - head)]);
+            &scratch[..]
+        };
+        handler(str::from_utf8(bytes).map_err(invalid_data)?);
+        Ok(())
+    }
+
+    fn read_all<R, F>(&mut self, source: &mut R, handler: &mut F) -> io::Result<()>
+        where R: Read, F: FnMut(&str)
+    {
+        loop {
+            if self.len == self.buf.len() {
+                self.grow();
+            }
+            let cap = self.buf.len();
+            let tail = (self.head + self.len) % cap;
+            let free_end = if tail >= self.head { cap } else { self.head };
+            let n = read_some(source, &mut self.buf[tail..free_end])?;
+            if n == 0 {
+                break;
+            }
+            self.len += n;
+            self.drain_lines(handler)?;
+        }
+        if self.len > 0 {
+            RingBuffer::emit(&self.buf, &mut self.scratch, self.head, self.len, handler)?;
+        }
+        Ok(())
+    }
+
+    fn drain_lines<F: FnMut(&str)>(&mut self, handler: &mut F) -> io::Result<()> {
+        let cap = self.buf.len();
+        while self.scanned < self.len {
+            let pos = (self.head + self.scanned) % cap;
+            let run_end = cmp::min(cap, pos + self.len - self.scanned);
+            match self.buf[pos..run_end].iter().position(|b| *b == b'\n') {
+                Some(idx) => {
+                    let line_len = self.scanned + idx;
+                    RingBuffer::emit(&self.buf, &mut self.scratch, self.head,
+                                     line_len, handler)?;
+                    self.head = (self.head + line_len + 1) % cap;
+                    self.len -= line_len + 1;
+                    self.scanned = 0;
+                    if self.len == 0 {
+                        // maximize the contiguous space for the next read
+                        self.head = 0;
+                    }
+                }
+                None => self.scanned += run_end - pos,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ScanningBuffer {
+    pub fn new() -> Self {
+        ScanningBuffer::default()
+    }
+
+    /// Makes room at the end of the buffer, either by moving a partial line
+    /// to the front or, if the line fills the whole buffer, by growing it.
+    fn make_room(&mut self) {
+        if self.start > 0 {
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.scanned -= self.start;
+            self.start = 0;
+        } else {
+            let new_len = cmp::max(self.buf.len() * 2, READ_SIZE);
+            self.buf.resize(new_len, 0);
+        }
+    }
+
+    fn read_all<R, F>(&mut self, source: &mut R, handler: &mut F) -> io::Result<()>
+        where R: Read, F: FnMut(&str)
+    {
+        loop {
+            if self.end == self.buf.len() {
+                self.make_room();
+            }
+            let n = read_some(source, &mut self.buf[self.end..])?;
+            if n == 0 {
+                break;
+            }
+            self.end += n;
+
+            while let Some(idx) = self.buf[self.scanned..self.end]
+                .iter().position(|b| *b == b'\n') {
+                let newline = self.scanned + idx;
+                handler(str::from_utf8(&self.buf[self.start..newline]).map_err(invalid_data)?);
+                self.start = newline + 1;
+                self.scanned = self.start;
+            }
+            self.scanned = self.end;
+
+            if self.start == self.end {
+                self.start = 0;
+                self.end = 0;
+                self.scanned = 0;
+            }
+        }
+        if self.start < self.end {
+            handler(str::from_utf8(&self.buf[self.start..self.end]).map_err(invalid_data)?);
+        }
+        Ok(())
+    }
+}
+
+impl Reassembler for StringAccumulator {
+    fn name(&self) -> &'static str {
+        "string"
+    }
+
+    fn read_lines<R, F>(&mut self, mut source: R, mut handler: F) -> io::Result<()>
+        where R: Read, F: FnMut(&str)
+    {
+        let result = self.read_all(&mut source, &mut handler);
+        self.buf.clear();
+        self.carry.clear();
+        result
+    }
+}
+
+impl Reassembler for RingBuffer {
+    fn name(&self) -> &'static str {
+        "ring"
+    }
+
+    fn read_lines<R, F>(&mut self, mut source: R, mut handler: F) -> io::Result<()>
+        where R: Read, F: FnMut(&str)
+    {
+        let result = self.read_all(&mut source, &mut handler);
+        self.head = 0;
+        self.len = 0;
+        self.scanned = 0;
+        result
+    }
+}
+
+impl Reassembler for ScanningBuffer {
+    fn name(&self) -> &'static str {
+        "scan"
+    }
+
+    fn read_lines<R, F>(&mut self, mut source: R, mut handler: F) -> io::Result<()>
+        where R: Read, F: FnMut(&str)
+    {
+        let result = self.read_all(&mut source, &mut handler);
+        self.start = 0;
+        self.end = 0;
+        self.scanned = 0;
+        result
+    }
+}
+
+/// Like `Read::read`, but retries on `Interrupted`.
+fn read_some<R: Read>(source: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match source.read(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            other => return other,
+        }
+    }
+}
+
+/// Appends the longest valid utf-8 prefix of `bytes` to `buf`, returning the
+/// number of bytes used. It is only an error if the invalid bytes are not
+/// an incomplete sequence at the end of `bytes`.
+fn push_utf8(buf: &mut String, bytes: &[u8]) -> io::Result<usize> {
+    let valid = match str::from_utf8(bytes) {
+        Ok(s) => s.len(),
+        Err(ref e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(e) => return Err(invalid_data(e)),
+    };
+    buf.push_str(str::from_utf8(&bytes[..valid]).unwrap());
+    Ok(valid)
+}
+
+fn invalid_data(err: str::Utf8Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TEST_JSON;
+
+    fn collect<R: Reassembler>(r: &mut R, data: &str, seed: u64, max_chunk: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        r.read_lines(ChunkedReader::new(data.as_bytes(), seed, max_chunk),
+                     |line| lines.push(line.to_owned())).unwrap();
+        lines
+    }
+
+    fn check_reassembler<R: Reassembler>(mut r: R) {
+        let inputs = [
+            TEST_JSON,
+            "",
+            "one line, no newline",
+            "trailing newline\n",
+            "\n\nempty\n\nlines\n",
+            "h\u{e9}llo\n\u{1F600} w\u{f6}rld\n\u{65e5}\u{672c}\u{8a9e}",
+        ];
+        for input in inputs.iter() {
+            let expected = input.lines().collect::<Vec<_>>();
+            for &max_chunk in [1, 2, 3, 7, 64, 5000].iter() {
+                for seed in 0..20 {
+                    assert_eq!(collect(&mut r, input, seed, max_chunk), expected,
+                               "{} seed {} max_chunk {}", r.name(), seed, max_chunk);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn string_accumulator() {
+        check_reassembler(StringAccumulator::new());
+    }
+
+    #[test]
+    fn ring_buffer() {
+        check_reassembler(RingBuffer::new());
+    }
+
+    #[test]
+    fn scanning_buffer() {
+        check_reassembler(ScanningBuffer::new());
+    }
+
+    #[test]
+    fn long_lines() {
+        // longer than READ_SIZE, so buffers have to grow
+        let long = format!("{}\n{}\nshort", "a".repeat(READ_SIZE * 3), "b".repeat(READ_SIZE + 1));
+        let expected = long.lines().collect::<Vec<_>>();
+        assert_eq!(collect(&mut StringAccumulator::new(), &long, 1, 997), expected);
+        assert_eq!(collect(&mut RingBuffer::new(), &long, 1, 997), expected);
+        assert_eq!(collect(&mut ScanningBuffer::new(), &long, 1, 997), expected);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let bytes = b"ok\n\xff\xfe\nok\n";
+        let mut r = ScanningBuffer::new();
+        assert!(r.read_lines(ChunkedReader::new(bytes, 1, 4), |_| ()).is_err());
+        let mut r = RingBuffer::new();
+        assert!(r.read_lines(ChunkedReader::new(bytes, 1, 4), |_| ()).is_err());
+        let mut r = StringAccumulator::new();
+        assert!(r.read_lines(ChunkedReader::new(bytes, 1, 4), |_| ()).is_err());
+    }
+}
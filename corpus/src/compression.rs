@@ -0,0 +1,145 @@
+//! Compressed corpora.
+//!
+//! Recorded sessions run to hundreds of megabytes, which isn't something to
+//! keep around uncompressed. Corpora ending in `.gz`, `.zst` or `.lz4` are
+//! decompressed as they're read, and compressed as they're written, with
+//! the gzip, zstd and lz4 features respectively.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+#[cfg(feature = "gzip")]
+use flate2;
+#[cfg(feature = "zstd")]
+use zstd;
+#[cfg(feature = "lz4")]
+use lz4_flex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    /// The compression implied by `path`'s extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Compression {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            Some("lz4") => Compression::Lz4,
+            _ => Compression::None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+        }
+    }
+
+    /// Wraps `reader` so that reads return decompressed data.
+    pub fn decoder<'a, R: Read + 'a>(self, reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+        match self {
+            Compression::None => Ok(Box::new(BufReader::new(reader))),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                // concatenated members are common in logs that were appended to
+                Ok(Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(reader))))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(BufReader::new(zstd::Decoder::new(reader)?))),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                Ok(Box::new(BufReader::new(lz4_flex::frame::FrameDecoder::new(reader))))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(self.unsupported()),
+        }
+    }
+
+    /// Wraps `writer` so that writes are compressed. The stream is finished
+    /// when the returned writer is dropped.
+    pub fn encoder<'a, W: Write + 'a>(self, writer: W) -> io::Result<Box<dyn Write + 'a>> {
+        match self {
+            Compression::None => Ok(Box::new(writer)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                Ok(Box::new(flate2::write::GzEncoder::new(writer, flate2::Compression::default())))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(zstd::Encoder::new(writer, 0)?.auto_finish())),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                Ok(Box::new(lz4_flex::frame::FrameEncoder::new(writer).auto_finish()))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(self.unsupported()),
+        }
+    }
+
+    /// Compresses `data` in memory.
+    pub fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        {
+            let mut encoder = self.encoder(&mut out)?;
+            encoder.write_all(data)?;
+        }
+        Ok(out)
+    }
+
+    fn unsupported(self) -> io::Error {
+        io::Error::new(io::ErrorKind::Other,
+                       format!("{} corpora need the {} feature", self.name(), self.name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TEST_JSON;
+
+    fn round_trip(compression: Compression) {
+        let compressed = compression.compress(TEST_JSON.as_bytes()).unwrap();
+        let mut out = String::new();
+        compression.decoder(&compressed[..]).unwrap().read_to_string(&mut out).unwrap();
+        assert_eq!(out, TEST_JSON);
+    }
+
+    #[test]
+    fn from_path() {
+        assert_eq!(Compression::from_path("a/session.jsonl.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_path("session.jsonl.zst"), Compression::Zstd);
+        assert_eq!(Compression::from_path("session.jsonl.lz4"), Compression::Lz4);
+        assert_eq!(Compression::from_path("session.jsonl"), Compression::None);
+        assert_eq!(Compression::from_path("gz"), Compression::None);
+    }
+
+    #[test]
+    fn uncompressed() {
+        round_trip(Compression::None);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip() {
+        round_trip(Compression::Gzip);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd() {
+        round_trip(Compression::Zstd);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4() {
+        round_trip(Compression::Lz4);
+    }
+}
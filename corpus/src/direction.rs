@@ -0,0 +1,92 @@
+//! Which way a corpus's messages go.
+//!
+//! Most of the corpora, and most of the strategies, are of what a frontend
+//! sends xi-core, but that's a quarter of the protocol: core answers with
+//! updates and styles, and plugins, which talk to core over the same kind of
+//! pipe, send it spans and receive its edits. Updates are the biggest
+//! messages there are, so a change to the protocol that only looks at the
+//! client's side is missing most of the bytes.
+
+use std::fmt;
+
+use {ANNOTATED_JSON, FROM_PLUGIN_JSON, GESTURE_JSON, IDS_JSON, IME_JSON, TEST_JSON,
+     TO_CLIENT_JSON, TO_PLUGIN_JSON};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+         Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// From a frontend to core. The default, as the corpora were all of
+    /// these before there were others.
+    #[default]
+    C2S,
+    /// From core to a frontend.
+    S2C,
+    /// From a plugin to core.
+    P2C,
+    /// From core to a plugin.
+    C2P,
+}
+
+impl Direction {
+    /// Every direction, in the order they're reported.
+    pub const ALL: [Direction; 4] = [Direction::C2S, Direction::S2C, Direction::P2C,
+                                     Direction::C2P];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::C2S => "c2s",
+            Direction::S2C => "s2c",
+            Direction::P2C => "p2c",
+            Direction::C2P => "c2p",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        Direction::ALL.iter().cloned().find(|d| d.name() == name)
+    }
+
+    /// Who sends what to whom, for headings.
+    pub fn description(self) -> &'static str {
+        match self {
+            Direction::C2S => "client to core",
+            Direction::S2C => "core to client",
+            Direction::P2C => "plugin to core",
+            Direction::C2P => "core to plugin",
+        }
+    }
+
+    /// The built-in corpora of messages sent this way. The relaxed corpus
+    /// isn't JSON, so it's left out.
+    pub fn corpora(self) -> &'static [&'static str] {
+        match self {
+            Direction::C2S => &[TEST_JSON, GESTURE_JSON, IDS_JSON, IME_JSON, ANNOTATED_JSON],
+            Direction::S2C => &[TO_CLIENT_JSON],
+            Direction::P2C => &[FROM_PLUGIN_JSON],
+            Direction::C2P => &[TO_PLUGIN_JSON],
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+    #[test]
+    fn names() {
+        for &d in &Direction::ALL {
+            assert_eq!(Direction::from_name(d.name()), Some(d));
+            assert_eq!(serde_json::to_string(&d).unwrap(), format!("\"{}\"", d));
+            assert!(!d.corpora().is_empty());
+        }
+        assert_eq!(Direction::from_name("C2S"), None);
+    }
+}
@@ -0,0 +1,150 @@
+//! Where one message ends and the next begins.
+//!
+//! xi-core and its frontends write one message per line, and so do the
+//! corpora here. Some clients written for debugging pretty-print what they
+//! send, though, and a session captured from one of those has messages
+//! spread over many lines. Framed by `Braces`, a message ends where the
+//! braces or brackets it opened are all closed again, counting only those
+//! outside of strings, so it can span any number of lines, and several can
+//! share one. The brackets aren't checked to match: that's the parser's job,
+//! and a message with a stray one is cut short or runs on into the next,
+//! either way failing to parse.
+//!
+//! The messages are kept as they were written, whitespace and all, so that
+//! the strategies pay to skip it just as they would reading from the client.
+
+use std::io::{self, BufRead};
+
+use read_lines;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// One message per line, and blank lines skipped.
+    Lines,
+    /// Messages spanning any number of lines, each ended by its closing
+    /// brace or bracket.
+    Braces,
+}
+
+impl Framing {
+    pub fn name(self) -> &'static str {
+        match self {
+            Framing::Lines => "lines",
+            Framing::Braces => "braces",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Framing> {
+        [Framing::Lines, Framing::Braces].iter().cloned().find(|f| f.name() == name)
+    }
+
+    /// Reads the messages from `reader`.
+    pub fn read<R: BufRead>(self, mut reader: R) -> io::Result<Vec<String>> {
+        match self {
+            Framing::Lines => read_lines(reader),
+            Framing::Braces => {
+                let mut text = String::new();
+                reader.read_to_string(&mut text)?;
+                braces(&text).map(|msg| msg.map(String::from)).collect()
+            }
+        }
+    }
+}
+
+/// The messages in `text`, framed by `Braces`. Anything but whitespace
+/// between them is an error, as is a message that's still open at the end,
+/// and the first error ends the iteration.
+pub fn braces(text: &str) -> Braces<'_> {
+    Braces { text, pos: 0 }
+}
+
+pub struct Braces<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Iterator for Braces<'a> {
+    type Item = io::Result<&'a str>;
+
+    fn next(&mut self) -> Option<io::Result<&'a str>> {
+        let bytes = self.text.as_bytes();
+        let start = self.pos + bytes[self.pos..].iter().position(|b| !b.is_ascii_whitespace())?;
+        // past anything that goes wrong, so that it ends here
+        self.pos = bytes.len();
+        if bytes[start] != b'{' && bytes[start] != b'[' {
+            return Some(Err(invalid(format!("expected '{{' or '[' at byte {}", start))));
+        }
+        let (mut depth, mut in_string, mut escaped) = (0, false, false);
+        for (i, &b) in bytes.iter().enumerate().skip(start) {
+            if in_string {
+                match b {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match b {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.pos = i + 1;
+                        return Some(Ok(&self.text[start..i + 1]));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(Err(invalid(format!("message at byte {} isn't closed", start))))
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+    use TEST_JSON;
+
+    #[test]
+    fn pretty() {
+        let pretty = TEST_JSON.lines()
+            .map(|l| serde_json::to_string_pretty(&serde_json::from_str::<Value>(l).unwrap()))
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(pretty.iter().all(|msg| msg.contains('\n')));
+        let messages = Framing::Braces.read(pretty.join("\n").as_bytes()).unwrap();
+        assert_eq!(messages, pretty);
+
+        // one per line is framed the same either way
+        let lines = Framing::Lines.read(TEST_JSON.as_bytes()).unwrap();
+        assert_eq!(Framing::Braces.read(TEST_JSON.as_bytes()).unwrap(), lines);
+    }
+
+    #[test]
+    fn strings_and_batches() {
+        let text = "{\"chars\": \"}{\\\"]\"}[{\"a\": [1, {}]},\n {}]  \n\n{}";
+        let messages = braces(text).collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(messages, ["{\"chars\": \"}{\\\"]\"}", "[{\"a\": [1, {}]},\n {}]", "{}"]);
+        assert_eq!(braces(" \n").count(), 0);
+    }
+
+    #[test]
+    fn errors() {
+        let mut unclosed = braces("{} {\"a\": [1}");
+        assert!(unclosed.next().unwrap().is_ok());
+        assert!(unclosed.next().unwrap().is_err());
+        assert!(unclosed.next().is_none());
+
+        assert!(braces("Content-Length: 2\r\n\r\n{}").next().unwrap().is_err());
+        assert!(Framing::Braces.read(&b"{\"a\": \"}"[..]).is_err());
+        assert_eq!(Framing::from_name("braces"), Some(Framing::Braces));
+        assert_eq!(Framing::from_name("lsp"), None);
+    }
+}
@@ -0,0 +1,474 @@
+//! Generated corpora, for traffic that's tedious to record.
+//!
+//! Each preset models one kind of input that stresses the strategies
+//! differently from an ordinary editing session. Generation is seeded, so
+//! a generated corpus can be described in a manifest by its preset and
+//! seed, and regenerated rather than checked in.
+
+use serde_json;
+
+use rng::XorShift;
+
+/// The view every generated message is addressed to.
+const VIEW_ID: &str = "view-id-1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// A key held down: long runs of the same `move_*` notification, as
+    /// sent by key auto-repeat, with no payload beyond the envelope. This
+    /// is the case where envelope overhead dominates.
+    CursorBurst,
+    /// Trackpad scrolling: a `scroll` for each frame, each followed by a
+    /// `request_lines` for the lines coming into view, with the position
+    /// moving by flings that decay, as with momentum scrolling. This is the
+    /// highest sustained message rate a frontend produces.
+    ScrollStorm,
+    /// Whole files at a time: a `new_view` with a path, a few `save`s, some
+    /// of them to a new path, and a `close_view`. The paths are long and
+    /// absolute, and many of the file names aren't ASCII, so the messages
+    /// are mostly path: a different allocation profile from edit text.
+    FileSession,
+}
+
+/// Movements a held key produces, by their edit method.
+const MOVES: &[&str] = &["move_up", "move_down", "move_left", "move_right", "move_word_left",
+    "move_word_right", "move_up_and_modify_selection", "move_down_and_modify_selection",
+    "move_left_and_modify_selection", "move_right_and_modify_selection", "scroll_page_up",
+    "scroll_page_down"];
+
+impl Preset {
+    pub fn all() -> Vec<Preset> {
+        vec![Preset::CursorBurst, Preset::ScrollStorm, Preset::FileSession]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Preset::CursorBurst => "cursor_burst",
+            Preset::ScrollStorm => "scroll_storm",
+            Preset::FileSession => "file_session",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::all().into_iter().find(|p| p.name() == name)
+    }
+
+    /// Generates `messages` messages, starting with the `new_view` request
+    /// that opens the view they're addressed to.
+    pub fn generate(&self, messages: usize, seed: u64) -> Vec<String> {
+        let mut rng = XorShift::new(seed);
+        // where scrolling has got to, from one fling to the next
+        let mut top = 0;
+        // views opened so far, including the first
+        let mut views = 1;
+        let mut lines = Vec::with_capacity(messages);
+        if messages > 0 {
+            lines.push(r#"{"id":0,"method":"new_view","params":{}}"#.to_owned());
+        }
+        while lines.len() < messages {
+            match *self {
+                Preset::CursorBurst => cursor_burst(&mut rng, messages - lines.len(), &mut lines),
+                Preset::ScrollStorm => {
+                    scroll_storm(&mut rng, &mut top, messages - lines.len(), &mut lines)
+                }
+                Preset::FileSession => {
+                    file_session(&mut rng, &mut views, messages - lines.len(), &mut lines)
+                }
+            }
+        }
+        lines
+    }
+}
+
+/// Pushes one key's worth of repeats, up to `limit` of them. Repeat rates
+/// are around 30 a second, so holding a key for a few seconds sends a
+/// hundred or so.
+fn cursor_burst(rng: &mut XorShift, limit: usize, lines: &mut Vec<String>) {
+    let method = MOVES[rng.range(0, MOVES.len() - 1)];
+    let line = edit(method, "[]");
+    for _ in 0..rng.range(1, 200).min(limit) {
+        lines.push(line.clone());
+    }
+}
+
+/// The length of the document being scrolled, in lines.
+const DOC_LINES: i64 = 20_000;
+
+/// Pushes one fling's worth of frames, up to `limit` messages: a scroll and
+/// a request for the lines it brings into view, per frame. `top` is the
+/// first visible line.
+fn scroll_storm(rng: &mut XorShift, top: &mut i64, limit: usize, lines: &mut Vec<String>) {
+    let height = rng.range(30, 80) as i64;
+    let mut velocity = rng.range(1, 120) as f64 * if rng.range(0, 1) == 0 { -1.0 } else { 1.0 };
+    let mut budget = limit;
+    while budget > 0 && velocity.abs() >= 0.5 {
+        let prev = *top;
+        // a frame's worth of movement, give or take
+        let jitter = rng.range(0, 4) as i64 - 2;
+        *top = (*top + velocity as i64 + jitter).max(0).min(DOC_LINES - height);
+        velocity *= 0.92;
+        lines.push(edit("scroll", &format!("[{},{}]", *top, *top + height)));
+        budget -= 1;
+        if budget == 0 {
+            break;
+        }
+        // the newly exposed lines, with some slop either side
+        let (first, last) = if *top >= prev {
+            (prev + height, *top + height)
+        } else {
+            (*top, prev)
+        };
+        let slop = rng.range(0, 8) as i64;
+        let first = (first - slop).max(0).min(DOC_LINES - 1);
+        let last = (last + slop).min(DOC_LINES).max(first + 1);
+        lines.push(edit("request_lines", &format!("[{},{}]", first, last)));
+        budget -= 1;
+    }
+}
+
+/// Where files live. None of the path pieces here need escaping in JSON:
+/// the borrowing strategies can't borrow an escaped path.
+const ROOTS: &[&str] = &[
+    "/Users/jörg.müller/Library/Mobile Documents/com~apple~CloudDocs",
+    "/home/developer/src/github.com/xi-editor/xi-editor/rust",
+    "/Volumes/Macintosh HD/Projects",
+    "/private/var/folders/6v/q0k2bwxd1m3c9hmx9t5fr7wc0000gn/T",
+    "/home/田中/ドキュメント",
+];
+const DIRS: &[&str] = &["core-lib", "src", "tests", "plugins", "syntect-plugin", "Документы",
+    "résumés", "2018 – drafts", "node_modules", "@types", "very-long-directory-name-for-testing",
+    "数据", "ünïcödé"];
+const FILES: &[&str] = &["lib.rs", "main.rs", "README.md", "Cargo.toml", "résumé.md",
+    "日本語のメモ.txt", "Привет мир.txt", "naïve café.rs", "😀 notes.md", "Ελληνικά.tex",
+    "مرحبا.txt", "index.d.ts"];
+
+fn path(rng: &mut XorShift) -> String {
+    let mut path = ROOTS[rng.range(0, ROOTS.len() - 1)].to_owned();
+    for _ in 0..rng.range(1, 10) {
+        path.push('/');
+        path.push_str(DIRS[rng.range(0, DIRS.len() - 1)]);
+    }
+    path.push('/');
+    path.push_str(FILES[rng.range(0, FILES.len() - 1)]);
+    path
+}
+
+/// Pushes one file's worth of messages, up to `limit` of them: opening it,
+/// saving it a few times and closing it. `views` counts the views opened,
+/// which xi numbers from 1 in the order it's asked for them.
+fn file_session(rng: &mut XorShift, views: &mut usize, limit: usize, lines: &mut Vec<String>) {
+    let mut file = path(rng);
+    let view_id = format!("view-id-{}", *views + 1);
+    let mut session = vec![format!(concat!(r#"{{"id":{},"method":"new_view","#,
+                                           r#""params":{{"file_path":"{}"}}}}"#), *views, file)];
+    *views += 1;
+    for _ in 0..rng.range(1, 5) {
+        // now and then, a save as
+        if rng.range(0, 3) == 0 {
+            file = path(rng);
+        }
+        session.push(format!(concat!(r#"{{"method":"save","params":"#,
+                                     r#"{{"view_id":"{}","file_path":"{}"}}}}"#), view_id, file));
+    }
+    session.push(format!(r#"{{"method":"close_view","params":{{"view_id":"{}"}}}}"#, view_id));
+    lines.extend(session.into_iter().take(limit));
+}
+
+fn edit(method: &str, params: &str) -> String {
+    format!(r#"{{"method":"edit","params":{{"view_id":"{}","method":"{}","params":{}}}}}"#,
+            VIEW_ID, method, params)
+}
+
+/// How deep a `deep_nesting` message's params are in it: inside the
+/// message, its params, and the RPC. serde_json gives up on anything nested
+/// more than 127 deep, so params nested 124 deep are as deep as it goes.
+pub const ENVELOPE_DEPTH: usize = 3;
+
+/// Keys of the objects in deeply nested params.
+const KEYS: &[&str] = &["children", "node", "value", "items", "next", "attrs"];
+
+/// Generates `messages` messages, starting with a `new_view` request as the
+/// presets do, and the rest plugin RPCs whose params nest objects and
+/// arrays `depth` deep: a plugin's syntax tree, say. A plugin can send core
+/// anything at all, and core forwards it without looking, so this is what
+/// a strategy has to survive rather than what it has to be fast at.
+pub fn deep_nesting(depth: usize, messages: usize, seed: u64) -> Vec<String> {
+    let mut rng = XorShift::new(seed);
+    let mut lines = Vec::with_capacity(messages);
+    if messages > 0 {
+        lines.push(r#"{"id":0,"method":"new_view","params":{}}"#.to_owned());
+    }
+    while lines.len() < messages {
+        lines.push(format!(concat!(r#"{{"method":"plugin","params":{{"command":"plugin_rpc","#,
+                                   r#""view_id":"{}","receiver":"syntax-tree","rpc":"#,
+                                   r#"{{"method":"update_tree","params":{},"#,
+                                   r#""rpc_type":"notification"}}}}}}"#),
+                           VIEW_ID, nested(&mut rng, depth)));
+    }
+    lines
+}
+
+/// Objects and arrays `depth` deep, each in the last, some of them with a
+/// number beside it. It's written from both ends at once rather than
+/// recursively, so that it can be deeper than the stack would allow.
+fn nested(rng: &mut XorShift, depth: usize) -> String {
+    let mut open = String::with_capacity(depth * 12);
+    let mut close = String::with_capacity(depth);
+    for _ in 0..depth {
+        let beside = rng.range(0, 3) == 0;
+        if rng.range(0, 1) == 0 {
+            open.push('{');
+            if beside {
+                open.push_str(&format!(r#""id":{},"#, rng.range(0, 9999)));
+            }
+            open.push_str(&format!(r#""{}":"#, KEYS[rng.range(0, KEYS.len() - 1)]));
+            close.push('}');
+        } else {
+            open.push('[');
+            if beside {
+                open.push_str(&format!("{},", rng.range(0, 9999)));
+            }
+            close.push(']');
+        }
+    }
+    open.push_str(if depth == 0 { "null" } else { "0" });
+    open.extend(close.chars().rev());
+    open
+}
+
+/// What's typed or pasted into a plugin's buffer. Unlike the paths, much of
+/// it has to be escaped.
+const SNIPPETS: &[&str] = &["fn main() {\n", "    let name = \"world\";\n", "}\n", "// TODO: ",
+    "println!(\"{}\\n\", name);", "\t", "é", "Привет", "😀", "if x < 0 {\n    return;\n}\n",
+    "<a href=\"/\">home</a>", "use std::io;\n"];
+
+/// Generates `messages` messages of what core sends a plugin while a file is
+/// edited: an `initialize` with the buffer's info, then an `update` request
+/// for each edit, with its delta against the text as the last one left it,
+/// and now and then a `ping` or a `did_save`. Most edits are a character
+/// typed or deleted; some are pastes, and a few are too big for core to
+/// send, and have no delta, so the plugin has to ask for the text.
+pub fn plugin_session(messages: usize, seed: u64) -> Vec<String> {
+    let mut rng = XorShift::new(seed);
+    let mut lines = Vec::with_capacity(messages);
+    let file = path(&mut rng);
+    let mut len = rng.range(0, 20_000);
+    if messages > 0 {
+        lines.push(format!(concat!(r#"{{"method":"initialize","params":{{"plugin_id":1,"#,
+                                   r#""buffer_info":[{{"buffer_id":1,"path":"{}","#,
+                                   r#""views":["{}"],"rev":0,"buf_size":{},"nb_lines":{},"#,
+                                   r#""syntax":"rust"}}]}}}}"#),
+                           file, VIEW_ID, len, len / 40 + 1));
+    }
+    let mut rev = 0;
+    while lines.len() < messages {
+        match rng.range(0, 19) {
+            0 => lines.push(r#"{"method":"ping","params":{}}"#.to_owned()),
+            1 => lines.push(format!(concat!(r#"{{"method":"did_save","params":"#,
+                                            r#"{{"view_id":"{}","path":"{}"}}}}"#),
+                                    VIEW_ID, file)),
+            _ => {
+                let (delta, new_len, edit_type) = delta(&mut rng, len);
+                lines.push(format!(concat!(r#"{{"id":{},"method":"update","params":"#,
+                                           r#"{{"view_id":"{}","delta":{},"new_len":{},"#,
+                                           r#""rev":{},"edit_type":"{}","author":"core"}}}}"#),
+                                   rev, VIEW_ID, delta, new_len, rev + 1, edit_type));
+                rev += 1;
+                len = new_len;
+            }
+        }
+    }
+    lines
+}
+
+/// An edit to text `len` bytes long, as JSON, with the text's new length
+/// and the edit's type.
+fn delta(rng: &mut XorShift, len: usize) -> (String, usize, &'static str) {
+    let mut els = Vec::new();
+    let copy = |els: &mut Vec<String>, start: usize, end: usize| {
+        if start < end {
+            els.push(format!(r#"{{"copy":[{},{}]}}"#, start, end));
+        }
+    };
+    let (new_len, edit_type) = match rng.range(0, 19) {
+        0 => return ("null".to_owned(), rng.range(0, 100_000), "other"),
+        n if n < 7 && len > 0 => {
+            let start = rng.range(0, len - 1);
+            let end = (start + rng.range(1, 10)).min(len);
+            copy(&mut els, 0, start);
+            copy(&mut els, end, len);
+            (len - (end - start), "delete")
+        }
+        n => {
+            // a paste, or a character typed
+            let text = if n < 10 {
+                (0..rng.range(2, 20)).map(|_| SNIPPETS[rng.range(0, SNIPPETS.len() - 1)])
+                    .collect::<String>()
+            } else {
+                SNIPPETS[rng.range(0, SNIPPETS.len() - 1)].chars().next().unwrap().to_string()
+            };
+            let at = rng.range(0, len);
+            copy(&mut els, 0, at);
+            els.push(format!(r#"{{"insert":{}}}"#, serde_json::to_string(&text).unwrap()));
+            copy(&mut els, at, len);
+            (len + text.len(), "insert")
+        }
+    };
+    (format!(r#"{{"els":[{}],"base_len":{}}}"#, els.join(","), len), new_len, edit_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+
+    #[test]
+    fn cursor_burst() {
+        let lines = Preset::CursorBurst.generate(5000, 3);
+        assert_eq!(lines.len(), 5000);
+        assert_eq!(lines, Preset::CursorBurst.generate(5000, 3));
+        assert_ne!(lines, Preset::CursorBurst.generate(5000, 4));
+        let mut runs = 0;
+        for (i, line) in lines.iter().enumerate().skip(1) {
+            let val = serde_json::from_str::<Value>(line).unwrap();
+            assert_eq!(val["params"]["view_id"], VIEW_ID);
+            assert!(MOVES.contains(&val["params"]["method"].as_str().unwrap()));
+            if *line != lines[i - 1] {
+                runs += 1;
+            }
+        }
+        // mostly repeats
+        assert!(runs < 200, "{}", runs);
+        assert_eq!(Preset::from_name("cursor_burst"), Some(Preset::CursorBurst));
+        assert!(Preset::CursorBurst.generate(0, 1).is_empty());
+    }
+
+    #[test]
+    fn scroll_storm() {
+        let lines = Preset::ScrollStorm.generate(5000, 3);
+        assert_eq!(lines.len(), 5000);
+        assert_eq!(lines, Preset::ScrollStorm.generate(5000, 3));
+        let mut scrolls = 0;
+        for (i, line) in lines.iter().enumerate().skip(1) {
+            let val = serde_json::from_str::<Value>(line).unwrap();
+            let params = &val["params"];
+            let range = params["params"].as_array().unwrap();
+            let (first, last) = (range[0].as_i64().unwrap(), range[1].as_i64().unwrap());
+            assert!(0 <= first && first < last && last <= DOC_LINES, "{}", line);
+            match params["method"].as_str().unwrap() {
+                "scroll" => scrolls += 1,
+                // every request follows a scroll
+                "request_lines" => assert!(lines[i - 1].contains(r#""method":"scroll""#)),
+                other => panic!("{}", other),
+            }
+        }
+        assert!(scrolls >= 2500, "{}", scrolls);
+        assert_eq!(Preset::from_name("scroll_storm"), Some(Preset::ScrollStorm));
+    }
+
+    #[test]
+    fn file_session() {
+        let lines = Preset::FileSession.generate(1000, 3);
+        assert_eq!(lines.len(), 1000);
+        let mut open: Option<String> = None;
+        for (i, line) in lines.iter().enumerate().skip(1) {
+            let val = serde_json::from_str::<Value>(line).unwrap();
+            let params = &val["params"];
+            match val["method"].as_str().unwrap() {
+                "new_view" => {
+                    assert!(open.is_none(), "{}", line);
+                    let id = val["id"].as_u64().unwrap();
+                    open = Some(format!("view-id-{}", id + 1));
+                }
+                "save" => assert_eq!(params["view_id"].as_str(), open.as_deref()),
+                "close_view" => assert_eq!(params["view_id"].as_str(), open.take().as_deref()),
+                other => panic!("{}", other),
+            }
+            if let Some(path) = params["file_path"].as_str() {
+                assert!(path.starts_with('/'));
+                // written as is, with nothing escaped
+                assert!(line.contains(path), "{}", i);
+            }
+        }
+        assert!(lines.iter().any(|l| !l.is_ascii()));
+        assert_eq!(Preset::from_name("file_session"), Some(Preset::FileSession));
+    }
+
+    /// How deep `json` nests, by its brackets: none of the generated
+    /// strings have any.
+    fn depth(json: &str) -> usize {
+        json.bytes().scan(0isize, |depth, b| {
+            match b {
+                b'{' | b'[' => *depth += 1,
+                b'}' | b']' => *depth -= 1,
+                _ => {}
+            }
+            Some(*depth as usize)
+        }).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let limit = 127 - ENVELOPE_DEPTH;
+        let lines = super::deep_nesting(limit, 100, 3);
+        assert_eq!(lines.len(), 100);
+        assert_eq!(lines, super::deep_nesting(limit, 100, 3));
+        for line in &lines[1..] {
+            assert_eq!(depth(line), limit + ENVELOPE_DEPTH);
+            let val = serde_json::from_str::<Value>(line).unwrap();
+            assert_eq!(val["params"]["rpc"]["method"], "update_tree");
+            assert_eq!(depth(&val["params"]["rpc"]["params"].to_string()), limit);
+        }
+        // a level more, and serde_json gives up
+        for line in &super::deep_nesting(limit + 1, 10, 3)[1..] {
+            let err = serde_json::from_str::<Value>(line).unwrap_err();
+            assert!(err.to_string().starts_with("recursion limit exceeded"), "{}", err);
+        }
+        let deep = super::deep_nesting(100_000, 2, 3);
+        assert_eq!(depth(&deep[1]), 100_000 + ENVELOPE_DEPTH);
+        let flat = super::deep_nesting(0, 2, 3);
+        assert!(flat[1].contains(r#""params":null,"#), "{}", flat[1]);
+    }
+
+    #[test]
+    fn plugin_session() {
+        let lines = super::plugin_session(2000, 4);
+        assert_eq!(lines.len(), 2000);
+        assert_eq!(lines, super::plugin_session(2000, 4));
+        let init = serde_json::from_str::<Value>(&lines[0]).unwrap();
+        assert_eq!(init["method"], "initialize");
+        let mut len = init["params"]["buffer_info"][0]["buf_size"].as_u64().unwrap();
+
+        let (mut updates, mut without_delta) = (0, 0);
+        for line in &lines[1..] {
+            let val = serde_json::from_str::<Value>(line).unwrap();
+            if val["method"] != "update" {
+                assert!(val["method"] == "ping" || val["method"] == "did_save", "{}", line);
+                continue;
+            }
+            let params = &val["params"];
+            assert_eq!(val["id"].as_u64(), Some(updates));
+            assert_eq!(params["rev"].as_u64(), Some(updates + 1));
+            updates += 1;
+            let new_len = params["new_len"].as_u64().unwrap();
+            if params["delta"].is_null() {
+                without_delta += 1;
+                len = new_len;
+                continue;
+            }
+            // each delta applies to the text the last one left
+            assert_eq!(params["delta"]["base_len"].as_u64(), Some(len), "{}", line);
+            let applied = params["delta"]["els"].as_array().unwrap().iter().map(|el| {
+                match el.get("copy") {
+                    Some(range) => range[1].as_u64().unwrap() - range[0].as_u64().unwrap(),
+                    None => el["insert"].as_str().unwrap().len() as u64,
+                }
+            }).sum::<u64>();
+            assert_eq!(applied, new_len, "{}", line);
+            len = new_len;
+        }
+        assert!(updates > 1500 && without_delta > 0, "{} {}", updates, without_delta);
+        assert!(lines.iter().any(|l| l.contains(r#"\n"#)));
+    }
+}
@@ -0,0 +1,171 @@
+//! Reordering corpora without breaking them.
+//!
+//! A recorded session is one interleaving of what was happening in each
+//! view. Shuffling picks another, seeded so that it can be reproduced, and
+//! interleaving merges several sessions into one, to get stress corpora
+//! mixing, say, plugin spans into a session of edits and finds.
+//!
+//! Messages are ordered, not shuffled freely: a view's messages stay in
+//! their original order, and a view's first message stays after the
+//! messages without a view that came before it (the `new_view` that created
+//! it, most likely). Messages without a view stay in order among
+//! themselves. When interleaving, view ids that one corpus shares with an
+//! earlier one are renamed, so that separate sessions stay separate;
+//! request ids are left as they are.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use serde_json::{self, Value};
+
+use rng::XorShift;
+
+/// Reorders `lines`, keeping each view's messages in order.
+pub fn shuffle(lines: &[String], seed: u64) -> Vec<String> {
+    interleave(&[lines.to_vec()], seed)
+}
+
+/// Merges `corpora` into one, keeping each view's messages in order.
+pub fn interleave(corpora: &[Vec<String>], seed: u64) -> Vec<String> {
+    let mut streams = Vec::new();
+    let mut taken = HashSet::new();
+    for (source, lines) in corpora.iter().enumerate() {
+        let renamed = rename_views(lines, source, &mut taken);
+        streams.extend(split(renamed));
+    }
+
+    let total = streams.iter().map(|s| s.lines.len()).sum();
+    // how many of each source's global messages have been emitted
+    let mut globals_done = vec![0; corpora.len()];
+    let mut rng = XorShift::new(seed);
+    let mut out = Vec::with_capacity(total);
+    let mut ready = Vec::new();
+    while out.len() < total {
+        ready.clear();
+        ready.extend((0..streams.len()).filter(|&i| {
+            let s = &streams[i];
+            s.next < s.lines.len() && (s.next > 0 || globals_done[s.source] >= s.after)
+        }));
+        // the global stream is always ready until it's exhausted, and every
+        // view is waiting on some prefix of it, so something is ready
+        let i = ready[rng.range(0, ready.len() - 1)];
+        let stream = &mut streams[i];
+        out.push(stream.lines[stream.next].clone());
+        stream.next += 1;
+        if stream.view.is_none() {
+            globals_done[stream.source] += 1;
+        }
+    }
+    out
+}
+
+/// One view's messages from one corpus, or the messages without a view.
+struct Stream {
+    source: usize,
+    view: Option<String>,
+    lines: Vec<String>,
+    /// How many of the source's global messages precede the first of these.
+    after: usize,
+    next: usize,
+}
+
+fn split(lines: Vec<(usize, Option<String>, String)>) -> Vec<Stream> {
+    let mut streams: Vec<Stream> = Vec::new();
+    let mut by_view = HashMap::new();
+    let mut globals = 0;
+    for (source, view, line) in lines {
+        let is_global = view.is_none();
+        let idx = *by_view.entry(view.clone()).or_insert_with(|| {
+            streams.push(Stream { source, view, lines: Vec::new(), after: globals, next: 0 });
+            streams.len() - 1
+        });
+        streams[idx].lines.push(line);
+        if is_global {
+            globals += 1;
+        }
+    }
+    streams
+}
+
+/// Tags each line with its source and view, renaming views that an earlier
+/// source already used.
+fn rename_views(lines: &[String], source: usize, taken: &mut HashSet<String>)
+    -> Vec<(usize, Option<String>, String)>
+{
+    let views = lines.iter().filter_map(|l| view_id(l)).collect::<HashSet<_>>();
+    let renames = views.iter()
+        .filter(|v| taken.contains(*v))
+        .map(|v| (v.clone(), format!("{}-{}", v, source)))
+        .collect::<HashMap<_, _>>();
+    taken.extend(views);
+    taken.extend(renames.values().cloned());
+
+    lines.iter().map(|line| {
+        let view = view_id(line);
+        match view.as_ref().and_then(|v| renames.get(v)) {
+            Some(renamed) => {
+                let mut val = serde_json::from_str::<Value>(line).expect("view_id came from it");
+                val["params"]["view_id"] = Value::String(renamed.clone());
+                (source, Some(renamed.clone()), val.to_string())
+            }
+            None => (source, view, line.clone()),
+        }
+    }).collect()
+}
+
+/// The view a message is addressed to, if any.
+fn view_id(line: &str) -> Option<String> {
+    let val = serde_json::from_str::<Value>(line).ok()?;
+    val.get("params")?.get("view_id")?.as_str().map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TEST_JSON;
+
+    fn views_in_order(original: &[String], reordered: &[String]) -> bool {
+        let keep = |lines: &[String], view: &Option<String>| {
+            lines.iter().filter(|l| view_id(l) == *view).cloned().collect::<Vec<_>>()
+        };
+        let views = original.iter().map(|l| view_id(l)).collect::<HashSet<_>>();
+        views.iter().all(|v| keep(original, v) == keep(reordered, v))
+    }
+
+    #[test]
+    fn shuffle_keeps_views_in_order() {
+        let mut lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        lines.push(r#"{"method":"close_view","params":{"view_id":"view-id-2"}}"#.into());
+        let shuffled = shuffle(&lines, 7);
+        assert_eq!(shuffled, shuffle(&lines, 7));
+        assert!(views_in_order(&lines, &shuffled));
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        let mut expected = lines.clone();
+        expected.sort();
+        assert_eq!(sorted, expected);
+        // the first view-id-1 message stays after new_view
+        let new_view = shuffled.iter().position(|l| l.contains("new_view")).unwrap();
+        let first = shuffled.iter().position(|l| l.contains("view-id-1")).unwrap();
+        assert!(new_view < first);
+        assert!((1..20).any(|seed| shuffle(&lines, seed) != lines));
+    }
+
+    #[test]
+    fn interleave_renames_shared_views() {
+        let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        let merged = interleave(&[lines.clone(), lines.clone()], 3);
+        assert_eq!(merged.len(), lines.len() * 2);
+        let views = merged.iter().filter_map(|l| view_id(l)).collect::<HashSet<_>>();
+        assert!(views.contains("view-id-1") && views.contains("view-id-1-1"));
+        let second = merged.iter().filter(|l| view_id(l).as_ref().map(String::as_str)
+                                          == Some("view-id-1-1"))
+            .map(|l| l.replace("view-id-1-1", "view-id-1"))
+            .map(|l| serde_json::from_str::<Value>(&l).unwrap())
+            .collect::<Vec<_>>();
+        let first = lines.iter().filter(|l| view_id(l).is_some())
+            .map(|l| serde_json::from_str::<Value>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(second, first);
+    }
+}
@@ -0,0 +1,226 @@
+//! Reading and writing corpora.
+//!
+//! A corpus is a file of newline-delimited JSON, one RPC per line, exactly
+//! as it would appear on the wire between xi-core and a frontend. One
+//! captured from a client that pretty-prints can be read as it is; see
+//! `framing`.
+//!
+//! The corpora in `corpora/` are built in, for the benches and tests, and
+//! described in `corpora/corpus.toml`; see `manifest`.
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
+extern crate toml;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+#[cfg(feature = "lz4")]
+extern crate lz4_flex;
+
+pub mod rng;
+pub mod chunked;
+pub mod compression;
+pub mod manifest;
+pub mod interleave;
+pub mod generate;
+pub mod paste;
+pub mod buffer;
+pub mod direction;
+pub mod framing;
+
+pub use buffer::Buffer;
+pub use compression::Compression;
+pub use direction::Direction;
+pub use framing::Framing;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufWriter;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use serde::Serialize;
+
+#[cfg(not(target_arch = "wasm32"))]
+use manifest::{Entry, Manifest};
+
+/// A short editing session: a new view, a paste, some movement and
+/// deletion, and closing the view.
+pub const TEST_JSON: &str = include_str!("../corpora/basic.jsonl");
+
+/// Gesture edits, one or more of each `GestureType`.
+///
+/// These are kept separate from TEST_JSON because xi-core doesn't yet know
+/// about most of these gestures.
+pub const GESTURE_JSON: &str = include_str!("../corpora/gestures.jsonl");
+
+/// Requests with each shape of id: integers (up to u64::MAX), strings (with
+/// and without escapes), and null.
+pub const IDS_JSON: &str = include_str!("../corpora/ids.jsonl");
+
+/// TEST_JSON, with trailing commas and comments: valid JSON5, but not JSON.
+pub const RELAXED_JSON: &str = include_str!("../corpora/relaxed.jsonl");
+
+/// Input method composition, as when typing Japanese: `set_marked_text`
+/// for each keystroke and conversion, with the text and ranges together.
+pub const IME_JSON: &str = include_str!("../corpora/ime.jsonl");
+
+/// TEST_JSON, with the `undo_group` and `annotation` fields that protocol 2
+/// allows on edits, on the edits that change the text.
+pub const ANNOTATED_JSON: &str = include_str!("../corpora/annotated.jsonl");
+
+/// What core sends a frontend over TEST_JSON's session: the theme's style
+/// definitions, and updates to the selection, up to forty regions of it.
+pub const TO_CLIENT_JSON: &str = include_str!("../corpora/to_client.jsonl");
+
+/// Spans from a syntax highlighting plugin, up to one per token of a
+/// screenful of text.
+pub const FROM_PLUGIN_JSON: &str = include_str!("../corpora/from_plugin.jsonl");
+
+/// What core sends a plugin: its initialization, edits as deltas, a save,
+/// and shutting it down.
+pub const TO_PLUGIN_JSON: &str = include_str!("../corpora/to_plugin.jsonl");
+
+/// The manifest describing the built-in corpora.
+pub const MANIFEST_TOML: &str = include_str!("../corpora/corpus.toml");
+
+/// The path that stands for standard input.
+pub const STDIN: &str = "-";
+
+/// Reads the corpus at `path`, returning its non-empty lines. Compressed
+/// corpora are decompressed; see `compression`. If the corpus is described
+/// in a manifest beside it, it's an error for it not to match.
+///
+/// A `path` of `STDIN` reads standard input to the end, as when it's piped
+/// from `xi-core --log-rpc`. That's read uncompressed, and has no manifest.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_jsonl<P: AsRef<Path>>(path: P) -> io::Result<Vec<String>> {
+    read_described(path).map(|(lines, _)| lines)
+}
+
+/// As `read_jsonl`, also returning the corpus's manifest entry, if it has
+/// one.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_described<P: AsRef<Path>>(path: P) -> io::Result<(Vec<String>, Option<Entry>)> {
+    read_framed(path, Framing::Lines)
+}
+
+/// As `read_described`, with the messages framed by `framing` rather than
+/// one per line.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_framed<P: AsRef<Path>>(path: P, framing: Framing)
+    -> io::Result<(Vec<String>, Option<Entry>)>
+{
+    let path = path.as_ref();
+    if path == Path::new(STDIN) {
+        let stdin = io::stdin();
+        return Ok((framing.read(stdin.lock())?, None));
+    }
+    let compression = Compression::from_path(path);
+    let lines = framing.read(compression.decoder(File::open(path)?)?)?;
+    let file = path.file_name().and_then(|f| f.to_str()).unwrap_or("");
+    let entry = Manifest::beside(path)?.and_then(|m| m.entry(file).cloned());
+    if let Some(ref entry) = entry {
+        entry.check(&lines).map_err(|mismatches| {
+            let mismatches = mismatches.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+            io::Error::new(io::ErrorKind::InvalidData,
+                           format!("doesn't match {}: {}", manifest::MANIFEST,
+                                   mismatches.join(", ")))
+        })?;
+    }
+    Ok((lines, entry))
+}
+
+/// Reads the non-empty lines of a corpus from `reader`.
+pub fn read_lines<R: BufRead>(reader: R) -> io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            lines.push(line);
+        }
+    }
+    Ok(lines)
+}
+
+/// Writes `messages` to `path` in the corpus format, one message per line,
+/// compressed according to the extension.
+///
+/// This is the inverse of `read_jsonl`, for persisting generated or mutated
+/// typed messages.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_jsonl<T: Serialize, P: AsRef<Path>>(messages: &[T], path: P) -> io::Result<()> {
+    let compression = Compression::from_path(&path);
+    let mut writer = BufWriter::new(compression.encoder(File::create(path)?)?);
+    write_lines(messages, &mut writer)?;
+    writer.flush()
+}
+
+/// Writes `messages` to `writer`, one message per line.
+pub fn write_lines<T: Serialize, W: Write>(messages: &[T], mut writer: W) -> io::Result<()> {
+    for msg in messages {
+        serde_json::to_writer(&mut writer, msg)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Coalesces consecutive lines into JSON-RPC batches of up to `size`
+/// messages, as some clients do.
+///
+/// The lines are assumed to be valid JSON; they're spliced in unchanged.
+pub fn batch(lines: &[String], size: usize) -> Vec<String> {
+    lines.chunks(size.max(1))
+        .map(|chunk| format!("[{}]", chunk.join(",")))
+        .collect()
+}
+
+/// A stable hash of a corpus's contents, for recognizing it across runs.
+pub fn hash(lines: &[String]) -> String {
+    // 64-bit FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for line in lines {
+        for &b in line.as_bytes().iter().chain(b"\n") {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+
+    #[test]
+    fn batches() {
+        let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        let batches = batch(&lines, 4);
+        assert_eq!(batches.len(), (lines.len() + 3) / 4);
+        let mut messages = Vec::new();
+        for b in &batches {
+            messages.extend(serde_json::from_str::<Vec<Value>>(b).unwrap());
+        }
+        let expected = lines.iter().map(|l| serde_json::from_str::<Value>(l).unwrap());
+        assert!(messages.into_iter().eq(expected));
+    }
+
+    #[test]
+    fn one_message_per_line() {
+        let messages = vec![json!({"method": "a", "params": {"chars": "x\ny"}}),
+                            json!({"method": "b", "params": []})];
+        let mut out = Vec::new();
+        write_lines(&messages, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.ends_with('\n'));
+    }
+}
@@ -0,0 +1,187 @@
+//! Corpus manifests: a `corpus.toml` beside the corpora, describing each.
+//!
+//! A manifest entry names a corpus and records what it should contain, so
+//! that a report can say which corpus it ran over in terms that mean
+//! something elsewhere, and so that a corpus that's been edited or
+//! truncated since it was described is caught when it's loaded rather than
+//! showing up as a change in the numbers.
+
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use toml;
+
+use hash;
+use Direction;
+
+/// The name of a manifest file.
+pub const MANIFEST: &str = "corpus.toml";
+
+/// The revision of xi's RPC protocol that the strategies' types describe.
+/// Bump this when they change in a way that older corpora don't exercise.
+///
+/// 2 added the optional `undo_group` and `annotation` fields to edits.
+pub const PROTOCOL: u32 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Manifest {
+    #[serde(rename = "corpus", default)]
+    pub corpora: Vec<Entry>,
+}
+
+/// What a corpus should contain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    /// The corpus's file name, relative to the manifest.
+    pub file: String,
+    pub description: String,
+    /// The number of messages, that is, non-empty lines.
+    pub messages: usize,
+    /// `hash` of the messages, if it's pinned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// The seed of the generator that produced the corpus, if one did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Who sends the messages to whom.
+    #[serde(default)]
+    pub direction: Direction,
+    /// The protocol revision the messages are written against.
+    pub protocol: u32,
+}
+
+/// A way in which a corpus doesn't match its entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    Messages { expected: usize, found: usize },
+    Hash { expected: String, found: String },
+    Protocol { found: u32 },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mismatch::Messages { expected, found } => {
+                write!(f, "expected {} messages, found {}", expected, found)
+            }
+            Mismatch::Hash { ref expected, ref found } => {
+                write!(f, "expected hash {}, found {}", expected, found)
+            }
+            Mismatch::Protocol { found } => {
+                write!(f, "written for protocol {}, but only {} is understood", found, PROTOCOL)
+            }
+        }
+    }
+}
+
+impl Manifest {
+    pub fn parse(toml: &str) -> Result<Manifest, String> {
+        toml::from_str(toml).map_err(|e| e.to_string())
+    }
+
+    /// Loads the manifest at `path`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Manifest> {
+        let path = path.as_ref();
+        Manifest::parse(&fs::read_to_string(path)?).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })
+    }
+
+    /// Loads the manifest in the same directory as the corpus at `path`,
+    /// if there is one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn beside<P: AsRef<Path>>(path: P) -> io::Result<Option<Manifest>> {
+        let manifest = match path.as_ref().parent() {
+            Some(dir) => dir.join(MANIFEST),
+            None => return Ok(None),
+        };
+        if manifest.is_file() { Manifest::load(manifest).map(Some) } else { Ok(None) }
+    }
+
+    /// The entry for the corpus whose file name is `file`.
+    pub fn entry(&self, file: &str) -> Option<&Entry> {
+        self.corpora.iter().find(|e| e.file == file)
+    }
+}
+
+impl Entry {
+    /// Checks `lines` against this entry.
+    pub fn check(&self, lines: &[String]) -> Result<(), Vec<Mismatch>> {
+        let mut mismatches = Vec::new();
+        if lines.len() != self.messages {
+            mismatches.push(Mismatch::Messages { expected: self.messages, found: lines.len() });
+        }
+        if let Some(ref expected) = self.hash {
+            let found = hash(lines);
+            if found != *expected {
+                mismatches.push(Mismatch::Hash { expected: expected.clone(), found });
+            }
+        }
+        if self.protocol > PROTOCOL {
+            mismatches.push(Mismatch::Protocol { found: self.protocol });
+        }
+        if mismatches.is_empty() { Ok(()) } else { Err(mismatches) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {ANNOTATED_JSON, FROM_PLUGIN_JSON, GESTURE_JSON, IDS_JSON, IME_JSON, MANIFEST_TOML,
+         RELAXED_JSON, TEST_JSON, TO_CLIENT_JSON, TO_PLUGIN_JSON};
+
+    fn lines(corpus: &str) -> Vec<String> {
+        corpus.lines().filter(|l| !l.trim().is_empty()).map(String::from).collect()
+    }
+
+    #[test]
+    fn built_in_corpora_match() {
+        let manifest = Manifest::parse(MANIFEST_TOML).unwrap();
+        let built_in = [("basic.jsonl", TEST_JSON), ("gestures.jsonl", GESTURE_JSON),
+                        ("ids.jsonl", IDS_JSON), ("relaxed.jsonl", RELAXED_JSON),
+                        ("ime.jsonl", IME_JSON), ("annotated.jsonl", ANNOTATED_JSON),
+                        ("to_client.jsonl", TO_CLIENT_JSON),
+                        ("from_plugin.jsonl", FROM_PLUGIN_JSON),
+                        ("to_plugin.jsonl", TO_PLUGIN_JSON)];
+        assert_eq!(manifest.corpora.len(), built_in.len());
+        for &(file, corpus) in &built_in {
+            let entry = manifest.entry(file).unwrap();
+            assert_eq!(entry.check(&lines(corpus)), Ok(()), "{}", file);
+            assert_eq!(entry.direction.corpora().contains(&corpus),
+                       file != "relaxed.jsonl", "{}", file);
+        }
+    }
+
+    #[test]
+    fn mismatches() {
+        let manifest = Manifest::parse(r#"
+            [[corpus]]
+            name = "two"
+            file = "two.jsonl"
+            description = ""
+            messages = 2
+            seed = 7
+            protocol = 99
+        "#).unwrap();
+        let entry = manifest.entry("two.jsonl").unwrap();
+        assert_eq!(entry.seed, Some(7));
+        assert_eq!(entry.direction, Direction::C2S);
+        let mut pinned = entry.clone();
+        pinned.hash = Some(hash(&lines("[1]\n[2]")));
+        pinned.protocol = PROTOCOL;
+        assert_eq!(pinned.check(&lines("[1]\n\n[2]\n")), Ok(()));
+        assert_eq!(entry.check(&lines("[1]")),
+                   Err(vec![Mismatch::Messages { expected: 2, found: 1 },
+                            Mismatch::Protocol { found: 99 }]));
+        assert!(pinned.check(&lines("[1]\n[3]")).unwrap_err()[0].to_string()
+                .starts_with("expected hash"));
+        assert!(Manifest::parse("[[corpus]]\nname = 1").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Splitting big pastes into several inserts.
+//!
+//! A frontend sends a paste as one insert, however big it is, and core can't
+//! do anything else until it's parsed the whole of it. The proposal modeled
+//! here is for frontends to send it in chunks of a few KB instead, as
+//! consecutive inserts sharing an undo group, so that no one message takes
+//! long to parse; core would put them back together, as the strategies
+//! crate's `reassemble` does. Whether that costs throughput is what the
+//! `paste_*` benches are for.
+//!
+//! Inserts that already have an undo group keep it in each of their chunks;
+//! the others are given one past the highest group in the corpus, and so on,
+//! so that the chunks of one paste can't be mistaken for another's.
+
+use serde_json::{self, Value};
+
+/// Returns `lines` with each insert whose chars are longer than
+/// `chunk_size` bytes split into inserts of at most that many. Everything
+/// else, including lines that aren't JSON, is left as it is.
+pub fn chunk(lines: &[String], chunk_size: usize) -> Vec<String> {
+    assert!(chunk_size > 0, "chunk size must be positive");
+    let parsed = lines.iter().map(|line| serde_json::from_str::<Value>(line).ok())
+        .collect::<Vec<_>>();
+    let mut next_group = parsed.iter().flatten()
+        .filter_map(|val| val["params"]["undo_group"].as_u64())
+        .max()
+        .map_or(0, |group| group + 1);
+
+    let mut out = Vec::with_capacity(lines.len());
+    for (line, val) in lines.iter().zip(parsed) {
+        let mut val = match val {
+            Some(val) if is_long_insert(&val, chunk_size) => val,
+            _ => {
+                out.push(line.clone());
+                continue;
+            }
+        };
+        let group = match val["params"]["undo_group"].as_u64() {
+            Some(group) => group,
+            None => {
+                next_group += 1;
+                next_group - 1
+            }
+        };
+        val["params"]["undo_group"] = Value::from(group);
+        let chars = val["params"]["params"]["chars"].take();
+        for piece in pieces(chars.as_str().unwrap_or_default(), chunk_size) {
+            val["params"]["params"]["chars"] = Value::from(piece);
+            out.push(val.to_string());
+        }
+    }
+    out
+}
+
+fn is_long_insert(val: &Value, chunk_size: usize) -> bool {
+    let params = &val["params"];
+    val["method"] == "edit" && params["method"] == "insert"
+        && params["params"]["chars"].as_str().map_or(false, |chars| chars.len() > chunk_size)
+}
+
+/// `s` in pieces of at most `size` bytes, split on char boundaries. A char
+/// longer than `size` is a piece of its own.
+fn pieces(s: &str, size: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(chars: &str, undo_group: Option<u64>) -> String {
+        let mut val = json!({"method": "edit", "params": {"view_id": "view-id-1",
+            "method": "insert", "params": {"chars": chars}}});
+        if let Some(group) = undo_group {
+            val["params"]["undo_group"] = json!(group);
+        }
+        val.to_string()
+    }
+
+    #[test]
+    fn chunks() {
+        let text = "héllo, wörld ".repeat(20);
+        let other = json!({"method": "edit", "params": {"view_id": "view-id-1",
+            "method": "scroll", "params": [0, 18]}}).to_string();
+        let lines = vec![insert(&text, None), other.clone(), insert("short", None),
+                         insert(&text, Some(7)), insert(&text, None), "not json".to_owned()];
+        let chunked = chunk(&lines, 16);
+        let vals = chunked.iter().filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .collect::<Vec<_>>();
+
+        let mut joined = String::new();
+        let mut groups = Vec::new();
+        for val in &vals {
+            if let Some(chars) = val["params"]["params"]["chars"].as_str() {
+                assert!(chars.len() <= 16);
+                joined.push_str(chars);
+                groups.push(val["params"]["undo_group"].as_u64());
+            }
+        }
+        assert_eq!(joined, format!("{0}short{0}{0}", text));
+        // the first paste, the short insert, and the other two pastes
+        let per_paste = pieces(&text, 16).len();
+        assert!(groups[..per_paste].iter().all(|&g| g == Some(8)));
+        assert_eq!(groups[per_paste], None);
+        assert!(groups[per_paste + 1..2 * per_paste + 1].iter().all(|&g| g == Some(7)));
+        assert!(groups[2 * per_paste + 1..].iter().all(|&g| g == Some(9)));
+
+        assert_eq!(chunked[per_paste], other);
+        assert_eq!(chunked.last().unwrap(), "not json");
+        assert_eq!(chunk(&lines[1..3], 16), &lines[1..3]);
+    }
+
+    #[test]
+    fn splits_on_char_boundaries() {
+        assert_eq!(pieces("aé€", 2), vec!["a", "é", "€"]);
+        assert_eq!(pieces("abcdef", 4), vec!["abcd", "ef"]);
+        assert_eq!(pieces("", 4), Vec::<&str>::new());
+    }
+}
@@ -0,0 +1,68 @@
+//! Captures details of the build (toolchain, features, dependency versions)
+//! so that they can be included in reports.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The dependencies whose versions are most likely to move the results.
+const TRACKED_DEPS: &[&str] = &["serde", "serde_json", "simd-json", "tikv-jemallocator",
+    "mimalloc"];
+
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let rustc_version = Command::new(&rustc).arg("--version").output().ok()
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|s| s.trim().to_owned())
+        .unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=DER_BENCH_RUSTC_VERSION={}", rustc_version);
+
+    let mut features = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_")
+                    .map(|f| f.to_lowercase().replace('_', "-")))
+        .collect::<Vec<_>>();
+    features.sort();
+    println!("cargo:rustc-env=DER_BENCH_FEATURES={}", features.join(","));
+    // the profile's, which `run --opt-matrix` overrides
+    println!("cargo:rustc-env=DER_BENCH_OPT_LEVEL={}", env::var("OPT_LEVEL").unwrap_or_default());
+    // set by hand, to check the shm ring with loom; see src/shm.rs
+    println!("cargo:rustc-check-cfg=cfg(loom)");
+
+    // in a workspace, the lock file is next to the workspace's manifest
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let lock_path = Path::new(&manifest_dir).ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.exists());
+    let deps = lock_path.as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|lock| locked_versions(&lock))
+        .unwrap_or_default();
+    println!("cargo:rustc-env=DER_BENCH_DEPS={}", deps.join(","));
+    if let Some(path) = lock_path {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
+
+/// Returns "name version" for each tracked package in a Cargo.lock.
+fn locked_versions(lock: &str) -> Vec<String> {
+    let mut versions = Vec::new();
+    let mut name = None;
+    for line in lock.lines() {
+        let line = line.trim();
+        if line == "[[package]]" {
+            name = None;
+        } else if let Some(n) = line.strip_prefix("name = ") {
+            name = Some(n.trim_matches('"').to_owned());
+        } else if let Some(v) = line.strip_prefix("version = ") {
+            match name {
+                Some(ref n) if TRACKED_DEPS.contains(&n.as_str()) =>
+                    versions.push(format!("{} {}", n, v.trim_matches('"'))),
+                _ => (),
+            }
+        }
+    }
+    versions.sort();
+    versions
+}
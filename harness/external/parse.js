@@ -0,0 +1,38 @@
+#!/usr/bin/env node
+// An external runner for der_bench: JSON.parse over each line of a corpus.
+//
+// usage: node parse.js CORPUS PASSES
+//
+// See harness/src/external.rs.
+
+"use strict";
+
+const fs = require("fs");
+
+function parseAll(lines) {
+    let errors = 0;
+    for (const line of lines) {
+        try {
+            JSON.parse(line);
+        } catch (e) {
+            errors += 1;
+        }
+    }
+    return errors;
+}
+
+const [path, passes] = [process.argv[2], parseInt(process.argv[3], 10)];
+const lines = fs.readFileSync(path, "utf8").split("\n").filter(line => line.length > 0);
+// a pass to warm up, and to count errors
+const errors = parseAll(lines);
+const start = process.hrtime.bigint();
+for (let i = 0; i < passes; i++) {
+    parseAll(lines);
+}
+const elapsed = process.hrtime.bigint() - start;
+console.log(JSON.stringify({
+    name: "node_json",
+    iterations: passes,
+    elapsed_ns: Number(elapsed),
+    errors,
+}));
@@ -0,0 +1,39 @@
+#!/usr/bin/env python3
+"""An external runner for der_bench: json.loads over each line of a corpus.
+
+usage: parse.py CORPUS PASSES
+
+See harness/src/external.rs.
+"""
+
+import json
+import sys
+import time
+
+
+def parse_all(lines):
+    errors = 0
+    for line in lines:
+        try:
+            json.loads(line)
+        except ValueError:
+            errors += 1
+    return errors
+
+
+def main():
+    path, passes = sys.argv[1], int(sys.argv[2])
+    with open(path, encoding="utf-8") as f:
+        lines = [line for line in f.read().split("\n") if line]
+    # a pass to warm up, and to count errors
+    errors = parse_all(lines)
+    start = time.perf_counter_ns()
+    for _ in range(passes):
+        parse_all(lines)
+    elapsed = time.perf_counter_ns() - start
+    print(json.dumps({"name": "python_json", "iterations": passes,
+                      "elapsed_ns": elapsed, "errors": errors}))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,49 @@
+#!/bin/sh
+# Profile-guided optimization for the strategies' benches, as `der_bench pgo`
+# does for der_bench: build the benches instrumented and run the ones
+# matching TRAIN, merge the profiles, then run the ones matching BENCH (all
+# of them, if not given) without and with the profiles.
+#
+# usage: harness/scripts/pgo_bench.sh TRAIN [BENCH]
+#
+# For example, to train on the cursor_burst benches and measure the rest:
+#
+#     harness/scripts/pgo_bench.sh cursor_burst file_session
+#
+# Needs nightly, for the benches, and llvm-profdata from the same LLVM as
+# rustc: `rustup component add llvm-tools-preview`, or set LLVM_PROFDATA.
+# See harness/src/pgo.rs.
+
+set -eu
+
+if [ $# -lt 1 ]; then
+    sed -n 's/^# usage: /usage: /p' "$0" >&2
+    exit 2
+fi
+train=$1
+bench=${2:-}
+
+root=$(cd "$(dirname "$0")/../.." && pwd)
+dir=$root/target/pgo-bench
+profiles=$dir/profiles
+rm -rf "$profiles"
+
+if [ -z "${LLVM_PROFDATA:-}" ]; then
+    LLVM_PROFDATA=$(find "$(rustc --print sysroot)/lib/rustlib" -name llvm-profdata \
+        -path '*/bin/*' 2>/dev/null | head -n 1)
+    LLVM_PROFDATA=${LLVM_PROFDATA:-llvm-profdata}
+fi
+
+bench() {
+    cargo bench --manifest-path "$root/Cargo.toml" -p xi-der-strategies \
+        --target-dir "$dir/$1" -- "$2"
+}
+
+echo "training on '$train'"
+RUSTFLAGS="${RUSTFLAGS:-} -Cprofile-generate=$profiles" bench instrumented "$train" >/dev/null
+"$LLVM_PROFDATA" merge -o "$dir/merged.profdata" "$profiles"
+
+echo "release:"
+bench release "$bench"
+echo "pgo:"
+RUSTFLAGS="${RUSTFLAGS:-} -Cprofile-use=$dir/merged.profdata" bench pgo "$bench"
@@ -0,0 +1,239 @@
+//! Where a strategy's time goes, message by message.
+//!
+//! `measure` times whole passes over a corpus, which says which strategy is
+//! fastest but not what it spends its time on. Here each message is timed
+//! on its own, in corpus order, so that the caches and the branch predictor
+//! see what they would in a pass, and the times are added up by method, so
+//! as to say which RPCs are the most expensive to parse, and so the most
+//! worth redesigning, or picked out one by one, the slowest first, as the
+//! makings of a micro-benchmark for whatever makes them slow.
+//!
+//! Reading the clock twice a message costs some tens of nanoseconds, which
+//! is as long as some messages take to parse. The least that costs is
+//! measured first and taken off each message's time, which gets the sum
+//! close to a pass's time, but leaves the time of the smallest messages
+//! rough.
+
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::time::Instant;
+
+use corpus::Buffer;
+use estimate::message_type;
+use runner::duration_ns;
+use sink::Sink;
+use strategy::Strategy;
+
+/// The time a strategy took over the messages with one method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodTime {
+    /// As `estimate::message_type` has it: edits are qualified, as in
+    /// "edit/insert".
+    pub method: String,
+    pub messages: usize,
+    pub bytes: usize,
+    /// The time for all of them, in nanoseconds a pass.
+    pub ns: f64,
+}
+
+/// A strategy's time over a corpus, by method.
+#[derive(Debug, Clone)]
+pub struct Breakdown {
+    pub strategy: &'static str,
+    /// The most expensive first.
+    pub methods: Vec<MethodTime>,
+}
+
+impl MethodTime {
+    pub fn ns_per_message(&self) -> f64 {
+        self.ns / self.messages as f64
+    }
+}
+
+/// One of the slowest messages in a corpus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slow {
+    /// Where it is in the corpus, counting from 1, as `corpus::read_jsonl`
+    /// reads it, without the blank lines.
+    pub index: usize,
+    /// Its mean time, in nanoseconds.
+    pub ns: f64,
+    /// Its length, in bytes.
+    pub bytes: usize,
+    pub method: String,
+    /// Its text, cut short after `TEXT_CHARS` characters.
+    pub text: String,
+}
+
+/// How much of a slow message's text is kept.
+pub const TEXT_CHARS: usize = 80;
+
+impl Breakdown {
+    /// Adds up `times`, from `time_lines`, by the method of each line of
+    /// `corpus`.
+    pub fn of(strategy: &'static str, corpus: &Buffer, times: &[f64]) -> Breakdown {
+        let mut methods = BTreeMap::<String, MethodTime>::new();
+        for (line, &ns) in corpus.lines().zip(times) {
+            let method = message_type(line);
+            let entry = methods.entry(method.clone())
+                .or_insert_with(|| MethodTime { method, messages: 0, bytes: 0, ns: 0.0 });
+            entry.messages += 1;
+            entry.bytes += line.len();
+            entry.ns += ns;
+        }
+        let mut methods = methods.into_iter().map(|(_, m)| m).collect::<Vec<_>>();
+        methods.sort_by(|a, b| b.ns.total_cmp(&a.ns));
+        Breakdown { strategy, methods }
+    }
+
+    /// The time for the whole corpus, in nanoseconds a pass.
+    pub fn ns(&self) -> f64 {
+        self.methods.iter().map(|m| m.ns).sum()
+    }
+}
+
+/// The least time between two readings of the clock, in nanoseconds.
+fn clock_overhead() -> f64 {
+    (0..1000).map(|_| {
+        let start = Instant::now();
+        duration_ns(black_box(start).elapsed())
+    }).fold(f64::INFINITY, f64::min)
+}
+
+/// The mean time `strategy` took over each line of `corpus`, in
+/// nanoseconds, over `passes` passes, after one to warm up. Errors are
+/// timed like anything else.
+pub fn time_lines<S>(strategy: &S, corpus: &Buffer, passes: u64) -> Vec<f64>
+    where S: Strategy + ?Sized
+{
+    let _span = info_span!("time_lines", strategy = strategy.name(), passes).entered();
+    let overhead = clock_overhead();
+    let mut totals = vec![0.0; corpus.len()];
+    let mut sink = Sink::new();
+    strategy.prepare();
+    for line in corpus.lines() {
+        let _ = strategy.parse_into(line, &mut sink);
+    }
+    for _ in 0..passes {
+        for (total, line) in totals.iter_mut().zip(corpus.lines()) {
+            let start = Instant::now();
+            let _ = strategy.parse_into(line, &mut sink);
+            *total += (duration_ns(start.elapsed()) - overhead).max(0.0);
+        }
+    }
+    strategy.reset();
+    black_box(sink);
+    totals.iter().map(|t| t / passes.max(1) as f64).collect()
+}
+
+/// Times `strategy` over each line of `corpus`, and adds the times up by
+/// method.
+pub fn by_method<S>(strategy: &S, corpus: &Buffer, passes: u64) -> Breakdown
+    where S: Strategy + ?Sized
+{
+    Breakdown::of(strategy.name(), corpus, &time_lines(strategy, corpus, passes))
+}
+
+/// The `n` lines of `corpus` with the greatest `times`, from `time_lines`,
+/// the slowest first.
+pub fn slowest(corpus: &Buffer, times: &[f64], n: usize) -> Vec<Slow> {
+    let mut order = (0..times.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| times[b].total_cmp(&times[a]));
+    let lines = corpus.lines().collect::<Vec<_>>();
+    order.into_iter().take(n).map(|idx| {
+        let line = lines[idx];
+        let mut text = line.chars().take(TEXT_CHARS).collect::<String>();
+        if text.len() < line.len() {
+            text.push('…');
+        }
+        Slow { index: idx + 1, ns: times[idx], bytes: line.len(), method: message_type(line),
+               text }
+    }).collect()
+}
+
+/// Prints a table for each breakdown, of each method's share of the
+/// strategy's time.
+pub fn print_breakdowns(breakdowns: &[Breakdown]) {
+    for b in breakdowns {
+        println!("{} ({:.0} ns/iter):", b.strategy, b.ns());
+        println!("    {:<36}{:>10}{:>14}{:>14}{:>10}",
+                 "method", "messages", "ns/message", "ns/byte", "share%");
+        let total = b.ns();
+        for m in &b.methods {
+            println!("    {:<36}{:>10}{:>14.1}{:>14.2}{:>10.1}",
+                     m.method, m.messages, m.ns_per_message(), m.ns / m.bytes.max(1) as f64,
+                     m.ns / total * 100.0);
+        }
+        println!();
+    }
+}
+
+/// Prints a strategy's slowest messages.
+pub fn print_slowest(strategy: &str, slow: &[Slow]) {
+    println!("{}, slowest messages:", strategy);
+    println!("    {:>8}{:>12}{:>10}  {:<28}{}", "message", "ns", "bytes", "method", "text");
+    for s in slow {
+        println!("    {:>8}{:>12.0}{:>10}  {:<28}{}", s.index, s.ns, s.bytes, s.method, s.text);
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use corpus;
+    use xi_der_strategies::error::ParseError;
+
+    use super::*;
+
+    /// Sleeps longer for longer messages.
+    struct Sleepy;
+
+    impl Strategy for Sleepy {
+        fn name(&self) -> &'static str {
+            "sleepy"
+        }
+
+        fn parse_into(&self, json: &str, _sink: &mut Sink) -> Result<(), ParseError> {
+            ::std::thread::sleep(Duration::from_micros(json.len() as u64 * 20));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn methods() {
+        let undo = r#"{"method":"edit","params":{"view_id":"v","method":"undo","params":[]}}"#;
+        let started = r#"{"method":"client_started","params":{}}"#;
+        let lines = Buffer::new(&[undo, started, undo]);
+        assert_eq!(time_lines(&Sleepy, &lines, 2).len(), 3);
+
+        let b = by_method(&Sleepy, &lines, 2);
+        let found = b.methods.iter().map(|m| (m.method.as_str(), m.messages)).collect::<Vec<_>>();
+        assert_eq!(found, [("edit/undo", 2), ("client_started", 1)]);
+        assert!(b.methods[0].ns_per_message() > b.methods[1].ns_per_message());
+        assert!(b.ns() >= 3.0 * 20_000.0 * started.len() as f64);
+
+        // every line of a real corpus is counted once
+        let buffer = Buffer::new(corpus::TEST_JSON.lines());
+        let b = by_method(&::strategy::Hand, &buffer, 1);
+        assert_eq!(b.methods.iter().map(|m| m.messages).sum::<usize>(), buffer.len());
+    }
+
+    #[test]
+    fn slowest_first() {
+        let started = r#"{"method":"client_started","params":{}}"#;
+        let params = format!(r#"{{"chars":"{}"}}"#, "a".repeat(100));
+        let insert = [r#"{"method":"edit","params":{"view_id":"v","method":"insert","params":"#,
+                      &params[..], "}}"].concat();
+        let lines = Buffer::new(&[started, insert.as_str(), started]);
+        let times = [10.0, 30.0, 20.0];
+        let slow = slowest(&lines, &times, 2);
+        let found = slow.iter().map(|s| (s.index, s.ns, s.method.as_str())).collect::<Vec<_>>();
+        assert_eq!(found, [(2, 30.0, "edit/insert"), (3, 20.0, "client_started")]);
+        assert_eq!(slow[0].text.chars().count(), TEXT_CHARS + 1);
+        assert!(insert.starts_with(slow[0].text.trim_end_matches('…')));
+        assert_eq!((slow[0].bytes, slow[1].text.as_str()), (insert.len(), started));
+        assert_eq!(slowest(&lines, &times, 10).len(), 3);
+    }
+}
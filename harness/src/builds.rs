@@ -0,0 +1,188 @@
+//! Rebuilding der_bench with other settings, running each build, and putting
+//! their results side by side; for `opt_matrix` and `pgo`.
+//!
+//! Each `Build` is made by running cargo on the workspace this binary was
+//! built from, so the source has to still be there, with whatever the build
+//! changes set in cargo's environment. Each has a target directory of its
+//! own under target/, so that the builds don't invalidate each other. The
+//! build is run with a `run` command, writing a report, which is read back.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+use serde_json::{self, Value};
+
+/// One build's settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Build {
+    /// Used in reports, and as the name of its target directory.
+    pub name: String,
+    /// The directory under target/ that its target directory is in.
+    pub group: &'static str,
+    /// Set in cargo's environment, such as `CARGO_PROFILE_RELEASE_LTO`.
+    pub env: Vec<(String, String)>,
+}
+
+/// The workspace der_bench was built from.
+pub fn workspace() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..")
+}
+
+impl Build {
+    pub fn new(group: &'static str, name: &str) -> Build {
+        Build { name: name.to_owned(), group, env: Vec::new() }
+    }
+
+    pub fn env(mut self, key: &str, value: &str) -> Build {
+        self.env.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Adds `flags` to `RUSTFLAGS`, after any already in the environment.
+    pub fn rustflags(self, flags: &str) -> Build {
+        let rustflags = match env::var("RUSTFLAGS") {
+            Ok(ref existing) if !existing.is_empty() => format!("{} {}", existing, flags),
+            _ => flags.to_owned(),
+        };
+        self.env("RUSTFLAGS", &rustflags)
+    }
+
+    /// Builds der_bench, in release mode, with `features` passed to cargo as
+    /// they are, and runs it with `run_args`, which start with "run".
+    /// Returns the report it wrote.
+    pub fn run(&self, run_args: &[String], features: Option<&str>) -> Result<Value, String> {
+        let _span = info_span!("build", build = self.name.as_str()).entered();
+        let workspace = workspace();
+        let output = env::temp_dir()
+            .join(format!("der_bench_build_{}_{}.json", process::id(), self.name));
+        let mut cargo = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+        cargo.args(&["run", "--release", "--quiet", "-p", "xi-der-cli", "--bin", "der_bench"])
+            .arg("--manifest-path").arg(workspace.join("Cargo.toml"))
+            .arg("--target-dir").arg(workspace.join("target").join(self.group).join(&self.name))
+            .envs(self.env.iter().map(|&(ref k, ref v)| (k, v)));
+        if let Some(features) = features {
+            cargo.arg("--features").arg(features);
+        }
+        cargo.arg("--").args(run_args).arg("--output").arg(&output);
+
+        println!("{}:", self.name);
+        let status = cargo.status().map_err(|e| format!("cargo: {}", e))?;
+        println!();
+        if !status.success() {
+            return Err(format!("{}: cargo {}", self.name, status));
+        }
+        let report = fs::read_to_string(&output).map_err(|e| format!("{}: {}", self.name, e));
+        let _ = fs::remove_file(&output);
+        serde_json::from_str::<Value>(&report?).map_err(|e| format!("{}: {}", self.name, e))
+    }
+}
+
+/// Each strategy's ns/message in each build.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub builds: Vec<String>,
+    /// In the order the strategies were first reported; `None` where a
+    /// build didn't report on one.
+    pub rows: Vec<(String, Vec<Option<f64>>)>,
+}
+
+impl Comparison {
+    pub fn new(builds: Vec<String>) -> Comparison {
+        Comparison { builds, rows: Vec::new() }
+    }
+
+    /// Adds the results in `report`, as `run --output` writes it, to the
+    /// column for the `idx`th build.
+    pub fn add_report(&mut self, idx: usize, report: &Value) -> Result<(), String> {
+        let results = report["results"].as_array().ok_or("report without results")?;
+        for result in results {
+            let (strategy, ns) = match (result["strategy"].as_str(),
+                                        result["ns_per_message"].as_f64()) {
+                (Some(strategy), Some(ns)) => (strategy, ns),
+                _ => return Err(format!("malformed result: {}", result)),
+            };
+            let width = self.builds.len();
+            let row = match self.rows.iter().position(|&(ref name, _)| name == strategy) {
+                Some(row) => row,
+                None => {
+                    self.rows.push((strategy.to_owned(), vec![None; width]));
+                    self.rows.len() - 1
+                }
+            };
+            self.rows[row].1[idx] = Some(ns);
+        }
+        Ok(())
+    }
+
+    /// The rank of each strategy in the `idx`th build, 1 being the fastest,
+    /// in the order of `rows`.
+    pub fn ranks(&self, idx: usize) -> Vec<Option<usize>> {
+        self.rows.iter()
+            .map(|&(_, ref cols)| cols[idx].map(|ns| {
+                1 + self.rows.iter().filter(|&&(_, ref other)| {
+                    other[idx].map_or(false, |other| other < ns)
+                }).count()
+            }))
+            .collect()
+    }
+
+    /// Prints ns/message, and the rank in parentheses, with a column for
+    /// each build.
+    pub fn print(&self) {
+        print!("{:<20}", "ns/message");
+        for build in &self.builds {
+            print!("{:>14}", build);
+        }
+        println!();
+        let ranks = (0..self.builds.len()).map(|idx| self.ranks(idx)).collect::<Vec<_>>();
+        for (row, &(ref strategy, ref cols)) in self.rows.iter().enumerate() {
+            print!("{:<20}", strategy);
+            for (idx, ns) in cols.iter().enumerate() {
+                let cell = match (*ns, ranks[idx][row]) {
+                    (Some(ns), Some(rank)) => format!("{:.1} ({})", ns, rank),
+                    _ => "-".to_owned(),
+                };
+                print!("{:>14}", cell);
+            }
+            println!();
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let results = self.rows.iter()
+            .map(|&(ref strategy, ref cols)| {
+                let by_build = self.builds.iter().cloned().zip(cols.iter().map(|ns| json!(ns)))
+                    .collect::<serde_json::Map<_, _>>();
+                json!({"strategy": strategy, "ns_per_message": by_build})
+            })
+            .collect::<Vec<_>>();
+        json!({"builds": self.builds, "results": results})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_reports() {
+        let mut comparison = Comparison::new(vec!["O2".into(), "O2+lto".into()]);
+        comparison.add_report(0, &json!({"results": [
+            {"strategy": "serde", "ns_per_message": 300.0},
+            {"strategy": "hand", "ns_per_message": 100.0},
+        ]})).unwrap();
+        comparison.add_report(1, &json!({"results": [
+            {"strategy": "hand", "ns_per_message": 90.0},
+            {"strategy": "tape", "ns_per_message": 50.0},
+        ]})).unwrap();
+        assert_eq!(comparison.rows, vec![("serde".to_owned(), vec![Some(300.0), None]),
+                                         ("hand".to_owned(), vec![Some(100.0), Some(90.0)]),
+                                         ("tape".to_owned(), vec![None, Some(50.0)])]);
+        assert_eq!(comparison.ranks(0), vec![Some(2), Some(1), None]);
+        assert_eq!(comparison.ranks(1), vec![None, Some(2), Some(1)]);
+        assert_eq!(comparison.to_json()["results"][1]["ns_per_message"]["O2+lto"], json!(90.0));
+        assert!(comparison.add_report(0, &json!({"results": [{"strategy": "x"}]})).is_err());
+    }
+}
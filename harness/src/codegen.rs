@@ -0,0 +1,710 @@
+//! Generating the owned, borrowed and Cow flavours of a protocol's types from
+//! one description of it.
+//!
+//! rpc2, rpc3 and the rest each spell out the same protocol again, and a new
+//! method has to be added to all of them by hand. A `Protocol` is the
+//! methods and the shapes of their params, in TOML, and `Protocol::generate`
+//! writes a module of serde types for it in each `Flavour`, with a strategy
+//! that parses into them, to be added to the strategies crate:
+//!
+//! ```toml
+//! name = "core"                   # names the modules and strategies
+//! root = "CoreNotification"       # what a message is parsed into
+//!
+//! [[enum]]
+//! name = "CoreNotification"
+//! tag = "method"                  # internally tagged, or, with content,
+//! content = "params"              # adjacently; externally if neither
+//!
+//! [[enum.variant]]
+//! name = "close_view"             # as on the wire
+//! fields = ["view_id: str"]       # a struct variant; `fields = []` for `{}`
+//!
+//! [[enum.variant]]
+//! name = "scroll"
+//! tuple = ["u64", "u64"]          # a tuple variant, or `type = "T"` for one
+//!                                 # value; a unit variant if none of these
+//!
+//! [[struct]]
+//! name = "EditCommand"
+//! fields = ["view_id: str", "cmd: flatten<EditNotification>"]
+//! ```
+//!
+//! The types are `str`, `u64`, `bool`, `value` (a `serde_json::Value`),
+//! `option<T>`, `list<T>`, and the enums and structs described. Only `str`
+//! differs between the flavours: it's a `String`, a `&str`, or a `Cow<str>`
+//! that borrows unless the string has escapes. serde only borrows a `Cow`
+//! that's the whole of a field's type, so one in an option or a list is
+//! always owned. strategies/protocols/core.toml and core_requests.toml
+//! describe what a frontend sends core.
+//!
+//! `Protocol::schema` is a JSON Schema for what the generated types accept,
+//! so that frontends written in other languages can check what they send
+//! against what's benchmarked here.
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use serde_json::{Map, Value};
+use toml;
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Protocol {
+    /// The modules are named for this and their flavour, as "core_owned".
+    pub name: String,
+    /// The type a message is parsed into.
+    pub root: String,
+    #[serde(rename = "enum", default)]
+    pub enums: Vec<EnumDef>,
+    #[serde(rename = "struct", default)]
+    pub structs: Vec<StructDef>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EnumDef {
+    pub name: String,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(rename = "variant", default)]
+    pub variants: Vec<VariantDef>,
+}
+
+/// A variant has at most one of `fields`, `tuple` and `newtype`, and is a
+/// unit variant if it has none.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct VariantDef {
+    /// The name on the wire, in snake case.
+    pub name: String,
+    #[serde(default)]
+    pub fields: Option<Vec<Field>>,
+    #[serde(default)]
+    pub tuple: Option<Vec<Type>>,
+    #[serde(rename = "type", default)]
+    pub newtype: Option<Type>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+/// A field, written `"name: type"`, or `"name: flatten<Type>"` to flatten
+/// an enum or struct into the one it's in.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Field {
+    pub name: String,
+    pub ty: Type,
+    pub flatten: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub enum Type {
+    Str,
+    U64,
+    Bool,
+    Value,
+    Option(Box<Type>),
+    List(Box<Type>),
+    /// An enum or struct in the description.
+    Named(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavour {
+    Owned,
+    Borrowed,
+    Cow,
+}
+
+impl Flavour {
+    pub const ALL: [Flavour; 3] = [Flavour::Owned, Flavour::Borrowed, Flavour::Cow];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Flavour::Owned => "owned",
+            Flavour::Borrowed => "borrowed",
+            Flavour::Cow => "cow",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Flavour::Owned => "with owned strings",
+            Flavour::Borrowed => "borrowing their strings",
+            Flavour::Cow => "borrowing their strings unless they have escapes",
+        }
+    }
+}
+
+impl TryFrom<String> for Type {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Type, String> {
+        Type::parse(&s)
+    }
+}
+
+impl Type {
+    fn parse(s: &str) -> Result<Type, String> {
+        let s = s.trim();
+        if let Some(inner) = wrapped(s, "option") {
+            return Ok(Type::Option(Box::new(Type::parse(inner)?)));
+        }
+        if let Some(inner) = wrapped(s, "list") {
+            return Ok(Type::List(Box::new(Type::parse(inner)?)));
+        }
+        match s {
+            "str" => Ok(Type::Str),
+            "u64" => Ok(Type::U64),
+            "bool" => Ok(Type::Bool),
+            "value" => Ok(Type::Value),
+            name if is_type_name(name) => Ok(Type::Named(name.to_owned())),
+            _ => Err(format!("unknown type '{}'", s)),
+        }
+    }
+
+    /// The enum or struct it refers to, if any.
+    fn named(&self) -> Option<&str> {
+        match *self {
+            Type::Option(ref inner) | Type::List(ref inner) => inner.named(),
+            Type::Named(ref name) => Some(name.as_str()),
+            _ => None,
+        }
+    }
+
+    fn has_value(&self) -> bool {
+        match *self {
+            Type::Value => true,
+            Type::Option(ref inner) | Type::List(ref inner) => inner.has_value(),
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<String> for Field {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Field, String> {
+        let (name, ty) = s.split_once(':')
+            .ok_or_else(|| format!("expected 'name: type', not '{}'", s))?;
+        let name = name.trim();
+        if !is_snake_case(name) {
+            return Err(format!("'{}' isn't a field name", name));
+        }
+        let (ty, flatten) = match wrapped(ty.trim(), "flatten") {
+            Some(inner) => (inner, true),
+            None => (ty, false),
+        };
+        Ok(Field { name: name.to_owned(), ty: Type::parse(ty)?, flatten })
+    }
+}
+
+/// What's inside `wrapper<...>`.
+fn wrapped<'s>(s: &'s str, wrapper: &str) -> Option<&'s str> {
+    s.strip_prefix(wrapper)?.strip_prefix('<')?.strip_suffix('>')
+}
+
+fn is_type_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_uppercase()) && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn is_snake_case(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_lowercase())
+        && s.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// "move_up" as "MoveUp".
+fn camel_case(snake: &str) -> String {
+    snake.split('_').flat_map(|word| {
+        let mut chars = word.chars();
+        chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars)
+    }).collect()
+}
+
+/// "MoveUp" as "move_up", as serde's `rename_all = "snake_case"` has it.
+fn snake_case(camel: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in camel.char_indices() {
+        if c.is_ascii_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+impl Protocol {
+    pub fn parse(toml: &str) -> Result<Protocol, String> {
+        let protocol = toml::from_str::<Protocol>(toml).map_err(|e| e.to_string())?;
+        protocol.validate()?;
+        Ok(protocol)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Protocol, String> {
+        let path = path.as_ref();
+        let toml = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Protocol::parse(&toml).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Checks that what's described can be generated: that every type it
+    /// refers to is described, and that serde can deserialize each shape
+    /// with its enum's tagging.
+    fn validate(&self) -> Result<(), String> {
+        if !is_snake_case(&self.name) {
+            return Err(format!("'{}' isn't a module name", self.name));
+        }
+        let mut names = BTreeSet::new();
+        for name in self.enums.iter().map(|e| &e.name).chain(self.structs.iter().map(|s| &s.name)) {
+            if !is_type_name(name) {
+                return Err(format!("'{}' isn't a type name", name));
+            }
+            if !names.insert(name.as_str()) {
+                return Err(format!("'{}' is described twice", name));
+            }
+        }
+        if !names.contains(self.root.as_str()) {
+            return Err(format!("the root, '{}', isn't described", self.root));
+        }
+        for name in self.types().iter().filter_map(|ty| ty.named()) {
+            if !names.contains(name) {
+                return Err(format!("'{}' isn't described", name));
+            }
+        }
+        for e in &self.enums {
+            if e.content.is_some() && e.tag.is_none() {
+                return Err(format!("{}: content without a tag", e.name));
+            }
+            let internal = e.tag.is_some() && e.content.is_none();
+            for v in &e.variants {
+                if !is_snake_case(&v.name) {
+                    return Err(format!("{}: '{}' isn't a variant name", e.name, v.name));
+                }
+                let shapes = [v.fields.is_some(), v.tuple.is_some(), v.newtype.is_some()];
+                if shapes.iter().filter(|&&s| s).count() > 1 {
+                    return Err(format!("{}::{}: more than one of fields, tuple and type",
+                                       e.name, v.name));
+                }
+                if internal && v.tuple.is_some() {
+                    return Err(format!("{}::{}: internally tagged tuple variant",
+                                       e.name, v.name));
+                }
+            }
+        }
+        let fields = self.enums.iter().flat_map(|e| &e.variants).flat_map(|v| v.fields.iter())
+            .chain(self.structs.iter().map(|s| &s.fields))
+            .flatten();
+        for field in fields {
+            if field.flatten && !matches!(field.ty, Type::Named(_)) {
+                return Err(format!("{}: only enums and structs can be flattened", field.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every type in a field, tuple or newtype.
+    fn types(&self) -> Vec<&Type> {
+        let mut types = Vec::new();
+        for e in &self.enums {
+            for v in &e.variants {
+                types.extend(v.fields.iter().flatten().map(|f| &f.ty));
+                types.extend(v.tuple.iter().flatten());
+                types.extend(v.newtype.iter());
+            }
+        }
+        types.extend(self.structs.iter().flat_map(|s| &s.fields).map(|f| &f.ty));
+        types
+    }
+
+    /// The types in the description of `name`.
+    fn types_of(&self, name: &str) -> Vec<&Type> {
+        let mut types = Vec::new();
+        for e in self.enums.iter().filter(|e| e.name == name) {
+            for v in &e.variants {
+                types.extend(v.fields.iter().flatten().map(|f| &f.ty));
+                types.extend(v.tuple.iter().flatten());
+                types.extend(v.newtype.iter());
+            }
+        }
+        for s in self.structs.iter().filter(|s| s.name == name) {
+            types.extend(s.fields.iter().map(|f| &f.ty));
+        }
+        types
+    }
+
+    /// Whether `ty` has a `str` in it, and so takes a lifetime unless it's
+    /// owned.
+    fn borrows(&self, ty: &Type) -> bool {
+        self.borrows_seen(ty, &mut BTreeSet::new())
+    }
+
+    fn borrows_seen<'p>(&'p self, ty: &'p Type, seen: &mut BTreeSet<&'p str>) -> bool {
+        match *ty {
+            Type::Str => true,
+            Type::Option(ref inner) | Type::List(ref inner) => self.borrows_seen(inner, seen),
+            // a type that's already been looked into is answered there
+            Type::Named(ref name) => {
+                seen.insert(name)
+                    && self.types_of(name).into_iter().any(|t| self.borrows_seen(t, seen))
+            }
+            _ => false,
+        }
+    }
+
+    fn rust_type(&self, ty: &Type, flavour: Flavour) -> String {
+        match *ty {
+            Type::Str => match flavour {
+                Flavour::Owned => "String".into(),
+                Flavour::Borrowed => "&'a str".into(),
+                Flavour::Cow => "Cow<'a, str>".into(),
+            },
+            Type::U64 => "u64".into(),
+            Type::Bool => "bool".into(),
+            Type::Value => "Value".into(),
+            Type::Option(ref inner) => format!("Option<{}>", self.rust_type(inner, flavour)),
+            Type::List(ref inner) => format!("Vec<{}>", self.rust_type(inner, flavour)),
+            Type::Named(ref name) => format!("{}{}", name, self.lifetime(ty, flavour)),
+        }
+    }
+
+    fn lifetime(&self, ty: &Type, flavour: Flavour) -> &'static str {
+        if flavour != Flavour::Owned && self.borrows(ty) { "<'a>" } else { "" }
+    }
+
+    /// Whether a field of type `ty` needs `#[serde(borrow)]`. serde borrows
+    /// a `&str` without being asked, but nothing else.
+    fn needs_borrow(&self, ty: &Type, flavour: Flavour) -> bool {
+        match flavour {
+            Flavour::Owned => false,
+            Flavour::Borrowed => *ty != Type::Str && self.borrows(ty),
+            Flavour::Cow => self.borrows(ty),
+        }
+    }
+
+    /// A field's `#[serde]` attribute, with a space after it, or nothing.
+    fn field_attr(&self, ty: &Type, flatten: bool, flavour: Flavour) -> String {
+        let mut attrs = Vec::new();
+        if self.needs_borrow(ty, flavour) {
+            attrs.push("borrow");
+        }
+        if flatten {
+            attrs.push("flatten");
+        }
+        if attrs.is_empty() { String::new() } else { format!("#[serde({})] ", attrs.join(", ")) }
+    }
+
+    /// The module for `flavour`, described in `source`.
+    pub fn generate(&self, flavour: Flavour, source: &str) -> String {
+        let mut out = vec![
+            format!("//! The {} protocol's types, {},", self.name, flavour.description()),
+            "//! and a strategy that parses into them.".into(),
+            "//!".into(),
+            format!("//! Generated by `der_bench generate` from {}. Edit that and", source),
+            "//! regenerate, rather than editing this.".into(),
+            String::new(),
+        ];
+        let types = self.types();
+        if flavour == Flavour::Cow && types.iter().any(|ty| self.borrows(ty)) {
+            out.push("use std::borrow::Cow;".into());
+        }
+        out.push("use std::hint::black_box;".into());
+        out.push(String::new());
+        if types.iter().any(|ty| ty.has_value()) {
+            out.push("use serde_json::{self, Value};".into());
+        } else {
+            out.push("use serde_json;".into());
+        }
+        out.push(String::new());
+        out.push("use error::ParseError;".into());
+        out.push("use sink::Sink;".into());
+        out.push("use strategy::Strategy;".into());
+
+        for e in &self.enums {
+            out.push(String::new());
+            out.push("#[derive(Deserialize, Debug, PartialEq)]".into());
+            out.push("#[serde(rename_all = \"snake_case\")]".into());
+            match (&e.tag, &e.content) {
+                (&Some(ref tag), &Some(ref content)) => {
+                    out.push(format!("#[serde(tag = \"{}\", content = \"{}\")]", tag, content));
+                }
+                (&Some(ref tag), &None) => out.push(format!("#[serde(tag = \"{}\")]", tag)),
+                _ => {}
+            }
+            let ty = Type::Named(e.name.clone());
+            out.push(format!("pub enum {}{} {{", e.name, self.lifetime(&ty, flavour)));
+            for v in &e.variants {
+                let name = camel_case(&v.name);
+                if snake_case(&name) != v.name {
+                    out.push(format!("    #[serde(rename = \"{}\")]", v.name));
+                }
+                if let Some(ref fields) = v.fields {
+                    out.push(format!("    {} {{", name));
+                    for f in fields {
+                        out.push(format!("        {}{}: {},",
+                                         self.field_attr(&f.ty, f.flatten, flavour), f.name,
+                                         self.rust_type(&f.ty, flavour)));
+                    }
+                    out.push("    },".into());
+                } else if let Some(ref tuple) = v.tuple {
+                    let tys = tuple.iter().map(|ty| {
+                        format!("{}{}", self.field_attr(ty, false, flavour),
+                                self.rust_type(ty, flavour))
+                    }).collect::<Vec<_>>();
+                    out.push(format!("    {}({}),", name, tys.join(", ")));
+                } else if let Some(ref ty) = v.newtype {
+                    out.push(format!("    {}({}{}),", name, self.field_attr(ty, false, flavour),
+                                     self.rust_type(ty, flavour)));
+                } else {
+                    out.push(format!("    {},", name));
+                }
+            }
+            out.push("}".into());
+        }
+
+        for s in &self.structs {
+            out.push(String::new());
+            out.push("#[derive(Deserialize, Debug, PartialEq)]".into());
+            let ty = Type::Named(s.name.clone());
+            out.push(format!("pub struct {}{} {{", s.name, self.lifetime(&ty, flavour)));
+            for f in &s.fields {
+                let attr = self.field_attr(&f.ty, f.flatten, flavour);
+                if !attr.is_empty() {
+                    out.push(format!("    {}", attr.trim_end()));
+                }
+                out.push(format!("    pub {}: {},", f.name, self.rust_type(&f.ty, flavour)));
+            }
+            out.push("}".into());
+        }
+
+        out.push(String::new());
+        out.push(format!("/// Parses a message into `{}`.", self.root));
+        out.push("pub struct Generated;".into());
+        out.push(String::new());
+        out.push("impl Strategy for Generated {".into());
+        out.push("    fn name(&self) -> &'static str {".into());
+        out.push(format!("        \"{}_{}\"", self.name, flavour.name()));
+        out.push("    }".into());
+        out.push(String::new());
+        out.push("    fn parse_into(&self, json: &str, _sink: &mut Sink) \
+                  -> Result<(), ParseError> {".into());
+        out.push(format!("        serde_json::from_str::<{}>(json)", self.root));
+        out.push("            .map(|msg| { black_box(msg); })".into());
+        out.push("            .map_err(|e| ParseError::from_json(json, e))".into());
+        out.push("    }".into());
+        out.push(String::new());
+        out.push("    fn checksums(&self) -> bool {".into());
+        out.push("        false".into());
+        out.push("    }".into());
+        out.push("}".into());
+        out.push(String::new());
+        out.join("\n")
+    }
+
+    /// A JSON Schema (draft 7) for what the generated types accept. Their
+    /// enums and structs are its definitions, and, as serde does, it allows
+    /// fields it doesn't know.
+    pub fn schema(&self) -> Value {
+        let mut definitions = Map::new();
+        for e in &self.enums {
+            let variants = e.variants.iter().map(|v| variant_schema(e, v)).collect::<Vec<_>>();
+            definitions.insert(e.name.clone(), json!({ "oneOf": variants }));
+        }
+        for s in &self.structs {
+            definitions.insert(s.name.clone(), fields_schema(&s.fields, Map::new()));
+        }
+        json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": self.root,
+            "allOf": [type_schema(&Type::Named(self.root.clone()))],
+            "definitions": definitions,
+        })
+    }
+
+    /// The file name of the module for `flavour`.
+    pub fn module_file(&self, flavour: Flavour) -> String {
+        format!("{}_{}.rs", self.name, flavour.name())
+    }
+}
+
+fn type_schema(ty: &Type) -> Value {
+    match *ty {
+        Type::Str => json!({ "type": "string" }),
+        Type::U64 => json!({ "type": "integer", "minimum": 0 }),
+        Type::Bool => json!({ "type": "boolean" }),
+        Type::Value => json!({}),
+        Type::Option(ref inner) => json!({ "anyOf": [type_schema(inner), { "type": "null" }] }),
+        Type::List(ref inner) => json!({ "type": "array", "items": type_schema(inner) }),
+        Type::Named(ref name) => json!({ "$ref": format!("#/definitions/{}", name) }),
+    }
+}
+
+/// An object with `fields` and `properties`, all of them required but the
+/// options. Flattened fields are schemas the object also has to match.
+fn fields_schema(fields: &[Field], mut properties: Map<String, Value>) -> Value {
+    let mut required = properties.keys().cloned().collect::<Vec<_>>();
+    let mut flattened = Vec::new();
+    for f in fields {
+        if f.flatten {
+            flattened.push(type_schema(&f.ty));
+            continue;
+        }
+        if !matches!(f.ty, Type::Option(_)) {
+            required.push(f.name.clone());
+        }
+        properties.insert(f.name.clone(), type_schema(&f.ty));
+    }
+    let object = json!({ "type": "object", "properties": properties, "required": required });
+    if flattened.is_empty() {
+        return object;
+    }
+    flattened.insert(0, object);
+    json!({ "allOf": flattened })
+}
+
+/// The schema of a variant's content, or `None` for a unit variant.
+fn content_schema(v: &VariantDef) -> Option<Value> {
+    if let Some(ref fields) = v.fields {
+        return Some(fields_schema(fields, Map::new()));
+    }
+    if let Some(ref tuple) = v.tuple {
+        let items = tuple.iter().map(type_schema).collect::<Vec<_>>();
+        return Some(json!({
+            "type": "array", "items": items, "minItems": items.len(), "maxItems": items.len(),
+        }));
+    }
+    v.newtype.as_ref().map(type_schema)
+}
+
+fn variant_schema(e: &EnumDef, v: &VariantDef) -> Value {
+    match (&e.tag, &e.content) {
+        (&Some(ref tag), &Some(ref key)) => {
+            let mut properties = Map::new();
+            properties.insert(tag.clone(), json!({ "const": v.name }));
+            let mut required = vec![tag.clone()];
+            match content_schema(v) {
+                Some(content) => {
+                    properties.insert(key.clone(), content);
+                    required.push(key.clone());
+                }
+                // frontends send an empty array, and serde takes anything
+                // empty, or nothing at all
+                None => {
+                    properties.insert(key.clone(), json!({
+                        "type": ["array", "object", "null"], "maxItems": 0, "maxProperties": 0,
+                    }));
+                }
+            }
+            json!({ "type": "object", "properties": properties, "required": required })
+        }
+        (&Some(ref tag), &None) => {
+            let mut properties = Map::new();
+            properties.insert(tag.clone(), json!({ "const": v.name }));
+            match (&v.fields, content_schema(v)) {
+                (&Some(ref fields), _) => fields_schema(fields, properties),
+                (_, Some(content)) => json!({ "allOf": [fields_schema(&[], properties), content] }),
+                (_, None) => fields_schema(&[], properties),
+            }
+        }
+        _ => match content_schema(v) {
+            Some(content) => {
+                let mut properties = Map::new();
+                properties.insert(v.name.clone(), content);
+                json!({
+                    "type": "object", "properties": properties, "required": [v.name],
+                    "additionalProperties": false,
+                })
+            }
+            None => json!({ "const": v.name }),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORE: &str = include_str!("../../strategies/protocols/core.toml");
+    const CORE_REQUESTS: &str = include_str!("../../strategies/protocols/core_requests.toml");
+
+    #[test]
+    fn generates() {
+        let protocol = Protocol::parse(CORE).unwrap();
+        let owned = protocol.generate(Flavour::Owned, "core.toml");
+        assert!(owned.contains("pub enum CoreNotification {"), "{}", owned);
+        assert!(owned.contains("        view_id: String,"), "{}", owned);
+        assert!(!owned.contains("'a"), "{}", owned);
+        let borrowed = protocol.generate(Flavour::Borrowed, "core.toml");
+        assert!(borrowed.contains("    Edit(#[serde(borrow)] EditCommand<'a>),"), "{}", borrowed);
+        assert!(borrowed.contains("        view_id: &'a str,"), "{}", borrowed);
+        assert!(borrowed.contains("pub enum GestureType {"), "{}", borrowed);
+        let cow = protocol.generate(Flavour::Cow, "core.toml");
+        assert!(cow.contains("        #[serde(borrow)] view_id: Cow<'a, str>,"), "{}", cow);
+        assert!(cow.contains("    #[serde(borrow, flatten)]\n    pub cmd: EditNotification<'a>,"),
+                "{}", cow);
+        assert!(cow.contains("\"core_cow\""), "{}", cow);
+        assert_eq!(protocol.module_file(Flavour::Cow), "core_cow.rs");
+    }
+
+    #[test]
+    fn schema() {
+        let schema = Protocol::parse(CORE).unwrap().schema();
+        assert_eq!(schema["allOf"][0]["$ref"], "#/definitions/CoreNotification");
+        let defs = &schema["definitions"];
+        let close_view = &defs["CoreNotification"]["oneOf"][2];
+        assert_eq!(close_view["properties"]["method"], json!({ "const": "close_view" }));
+        assert_eq!(close_view["properties"]["params"]["required"], json!(["view_id"]));
+        let edit = &defs["EditCommand"]["allOf"];
+        assert_eq!(edit[1], json!({ "$ref": "#/definitions/EditNotification" }));
+        let delete = &defs["EditNotification"]["oneOf"][1];
+        assert_eq!(delete["required"], json!(["method"]));
+        let scroll = defs["EditNotification"]["oneOf"].as_array().unwrap().iter()
+            .find(|v| v["properties"]["method"]["const"] == "scroll").unwrap();
+        assert_eq!(scroll["properties"]["params"]["maxItems"], 2);
+        assert_eq!(defs["GestureType"]["oneOf"][0], json!({ "const": "point_select" }));
+        let start = &defs["PluginNotification"]["oneOf"][0];
+        assert_eq!(start["required"], json!(["command", "view_id", "plugin_name"]));
+
+        let requests = Protocol::parse(CORE_REQUESTS).unwrap().schema();
+        let new_view = &requests["definitions"]["CoreRequest"]["oneOf"][1];
+        assert_eq!(new_view["properties"]["params"]["required"], json!([]));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(camel_case("move_up_and_modify_selection"), "MoveUpAndModifySelection");
+        assert_eq!(snake_case("MoveUpAndModifySelection"), "move_up_and_modify_selection");
+        assert_eq!(Type::parse("option<list<str>>"),
+                   Ok(Type::Option(Box::new(Type::List(Box::new(Type::Str))))));
+        assert!(Type::parse("string").is_err());
+    }
+
+    #[test]
+    fn rejects() {
+        let base = "name = \"p\"\nroot = \"M\"\n[[enum]]\nname = \"M\"\ntag = \"method\"\n";
+        assert!(Protocol::parse(&format!("{}[[enum.variant]]\nname = \"a\"\n", base)).is_ok());
+        for bad in &[
+            "[[enum.variant]]\nname = \"a\"\nfields = [\"b: Undescribed\"]",
+            "[[enum.variant]]\nname = \"a\"\ntuple = [\"u64\"]",
+            "[[enum.variant]]\nname = \"a\"\nfields = []\ntype = \"u64\"",
+            "[[enum.variant]]\nname = \"a\"\nfields = [\"b: flatten<str>\"]",
+            "[[enum.variant]]\nname = \"A\"",
+            "[[enum]]\nname = \"M\"",
+        ] {
+            assert!(Protocol::parse(&format!("{}{}", base, bad)).is_err(), "{}", bad);
+        }
+        assert!(Protocol::parse("name = \"p\"\nroot = \"M\"\n").is_err());
+    }
+}
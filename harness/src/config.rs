@@ -0,0 +1,205 @@
+//! A `bench.toml`, describing a matrix of runs: which strategies, over which
+//! corpora, for how long, with which caches and how many threads, and what to
+//! write out. Checked in beside the results, it says how they were produced
+//! better than a shell history does.
+//!
+//! ```toml
+//! strategies = ["serde", "hand", "tape"]   # all of them, if left out
+//! allocator = "jemalloc"                   # whatever was built, if left out
+//! reports = ["table", "json"]              # just the table, if left out
+//! output_dir = "results"                   # for json reports
+//!
+//! [[corpus]]
+//! path = "corpora/basic.jsonl"             # relative to bench.toml
+//! iterations = 1000                        # or max_time = 2.5; 100 if neither
+//! cache = ["hot", "cold"]                  # hot, if left out
+//! threads = [1, 4]                         # 1, if left out
+//! ```
+//!
+//! The allocator is chosen when the binary is built, so the config can't
+//! change it, but it can say which one its results are meant to be from, and
+//! a run with another is an error.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::time::Duration;
+
+use toml;
+
+use corpus;
+use runner::{Budget, Cache};
+use strategy;
+use xi_der_strategies::ALLOCATOR;
+
+/// The name of the config file the CLI looks for.
+pub const CONFIG: &str = "bench.toml";
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The strategies to run, by name; the ones for each corpus's direction
+    /// if `None`.
+    #[serde(default)]
+    pub strategies: Option<Vec<String>>,
+    /// The allocator the binary has to have been built with, if any.
+    #[serde(default)]
+    pub allocator: Option<String>,
+    #[serde(default = "default_reports")]
+    pub reports: Vec<ReportFormat>,
+    /// Where json reports are written, one per corpus.
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    #[serde(rename = "corpus", default)]
+    pub corpora: Vec<CorpusRuns>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    /// The table `run` prints.
+    Table,
+    /// `report::Report`, as `run --output` writes it.
+    Json,
+}
+
+/// The runs over one corpus: each strategy with each cache and each number
+/// of threads.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CorpusRuns {
+    pub path: String,
+    #[serde(default)]
+    pub iterations: Option<u64>,
+    /// Seconds.
+    #[serde(default)]
+    pub max_time: Option<f64>,
+    #[serde(default = "default_cache")]
+    pub cache: Vec<Cache>,
+    #[serde(default = "default_threads")]
+    pub threads: Vec<u32>,
+}
+
+fn default_reports() -> Vec<ReportFormat> {
+    vec![ReportFormat::Table]
+}
+
+fn default_cache() -> Vec<Cache> {
+    vec![Cache::Hot]
+}
+
+fn default_threads() -> Vec<u32> {
+    vec![1]
+}
+
+impl Config {
+    pub fn parse(toml: &str) -> Result<Config, String> {
+        let config = toml::from_str::<Config>(toml).map_err(|e| e.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads the config at `path`, with the corpora's paths made relative to
+    /// the current directory rather than the config's.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, String> {
+        let path = path.as_ref();
+        let toml = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut config = Config::parse(&toml)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if let Some(dir) = path.parent() {
+            for runs in &mut config.corpora {
+                if runs.path != corpus::STDIN {
+                    runs.path = dir.join(&runs.path).to_string_lossy().into_owned();
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Checks what can be checked before anything's run: that the strategies
+    /// exist, that this is the allocator asked for, and that each corpus's
+    /// runs make sense.
+    fn validate(&self) -> Result<(), String> {
+        for name in self.strategies.iter().flatten() {
+            if strategy::by_name(name).is_none() {
+                return Err(format!("unknown strategy '{}'", name));
+            }
+        }
+        if let Some(allocator) = self.allocator.as_ref().filter(|a| *a != ALLOCATOR) {
+            return Err(format!("the config is for the {} allocator, but this was built with {}",
+                               allocator, ALLOCATOR));
+        }
+        if self.corpora.is_empty() {
+            return Err("no corpora".into());
+        }
+        for runs in &self.corpora {
+            runs.budget().map_err(|e| format!("{}: {}", runs.path, e))?;
+            if runs.cache.is_empty() || runs.threads.is_empty() || runs.threads.contains(&0) {
+                return Err(format!("{}: nothing to run", runs.path));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CorpusRuns {
+    /// The budget for each run, as `run`'s `--iterations` and `--max-time`.
+    pub fn budget(&self) -> Result<Budget, String> {
+        match (self.iterations, self.max_time) {
+            (Some(_), Some(_)) => Err("iterations and max_time are exclusive".into()),
+            (None, Some(secs)) if secs > 0.0 => Duration::try_from_secs_f64(secs)
+                .map(Budget::Time).map_err(|_| format!("invalid max_time: {}", secs)),
+            (None, Some(secs)) => Err(format!("invalid max_time: {}", secs)),
+            (Some(n), None) => Ok(Budget::Iterations(n)),
+            (None, None) => Ok(Budget::Iterations(100)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let config = Config::parse(r#"
+            strategies = ["serde", "tape"]
+            reports = ["table", "json"]
+            output_dir = "results"
+
+            [[corpus]]
+            path = "basic.jsonl"
+            max_time = 0.5
+            cache = ["hot", "flushed"]
+            threads = [1, 4]
+
+            [[corpus]]
+            path = "-"
+        "#).unwrap();
+        assert_eq!(config.strategies, Some(vec!["serde".into(), "tape".into()]));
+        assert_eq!(config.reports, vec![ReportFormat::Table, ReportFormat::Json]);
+        let (first, second) = (&config.corpora[0], &config.corpora[1]);
+        assert_eq!(first.budget(), Ok(Budget::Time(Duration::from_millis(500))));
+        assert_eq!(first.cache, vec![Cache::Hot, Cache::Flushed]);
+        assert_eq!(second.budget(), Ok(Budget::Iterations(100)));
+        assert_eq!((&second.cache[..], &second.threads[..]), (&[Cache::Hot][..], &[1][..]));
+    }
+
+    #[test]
+    fn rejects() {
+        assert!(Config::parse("[[corpus]]\npath = \"a.jsonl\"\n").is_ok());
+        for bad in &[
+            "",
+            "strategies = [\"frobnicate\"]\n[[corpus]]\npath = \"a.jsonl\"",
+            "allocator = \"tcmalloc\"\n[[corpus]]\npath = \"a.jsonl\"",
+            "[[corpus]]\npath = \"a.jsonl\"\niterations = 1\nmax_time = 1.0",
+            "[[corpus]]\npath = \"a.jsonl\"\nthreads = [0]",
+            "[[corpus]]\npath = \"a.jsonl\"\nitertions = 1",
+            "[[corpus]]\npath = \"a.jsonl\"\nmax_time = inf",
+        ] {
+            assert!(Config::parse(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Hardware performance counters around a strategy's passes over a corpus,
+//! for the costs that don't show up as time in a microbenchmark.
+//!
+//! The derived `Deserialize` impls are big, and in a microbenchmark they
+//! have the instruction cache to themselves. In xi they share it with
+//! everything else, so instruction cache and iTLB misses are worth watching
+//! alongside the instruction count. Linux only, and the kernel has to allow
+//! it (see `perf_event_paranoid`).
+
+use std::io;
+
+use perf_event::events::{Cache, CacheOp, CacheResult, Hardware, WhichCache};
+use perf_event::{Builder, Counter, Group};
+
+use strategy::Strategy;
+
+const L1I_MISSES: Cache = Cache {
+    which: WhichCache::L1I,
+    operation: CacheOp::READ,
+    result: CacheResult::MISS,
+};
+
+const ITLB_MISSES: Cache = Cache {
+    which: WhichCache::ITLB,
+    operation: CacheOp::READ,
+    result: CacheResult::MISS,
+};
+
+/// Counts over some number of passes over a corpus.
+#[derive(Debug, Clone)]
+pub struct Counts {
+    pub strategy: &'static str,
+    pub messages: u64,
+    pub cycles: u64,
+    pub instructions: u64,
+    pub l1i_misses: u64,
+    pub itlb_misses: u64,
+}
+
+impl Counts {
+    pub fn per_message(&self, count: u64) -> f64 {
+        count as f64 / self.messages.max(1) as f64
+    }
+}
+
+/// Counts `passes` passes of `strategy` over `lines`, after one to warm up.
+pub fn count(strategy: &dyn Strategy, lines: &[String], passes: u64) -> io::Result<Counts> {
+    let mut group = Group::new()?;
+    let cycles = counter(&mut group, Hardware::CPU_CYCLES)?;
+    let instructions = counter(&mut group, Hardware::INSTRUCTIONS)?;
+    let l1i_misses = counter(&mut group, L1I_MISSES)?;
+    let itlb_misses = counter(&mut group, ITLB_MISSES)?;
+
+    for line in lines {
+        let _ = strategy.parse(line);
+    }
+    group.enable()?;
+    for _ in 0..passes {
+        for line in lines {
+            let _ = strategy.parse(line);
+        }
+    }
+    group.disable()?;
+
+    let counts = group.read()?;
+    Ok(Counts {
+        strategy: strategy.name(),
+        messages: lines.len() as u64 * passes,
+        cycles: counts[&cycles],
+        instructions: counts[&instructions],
+        l1i_misses: counts[&l1i_misses],
+        itlb_misses: counts[&itlb_misses],
+    })
+}
+
+fn counter<E: Into<::perf_event::events::Event>>(group: &mut Group, event: E)
+    -> io::Result<Counter>
+{
+    Builder::new().group(group).kind(event).build()
+}
+
+/// Prints a table of counts, per message.
+pub fn print_counts(counts: &[Counts]) {
+    println!("{:<14}{:>12}{:>14}{:>10}{:>14}{:>14}",
+             "strategy", "cycles/msg", "instrs/msg", "IPC", "L1i miss/msg", "iTLB miss/msg");
+    for c in counts {
+        println!("{:<14}{:>12.0}{:>14.0}{:>10.2}{:>14.2}{:>14.3}",
+                 c.strategy, c.per_message(c.cycles), c.per_message(c.instructions),
+                 c.instructions as f64 / c.cycles.max(1) as f64, c.per_message(c.l1i_misses),
+                 c.per_message(c.itlb_misses));
+    }
+}
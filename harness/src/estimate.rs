@@ -0,0 +1,189 @@
+//! Predicting a strategy's time over a corpus from what's in it.
+//!
+//! If each message costs a fixed amount for its type, plus an amount for each
+//! of its bytes, then a corpus's time is a sum over the types in it, and a
+//! workload can be sized up from counts and lengths alone. `calibrate` fits
+//! that model for a strategy by timing the lines of a calibration corpus one
+//! at a time, and `Model::predict` applies it to another corpus's `Stats`.
+//!
+//! How far a prediction is from a real run says whether the model is good
+//! enough. It has reasons to be off: a line timed on its own has the caches
+//! and the branch predictor to itself, where in a corpus it shares them with
+//! every other type.
+
+use std::collections::BTreeMap;
+
+use corpus::Buffer;
+use runner::{self, duration_ns};
+use strategy::Strategy;
+use xi_der_strategies::tape::{Message, Op, Tape};
+
+/// What a corpus has in it, by message type.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub types: BTreeMap<String, TypeStats>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypeStats {
+    pub messages: usize,
+    pub bytes: usize,
+}
+
+impl Stats {
+    pub fn of<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> Stats {
+        let mut stats = Stats::default();
+        for line in lines {
+            let entry = stats.types.entry(message_type(line)).or_default();
+            entry.messages += 1;
+            entry.bytes += line.len();
+        }
+        stats
+    }
+
+    pub fn messages(&self) -> usize {
+        self.types.values().map(|t| t.messages).sum()
+    }
+}
+
+/// The type a line is costed as: its method, with edits qualified as in
+/// "edit/insert". Batches are a type of their own, as is anything without a
+/// method.
+pub fn message_type(line: &str) -> String {
+    let tape = match Tape::parse(line) {
+        Ok(tape) => tape,
+        Err(_) => return "other".into(),
+    };
+    if tape.root().op() == Op::Array {
+        return "batch".into();
+    }
+    Message::from_tape(&tape).map(|m| m.qualified_method()).unwrap_or_else(|_| "other".into())
+}
+
+/// The cost of a message of one type, in ns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fit {
+    pub per_message: f64,
+    pub per_byte: f64,
+}
+
+impl Fit {
+    /// The least squares line through `(bytes, ns)` points. If they're all
+    /// the same size there's no slope to fit, and it's all put down to the
+    /// message.
+    fn through(points: &[(f64, f64)]) -> Fit {
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let sxx = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum::<f64>();
+        let sxy = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum::<f64>();
+        if sxx < 1e-9 {
+            return Fit { per_message: mean_y, per_byte: 0.0 };
+        }
+        let per_byte = sxy / sxx;
+        Fit { per_message: mean_y - per_byte * mean_x, per_byte }
+    }
+
+    pub fn cost(&self, stats: TypeStats) -> f64 {
+        self.per_message * stats.messages as f64 + self.per_byte * stats.bytes as f64
+    }
+}
+
+/// A strategy's cost for each type of message.
+#[derive(Debug, Clone)]
+pub struct Model {
+    pub strategy: &'static str,
+    pub types: BTreeMap<String, Fit>,
+    /// A fit over every calibration line, for types it didn't have.
+    pub overall: Fit,
+}
+
+impl Model {
+    /// The predicted time for one pass over a corpus with `stats`, in ns.
+    pub fn predict(&self, stats: &Stats) -> f64 {
+        stats.types.iter().map(|(t, s)| self.types.get(t).unwrap_or(&self.overall).cost(*s)).sum()
+    }
+
+    /// The types in `stats` that were costed with `overall`.
+    pub fn uncovered<'s>(&self, stats: &'s Stats) -> Vec<&'s str> {
+        stats.types.keys().filter(|t| !self.types.contains_key(*t)).map(|t| t.as_str()).collect()
+    }
+}
+
+/// Fits a `Model` for `strategy`, timing `passes` passes over each of
+/// `lines` on its own, after one pass to warm up.
+pub fn calibrate<S>(strategy: &S, lines: &[String], passes: u64) -> Model
+    where S: Strategy + ?Sized
+{
+    let _span = info_span!("calibrate", strategy = strategy.name(), lines = lines.len()).entered();
+    let mut points = BTreeMap::<String, Vec<(f64, f64)>>::new();
+    for line in lines {
+        let buffer = Buffer::new(&[line]);
+        runner::run_batch(strategy, &buffer, 1);
+        let ns = duration_ns(runner::run_batch(strategy, &buffer, passes)) / passes as f64;
+        points.entry(message_type(line)).or_default().push((line.len() as f64, ns));
+    }
+    let all = points.values().flatten().cloned().collect::<Vec<_>>();
+    Model {
+        strategy: strategy.name(),
+        types: points.iter().map(|(t, p)| (t.clone(), Fit::through(p))).collect(),
+        overall: Fit::through(&all),
+    }
+}
+
+/// A prediction for one pass over a corpus, and what a run measured.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub strategy: &'static str,
+    pub predicted_ns: f64,
+    pub actual_ns: f64,
+}
+
+impl Estimate {
+    /// How far the prediction was off, as a fraction of the actual time;
+    /// positive if it was too high.
+    pub fn error(&self) -> f64 {
+        self.predicted_ns / self.actual_ns - 1.0
+    }
+}
+
+pub fn print_estimates(estimates: &[Estimate]) {
+    println!("{:<14}{:>16}{:>16}{:>10}", "strategy", "predicted ns", "actual ns", "error%");
+    for e in estimates {
+        println!("{:<14}{:>16.0}{:>16.0}{:>+10.1}",
+                 e.strategy, e.predicted_ns, e.actual_ns, e.error() * 100.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use corpus;
+    use strategy;
+
+    use super::*;
+
+    #[test]
+    fn fits() {
+        let line = Fit::through(&[(10.0, 25.0), (20.0, 45.0), (30.0, 65.0)]);
+        assert!((line.per_message - 5.0).abs() < 1e-6 && (line.per_byte - 2.0).abs() < 1e-6);
+        let flat = Fit::through(&[(10.0, 20.0), (10.0, 40.0)]);
+        assert_eq!(flat, Fit { per_message: 30.0, per_byte: 0.0 });
+        assert_eq!(flat.cost(TypeStats { messages: 2, bytes: 100 }), 60.0);
+    }
+
+    #[test]
+    fn predicts() {
+        assert_eq!(message_type(r#"{"method":"edit","params":{"method":"undo"}}"#), "edit/undo");
+        assert_eq!(message_type("[]"), "batch");
+        assert_eq!(message_type("{"), "other");
+
+        let lines = corpus::TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        let stats = Stats::of(corpus::TEST_JSON.lines());
+        assert_eq!(stats.messages(), lines.len());
+        let model = calibrate(&*strategy::by_name("serde").unwrap(), &lines, 2);
+        assert!(model.uncovered(&stats).is_empty());
+        assert!(model.predict(&stats) > 0.0);
+        let other = Stats::of(vec!["{"]);
+        assert_eq!(model.uncovered(&other), vec!["other"]);
+    }
+}
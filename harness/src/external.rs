@@ -0,0 +1,106 @@
+//! Timing parsers written in other languages over the same corpus, beside
+//! the strategies.
+//!
+//! xi's frontends parse what core sends them in Swift, JavaScript, Python
+//! and so on, and how Rust compares with them keeps coming up. An external
+//! runner is a command that's given the path of a file holding the corpus,
+//! and a number of passes, as its last two arguments. It parses each line
+//! that many times, and prints, as the last line of its output, what it
+//! measured:
+//!
+//! ```json
+//! {"name": "python_json", "iterations": 100, "elapsed_ns": 123456789, "errors": 0}
+//! ```
+//!
+//! The corpus is written out as the harness read it, decompressed, so that
+//! every runner sees the same lines. The timing is the runner's own, which
+//! leaves out starting its runtime; the scripts in harness/external make a
+//! pass first to warm up, as `runner::measure` does. Nothing is checked of
+//! what a runner parses into, which is whatever's idiomatic in its language,
+//! usually a generic tree like `Value`.
+
+use std::env;
+use std::fs;
+use std::process::{self, Command, Stdio};
+use std::time::Duration;
+
+use serde_json;
+
+use corpus::Direction;
+use runner::{Cache, Measurement};
+
+/// What a runner prints.
+#[derive(Deserialize, Debug)]
+struct Output {
+    name: String,
+    iterations: u64,
+    elapsed_ns: u64,
+    errors: usize,
+}
+
+/// Runs `command`, split on whitespace, for `passes` passes over `lines`.
+pub fn measure(command: &str, lines: &[String], direction: Direction, passes: u64)
+    -> Result<Measurement, String>
+{
+    let _span = info_span!("external", command).entered();
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or("empty external command")?;
+    let path = env::temp_dir().join(format!("der_bench_external_{}.jsonl", process::id()));
+    let mut corpus = lines.join("\n");
+    corpus.push('\n');
+    fs::write(&path, corpus).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let output = Command::new(program)
+        .args(words)
+        .arg(&path)
+        .arg(passes.to_string())
+        .stderr(Stdio::inherit())
+        .output();
+    let _ = fs::remove_file(&path);
+    let output = output.map_err(|e| format!("{}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("{}: {}", command, output.status));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let last = stdout.lines().last().ok_or_else(|| format!("{}: no output", command))?;
+    let out = serde_json::from_str::<Output>(last).map_err(|e| format!("{}: {}", command, e))?;
+    info!(iterations = out.iterations, errors = out.errors, "measured");
+
+    Ok(Measurement {
+        // strategies' names are static, and a run has only a few runners
+        strategy: Box::leak(out.name.into_boxed_str()),
+        direction,
+        messages: lines.len(),
+        errors: out.errors,
+        iterations: out.iterations,
+        elapsed: Duration::from_nanos(out.elapsed_ns),
+        cache: Cache::Hot,
+        memory: None,
+        checksum: None,
+        spread: None,
+        threads: 1,
+        warm_up: None,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_a_command() {
+        let script = env::temp_dir().join(format!("der_bench_runner_{}.sh", process::id()));
+        fs::write(&script, r#"
+            echo "starting"
+            lines=$(wc -l < "$1")
+            echo "{\"name\": \"sh\", \"iterations\": $2, \"elapsed_ns\": 1000, \"errors\": $lines}"
+        "#).unwrap();
+        let lines = vec!["{}".to_owned(), "[]".to_owned()];
+        let m = measure(&format!("sh {}", script.display()), &lines, Direction::S2C, 3);
+        fs::remove_file(&script).unwrap();
+        let m = m.unwrap();
+        assert_eq!((m.strategy, m.iterations, m.errors, m.messages), ("sh", 3, 2, 2));
+        assert_eq!((m.elapsed, m.direction), (Duration::from_nanos(1000), Direction::S2C));
+        assert!(measure("false", &lines, Direction::S2C, 3).is_err());
+        assert!(measure("", &lines, Direction::S2C, 3).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Recording results across runs, so we can see how strategies change over
+//! time; in particular, across serde_json upgrades.
+//!
+//! Each run is keyed by the git commit of this repo, the rustc version, the
+//! global allocator, and a hash of the corpus, and stored in a local SQLite
+//! database.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, Result};
+
+use corpus;
+use report;
+use runner::Measurement;
+
+/// Identifies the circumstances of a run.
+#[derive(Debug, Clone)]
+pub struct RunInfo {
+    pub timestamp: i64,
+    pub git_commit: String,
+    pub rustc_version: String,
+    pub allocator: String,
+    pub corpus_hash: String,
+}
+
+/// One strategy's result from one run.
+#[derive(Debug, Clone)]
+pub struct TrendRow {
+    /// The time of the run, as "YYYY-MM-DD HH:MM:SS" UTC.
+    pub date: String,
+    pub git_commit: String,
+    pub rustc_version: String,
+    pub allocator: String,
+    pub corpus_hash: String,
+    pub strategy: String,
+    /// The `Cache` mode's name. Hot and cold runs aren't comparable.
+    pub cache: String,
+    pub ns_per_message: f64,
+}
+
+pub struct History {
+    conn: Connection,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    git_commit TEXT NOT NULL,
+    rustc_version TEXT NOT NULL,
+    corpus_hash TEXT NOT NULL,
+    allocator TEXT NOT NULL DEFAULT 'system'
+);
+CREATE TABLE IF NOT EXISTS results (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    strategy TEXT NOT NULL,
+    iterations INTEGER NOT NULL,
+    errors INTEGER NOT NULL,
+    ns_per_iter REAL NOT NULL,
+    ns_per_message REAL NOT NULL,
+    cache TEXT NOT NULL DEFAULT 'hot'
+);";
+
+impl RunInfo {
+    /// Collects info about the current environment, for a run over `corpus`.
+    pub fn current(corpus: &[String]) -> RunInfo {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        RunInfo {
+            timestamp,
+            git_commit: command_output("git", &["rev-parse", "HEAD"]),
+            rustc_version: report::RUSTC_VERSION.to_owned(),
+            allocator: report::ALLOCATOR.to_owned(),
+            corpus_hash: corpus::hash(corpus),
+        }
+    }
+}
+
+/// Returns the trimmed stdout of a command, or "unknown" if it fails.
+fn command_output(cmd: &str, args: &[&str]) -> String {
+    Command::new(cmd).args(args).output().ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|s| s.trim().to_owned())
+        .unwrap_or_else(|| "unknown".into())
+}
+
+impl History {
+    /// Opens the database at `path`, creating it if necessary.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<History> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        // databases from before these existed only have hot runs, with the
+        // system allocator
+        add_column(&conn, "results", "cache TEXT NOT NULL DEFAULT 'hot'")?;
+        add_column(&conn, "runs", "allocator TEXT NOT NULL DEFAULT 'system'")?;
+        Ok(History { conn })
+    }
+
+    /// Records the results of a run.
+    pub fn record(&mut self, info: &RunInfo, measurements: &[Measurement]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("INSERT INTO runs (timestamp, git_commit, rustc_version, allocator, corpus_hash)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                   params![info.timestamp, info.git_commit, info.rustc_version, info.allocator,
+                           info.corpus_hash])?;
+        let run_id = tx.last_insert_rowid();
+        for m in measurements {
+            tx.execute("INSERT INTO results
+                        (run_id, strategy, iterations, errors, ns_per_iter, ns_per_message, cache)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                       params![run_id, m.strategy, m.iterations as i64, m.errors as i64,
+                               m.ns_per_iter(), m.ns_per_message(), m.cache.name()])?;
+        }
+        tx.commit()
+    }
+
+    /// Returns every recorded result, oldest first, optionally restricted to
+    /// one corpus and/or strategy.
+    pub fn trend(&self, corpus_hash: Option<&str>, strategy: Option<&str>) -> Result<Vec<TrendRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT datetime(runs.timestamp, 'unixepoch'), runs.git_commit, runs.rustc_version,
+                    runs.allocator, runs.corpus_hash, results.strategy, results.cache,
+                    results.ns_per_message
+             FROM results JOIN runs ON results.run_id = runs.id
+             WHERE (?1 IS NULL OR runs.corpus_hash = ?1)
+               AND (?2 IS NULL OR results.strategy = ?2)
+             ORDER BY runs.timestamp, runs.id")?;
+        let rows = stmt.query_map(params![corpus_hash, strategy], |row| {
+            Ok(TrendRow {
+                date: row.get(0)?,
+                git_commit: row.get(1)?,
+                rustc_version: row.get(2)?,
+                allocator: row.get(3)?,
+                corpus_hash: row.get(4)?,
+                strategy: row.get(5)?,
+                cache: row.get(6)?,
+                ns_per_message: row.get(7)?,
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+/// Adds a column to an existing table, if it isn't there already.
+fn add_column(conn: &Connection, table: &str, column: &str) -> Result<()> {
+    let name = column.split_whitespace().next().unwrap_or(column);
+    if conn.prepare(&format!("SELECT {} FROM {} LIMIT 0", name, table)).is_ok() {
+        return Ok(());
+    }
+    conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {}", table, column))
+}
+
+/// Prints each strategy's results over time, with the change from the
+/// previous run, if it was on the same corpus with the same cache mode and
+/// allocator.
+pub fn print_trend(rows: &[TrendRow]) {
+    let mut strategies = rows.iter().map(|r| r.strategy.as_str()).collect::<Vec<_>>();
+    strategies.sort();
+    strategies.dedup();
+
+    for strategy in strategies {
+        println!("{}", strategy);
+        let mut prev: Option<&TrendRow> = None;
+        for row in rows.iter().filter(|r| r.strategy == strategy) {
+            let change = match prev {
+                Some(p) if p.corpus_hash == row.corpus_hash && p.cache == row.cache
+                    && p.allocator == row.allocator =>
+                    format!("{:+.1}%", (row.ns_per_message / p.ns_per_message - 1.0) * 100.0),
+                _ => String::new(),
+            };
+            println!("  {}  {:.8}  {:.8}  {:<28}{:>9}{:>8}{:>12.1} ns/msg {:>8}",
+                     row.date, row.git_commit, row.corpus_hash, row.rustc_version,
+                     row.allocator, row.cache, row.ns_per_message, change);
+            prev = Some(row);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+    use runner::Cache;
+
+    fn measurement(strategy: &'static str, nanos: u32) -> Measurement {
+        Measurement {
+            strategy,
+            direction: corpus::Direction::C2S,
+            messages: 10,
+            errors: 0,
+            iterations: 1,
+            elapsed: Duration::new(0, nanos),
+            cache: Cache::Hot,
+            memory: None,
+            checksum: None,
+            spread: None,
+            threads: 1,
+            warm_up: None,
+        }
+    }
+
+    #[test]
+    fn record_and_trend() {
+        let mut history = History::open(":memory:").unwrap();
+        let mut info = RunInfo {
+            timestamp: 1_500_000_000,
+            git_commit: "abc".into(),
+            rustc_version: "rustc 1.20.0".into(),
+            allocator: "system".into(),
+            corpus_hash: "0123".into(),
+        };
+        history.record(&info, &[measurement("serde", 1000), measurement("own", 2000)]).unwrap();
+        info.timestamp += 60;
+        info.git_commit = "def".into();
+        history.record(&info, &[measurement("serde", 500)]).unwrap();
+
+        let mut cold = measurement("serde", 3000);
+        cold.cache = Cache::Cold;
+        history.record(&info, &[cold]).unwrap();
+
+        let serde = history.trend(None, Some("serde")).unwrap();
+        assert_eq!(serde.len(), 3);
+        assert_eq!(serde[2].cache, "cold");
+        assert_eq!(serde[2].allocator, "system");
+        assert_eq!(serde[0].git_commit, "abc");
+        assert_eq!(serde[1].ns_per_message, 50.0);
+        assert_eq!(history.trend(Some("0123"), None).unwrap().len(), 4);
+        assert!(history.trend(Some("4567"), None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,318 @@
+//! The assembly and LLVM IR the compiler produced for each strategy, so that
+//! a discussion of why one is faster can point at what it was compiled to.
+//!
+//! `run` builds the strategies crate in release mode with rustc asked to
+//! emit both, in one codegen unit so that each is one file, under
+//! target/inspect. It picks out the functions for each strategy: its
+//! `parse_into`, and its closures, and for the strategies that deserialize
+//! into one type, that type's `Deserialize` impl and visitors, derived for
+//! rpc2 and rpc3 and written by hand for rpc5. Each strategy's functions are
+//! written to STRATEGY.s and STRATEGY.ll in a directory.
+//!
+//! Whatever was inlined is in the function it was inlined into, so a
+//! strategy with no `Deserialize` functions of its own has them in its
+//! `parse_into`. The functions are found by their symbols, which are
+//! demangled here rather than with a crate for it; only the legacy scheme,
+//! rustc's default, is understood. A strategy whose type isn't named for
+//! it, as the generated ones aren't, isn't found.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use builds;
+
+/// The type each strategy deserializes its messages into, where it's one
+/// type, parsed straight from the string.
+const TARGETS: &[(&str, &str)] = &[
+    ("future_serde", "rpc3::CoreNotification"),
+    ("hand", "rpc5::CoreNotification"),
+    ("scan_id", "rpc2::CoreNotification"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Asm,
+    Ir,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Asm => "s",
+            Format::Ir => "ll",
+        }
+    }
+
+    fn comment(self) -> &'static str {
+        match self {
+            Format::Asm => "#",
+            Format::Ir => ";",
+        }
+    }
+}
+
+/// One function, as it is in the emitted file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// Demangled.
+    pub name: String,
+    pub text: String,
+}
+
+impl Function {
+    /// The lines of assembly that are instructions, rather than labels,
+    /// directives or comments.
+    pub fn instructions(&self) -> usize {
+        self.text.lines()
+            .filter(|line| line.starts_with(char::is_whitespace))
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('.') && !line.starts_with('#'))
+            .count()
+    }
+}
+
+/// What was written for one strategy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listing {
+    pub strategy: String,
+    pub functions: usize,
+    pub instructions: usize,
+}
+
+/// Builds the strategies crate, with `features` passed to cargo as they
+/// are, and writes the functions of each of `strategies` to `dir`.
+pub fn run(strategies: &[String], features: Option<&str>, dir: &Path)
+    -> Result<Vec<Listing>, String>
+{
+    let _span = info_span!("inspect").entered();
+    let emitted = build(features)?;
+    let asm = functions(&read(&emitted, Format::Asm)?, Format::Asm);
+    let ir = functions(&read(&emitted, Format::Ir)?, Format::Ir);
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    let mut listings = Vec::new();
+    for strategy in strategies {
+        let its_asm = asm.iter().filter(|f| belongs_to(strategy, &f.name)).collect::<Vec<_>>();
+        let its_ir = ir.iter().filter(|f| belongs_to(strategy, &f.name)).collect::<Vec<_>>();
+        if !its_asm.is_empty() {
+            write(&dir.join(format!("{}.s", strategy)), &its_asm, Format::Asm)?;
+            write(&dir.join(format!("{}.ll", strategy)), &its_ir, Format::Ir)?;
+        }
+        listings.push(Listing {
+            strategy: strategy.clone(),
+            functions: its_asm.len(),
+            instructions: its_asm.iter().map(|f| f.instructions()).sum(),
+        });
+    }
+    Ok(listings)
+}
+
+/// Builds the strategies crate, and returns the path of what it emitted,
+/// without an extension.
+fn build(features: Option<&str>) -> Result<PathBuf, String> {
+    let workspace = builds::workspace();
+    let target = workspace.join("target").join("inspect");
+    let mut cargo = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+    cargo.args(&["rustc", "--release", "--quiet", "-p", "xi-der-strategies", "--lib"])
+        .arg("--manifest-path").arg(workspace.join("Cargo.toml"))
+        .arg("--target-dir").arg(&target);
+    if let Some(features) = features {
+        cargo.arg("--features").arg(features);
+    }
+    cargo.args(&["--", "--emit", "asm,llvm-ir,link", "-C", "codegen-units=1"]);
+    let status = cargo.status().map_err(|e| format!("cargo: {}", e))?;
+    if !status.success() {
+        return Err(format!("cargo {}", status));
+    }
+
+    // deps has one for each build with other features; the newest is this
+    let deps = target.join("release").join("deps");
+    let newest = fs::read_dir(&deps).map_err(|e| format!("{}: {}", deps.display(), e))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "s"))
+        .filter(|path| path.file_name().and_then(|n| n.to_str())
+                .map_or(false, |n| n.starts_with("xi_der_strategies-")))
+        .max_by_key(|path| fs::metadata(path).and_then(|m| m.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH));
+    newest.map(|path| path.with_extension(""))
+        .ok_or_else(|| format!("{}: no assembly was emitted", deps.display()))
+}
+
+fn read(emitted: &Path, format: Format) -> Result<String, String> {
+    let path = emitted.with_extension(format.extension());
+    fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn write(path: &Path, functions: &[&Function], format: Format) -> Result<(), String> {
+    let text = functions.iter()
+        .map(|f| format!("{} {}\n{}\n", format.comment(), f.name, f.text))
+        .collect::<Vec<_>>();
+    fs::write(path, text.join("\n")).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Whether `function` is one of `strategy`'s.
+pub fn belongs_to(strategy: &str, function: &str) -> bool {
+    let parse_into = format!("strategy::{} as xi_der_strategies::strategy::Strategy>::parse_into",
+                             type_name(strategy));
+    function.contains(&parse_into)
+        || TARGETS.iter().any(|&(name, ty)| {
+            name == strategy && function.contains(ty) && function.contains("deserialize")
+        })
+}
+
+/// "scan_id" as "ScanId".
+fn type_name(strategy: &str) -> String {
+    strategy.split('_').flat_map(|word| {
+        let mut chars = word.chars();
+        chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars)
+    }).collect()
+}
+
+/// The functions defined in `text`, an assembly or IR file, by name.
+pub fn functions(text: &str, format: Format) -> Vec<Function> {
+    let mut functions = Vec::new();
+    let mut current: Option<(String, Vec<&str>)> = None;
+    for line in text.lines() {
+        if let Some((name, mut lines)) = current.take() {
+            let end = match format {
+                Format::Asm => line.starts_with(".Lfunc_end") || line.starts_with("Lfunc_end"),
+                Format::Ir => line == "}",
+            };
+            if format == Format::Ir || !end {
+                lines.push(line);
+            }
+            if end {
+                functions.push(Function { name, text: lines.join("\n") });
+            } else {
+                current = Some((name, lines));
+            }
+            continue;
+        }
+        let symbol = match format {
+            // followed by a comment, as "sym:  # @sym"
+            Format::Asm if !line.starts_with(|c: char| c.is_whitespace() || c == '.') => {
+                line.split(|c: char| c.is_whitespace() || c == '#').next()
+                    .and_then(|label| label.strip_suffix(':'))
+            }
+            Format::Ir if line.starts_with("define ") => {
+                line.find('@').map(|at| &line[at + 1..]).map(|rest| {
+                    let end = rest.find(|c: char| c == '(' || c.is_whitespace())
+                        .unwrap_or(rest.len());
+                    &rest[..end]
+                })
+            }
+            _ => None,
+        };
+        if let Some(name) = symbol.and_then(|s| demangle(s.trim_matches('"'))) {
+            current = Some((name, vec![line]));
+        }
+    }
+    functions
+}
+
+/// Demangles a symbol in rustc's legacy scheme, without the hash at the
+/// end; `None` if it isn't one.
+pub fn demangle(symbol: &str) -> Option<String> {
+    // macOS adds an underscore
+    let mut rest = symbol.trim_start_matches('_').strip_prefix("ZN")?;
+    let mut path = Vec::new();
+    while !rest.starts_with('E') {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let len = rest[..digits].parse::<usize>().ok()?;
+        path.push(rest.get(digits..digits + len)?);
+        rest = &rest[digits + len..];
+    }
+    let is_hash = |s: &str| {
+        s.len() == 17 && s.starts_with('h') && s[1..].bytes().all(|b| b.is_ascii_hexdigit())
+    };
+    if path.last().map_or(false, |&last| is_hash(last)) {
+        path.pop();
+    }
+    Some(path.into_iter().map(unescape).collect::<Vec<_>>().join("::"))
+}
+
+/// One segment of a legacy symbol, with its escapes replaced.
+fn unescape(segment: &str) -> String {
+    let mut rest = if segment.starts_with("_$") { &segment[1..] } else { segment };
+    let mut out = String::new();
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("..") {
+            out.push_str("::");
+            rest = &rest[2..];
+            continue;
+        }
+        if c == '$' {
+            if let Some(len) = rest[1..].find('$') {
+                let code = &rest[1..len + 1];
+                let decoded = match code {
+                    "SP" => Some('@'),
+                    "BP" => Some('*'),
+                    "RF" => Some('&'),
+                    "LT" => Some('<'),
+                    "GT" => Some('>'),
+                    "LP" => Some('('),
+                    "RP" => Some(')'),
+                    "C" => Some(','),
+                    _ => code.strip_prefix('u')
+                        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                        .and_then(::std::char::from_u32),
+                };
+                if let Some(decoded) = decoded {
+                    out.push(decoded);
+                    rest = &rest[len + 2..];
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARSE_INTO: &str = concat!("_ZN91_$LT$xi_der_strategies..strategy..Hand$u20$as$u20$",
+                                     "xi_der_strategies..strategy..Strategy$GT$10parse_into",
+                                     "17h0123456789abcdefE");
+
+    #[test]
+    fn demangles() {
+        let name = "<xi_der_strategies::strategy::Hand as xi_der_strategies::strategy::Strategy>\
+                    ::parse_into";
+        assert_eq!(demangle(PARSE_INTO).unwrap(), name);
+        assert_eq!(demangle(&format!("_{}", PARSE_INTO)).unwrap(), name);
+        assert_eq!(demangle("_ZN4core3ptr13drop_in_place17h0123456789abcdefE").unwrap(),
+                   "core::ptr::drop_in_place");
+        assert_eq!(demangle("_ZN4core3ptrE").unwrap(), "core::ptr");
+        assert_eq!(demangle("_ZN4core30ptrE"), None);
+        assert_eq!(demangle("main"), None);
+        assert!(belongs_to("hand", name));
+        assert!(!belongs_to("serde", name));
+        assert!(belongs_to("hand", "<xi_der_strategies::rpc5::CoreNotification \
+                                      as serde::de::Deserialize>::deserialize"));
+    }
+
+    #[test]
+    fn finds_functions() {
+        let asm = format!("\t.text\n{0}:  # @{0}\n\t.cfi_startproc\n\tpushq\t%rbx\n\
+                           .LBB0_1:\n\tretq\n.Lfunc_end0:\n\t.size\t{0}, .Lfunc_end0-{0}\n\
+                           main:\n", PARSE_INTO);
+        let found = functions(&asm, Format::Asm);
+        assert_eq!(found.len(), 1);
+        assert!(belongs_to("hand", &found[0].name));
+        assert_eq!(found[0].instructions(), 2);
+
+        let ir = format!("declare void @f()\n\ndefine internal void @{}(ptr %self) {{\n\
+                          start:\n  ret void\n}}\n", PARSE_INTO);
+        let found = functions(&ir, Format::Ir);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].text.ends_with("ret void\n}"));
+    }
+}
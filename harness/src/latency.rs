@@ -0,0 +1,113 @@
+//! Latency budgets: how long one keystroke may take to parse.
+//!
+//! The benches report throughput, and a strategy can do well on it while
+//! now and then taking much longer over a single message, which is what a
+//! user typing would notice. `sample` times each of many parses of one
+//! message on its own, and `check` fails if the 99th percentile is over a
+//! budget, so that a regression in what interactivity needs fails a build
+//! rather than shifting a number in a table.
+//!
+//! Budgets only mean something for a release build on a known class of
+//! machine, so the test below is ignored unless asked for, and takes its
+//! budget from the environment, in microseconds:
+//!
+//! ```sh
+//! DER_BENCH_P99_US=5 cargo test --release -p xi-der-harness latency -- --ignored
+//! ```
+//!
+//! Each parse is timed with `Instant`, which on most platforms costs a few
+//! tens of nanoseconds, and that's included; a budget of a few microseconds
+//! leaves room for it.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use pipeline::Latencies;
+use strategy::Strategy;
+use xi_der_strategies::sink::Sink;
+
+/// A keystroke, as xi-mac sends it.
+pub const KEYSTROKE: &str = concat!(r#"{"method":"edit","params":{"view_id":"view-id-1","#,
+                                   r#""method":"insert","params":{"chars":"a"}}}"#);
+
+/// The strategies checked when none is given: the ones `pipeline` runs,
+/// each a candidate for xi's parser.
+pub const STRATEGIES: &[&str] = &["serde", "future_serde", "hand", "tape"];
+
+/// The budget, in microseconds, when none is given.
+pub const DEFAULT_P99_US: f64 = 5.0;
+
+/// How many parses are made, and not timed, before sampling, so that the
+/// caches and branch predictors are as they would be in steady typing.
+const WARM_UP: usize = 1000;
+
+/// Parses `json` with `strategy` `samples` times, timing each parse.
+pub fn sample(strategy: &dyn Strategy, json: &str, samples: usize) -> Latencies {
+    let mut sink = Sink::new();
+    for _ in 0..WARM_UP {
+        let _ = strategy.parse_into(json, &mut sink);
+    }
+    let mut latencies = Vec::with_capacity(samples);
+    let mut errors = 0;
+    let start = Instant::now();
+    for _ in 0..samples {
+        let parse = Instant::now();
+        if strategy.parse_into(json, &mut sink).is_err() {
+            errors += 1;
+        }
+        latencies.push(parse.elapsed());
+    }
+    let elapsed = start.elapsed();
+    black_box(sink);
+    Latencies::new(strategy.name(), latencies, errors, elapsed)
+}
+
+/// Errors if any of the parses in `latencies` failed, or the 99th
+/// percentile is over `budget`.
+pub fn check(latencies: &Latencies, budget: Duration) -> Result<(), String> {
+    if latencies.errors > 0 {
+        return Err(format!("{}: {} of {} parses failed", latencies.name, latencies.errors,
+                           latencies.messages));
+    }
+    if latencies.p99 > budget {
+        return Err(format!("{}: p99 of {:.2}µs is over the budget of {:.2}µs", latencies.name,
+                           latencies.p99.as_secs_f64() * 1e6, budget.as_secs_f64() * 1e6));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use strategy;
+
+    #[test]
+    fn checks() {
+        let serde = strategy::by_name("serde").unwrap();
+        let latencies = sample(&*serde, KEYSTROKE, 100);
+        assert_eq!((latencies.messages, latencies.errors), (100, 0));
+        assert!(check(&latencies, Duration::from_secs(1)).is_ok());
+        assert!(check(&latencies, Duration::new(0, 0)).is_err());
+        assert!(check(&sample(&*serde, "{", 10), Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn keystroke_within_budget() {
+        let budget = env::var("DER_BENCH_P99_US").ok()
+            .map(|us| us.parse::<f64>().expect("DER_BENCH_P99_US should be a number"))
+            .unwrap_or(DEFAULT_P99_US);
+        let budget = match Duration::try_from_secs_f64(budget / 1e6) {
+            Ok(budget) if budget > Duration::new(0, 0) => budget,
+            _ => panic!("DER_BENCH_P99_US should be a positive number of microseconds, \
+                         not {}", budget),
+        };
+        let failures = STRATEGIES.iter()
+            .map(|name| strategy::by_name(name).unwrap())
+            .filter_map(|s| check(&sample(&*s, KEYSTROKE, 100_000), budget).err())
+            .collect::<Vec<_>>();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
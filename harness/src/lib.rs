@@ -0,0 +1,67 @@
+//! Timing the strategies over corpora outside of `cargo bench`, and
+//! recording the results along with the circumstances of the run.
+
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate tracing;
+extern crate toml;
+
+extern crate xi_der_corpus as corpus;
+extern crate xi_der_strategies;
+
+#[cfg(feature = "history")]
+#[macro_use]
+extern crate rusqlite;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(all(unix, feature = "shm"))]
+extern crate memmap2;
+#[cfg(all(target_os = "linux", feature = "perf"))]
+extern crate perf_event;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate crossbeam;
+#[cfg(feature = "flume")]
+extern crate flume;
+#[cfg(feature = "ringbuf")]
+extern crate ringbuf;
+#[cfg(loom)]
+extern crate loom;
+
+pub mod runner;
+pub mod memory;
+pub mod report;
+pub mod config;
+pub mod estimate;
+pub mod codegen;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod transport;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pipeline;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod external;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod latency;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod builds;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod opt_matrix;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pgo;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod inspect;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod breakdown;
+#[cfg(all(unix, feature = "shm"))]
+pub mod shm;
+#[cfg(all(target_os = "linux", feature = "perf"))]
+pub mod counters;
+#[cfg(feature = "history")]
+pub mod history;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use xi_der_strategies::{sink, strategy};
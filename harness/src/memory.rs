@@ -0,0 +1,145 @@
+//! Peak memory use while a strategy runs.
+//!
+//! The strategies that go through a `Value` allocate far more than the ones
+//! that don't, and on a large corpus that's a lot of memory that doesn't
+//! show up as time. This tracks the peak resident set size, on Linux: the
+//! kernel's high-water mark is reset before each run and read after it,
+//! and if it can't be reset, a thread samples the current size instead.
+//! Elsewhere there are no numbers.
+
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(target_os = "linux")]
+use std::sync::Arc;
+#[cfg(target_os = "linux")]
+use std::thread;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
+/// Resident set sizes over a run, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Memory {
+    /// When the run started.
+    pub before: u64,
+    /// The most at any point during the run.
+    pub peak: u64,
+}
+
+impl Memory {
+    /// How far above its starting point the run went.
+    pub fn growth(&self) -> u64 {
+        self.peak.saturating_sub(self.before)
+    }
+}
+
+/// Watches memory use from `start` until `finish`.
+pub struct Tracker {
+    #[cfg(target_os = "linux")]
+    before: Option<u64>,
+    #[cfg(target_os = "linux")]
+    sampler: Option<Sampler>,
+}
+
+impl Tracker {
+    #[cfg(target_os = "linux")]
+    pub fn start() -> Tracker {
+        let before = status_kb("VmRSS:");
+        // 5 resets the high-water mark to the current RSS; see proc(5)
+        let sampler = if fs::write("/proc/self/clear_refs", "5").is_ok() {
+            None
+        } else {
+            before.map(Sampler::start)
+        };
+        Tracker { before, sampler }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn start() -> Tracker {
+        Tracker {}
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn finish(self) -> Option<Memory> {
+        let before = self.before?;
+        let peak = match self.sampler {
+            Some(sampler) => sampler.finish(),
+            None => status_kb("VmHWM:")?,
+        };
+        Some(Memory { before: before * 1024, peak: peak.max(before) * 1024 })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn finish(self) -> Option<Memory> {
+        None
+    }
+}
+
+/// How often the fallback sampler looks at the RSS.
+#[cfg(target_os = "linux")]
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(1);
+
+#[cfg(target_os = "linux")]
+struct Sampler {
+    done: Arc<AtomicBool>,
+    peak: Arc<AtomicU64>,
+    thread: thread::JoinHandle<()>,
+}
+
+#[cfg(target_os = "linux")]
+impl Sampler {
+    fn start(before: u64) -> Sampler {
+        let done = Arc::new(AtomicBool::new(false));
+        let peak = Arc::new(AtomicU64::new(before));
+        let thread = {
+            let (done, peak) = (done.clone(), peak.clone());
+            thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    if let Some(rss) = status_kb("VmRSS:") {
+                        peak.fetch_max(rss, Ordering::Relaxed);
+                    }
+                    thread::sleep(SAMPLE_INTERVAL);
+                }
+            })
+        };
+        Sampler { done, peak, thread }
+    }
+
+    fn finish(self) -> u64 {
+        self.done.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+        self.peak.load(Ordering::Relaxed)
+    }
+}
+
+/// A field of /proc/self/status, in kB.
+#[cfg(target_os = "linux")]
+fn status_kb(field: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status.lines()
+        .find(|l| l.starts_with(field))
+        .and_then(|l| l[field.len()..].trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::hint::black_box;
+
+    use super::*;
+
+    #[test]
+    fn sees_a_big_allocation() {
+        let tracker = Tracker::start();
+        let mut big = vec![0u8; 64 << 20];
+        // touch every page, so that it's resident
+        for b in big.iter_mut().step_by(4096) {
+            *b = 1;
+        }
+        black_box(&big);
+        // before dropping it, in case another test resets the high-water mark
+        let memory = tracker.finish().unwrap();
+        drop(big);
+        assert!(memory.growth() >= 60 << 20, "{:?}", memory);
+    }
+}
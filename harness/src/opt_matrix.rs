@@ -0,0 +1,79 @@
+//! Running the strategies built at several opt-levels, with and without LTO.
+//!
+//! How the strategies rank can change with the optimizer's settings: a
+//! strategy that leans on inlining across crates gains more from LTO than
+//! one that's all in one function, and at opt-level 1 the generic serde
+//! code barely inlines at all. xi builds with its own profile, which needn't
+//! be the one these benches were run with.
+//!
+//! Each `Profile` is a `Build` with the release profile overridden through
+//! cargo's environment, under target/opt-matrix.
+
+use builds::{Build, Comparison};
+
+/// One build's settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    pub opt_level: u32,
+    /// Fat LTO, if set; none at all otherwise.
+    pub lto: bool,
+}
+
+impl Profile {
+    /// Each of `levels`, without and with LTO.
+    pub fn matrix(levels: &[u32]) -> Vec<Profile> {
+        levels.iter()
+            .flat_map(|&opt_level| vec![false, true].into_iter()
+                      .map(move |lto| Profile { opt_level, lto }))
+            .collect()
+    }
+
+    /// Parses a comma-separated list of opt-levels, such as "1,2,3".
+    pub fn parse_levels(s: &str) -> Result<Vec<u32>, String> {
+        s.split(',')
+            .map(|level| match level.trim().parse() {
+                Ok(n) if n <= 3 => Ok(n),
+                _ => Err(format!("invalid opt-level '{}'", level)),
+            })
+            .collect()
+    }
+
+    /// "O2", or "O2+lto".
+    pub fn name(&self) -> String {
+        format!("O{}{}", self.opt_level, if self.lto { "+lto" } else { "" })
+    }
+
+    pub fn build(&self) -> Build {
+        Build::new("opt-matrix", &self.name())
+            .env("CARGO_PROFILE_RELEASE_OPT_LEVEL", &self.opt_level.to_string())
+            .env("CARGO_PROFILE_RELEASE_LTO", if self.lto { "fat" } else { "off" })
+    }
+}
+
+/// Builds and runs der_bench under each of `profiles`, passing it
+/// `run_args`, and collects the results; see `Build::run`.
+pub fn run(profiles: &[Profile], run_args: &[String], features: Option<&str>)
+    -> Result<Comparison, String>
+{
+    let mut comparison = Comparison::new(profiles.iter().map(Profile::name).collect());
+    for (idx, profile) in profiles.iter().enumerate() {
+        let report = profile.build().run(run_args, features)?;
+        comparison.add_report(idx, &report).map_err(|e| format!("{}: {}", profile.name(), e))?;
+    }
+    Ok(comparison)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles() {
+        assert_eq!(Profile::parse_levels("1, 3"), Ok(vec![1, 3]));
+        assert!(Profile::parse_levels("4").is_err());
+        assert!(Profile::parse_levels("s").is_err());
+        let names = Profile::matrix(&[1, 3]).iter().map(Profile::name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["O1", "O1+lto", "O3", "O3+lto"]);
+        assert_eq!(Profile { opt_level: 1, lto: true }.build().env[1].1, "fat");
+    }
+}
@@ -0,0 +1,77 @@
+//! Profile-guided optimization, to see whether it changes which strategy
+//! wins.
+//!
+//! `run` builds der_bench instrumented, with `-Cprofile-generate`, runs it
+//! over a training corpus, merges the profiles it wrote with llvm-profdata,
+//! and rebuilds it with `-Cprofile-use`. That build and a plain release
+//! build are then run over another corpus; training and measuring on the
+//! same one would flatter PGO. The builds go under target/pgo, with the
+//! profiles.
+//!
+//! llvm-profdata has to read what rustc's LLVM wrote. `rustup component add
+//! llvm-tools-preview` installs a matching one in the sysroot; it's looked
+//! for in `LLVM_PROFDATA`, then there, then on the `PATH`. For the benches,
+//! rather than der_bench, see harness/scripts/pgo_bench.sh.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use builds::{self, Build, Comparison};
+
+/// Trains on `train_args` and measures with `measure_args`, each of which
+/// start with "run"; see `Build::run`.
+pub fn run(train_args: &[String], measure_args: &[String], features: Option<&str>)
+    -> Result<Comparison, String>
+{
+    let dir = builds::workspace().join("target").join("pgo");
+    let profiles = dir.join("profiles");
+    let merged = dir.join("merged.profdata");
+    // stale profiles from another build would be merged in
+    let _ = fs::remove_dir_all(&profiles);
+
+    let _span = info_span!("pgo").entered();
+    Build::new("pgo", "instrumented")
+        .rustflags(&format!("-Cprofile-generate={}", profiles.display()))
+        .run(train_args, features)?;
+    let profdata = llvm_profdata();
+    let status = Command::new(&profdata)
+        .arg("merge").arg("-o").arg(&merged).arg(&profiles)
+        .status()
+        .map_err(|e| format!("{}: {}", profdata.display(), e))?;
+    if !status.success() {
+        return Err(format!("{}: {}", profdata.display(), status));
+    }
+
+    let builds = vec![
+        Build::new("pgo", "release"),
+        Build::new("pgo", "pgo").rustflags(&format!("-Cprofile-use={}", merged.display())),
+    ];
+    let mut comparison = Comparison::new(builds.iter().map(|b| b.name.clone()).collect());
+    for (idx, build) in builds.iter().enumerate() {
+        let report = build.run(measure_args, features)?;
+        comparison.add_report(idx, &report).map_err(|e| format!("{}: {}", build.name, e))?;
+    }
+    Ok(comparison)
+}
+
+/// Where llvm-profdata is.
+pub fn llvm_profdata() -> PathBuf {
+    if let Some(path) = env::var_os("LLVM_PROFDATA") {
+        return PathBuf::from(path);
+    }
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let sysroot = Command::new(rustc).args(&["--print", "sysroot"]).output().ok()
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|s| PathBuf::from(s.trim()));
+    // lib/rustlib/<host>/bin, for the one host there is
+    let in_sysroot = sysroot
+        .and_then(|root| fs::read_dir(root.join("lib").join("rustlib")).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().join("bin").join("llvm-profdata"))
+        .find(|path| path.exists());
+    in_sysroot.unwrap_or_else(|| PathBuf::from("llvm-profdata"))
+}
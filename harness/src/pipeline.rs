@@ -0,0 +1,335 @@
+//! Parsing on one thread and handling on another, as xi does, timing each
+//! message from when it's read to when it's been dispatched.
+//!
+//! Where the parse can happen depends on the message type. rpc2's messages
+//! are owned, so the reader thread can parse them and hand them over. The
+//! borrowing types can't outlive the reader's buffer, so the line itself
+//! has to be sent, and parsed on the handler thread. The queue between the
+//! two is bounded, but for crossbeam's unbounded `Channel`, so latencies
+//! include any time spent waiting in it.
+//!
+//! Which queue suits xi's reader thread may depend on what's sent over it:
+//! a big owned enum moves differently than a `String`. Each pipeline can be
+//! run over each `Channel`, to see.
+
+use std::hint::black_box;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel as crossbeam_channel;
+#[cfg(feature = "flume")]
+use flume;
+use serde_json::{self, Value};
+
+use strategy::{self, Strategy};
+use xi_der_strategies::{dispatch, rpc2, rpc3};
+
+/// How many messages can be waiting for the handler.
+pub const QUEUE_DEPTH: usize = 64;
+
+/// The queue between the reader and the handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// `mpsc::sync_channel`.
+    Std,
+    CrossbeamBounded,
+    CrossbeamUnbounded,
+    #[cfg(feature = "flume")]
+    Flume,
+    /// A lock-free single-producer, single-consumer ring, which doesn't
+    /// block: each end spins, then yields, while it has to wait.
+    #[cfg(feature = "ringbuf")]
+    Ringbuf,
+}
+
+impl Channel {
+    /// Every channel this was built with.
+    pub fn all() -> Vec<Channel> {
+        let mut all = vec![Channel::Std, Channel::CrossbeamBounded, Channel::CrossbeamUnbounded];
+        #[cfg(feature = "flume")]
+        all.push(Channel::Flume);
+        #[cfg(feature = "ringbuf")]
+        all.push(Channel::Ringbuf);
+        all
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Std => "std",
+            Channel::CrossbeamBounded => "crossbeam",
+            Channel::CrossbeamUnbounded => "crossbeam_unbounded",
+            #[cfg(feature = "flume")]
+            Channel::Flume => "flume",
+            #[cfg(feature = "ringbuf")]
+            Channel::Ringbuf => "ringbuf",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Channel> {
+        Channel::all().into_iter().find(|c| c.name() == name)
+    }
+}
+
+/// The results of running one pipeline over a corpus.
+#[derive(Debug, Clone)]
+pub struct Latencies {
+    pub name: &'static str,
+    pub messages: usize,
+    pub errors: usize,
+    /// From the first read to the last dispatch.
+    pub elapsed: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Latencies {
+    pub fn new(name: &'static str, mut latencies: Vec<Duration>, errors: usize, elapsed: Duration)
+        -> Latencies
+    {
+        latencies.sort();
+        let at = |fraction: f64| {
+            let idx = ((latencies.len() as f64 * fraction) as usize).min(latencies.len() - 1);
+            latencies[idx]
+        };
+        let total = latencies.iter().fold(Duration::new(0, 0), |acc, &d| acc + d);
+        Latencies {
+            name,
+            messages: latencies.len(),
+            errors,
+            elapsed,
+            mean: total / latencies.len().max(1) as u32,
+            p50: if latencies.is_empty() { Duration::new(0, 0) } else { at(0.5) },
+            p99: if latencies.is_empty() { Duration::new(0, 0) } else { at(0.99) },
+            max: latencies.last().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Runs every pipeline over `passes` passes of `lines`, through `channel`.
+pub fn run_all(lines: &[String], passes: u64, channel: Channel) -> Vec<Latencies> {
+    let mut all = vec![
+        run("serde", channel, lines, passes, parse_rpc2, {
+            let mut handler = dispatch::Counter::default();
+            move |msg: Option<Rpc2>| match msg {
+                Some(Rpc2::Notification(msg)) => {
+                    dispatch::match_rpc2(&msg, &mut handler);
+                    true
+                }
+                Some(Rpc2::Request(req)) => {
+                    // there's no dispatch for requests yet
+                    black_box(req);
+                    handler.calls += 1;
+                    true
+                }
+                None => false,
+            }
+        }),
+        run("future_serde", channel, lines, passes, str::to_owned, {
+            let mut handler = dispatch::Counter::default();
+            move |line: String| match serde_json::from_str::<rpc3::CoreNotification>(&line) {
+                Ok(msg) => {
+                    dispatch::match_rpc3(&msg, &mut handler);
+                    true
+                }
+                Err(_) => false,
+            }
+        }),
+    ];
+    // these have no dispatch, so parsing is all the handler does
+    all.push(run("hand", channel, lines, passes, str::to_owned,
+                 |line: String| strategy::Hand.parse(&line).is_ok()));
+    all.push(run("tape", channel, lines, passes, str::to_owned,
+                 |line: String| strategy::Tape.parse(&line).is_ok()));
+    all
+}
+
+/// A parsed rpc2 message, ready to be handed over.
+enum Rpc2 {
+    Notification(rpc2::CoreNotification),
+    Request(rpc2::CoreRequest),
+}
+
+fn parse_rpc2(json: &str) -> Option<Rpc2> {
+    let mut val = serde_json::from_str::<Value>(json).ok()?;
+    let id = val.as_object_mut().and_then(|obj| obj.remove("id"));
+    if id.is_some() {
+        serde_json::from_value(val).ok().map(Rpc2::Request)
+    } else {
+        serde_json::from_value(val).ok().map(Rpc2::Notification)
+    }
+}
+
+/// Calls `parse` with each line on this thread, and `handle` with the
+/// results on another, sending them through `channel`. `handle` returns
+/// whether the message was valid.
+fn run<M, P, H>(name: &'static str, channel: Channel, lines: &[String], passes: u64, parse: P,
+                handle: H)
+    -> Latencies
+    where M: Send, P: Fn(&str) -> M, H: FnMut(M) -> bool + Send
+{
+    let span = info_span!("pipeline", strategy = name, channel = channel.name(), passes);
+    let _entered = span.enter();
+    // each is its own monomorphization, so that none pays for the others
+    match channel {
+        Channel::Std => {
+            let (tx, rx) = mpsc::sync_channel::<(Instant, M)>(QUEUE_DEPTH);
+            handoff(name, lines, passes, parse, handle, move |msg| tx.send(msg).is_ok(), rx)
+        }
+        Channel::CrossbeamBounded => {
+            let (tx, rx) = crossbeam_channel::bounded::<(Instant, M)>(QUEUE_DEPTH);
+            handoff(name, lines, passes, parse, handle, move |msg| tx.send(msg).is_ok(), rx)
+        }
+        Channel::CrossbeamUnbounded => {
+            let (tx, rx) = crossbeam_channel::unbounded::<(Instant, M)>();
+            handoff(name, lines, passes, parse, handle, move |msg| tx.send(msg).is_ok(), rx)
+        }
+        #[cfg(feature = "flume")]
+        Channel::Flume => {
+            let (tx, rx) = flume::bounded::<(Instant, M)>(QUEUE_DEPTH);
+            handoff(name, lines, passes, parse, handle, move |msg| tx.send(msg).is_ok(), rx)
+        }
+        #[cfg(feature = "ringbuf")]
+        Channel::Ringbuf => {
+            let (mut tx, rx) = spsc::channel::<(Instant, M)>(QUEUE_DEPTH);
+            handoff(name, lines, passes, parse, handle, move |msg| tx.send(msg), rx)
+        }
+    }
+}
+
+/// `run`, with a channel's ends. `send` returns false if the handler has
+/// gone, and the receiver ends once `send` is dropped.
+fn handoff<M, P, H, S, R>(name: &'static str, lines: &[String], passes: u64, parse: P,
+                          mut handle: H, mut send: S, rx: R) -> Latencies
+    where M: Send, P: Fn(&str) -> M, H: FnMut(M) -> bool + Send,
+          S: FnMut((Instant, M)) -> bool, R: IntoIterator<Item = (Instant, M)> + Send
+{
+    let start = Instant::now();
+    let (latencies, errors) = thread::scope(|scope| {
+        // in the pipeline's span, which is entered
+        let handler_span = debug_span!("handler");
+        let handler = scope.spawn(move || {
+            let _entered = handler_span.enter();
+            let mut latencies = Vec::new();
+            let mut errors = 0;
+            for (read_at, msg) in rx {
+                if !handle(msg) {
+                    errors += 1;
+                }
+                latencies.push(read_at.elapsed());
+            }
+            (latencies, errors)
+        });
+        // once the handler has gone, there's nothing to read for
+        'passes: for _ in 0..passes {
+            for line in lines {
+                let read_at = Instant::now();
+                if !send((read_at, parse(line))) {
+                    break 'passes;
+                }
+            }
+        }
+        drop(send);
+        handler.join().expect("handler thread panicked")
+    });
+    Latencies::new(name, latencies, errors, start.elapsed())
+}
+
+/// A `ringbuf` ring, with ends that wait for each other as channels do.
+#[cfg(feature = "ringbuf")]
+mod spsc {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    use ringbuf::{HeapCons, HeapProd, HeapRb};
+    use ringbuf::traits::{Consumer, Producer, Split};
+
+    pub struct Sender<T> {
+        ring: HeapProd<T>,
+        closed: Arc<AtomicBool>,
+    }
+
+    pub struct Receiver<T> {
+        ring: HeapCons<T>,
+        closed: Arc<AtomicBool>,
+    }
+
+    pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        let (prod, cons) = HeapRb::new(capacity).split();
+        let closed = Arc::new(AtomicBool::new(false));
+        (Sender { ring: prod, closed: closed.clone() }, Receiver { ring: cons, closed })
+    }
+
+    impl<T> Sender<T> {
+        /// Waits for room, if there isn't any. There's no telling whether
+        /// the receiver has gone, so this always succeeds.
+        pub fn send(&mut self, mut msg: T) -> bool {
+            while let Err(back) = self.ring.try_push(msg) {
+                msg = back;
+                thread::yield_now();
+            }
+            true
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            self.closed.store(true, Ordering::Release);
+        }
+    }
+
+    impl<T> Iterator for Receiver<T> {
+        type Item = T;
+
+        fn next(&mut self) -> Option<T> {
+            loop {
+                if let Some(msg) = self.ring.try_pop() {
+                    return Some(msg);
+                }
+                // once it's closed, everything sent is in the ring
+                if self.closed.load(Ordering::Acquire) {
+                    return self.ring.try_pop();
+                }
+                thread::yield_now();
+            }
+        }
+    }
+}
+
+/// Prints a table of latencies, in microseconds.
+pub fn print_latencies(all: &[Latencies]) {
+    println!("{:<14}{:>10}{:>8}{:>12}{:>10}{:>10}{:>10}{:>10}",
+             "pipeline", "messages", "errors", "msgs/sec", "mean µs", "p50 µs", "p99 µs",
+             "max µs");
+    let us = |d: Duration| d.as_secs_f64() * 1e6;
+    for l in all {
+        println!("{:<14}{:>10}{:>8}{:>12.0}{:>10.1}{:>10.1}{:>10.1}{:>10.1}",
+                 l.name, l.messages, l.errors, l.messages as f64 / l.elapsed.as_secs_f64(),
+                 us(l.mean), us(l.p50), us(l.p99), us(l.max));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corpus::TEST_JSON;
+
+    #[test]
+    fn every_message_arrives() {
+        let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        for channel in Channel::all() {
+            let all = run_all(&lines, 3, channel);
+            assert_eq!(all.iter().map(|l| l.name).collect::<Vec<_>>(),
+                       vec!["serde", "future_serde", "hand", "tape"]);
+            for l in &all {
+                assert_eq!(l.messages, lines.len() * 3, "{} over {:?}", l.name, channel);
+                assert_eq!(l.errors, 0, "{} over {:?}", l.name, channel);
+                assert!(l.p50 <= l.p99 && l.p99 <= l.max, "{:?}", l);
+            }
+            assert_eq!(Channel::from_name(channel.name()), Some(channel));
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Writing run results to files, along with enough information about the
+//! machine and build to make sense of them somewhere else.
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process::Command;
+
+use serde_json;
+
+use corpus::Direction;
+use corpus::manifest::Entry;
+use runner::{duration_ns, Cache, Measurement};
+use strategy::Traits;
+
+/// The version of rustc that built this binary.
+pub const RUSTC_VERSION: &str = env!("DER_BENCH_RUSTC_VERSION");
+
+/// The global allocator, as selected by cargo feature.
+pub use xi_der_strategies::ALLOCATOR;
+
+/// The circumstances of a run.
+#[derive(Serialize, Debug, Clone)]
+pub struct Environment {
+    pub rustc_version: String,
+    /// "name version" for the dependencies we care about, from Cargo.lock.
+    pub dependencies: Vec<String>,
+    pub features: Vec<String>,
+    /// The owned strategies in particular are sensitive to this.
+    pub allocator: String,
+    pub debug_assertions: bool,
+    /// The opt-level the harness was built at, which `run --opt-matrix`
+    /// varies; "" if cargo didn't say.
+    pub opt_level: String,
+    pub os: String,
+    pub arch: String,
+    pub cpu_model: String,
+    /// The cpufreq scaling governor, where available. Anything other than
+    /// "performance" makes results noisier.
+    pub cpu_governor: Option<String>,
+    /// Whether turbo/boost is enabled, where available.
+    pub turbo: Option<bool>,
+}
+
+/// A complete report, as written to an output file.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub environment: Environment,
+    pub corpus: String,
+    pub corpus_hash: String,
+    /// The corpus's manifest entry, if it's described in one.
+    pub corpus_manifest: Option<Entry>,
+    pub results: Vec<ResultRow>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ResultRow {
+    pub strategy: &'static str,
+    /// Which way the messages go, so that rows can be grouped by it.
+    pub direction: Direction,
+    pub cache: Cache,
+    pub messages: usize,
+    pub errors: usize,
+    pub iterations: u64,
+    pub ns_per_iter: f64,
+    pub ns_per_message: f64,
+    /// Peak resident memory during the run, and how far that was above
+    /// where it started, in bytes. Linux only.
+    pub peak_rss: Option<u64>,
+    pub rss_growth: Option<u64>,
+    /// The strategy's checksum of the corpus, in hex, if it takes one.
+    pub checksum: Option<String>,
+    /// The standard deviation of the time per pass, in nanoseconds, and how
+    /// much the run slowed down over its course, as a fraction of the mean;
+    /// see `runner::Spread`.
+    pub std_dev_ns_per_iter: Option<f64>,
+    pub drift: Option<f64>,
+    /// The number of threads that ran the strategy at once.
+    pub threads: u32,
+    /// How long the untimed warm-up pass took, and its first message, in
+    /// nanoseconds; see `runner::WarmUp`.
+    pub first_pass_ns: Option<f64>,
+    pub first_message_ns: Option<f64>,
+    /// What the strategy is like besides fast, if it's one of ours; see
+    /// `strategy::Traits`.
+    pub traits: Option<Traits>,
+}
+
+impl Environment {
+    pub fn current() -> Environment {
+        Environment {
+            rustc_version: RUSTC_VERSION.to_owned(),
+            dependencies: split_list(env!("DER_BENCH_DEPS")),
+            features: split_list(env!("DER_BENCH_FEATURES")),
+            allocator: ALLOCATOR.to_owned(),
+            debug_assertions: cfg!(debug_assertions),
+            opt_level: env!("DER_BENCH_OPT_LEVEL").to_owned(),
+            os: env::consts::OS.to_owned(),
+            arch: env::consts::ARCH.to_owned(),
+            cpu_model: cpu_model(),
+            cpu_governor: read_trimmed("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor"),
+            turbo: turbo_enabled(),
+        }
+    }
+}
+
+impl<'a> From<&'a Measurement> for ResultRow {
+    fn from(m: &'a Measurement) -> ResultRow {
+        ResultRow {
+            strategy: m.strategy,
+            direction: m.direction,
+            cache: m.cache,
+            messages: m.messages,
+            errors: m.errors,
+            iterations: m.iterations,
+            ns_per_iter: m.ns_per_iter(),
+            ns_per_message: m.ns_per_message(),
+            peak_rss: m.memory.map(|mem| mem.peak),
+            rss_growth: m.memory.map(|mem| mem.growth()),
+            checksum: m.checksum.map(|c| format!("{:016x}", c)),
+            std_dev_ns_per_iter: m.spread.map(|s| s.std_dev),
+            drift: m.spread.map(|s| s.drift),
+            threads: m.threads,
+            first_pass_ns: m.warm_up.map(|w| duration_ns(w.first_pass)),
+            first_message_ns: m.warm_up.map(|w| duration_ns(w.first_message)),
+            traits: m.traits(),
+        }
+    }
+}
+
+impl Report {
+    pub fn new(corpus: &str, corpus_hash: String, corpus_manifest: Option<Entry>,
+               measurements: &[Measurement]) -> Report {
+        Report {
+            environment: Environment::current(),
+            corpus: corpus.to_owned(),
+            corpus_hash,
+            corpus_manifest,
+            results: measurements.iter().map(ResultRow::from).collect(),
+        }
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',').filter(|s| !s.is_empty()).map(String::from).collect()
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_owned())
+}
+
+fn cpu_model() -> String {
+    let from_proc = fs::read_to_string("/proc/cpuinfo").ok().and_then(|info| {
+        info.lines()
+            .find(|l| l.starts_with("model name"))
+            .and_then(|l| l.split_once(':'))
+            .map(|(_, model)| model.trim().to_owned())
+    });
+    from_proc
+        .or_else(|| {
+            Command::new("sysctl").args(&["-n", "machdep.cpu.brand_string"]).output().ok()
+                .filter(|out| out.status.success())
+                .and_then(|out| String::from_utf8(out.stdout).ok())
+                .map(|s| s.trim().to_owned())
+        })
+        .unwrap_or_else(|| "unknown".into())
+}
+
+fn turbo_enabled() -> Option<bool> {
+    // intel_pstate reports the inverse
+    if let Some(no_turbo) = read_trimmed("/sys/devices/system/cpu/intel_pstate/no_turbo") {
+        return Some(no_turbo == "0");
+    }
+    read_trimmed("/sys/devices/system/cpu/cpufreq/boost").map(|boost| boost == "1")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::Value;
+
+    use super::*;
+    use runner::{Spread, WarmUp};
+
+    #[test]
+    fn report_includes_environment() {
+        let m = Measurement {
+            strategy: "serde",
+            direction: Direction::S2C,
+            messages: 4,
+            errors: 0,
+            iterations: 2,
+            elapsed: Duration::new(0, 800),
+            cache: Cache::Cold,
+            memory: None,
+            checksum: Some(0x1234),
+            spread: Some(Spread { batches: 4, std_dev: 40.0, drift: 0.02 }),
+            threads: 1,
+            warm_up: Some(WarmUp {
+                first_message: Duration::new(0, 300),
+                first_pass: Duration::new(0, 1200),
+            }),
+        };
+        let report = Report::new("basic.jsonl", "abcd".into(), None, &[m]);
+        let mut out = Vec::new();
+        report.write_json(&mut out).unwrap();
+
+        let json = serde_json::from_slice::<Value>(&out).unwrap();
+        assert_eq!(json["environment"]["rustc_version"], json!(RUSTC_VERSION));
+        assert!(json["environment"]["cpu_model"].is_string());
+        assert_eq!(json["environment"]["allocator"], json!(ALLOCATOR));
+        assert_eq!(json["results"][0]["ns_per_message"], json!(100.0));
+        assert_eq!(json["results"][0]["cache"], json!("cold"));
+        assert_eq!(json["results"][0]["direction"], json!("s2c"));
+        assert_eq!(json["results"][0]["checksum"], json!("0000000000001234"));
+        assert_eq!(json["results"][0]["std_dev_ns_per_iter"], json!(40.0));
+        assert_eq!(json["results"][0]["first_pass_ns"], json!(1200.0));
+        assert_eq!(json["results"][0]["traits"]["borrows"], json!(false));
+        assert_eq!(json["results"][0]["traits"]["maintainability"], json!("high"));
+        assert_eq!(json["corpus_manifest"], Value::Null);
+    }
+}
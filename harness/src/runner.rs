@@ -0,0 +1,741 @@
+//! Timing strategies over a corpus, outside of `cargo bench`.
+
+use std::hint::black_box;
+use std::time::Duration;
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+use std::time::Instant;
+
+use corpus::{Buffer, Direction};
+use memory::{Memory, Tracker};
+use sink::Sink;
+use strategy::{self, Strategy, Traits};
+
+/// How long to run each strategy for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Budget {
+    /// Run exactly this many passes over the corpus.
+    Iterations(u64),
+    /// Run as many passes as fit in this much wall-clock time.
+    Time(Duration),
+}
+
+/// The state of the CPU caches at the start of each pass over the corpus.
+///
+/// Back to back passes keep a small corpus hot in L2, which flatters
+/// strategies that touch a lot of memory. The cold modes evict the caches
+/// before every pass; the eviction itself isn't timed, but does make runs
+/// take much longer than their budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cache {
+    Hot,
+    /// Write a buffer larger than the last-level cache before each pass.
+    Cold,
+    /// As `Cold`, and also `clflush` the corpus itself, where supported
+    /// (x86_64 Linux). Elsewhere, this is the same as `Cold`.
+    Flushed,
+}
+
+impl Cache {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Cache::Hot => "hot",
+            Cache::Cold => "cold",
+            Cache::Flushed => "flushed",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Cache> {
+        [Cache::Hot, Cache::Cold, Cache::Flushed].iter().cloned().find(|c| c.name() == name)
+    }
+}
+
+/// The result of timing one strategy over a corpus.
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub strategy: &'static str,
+    /// Which way the messages the strategy parses go.
+    pub direction: Direction,
+    /// The number of lines in the corpus.
+    pub messages: usize,
+    /// The number of lines the strategy failed to parse.
+    pub errors: usize,
+    /// The number of passes over the corpus.
+    pub iterations: u64,
+    pub elapsed: Duration,
+    pub cache: Cache,
+    /// Resident memory over the run, where it can be measured.
+    pub memory: Option<Memory>,
+    /// The hash of what the strategy parsed from the corpus, for strategies
+    /// that feed a `Sink`.
+    pub checksum: Option<u64>,
+    /// How the time per pass varied over the run, if it was timed in enough
+    /// batches to tell.
+    pub spread: Option<Spread>,
+    /// The number of threads that ran it at once; see `measure_contended`.
+    pub threads: u32,
+    /// How long the warm-up pass took, where the strategy was run in
+    /// process.
+    pub warm_up: Option<WarmUp>,
+}
+
+/// The timing of the pass over the corpus before the timed ones, which is
+/// the first the strategy's instance sees.
+///
+/// A strategy that builds something lazily, a table or a cache or an
+/// interner, pays for it here rather than in the steady state, as does one
+/// that's the first to touch something process-wide, like a `phf` map or
+/// the detection of SIMD features. xi pays that at startup, before the
+/// first keystroke is handled, so it matters too. Anything process-wide is
+/// only cold for the first measurement in the process to touch it, so to
+/// see it for a strategy, run that strategy on its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarmUp {
+    /// How long the first message took.
+    pub first_message: Duration,
+    /// How long the whole pass took, the first message included.
+    pub first_pass: Duration,
+}
+
+/// How the time per pass varied between the batches of a run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spread {
+    /// The number of batches timed.
+    pub batches: usize,
+    /// The standard deviation of the time per pass, in nanoseconds, with
+    /// each batch weighted by its number of passes.
+    pub std_dev: f64,
+    /// How much slower the end of the run was than its start, as a fraction
+    /// of the mean, from a least-squares line through the batches. Positive
+    /// when the run slowed down.
+    pub drift: f64,
+}
+
+/// A run that slows down by more than this, over its course, is suspect:
+/// a steady slowdown is what thermal throttling looks like, or something
+/// else starting up on the machine.
+pub const MAX_DRIFT: f64 = 0.1;
+
+/// How many batches a fixed number of passes is split into, so that they
+/// have a spread.
+const BATCHES: u64 = 16;
+
+impl Measurement {
+    /// Mean time per pass over the corpus, in nanoseconds.
+    pub fn ns_per_iter(&self) -> f64 {
+        duration_ns(self.elapsed) / self.iterations as f64
+    }
+
+    /// Mean time per message, in nanoseconds.
+    pub fn ns_per_message(&self) -> f64 {
+        self.ns_per_iter() / self.messages.max(1) as f64
+    }
+
+    /// The standard deviation of the time per pass, as a fraction of the
+    /// mean.
+    pub fn variation(&self) -> Option<f64> {
+        self.spread.map(|s| s.std_dev / self.ns_per_iter())
+    }
+
+    /// Whether the run slowed down as it went, by more than `MAX_DRIFT`.
+    pub fn slowed(&self) -> bool {
+        self.spread.map_or(false, |s| s.drift > MAX_DRIFT)
+    }
+
+    /// How many times longer the warm-up pass took than the mean of the
+    /// timed ones.
+    pub fn warm_up_ratio(&self) -> Option<f64> {
+        self.warm_up.map(|w| duration_ns(w.first_pass) / self.ns_per_iter())
+    }
+
+    /// What the strategy is like besides fast, if it's one of ours rather
+    /// than an external one.
+    pub fn traits(&self) -> Option<Traits> {
+        strategy::by_name(self.strategy).map(|s| s.traits())
+    }
+}
+
+impl Spread {
+    /// Fits the batches of a run, each its number of passes and how long
+    /// they took. Each batch is placed at its middle pass, so that batches
+    /// of different sizes can be compared.
+    fn fit(batches: &[(u64, Duration)]) -> Option<Spread> {
+        if batches.len() < 3 {
+            return None;
+        }
+        let total = batches.iter().map(|&(n, _)| n as f64).sum::<f64>();
+        let mut start = 0.0;
+        let points = batches.iter().map(|&(n, elapsed)| {
+            let n = n as f64;
+            let x = start + n / 2.0;
+            start += n;
+            (x, duration_ns(elapsed) / n, n)
+        }).collect::<Vec<_>>();
+        let mean_x = points.iter().map(|&(x, _, w)| x * w).sum::<f64>() / total;
+        let mean_y = points.iter().map(|&(_, y, w)| y * w).sum::<f64>() / total;
+        let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+        for &(x, y, w) in &points {
+            sxx += w * (x - mean_x) * (x - mean_x);
+            sxy += w * (x - mean_x) * (y - mean_y);
+            syy += w * (y - mean_y) * (y - mean_y);
+        }
+        Some(Spread {
+            batches: batches.len(),
+            std_dev: (syy / total).sqrt(),
+            drift: sxy / sxx * total / mean_y,
+        })
+    }
+}
+
+/// Times `strategy` over `corpus`.
+///
+/// Every strategy should be given the same `Buffer`, so that none of them
+/// pays to fault in its pages; see `corpus::buffer`.
+///
+/// Every line is parsed once beforehand, to count errors, to take the
+/// checksum and to warm up. That pass is timed on its own, as `warm_up`, and
+/// left out of the steady-state time. Peak memory covers it too, so that it
+/// counts whatever the allocator holds on to afterwards.
+///
+/// The strategy is prepared before that pass, and reset after the run, so
+/// that the next measurement of the same instance starts as this one did;
+/// see `xi_der_strategies::scratch`.
+///
+/// Given a `&dyn Strategy`, as from `strategy::all`, each parse is a virtual
+/// call; given a concrete strategy, the passes are monomorphized for it, and
+/// the parse can be inlined. `compare_dispatch` measures the difference.
+pub fn measure<S>(strategy: &S, corpus: &Buffer, budget: Budget, cache: Cache) -> Measurement
+    where S: Strategy + ?Sized
+{
+    let _span = info_span!("measure", strategy = strategy.name(), cache = cache.name(),
+                           messages = corpus.len()).entered();
+    // allocated up front, so that it isn't counted against the strategy
+    let mut scrambler = if cache == Cache::Hot { None } else { Some(Scrambler::new()) };
+    let tracker = Tracker::start();
+    strategy.prepare();
+    let mut sink = Sink::new();
+    let (errors, warm_up) = debug_span!("warm_up").in_scope(|| {
+        let mut lines = corpus.lines();
+        let start = Timer::start();
+        let first = lines.next().map_or(false, |l| strategy.parse_into(l, &mut sink).is_err());
+        let first_message = start.elapsed();
+        let rest = lines.filter(|l| strategy.parse_into(l, &mut sink).is_err()).count();
+        (first as usize + rest, WarmUp { first_message, first_pass: start.elapsed() })
+    });
+    let checksum = if strategy.checksums() { Some(sink.hash()) } else { None };
+
+    let batches = match (budget, scrambler.as_mut()) {
+        (Budget::Iterations(n), None) => run_iterations(strategy, corpus, n),
+        (Budget::Time(limit), None) => run_for(strategy, corpus, limit),
+        (budget, Some(scrambler)) => {
+            run_cold(strategy, corpus, budget, scrambler, cache == Cache::Flushed)
+        }
+    };
+    let memory = tracker.finish();
+    strategy.reset();
+    let iterations = batches.iter().map(|&(n, _)| n).sum();
+    let elapsed = batches.iter().map(|&(_, elapsed)| elapsed).sum::<Duration>();
+    let spread = Spread::fit(&batches);
+    info!(iterations, errors, elapsed_ms = elapsed.as_millis() as u64,
+          first_pass_us = warm_up.first_pass.as_micros() as u64, "measured");
+
+    Measurement {
+        strategy: strategy.name(),
+        direction: strategy.direction(),
+        messages: corpus.len(),
+        errors,
+        iterations,
+        elapsed,
+        cache,
+        memory,
+        checksum,
+        spread,
+        threads: 1,
+        warm_up: Some(warm_up),
+    }
+}
+
+/// As `measure`, measuring again, up to `reruns` times, for as long as the
+/// run slows down as it goes. Returns the last measurement, which has still
+/// slowed down if every rerun did.
+pub fn measure_steady<S>(strategy: &S, corpus: &Buffer, budget: Budget, cache: Cache,
+                         reruns: u32) -> Measurement
+    where S: Strategy + ?Sized
+{
+    let mut m = measure(strategy, corpus, budget, cache);
+    for rerun in 1..=reruns {
+        if !m.slowed() {
+            break;
+        }
+        let drift = m.spread.map_or(0.0, |s| s.drift);
+        warn!(strategy = m.strategy, drift, rerun, "slowed down");
+        // give a throttled CPU a moment to cool off
+        #[cfg(not(target_arch = "wasm32"))]
+        ::std::thread::sleep(Duration::from_secs(1));
+        m = measure(strategy, corpus, budget, cache);
+    }
+    m
+}
+
+/// As `measure`, with the strategy named `name` run on `threads` threads at
+/// once, each with its own instance. Alone, a strategy has the allocator and
+/// the memory bus to itself, which a busy xi-core doesn't.
+///
+/// The result is the first thread's, with the others' passes and time added
+/// in, so that its time per pass is the mean of all of theirs. Resident
+/// memory is the process's, and can't be split between them, so it's left
+/// out. `None` if there's no strategy called `name`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn measure_contended(name: &str, corpus: &Buffer, budget: Budget, cache: Cache,
+                         threads: u32) -> Option<Measurement> {
+    let strategy = strategy::by_name(name)?;
+    if threads <= 1 {
+        return Some(measure(strategy.as_ref(), corpus, budget, cache));
+    }
+    let _span = info_span!("contended", threads).entered();
+    let mut measurements = ::std::thread::scope(|scope| {
+        let handles = (0..threads).map(|_| scope.spawn(|| {
+            let strategy = strategy::by_name(name).expect("checked above");
+            measure(strategy.as_ref(), corpus, budget, cache)
+        })).collect::<Vec<_>>();
+        handles.into_iter().map(|h| h.join().expect("measuring thread panicked"))
+            .collect::<Vec<_>>()
+    }).into_iter();
+    let mut m = measurements.next()?;
+    for other in measurements {
+        m.iterations += other.iterations;
+        m.elapsed += other.elapsed;
+    }
+    m.memory = None;
+    m.threads = threads;
+    Some(m)
+}
+
+/// Checks that every strategy with a checksum parsed the same thing, so that
+/// their timings are for the same work. Strategies for messages going
+/// different ways parse different corpora, so they're only compared with
+/// others for the same direction.
+///
+/// A strategy that fails to parse a line others accept also gets a different
+/// checksum, and that's deliberate: skipping work is the very thing to catch.
+pub fn verify_checksums(measurements: &[Measurement]) -> Result<(), String> {
+    for &direction in &Direction::ALL {
+        let mut summed = measurements.iter()
+            .filter(|m| m.direction == direction)
+            .filter_map(|m| m.checksum.map(|c| (m.strategy, c)));
+        let (first, expected) = match summed.next() {
+            Some(first) => first,
+            None => continue,
+        };
+        let differ = summed.filter(|&(_, c)| c != expected)
+            .map(|(name, c)| format!("{} ({:016x})", name, c))
+            .collect::<Vec<_>>();
+        if !differ.is_empty() {
+            return Err(format!("checksums differ from {}'s ({:016x}): {}",
+                               first, expected, differ.join(", ")));
+        }
+    }
+    Ok(())
+}
+
+/// Runs batches of doubling size until `limit` is reached.
+///
+/// Checking the clock only between batches keeps its overhead out of the
+/// measurement for fast strategies, and the first, tiny batches ensure slow
+/// strategies don't run far past the limit.
+fn run_for<S>(strategy: &S, corpus: &Buffer, limit: Duration) -> Vec<(u64, Duration)>
+    where S: Strategy + ?Sized
+{
+    let mut batches = Vec::new();
+    let mut iterations = 0;
+    let mut elapsed = Duration::new(0, 0);
+    let mut batch = 1;
+    while elapsed < limit {
+        let time = run_batch(strategy, corpus, batch);
+        batches.push((batch, time));
+        elapsed += time;
+        iterations += batch;
+
+        // don't overshoot: estimate how many more passes fit in the budget
+        let per_iter = duration_ns(elapsed) / iterations as f64;
+        let remaining = duration_ns(limit - elapsed.min(limit)) / per_iter;
+        batch = (batch * 2).min(remaining.ceil().max(1.0) as u64);
+    }
+    batches
+}
+
+/// Runs `n` passes, in `BATCHES` batches or fewer.
+fn run_iterations<S>(strategy: &S, corpus: &Buffer, n: u64) -> Vec<(u64, Duration)>
+    where S: Strategy + ?Sized
+{
+    let size = ((n + BATCHES - 1) / BATCHES).max(1);
+    let mut batches = Vec::new();
+    let mut done = 0;
+    while done < n {
+        let batch = size.min(n - done);
+        batches.push((batch, run_batch(strategy, corpus, batch)));
+        done += batch;
+    }
+    batches
+}
+
+/// Runs single passes, evicting the caches before each one.
+fn run_cold<S>(strategy: &S, corpus: &Buffer, budget: Budget, scrambler: &mut Scrambler,
+               flush: bool) -> Vec<(u64, Duration)>
+    where S: Strategy + ?Sized
+{
+    let mut batches = Vec::new();
+    let mut iterations = 0;
+    let mut elapsed = Duration::new(0, 0);
+    let done = |iterations: u64, elapsed: Duration| match budget {
+        Budget::Iterations(n) => iterations >= n,
+        Budget::Time(limit) => elapsed >= limit,
+    };
+    while !done(iterations, elapsed) {
+        trace_span!("scramble").in_scope(|| scrambler.scramble());
+        if flush {
+            clflush(corpus);
+        }
+        let time = run_batch(strategy, corpus, 1);
+        batches.push((1, time));
+        elapsed += time;
+        iterations += 1;
+    }
+    batches
+}
+
+/// Bigger than the last-level cache of anything we're likely to run on.
+const SCRAMBLE_BYTES: usize = 64 << 20;
+
+/// A buffer for pushing everything else out of the caches.
+struct Scrambler {
+    buf: Vec<u64>,
+}
+
+impl Scrambler {
+    fn new() -> Scrambler {
+        let mut scrambler = Scrambler { buf: vec![0; SCRAMBLE_BYTES / 8] };
+        // fault the pages in now
+        scrambler.scramble();
+        scrambler
+    }
+
+    /// Writes to every cache line of the buffer. Writes rather than reads,
+    /// so that dirty lines from the last pass get written back now, not
+    /// during the next one.
+    fn scramble(&mut self) {
+        for word in self.buf.iter_mut().step_by(64 / 8) {
+            *word = word.wrapping_add(1);
+        }
+        black_box(&mut self.buf);
+    }
+}
+
+/// Evicts the corpus from every level of cache. The offsets of its lines,
+/// a few bytes a line, are left.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn clflush(corpus: &Buffer) {
+    use std::arch::x86_64::{_mm_clflush, _mm_mfence};
+
+    fn flush_bytes(ptr: *const u8, len: usize) {
+        for offset in (0..len).step_by(64) {
+            // safe: clflush is part of SSE2, which x86_64 always has, and
+            // the addresses are all within a live allocation
+            unsafe { _mm_clflush(ptr.add(offset)) }
+        }
+    }
+
+    let text = corpus.as_str();
+    flush_bytes(text.as_ptr(), text.len());
+    unsafe { _mm_mfence() }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn clflush(_corpus: &Buffer) {}
+
+/// Times `iterations` back to back passes over `corpus`, with nothing
+/// beforehand: no warm-up and no checksum.
+pub fn run_batch<S>(strategy: &S, corpus: &Buffer, iterations: u64) -> Duration
+    where S: Strategy + ?Sized
+{
+    // entered outside of the timed region
+    let _span = trace_span!("batch", iterations).entered();
+    let mut sink = Sink::new();
+    let start = Timer::start();
+    for _ in 0..iterations {
+        for line in corpus.lines() {
+            let _ = strategy.parse_into(line, &mut sink);
+        }
+    }
+    let elapsed = start.elapsed();
+    black_box(sink);
+    elapsed
+}
+
+/// A stopwatch. `Instant` panics on wasm32-unknown-unknown, so there we ask
+/// the host instead.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+struct Timer(Instant);
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+impl Timer {
+    fn start() -> Timer {
+        Timer(Instant::now())
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+struct Timer(f64);
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl Timer {
+    fn start() -> Timer {
+        Timer(::wasm::now())
+    }
+
+    fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64((::wasm::now() - self.0).max(0.0) / 1000.0)
+    }
+}
+
+pub fn duration_ns(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1e9 + f64::from(d.subsec_nanos())
+}
+
+/// One strategy, measured through a `&dyn Strategy` and monomorphized.
+#[derive(Debug, Clone)]
+pub struct Dispatch {
+    pub dynamic: Measurement,
+    pub generic: Measurement,
+}
+
+impl Dispatch {
+    /// How much slower the dynamic runs were, as a fraction of the generic.
+    pub fn overhead(&self) -> f64 {
+        self.dynamic.ns_per_iter() / self.generic.ns_per_iter() - 1.0
+    }
+}
+
+/// Measures the fastest strategies both ways, to check whether the harness's
+/// virtual calls are distorting their numbers. The slower strategies spend
+/// too long in each parse for one call's overhead to matter.
+pub fn compare_dispatch(corpus: &Buffer, budget: Budget) -> Vec<Dispatch> {
+    fn both<S: Strategy>(strategy: S, corpus: &Buffer, budget: Budget) -> Dispatch {
+        // black_box, or the optimizer sees through the vtable
+        let dynamic = black_box(&strategy as &dyn Strategy);
+        Dispatch {
+            dynamic: measure(dynamic, corpus, budget, Cache::Hot),
+            generic: measure(&strategy, corpus, budget, Cache::Hot),
+        }
+    }
+
+    vec![
+        both(strategy::Tape, corpus, budget),
+        both(strategy::Hand, corpus, budget),
+        both(strategy::FutureSerde, corpus, budget),
+    ]
+}
+
+/// Prints a table of `compare_dispatch` results.
+pub fn print_dispatch(results: &[Dispatch]) {
+    println!("{:<14}{:>12}{:>16}{:>16}{:>10}",
+             "strategy", "iterations", "dyn ns/msg", "generic ns/msg", "overhead");
+    for d in results {
+        println!("{:<14}{:>12}{:>16.1}{:>16.1}{:>9.1}%",
+                 d.generic.strategy, d.generic.iterations, d.dynamic.ns_per_message(),
+                 d.generic.ns_per_message(), d.overhead() * 100.0);
+    }
+}
+
+/// Prints a table of measurements.
+///
+/// ±% is the standard deviation of the time per pass, and drift how much
+/// slower the end of the run was than the start; see `Spread`. Warm-up is
+/// how many times longer the untimed first pass took than the mean, and 1st
+/// msg how long its first message took; see `WarmUp`. Measurements
+/// of more than one direction are grouped by it, under a heading for each.
+pub fn print_measurements(measurements: &[Measurement]) {
+    let mixed = measurements.iter().any(|m| m.direction != measurements[0].direction);
+    if mixed {
+        for &direction in &Direction::ALL {
+            let group = measurements.iter().filter(|m| m.direction == direction)
+                .cloned().collect::<Vec<_>>();
+            if !group.is_empty() {
+                println!("{} ({}):", direction.description(), direction);
+                print_measurements(&group);
+                println!();
+            }
+        }
+        return;
+    }
+    println!("{:<14}{:>8}{:>12}{:>10}{:>16}{:>8}{:>8}{:>14}{:>10}{:>12}{:>12}",
+             "strategy", "cache", "iterations", "errors", "ns/iter", "±%", "drift%", "ns/message",
+             "warm-up", "1st msg ns", "peak +MiB");
+    for m in measurements {
+        let growth = m.memory.map(|mem| format!("{:.1}", mem.growth() as f64 / (1 << 20) as f64))
+            .unwrap_or_else(|| "-".into());
+        let variation = m.variation().map(|v| format!("{:.1}", v * 100.0))
+            .unwrap_or_else(|| "-".into());
+        let drift = m.spread.map(|s| format!("{:+.1}", s.drift * 100.0))
+            .unwrap_or_else(|| "-".into());
+        let warm_up = m.warm_up_ratio().map(|r| format!("{:.2}x", r))
+            .unwrap_or_else(|| "-".into());
+        let first_message = m.warm_up.map(|w| format!("{:.0}", duration_ns(w.first_message)))
+            .unwrap_or_else(|| "-".into());
+        let strategy = match m.threads {
+            1 => m.strategy.to_owned(),
+            n => format!("{}x{}", m.strategy, n),
+        };
+        println!("{:<14}{:>8}{:>12}{:>10}{:>16.0}{:>8}{:>8}{:>14.1}{:>10}{:>12}{:>12}",
+                 strategy, m.cache.name(), m.iterations, m.errors, m.ns_per_iter(), variation,
+                 drift, m.ns_per_message(), warm_up, first_message, growth);
+    }
+}
+
+/// Prints what each strategy is like besides fast, next to its time per
+/// message, the fastest first: what a choice between them trades off, where
+/// `print_measurements` only ranks them. External strategies are left out.
+pub fn print_traits(measurements: &[Measurement]) {
+    let mut rows = measurements.iter().filter_map(|m| m.traits().map(|t| (m, t)))
+        .collect::<Vec<_>>();
+    rows.sort_by(|(a, _), (b, _)| a.ns_per_message().total_cmp(&b.ns_per_message()));
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    println!("{:<24}{:>14}{:>8}{:>12}{:>9}{:>17}",
+             "strategy", "ns/message", "unsafe", "alloc-free", "borrows", "maintainability");
+    for (m, t) in rows {
+        println!("{:<24}{:>14.1}{:>8}{:>12}{:>9}{:>17}",
+                 m.strategy, m.ns_per_message(), yes_no(t.uses_unsafe),
+                 yes_no(t.allocation_free), yes_no(t.borrows), t.maintainability.name());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xi_der_strategies::error::ParseError;
+
+    use super::*;
+
+    struct Sleepy;
+
+    impl Strategy for Sleepy {
+        fn name(&self) -> &'static str {
+            "sleepy"
+        }
+
+        fn parse_into(&self, json: &str, _sink: &mut Sink) -> Result<(), ParseError> {
+            ::std::thread::sleep(Duration::from_millis(1));
+            if json.is_empty() { Err(ParseError::Frame("empty".into())) } else { Ok(()) }
+        }
+    }
+
+    #[test]
+    fn fixed_iterations() {
+        let corpus = Buffer::new(&["a", ""]);
+        let m = measure(&Sleepy, &corpus, Budget::Iterations(3), Cache::Hot);
+        assert_eq!(m.iterations, 3);
+        assert_eq!(m.errors, 1);
+        assert!(m.elapsed >= Duration::from_millis(6));
+        // timed apart from the rest
+        let warm_up = m.warm_up.unwrap();
+        assert!(warm_up.first_message >= Duration::from_millis(1));
+        assert!(warm_up.first_pass >= warm_up.first_message + Duration::from_millis(1));
+    }
+
+    #[test]
+    fn time_budget() {
+        let corpus = Buffer::new(&["a"]);
+        let limit = Duration::from_millis(50);
+        let m = measure(&Sleepy, &corpus, Budget::Time(limit), Cache::Hot);
+        assert!(m.elapsed >= limit);
+        // batches are sized to the remaining budget, so we shouldn't overshoot by much
+        assert!(m.elapsed < limit * 3, "{:?}", m.elapsed);
+        assert!(m.iterations > 1);
+    }
+
+    #[test]
+    fn dispatch() {
+        let results = compare_dispatch(&Buffer::new(corpus::TEST_JSON.lines()),
+                                       Budget::Iterations(2));
+        assert_eq!(results.len(), 3);
+        for d in &results {
+            assert_eq!(d.dynamic.strategy, d.generic.strategy);
+            assert_eq!((d.dynamic.errors, d.generic.errors), (0, 0));
+            assert_eq!(d.dynamic.iterations, 2);
+        }
+    }
+
+    #[test]
+    fn checksums() {
+        let buffer = Buffer::new(corpus::TEST_JSON.lines());
+        let mut measurements = strategy::all().iter()
+            .map(|s| measure(s.as_ref(), &buffer, Budget::Iterations(1), Cache::Hot))
+            .collect::<Vec<_>>();
+        assert!(measurements.iter().any(|m| m.checksum.is_some()));
+        assert_eq!(verify_checksums(&measurements), Ok(()));
+
+        // a strategy that skips a line
+        let rest = Buffer::new(buffer.lines().skip(1));
+        let mut short = measure(&strategy::Hand, &rest, Budget::Iterations(1), Cache::Hot);
+        short.strategy = "short";
+        measurements.push(short);
+        let err = verify_checksums(&measurements).unwrap_err();
+        assert!(err.contains("short"), "{}", err);
+        assert_eq!(verify_checksums(&[]), Ok(()));
+
+        // another direction's checksums aren't comparable
+        measurements.pop();
+        let to_client = Buffer::new(corpus::TO_CLIENT_JSON.lines());
+        let other = measure(&strategy::SerdeToClient, &to_client, Budget::Iterations(1),
+                            Cache::Hot);
+        assert_eq!((other.direction, other.errors), (Direction::S2C, 0));
+        measurements.push(other);
+        assert_eq!(verify_checksums(&measurements), Ok(()));
+    }
+
+    #[test]
+    fn contended() {
+        let corpus = Buffer::new(corpus::TEST_JSON.lines());
+        let m = measure_contended("hand", &corpus, Budget::Iterations(4), Cache::Hot, 3).unwrap();
+        assert_eq!((m.strategy, m.threads, m.iterations, m.errors), ("hand", 3, 12, 0));
+        assert!(m.memory.is_none());
+        assert!(measure_contended("frobnicate", &corpus, Budget::Iterations(1), Cache::Hot, 2)
+                .is_none());
+    }
+
+    #[test]
+    fn spread() {
+        let steady = [(4, Duration::from_micros(40)), (8, Duration::from_micros(80)),
+                      (16, Duration::from_micros(160))];
+        let s = Spread::fit(&steady).unwrap();
+        assert!(s.std_dev < 1e-6 && s.drift.abs() < 1e-9, "{:?}", s);
+
+        // each pass a little slower than the last: 10us, 11us, ..., 19us
+        let slowing = (10..20).map(|us| (1, Duration::from_micros(us))).collect::<Vec<_>>();
+        let s = Spread::fit(&slowing).unwrap();
+        // 1us a pass, so 10us over the run, against a mean of 14.5us
+        assert!((s.drift - 10.0 / 14.5).abs() < 1e-9, "{:?}", s);
+        assert!(s.drift > MAX_DRIFT);
+        assert_eq!(Spread::fit(&slowing[..2]), None);
+
+        let corpus = Buffer::new(&["a"]);
+        let m = measure(&Sleepy, &corpus, Budget::Iterations(32), Cache::Hot);
+        assert_eq!(m.spread.map(|s| s.batches), Some(BATCHES as usize));
+    }
+
+    #[test]
+    fn cold_cache() {
+        let corpus = Buffer::new(&["a", ""]);
+        for &cache in &[Cache::Cold, Cache::Flushed] {
+            let m = measure(&Sleepy, &corpus, Budget::Iterations(2), cache);
+            assert_eq!(m.iterations, 2);
+            assert_eq!(m.cache, cache);
+            assert!(m.elapsed >= Duration::from_millis(4));
+        }
+        assert_eq!(Cache::from_name("flushed"), Some(Cache::Flushed));
+        assert_eq!(Cache::from_name("lukewarm"), None);
+    }
+}
@@ -0,0 +1,348 @@
+//! An experimental transport: a single-producer, single-consumer ring
+//! buffer in a memory-mapped file shared by two processes.
+//!
+//! Nothing is copied out of the ring on the way in. Each message is
+//! contiguous in the mapping, and strategies parse it where it lies, so the
+//! borrowing strategies borrow straight from shared memory. Once the ring is
+//! mapped there are no syscalls; when one side has to wait for the other it
+//! spins, then yields.
+//!
+//! Layout: the write and read positions, each on its own cache line, a
+//! closed flag, then the data. Positions are byte counts that only
+//! increase. Messages are a little-endian u32 length and the bytes, padded
+//! to a multiple of 4; a length of `WRAP` means the rest of the data is
+//! unused, and the next message is at the start. A message can take up at
+//! most half the data, so that it fits after wrapping once whatever was
+//! before it has been read.
+//!
+//! The handoff is checked under every interleaving of the two ends with
+//! loom, which needs its own atomics, and its own cells for the data so as
+//! to see each access to it, and so can't map the ring; built with
+//! `--cfg loom`, `Ring::model` makes both ends in one process instead:
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg loom" cargo test --release -p xi-der-harness --features shm --lib shm
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+#[cfg(not(loom))]
+use std::ptr;
+#[cfg(not(loom))]
+use std::slice;
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+#[cfg(not(loom))]
+use std::thread;
+
+#[cfg(loom)]
+use loom;
+#[cfg(loom)]
+use loom::cell::UnsafeCell;
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+#[cfg(not(loom))]
+use memmap2::MmapMut;
+
+const DATA: usize = 192;
+
+const WRAP: u32 = u32::MAX;
+
+/// How many times to spin before yielding, when waiting for the other side.
+#[cfg(not(loom))]
+const SPINS: u32 = 1000;
+
+/// The start of the mapping, which is only ever cast to.
+#[cfg_attr(not(loom), allow(dead_code))]
+#[repr(C)]
+struct Header {
+    head: AtomicU64,
+    _head_line: [u8; 56],
+    tail: AtomicU64,
+    _tail_line: [u8; 56],
+    closed: AtomicU32,
+}
+
+/// One end of a ring. The producer calls `push` and `close`, and the
+/// consumer calls `pop`; each process opens its own `Ring`.
+pub struct Ring {
+    memory: Memory,
+    capacity: u64,
+}
+
+enum Memory {
+    #[cfg(not(loom))]
+    Mapped(MmapMut),
+    #[cfg(loom)]
+    Model(loom::sync::Arc<Model>),
+}
+
+/// A ring shared by two `Ring`s in one process.
+#[cfg(loom)]
+struct Model {
+    header: Header,
+    data: Box<[UnsafeCell<u8>]>,
+}
+
+// the ends only touch the data the header hands over to them
+#[cfg(loom)]
+unsafe impl Sync for Model {}
+
+impl Ring {
+    /// Creates the ring at `path`, with room for `capacity` bytes of
+    /// messages. `capacity` is rounded up to a multiple of 4.
+    pub fn create<P: AsRef<Path>>(path: P, capacity: usize) -> io::Result<Ring> {
+        let capacity = padded(capacity.max(8));
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true)
+            .open(path)?;
+        // a new file is zeroed, which is an empty, open ring
+        file.set_len((DATA + capacity) as u64)?;
+        Ring::map(&file)
+    }
+
+    /// Opens the ring created at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Ring> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ring::map(&file)
+    }
+
+    #[cfg(not(loom))]
+    fn map(file: &File) -> io::Result<Ring> {
+        // safe as long as only the two ends of the ring touch the file, and
+        // they only do so through the header's atomics and the regions
+        // those atomics hand over
+        let map = unsafe { MmapMut::map_mut(file)? };
+        if map.len() < DATA + 8 || (map.len() - DATA) % 4 != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a ring"));
+        }
+        let capacity = (map.len() - DATA) as u64;
+        Ok(Ring { memory: Memory::Mapped(map), capacity })
+    }
+
+    #[cfg(loom)]
+    fn map(_file: &File) -> io::Result<Ring> {
+        Err(io::Error::new(io::ErrorKind::Other, "rings can't be mapped under loom"))
+    }
+
+    /// Both ends of a ring with room for `capacity` bytes of messages, for
+    /// loom to check.
+    #[cfg(loom)]
+    pub fn model(capacity: usize) -> (Ring, Ring) {
+        let capacity = padded(capacity.max(8));
+        let model = loom::sync::Arc::new(Model {
+            header: Header {
+                head: AtomicU64::new(0),
+                _head_line: [0; 56],
+                tail: AtomicU64::new(0),
+                _tail_line: [0; 56],
+                closed: AtomicU32::new(0),
+            },
+            data: (0..capacity).map(|_| UnsafeCell::new(0)).collect(),
+        });
+        let end = || Ring { memory: Memory::Model(model.clone()), capacity: capacity as u64 };
+        (end(), end())
+    }
+
+    /// Appends `msg`, waiting for the consumer to make room if necessary.
+    pub fn push(&mut self, msg: &[u8]) -> io::Result<()> {
+        let len = 4 + padded(msg.len()) as u64;
+        if len > self.capacity / 2 || msg.len() >= WRAP as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "message larger than half the ring"));
+        }
+        let mut head = self.header().head.load(Ordering::Relaxed);
+        let offset = head % self.capacity;
+        let skip = if offset + len > self.capacity { self.capacity - offset } else { 0 };
+        self.wait(|ring| head + skip + len - ring.header().tail.load(Ordering::Acquire)
+                  <= ring.capacity);
+        if skip > 0 {
+            self.write(offset as usize, &WRAP.to_le_bytes());
+            head += skip;
+        }
+        let at = (head % self.capacity) as usize;
+        self.write(at, &(msg.len() as u32).to_le_bytes());
+        self.write(at + 4, msg);
+        self.header().head.store(head + len, Ordering::Release);
+        Ok(())
+    }
+
+    /// Marks the end of the stream. The consumer sees it once it has read
+    /// everything before it.
+    pub fn close(&self) {
+        self.header().closed.store(1, Ordering::Release);
+    }
+
+    /// Waits for the next message and calls `f` with it, in place. Returns
+    /// `None` once the ring is closed and empty.
+    pub fn pop<F, R>(&mut self, f: F) -> Option<R>
+        where F: FnOnce(&[u8]) -> R
+    {
+        let mut tail = self.header().tail.load(Ordering::Relaxed);
+        let mut closed = false;
+        self.wait(|ring| {
+            // check closed first: if it's set, everything has been pushed
+            closed = ring.header().closed.load(Ordering::Acquire) != 0;
+            ring.header().head.load(Ordering::Acquire) != tail || closed
+        });
+        if self.header().head.load(Ordering::Acquire) == tail {
+            debug_assert!(closed);
+            return None;
+        }
+        // only what the producer has handed over is read, or even borrowed
+        let mut offset = (tail % self.capacity) as usize;
+        let mut len = self.read_u32(offset);
+        if len == WRAP {
+            tail += self.capacity - offset as u64;
+            offset = 0;
+            len = self.read_u32(offset);
+        }
+        let result = self.read(offset + 4, len as usize, f);
+        tail += 4 + padded(len as usize) as u64;
+        self.header().tail.store(tail, Ordering::Release);
+        Some(result)
+    }
+
+    fn wait<F: FnMut(&Ring) -> bool>(&self, mut ready: F) {
+        let mut spins = 0;
+        while !ready(self) {
+            backoff(&mut spins);
+        }
+    }
+
+    fn header(&self) -> &Header {
+        match self.memory {
+            // the mapping is page aligned, and the header is before DATA
+            #[cfg(not(loom))]
+            Memory::Mapped(ref map) => unsafe { &*(map.as_ptr() as *const Header) },
+            #[cfg(loom)]
+            Memory::Model(ref model) => &model.header,
+        }
+    }
+
+    /// Copies `bytes` into the data at `offset`, which the consumer has to
+    /// have handed back, or never been given.
+    fn write(&mut self, offset: usize, bytes: &[u8]) {
+        match self.memory {
+            #[cfg(not(loom))]
+            Memory::Mapped(ref mut map) => unsafe {
+                let at = map.as_mut_ptr().add(DATA + offset);
+                ptr::copy_nonoverlapping(bytes.as_ptr(), at, bytes.len());
+            },
+            #[cfg(loom)]
+            Memory::Model(ref model) => {
+                for (cell, &byte) in model.data[offset..].iter().zip(bytes) {
+                    cell.with_mut(|p| unsafe { *p = byte });
+                }
+            }
+        }
+    }
+
+    /// Calls `f` with `len` bytes of the data at `offset`, which the
+    /// producer has to have handed over. Under loom they're copied out, a
+    /// cell at a time.
+    fn read<F, R>(&self, offset: usize, len: usize, f: F) -> R
+        where F: FnOnce(&[u8]) -> R
+    {
+        match self.memory {
+            #[cfg(not(loom))]
+            Memory::Mapped(ref map) => {
+                f(unsafe { slice::from_raw_parts(map.as_ptr().add(DATA + offset), len) })
+            }
+            #[cfg(loom)]
+            Memory::Model(ref model) => {
+                let bytes = model.data[offset..offset + len].iter()
+                    .map(|cell| cell.with(|p| unsafe { *p }))
+                    .collect::<Vec<_>>();
+                f(&bytes)
+            }
+        }
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        self.read(offset, 4, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+#[cfg(not(loom))]
+fn backoff(spins: &mut u32) {
+    if *spins < SPINS {
+        *spins += 1;
+        ::std::hint::spin_loop();
+    } else {
+        thread::yield_now();
+    }
+}
+
+/// loom has to be told when a thread is waiting for another, or it never
+/// runs the other.
+#[cfg(loom)]
+fn backoff(_spins: &mut u32) {
+    loom::thread::yield_now();
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(loom))]
+    use std::env;
+    #[cfg(not(loom))]
+    use std::fs;
+    #[cfg(not(loom))]
+    use std::process;
+    use std::str;
+
+    use super::*;
+
+    #[cfg(not(loom))]
+    #[test]
+    fn wraps_around() {
+        // one file a process, so that test runs side by side don't share it
+        let path = env::temp_dir().join(format!("der_bench_ring_test_{}", process::id()));
+        let mut consumer = Ring::create(&path, 64).unwrap();
+        let mut producer = Ring::open(&path).unwrap();
+
+        let messages = (0..500).map(|i| "x".repeat(i % 23)).collect::<Vec<_>>();
+        let expected = messages.clone();
+        let writer = thread::spawn(move || {
+            for msg in &messages {
+                producer.push(msg.as_bytes()).unwrap();
+            }
+            // more than half the ring
+            assert!(producer.push(&[0; 29]).is_err());
+            producer.close();
+        });
+        let mut received = Vec::new();
+        while let Some(msg) = consumer.pop(|m| str::from_utf8(m).unwrap().to_owned()) {
+            received.push(msg);
+        }
+        writer.join().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(received, expected);
+    }
+
+    /// Every interleaving of the two ends, through a ring small enough that
+    /// the producer waits for room, and wraps.
+    #[cfg(loom)]
+    #[test]
+    fn handoff() {
+        loom::model(|| {
+            let (mut producer, mut consumer) = Ring::model(20);
+            let writer = loom::thread::spawn(move || {
+                for msg in &["ab", "cd", "ef"] {
+                    producer.push(msg.as_bytes()).unwrap();
+                }
+                producer.close();
+            });
+            let mut received = Vec::new();
+            while let Some(msg) = consumer.pop(|m| str::from_utf8(m).unwrap().to_owned()) {
+                received.push(msg);
+            }
+            writer.join().unwrap();
+            assert_eq!(received, vec!["ab", "cd", "ef"]);
+        });
+    }
+}
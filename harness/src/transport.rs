@@ -0,0 +1,310 @@
+//! Streaming a corpus in from another process, so that syscalls and framing
+//! are part of what's measured.
+//!
+//! xi-core talks to its frontend over stdio pipes. Here a producer process
+//! writes the corpus into a pipe, a Unix domain socket, or a localhost TCP
+//! socket, and this process splits the stream into lines, as xi does, and
+//! parses each with a strategy.
+//!
+//! With the shm feature, there's also an experimental shared memory ring;
+//! see `shm`.
+
+#[cfg(unix)]
+use std::env;
+#[cfg(unix)]
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::process::{self, Command, Stdio};
+#[cfg(all(unix, feature = "shm"))]
+use std::str;
+use std::time::{Duration, Instant};
+
+use runner::{Cache, Measurement};
+#[cfg(all(unix, feature = "shm"))]
+use shm::Ring;
+use strategy::Strategy;
+#[cfg(all(unix, feature = "shm"))]
+use xi_der_strategies::error::ParseError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// The producer's stdout, as with xi.
+    Pipe,
+    #[cfg(unix)]
+    Unix,
+    Tcp,
+    /// A ring buffer in shared memory, parsed in place.
+    #[cfg(all(unix, feature = "shm"))]
+    Shm,
+}
+
+impl Transport {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Transport::Pipe => "pipe",
+            #[cfg(unix)]
+            Transport::Unix => "unix",
+            Transport::Tcp => "tcp",
+            #[cfg(all(unix, feature = "shm"))]
+            Transport::Shm => "shm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Transport> {
+        Transport::all().into_iter().find(|t| t.name() == name)
+    }
+
+    /// The transports available on this platform.
+    pub fn all() -> Vec<Transport> {
+        let mut all = vec![Transport::Pipe];
+        #[cfg(unix)]
+        all.push(Transport::Unix);
+        all.push(Transport::Tcp);
+        #[cfg(all(unix, feature = "shm"))]
+        all.push(Transport::Shm);
+        all
+    }
+}
+
+/// Writes `passes` copies of the corpus to `writer`, one message per line.
+pub fn produce<W: Write>(lines: &[String], passes: u64, writer: W) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    for _ in 0..passes {
+        for line in lines {
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    writer.flush()
+}
+
+/// The producer side: connects to `addr` and writes the corpus to it. For
+/// pipes the address is ignored, and the corpus goes to stdout; for shm it's
+/// the path of the ring.
+pub fn connect_and_produce(transport: Transport, addr: &str, lines: &[String], passes: u64)
+    -> io::Result<()>
+{
+    match transport {
+        Transport::Pipe => produce(lines, passes, io::stdout().lock()),
+        #[cfg(unix)]
+        Transport::Unix => produce(lines, passes, UnixStream::connect(addr)?),
+        Transport::Tcp => {
+            let stream = TcpStream::connect(addr)?;
+            stream.set_nodelay(true)?;
+            produce(lines, passes, stream)
+        }
+        #[cfg(all(unix, feature = "shm"))]
+        Transport::Shm => {
+            let mut ring = Ring::open(addr)?;
+            for _ in 0..passes {
+                for line in lines {
+                    ring.push(line.as_bytes())?;
+                }
+            }
+            ring.close();
+            Ok(())
+        }
+    }
+}
+
+/// Reads messages from `reader` until EOF, parsing each with `strategy`.
+/// Returns the number of messages, and the number that failed to parse.
+pub fn consume<R: Read>(reader: R, strategy: &dyn Strategy) -> io::Result<(usize, usize)> {
+    consume_buffered(&mut BufReader::new(reader), strategy)
+}
+
+fn consume_buffered<R: BufRead>(reader: &mut R, strategy: &dyn Strategy)
+    -> io::Result<(usize, usize)>
+{
+    let mut line = String::new();
+    let (mut messages, mut errors) = (0, 0);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok((messages, errors));
+        }
+        let json = line.trim_end();
+        if json.is_empty() {
+            continue;
+        }
+        messages += 1;
+        if strategy.parse(json).is_err() {
+            errors += 1;
+        }
+    }
+}
+
+/// Times `strategy` over a corpus streamed from a producer process.
+///
+/// `producer` should run `connect_and_produce`; the transport's name, the
+/// address, and `passes` are appended to its arguments. Timing starts when
+/// the first bytes arrive, so the producer's startup isn't counted.
+pub fn measure(strategy: &dyn Strategy, transport: Transport, passes: u64,
+               mut producer: Command) -> io::Result<Measurement>
+{
+    let _span = info_span!("stream", strategy = strategy.name(), transport = transport.name(),
+                           passes).entered();
+    let (messages, errors, elapsed) = match transport {
+        Transport::Pipe => {
+            let mut child = producer.args(&[transport.name(), "-", &passes.to_string()])
+                .stdout(Stdio::piped())
+                .spawn()?;
+            let stdout = child.stdout.take().expect("stdout is piped");
+            let result = timed_consume(stdout, strategy);
+            finish(child.wait()?)?;
+            result?
+        }
+        #[cfg(unix)]
+        Transport::Unix => {
+            let path = env::temp_dir().join(format!("der_bench_{}.sock", process::id()));
+            let _ = fs::remove_file(&path);
+            let listener = UnixListener::bind(&path)?;
+            let addr = path.to_string_lossy().into_owned();
+            let mut child = producer.args(&[transport.name(), &addr, &passes.to_string()])
+                .spawn()?;
+            let result = listener.accept()
+                .and_then(|(stream, _)| timed_consume(stream, strategy));
+            let _ = fs::remove_file(&path);
+            finish(child.wait()?)?;
+            result?
+        }
+        Transport::Tcp => {
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let addr = listener.local_addr()?.to_string();
+            let mut child = producer.args(&[transport.name(), &addr, &passes.to_string()])
+                .spawn()?;
+            let result = listener.accept()
+                .and_then(|(stream, _)| timed_consume(stream, strategy));
+            finish(child.wait()?)?;
+            result?
+        }
+        #[cfg(all(unix, feature = "shm"))]
+        Transport::Shm => {
+            let path = ring_dir().join(format!("der_bench_{}.ring", process::id()));
+            let ring = Ring::create(&path, RING_CAPACITY)?;
+            let addr = path.to_string_lossy().into_owned();
+            let mut child = producer.args(&[transport.name(), &addr, &passes.to_string()])
+                .spawn()?;
+            let result = timed_consume_ring(ring, strategy);
+            let _ = fs::remove_file(&path);
+            finish(child.wait()?)?;
+            result
+        }
+    };
+
+    let passes = passes.max(1);
+    Ok(Measurement {
+        strategy: strategy.name(),
+        direction: strategy.direction(),
+        messages: messages / passes as usize,
+        errors: errors / passes as usize,
+        iterations: passes,
+        elapsed,
+        cache: Cache::Hot,
+        memory: None,
+        checksum: None,
+        spread: None,
+        threads: 1,
+        warm_up: None,
+    })
+}
+
+fn timed_consume<R: Read>(reader: R, strategy: &dyn Strategy)
+    -> io::Result<(usize, usize, Duration)>
+{
+    let mut reader = BufReader::new(reader);
+    // wait for the producer to get going
+    reader.fill_buf()?;
+    let start = Instant::now();
+    let (messages, errors) = consume_buffered(&mut reader, strategy)?;
+    Ok((messages, errors, start.elapsed()))
+}
+
+/// Big enough for any message we're likely to see, each of which can take
+/// up at most half of it.
+#[cfg(all(unix, feature = "shm"))]
+const RING_CAPACITY: usize = 4 << 20;
+
+/// Where to put the ring: in memory, if there's a tmpfs for it.
+#[cfg(all(unix, feature = "shm"))]
+fn ring_dir() -> ::std::path::PathBuf {
+    let shm = ::std::path::Path::new("/dev/shm");
+    if shm.is_dir() { shm.to_owned() } else { env::temp_dir() }
+}
+
+/// As `timed_consume`, parsing each message where it lies in the ring.
+#[cfg(all(unix, feature = "shm"))]
+fn timed_consume_ring(mut ring: Ring, strategy: &dyn Strategy) -> (usize, usize, Duration) {
+    let (mut messages, mut errors) = (0, 0);
+    let mut start = None;
+    while let Some(ok) = ring.pop(|msg| {
+        start.get_or_insert_with(Instant::now);
+        str::from_utf8(msg).map_err(|e| ParseError::Frame(e.to_string()))
+            .and_then(|json| strategy.parse(json))
+            .is_ok()
+    }) {
+        messages += 1;
+        if !ok {
+            errors += 1;
+        }
+    }
+    (messages, errors, start.map(|s| s.elapsed()).unwrap_or_default())
+}
+
+fn finish(status: process::ExitStatus) -> io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::Other, format!("producer failed: {}", status)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use xi_der_strategies::error::ParseError;
+    use xi_der_strategies::sink::Sink;
+
+    use super::*;
+
+    struct NonEmpty;
+
+    impl Strategy for NonEmpty {
+        fn name(&self) -> &'static str {
+            "non_empty"
+        }
+
+        fn parse_into(&self, json: &str, _sink: &mut Sink) -> Result<(), ParseError> {
+            if json == "{}" { Err(ParseError::Envelope("empty".into())) } else { Ok(()) }
+        }
+    }
+
+    #[test]
+    fn framing() {
+        let lines = vec!["{\"a\":1}".to_owned(), "{}".to_owned(), "[2]".to_owned()];
+        let mut buf = Vec::new();
+        produce(&lines, 3, &mut buf).unwrap();
+        assert_eq!(consume(&buf[..], &NonEmpty).unwrap(), (9, 3));
+        assert_eq!(consume(&b"\n[1]\r\n\n{}"[..], &NonEmpty).unwrap(), (2, 1));
+    }
+
+    #[test]
+    fn over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let lines = vec!["[1]".to_owned(); 1000];
+        let producer = thread::spawn(move || {
+            connect_and_produce(Transport::Tcp, &addr, &lines, 5).unwrap();
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let (messages, errors, _) = timed_consume(stream, &NonEmpty).unwrap();
+        producer.join().unwrap();
+        assert_eq!((messages, errors), (5000, 0));
+        assert_eq!(Transport::from_name("tcp"), Some(Transport::Tcp));
+        assert_eq!(Transport::from_name("carrier-pigeon"), None);
+    }
+}
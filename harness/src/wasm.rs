@@ -0,0 +1,48 @@
+//! An entry point for running the strategies in a browser (or node).
+//!
+//! A frontend written for the web would run the client side of this
+//! protocol in wasm, where the relative costs of the strategies may well be
+//! different. To build:
+//!
+//! ```text
+//! cargo build -p xi-der-harness --lib --release --target wasm32-unknown-unknown --features wasm
+//! wasm-bindgen --target web target/wasm32-unknown-unknown/release/xi_der_harness.wasm --out-dir pkg
+//! ```
+//!
+//! There are no files to read there, so the corpus is passed in as a string.
+
+use std::time::Duration;
+
+use serde_json;
+use wasm_bindgen::prelude::*;
+
+use corpus::Buffer;
+use report::ResultRow;
+use runner::{self, Budget, Cache};
+use strategy;
+
+#[wasm_bindgen]
+extern "C" {
+    /// `performance.now()`, in milliseconds.
+    #[wasm_bindgen(js_namespace = performance)]
+    pub fn now() -> f64;
+}
+
+/// Times each strategy over `corpus`, a string of newline-delimited
+/// messages, for `max_ms` milliseconds each. Returns the results as JSON.
+/// A `max_ms` that isn't a number, or is negative, is taken as 0, and one
+/// too long for a `Duration`, `Infinity` included, as the longest there is.
+#[wasm_bindgen]
+pub fn bench(corpus: &str, max_ms: f64) -> String {
+    let buffer = Buffer::new(corpus.lines().filter(|l| !l.trim().is_empty()));
+    let time = if max_ms.is_nan() {
+        Duration::new(0, 0)
+    } else {
+        Duration::try_from_secs_f64(max_ms.max(0.0) / 1000.0).unwrap_or(Duration::MAX)
+    };
+    let budget = Budget::Time(time);
+    let results = strategy::all().iter()
+        .map(|s| ResultRow::from(&runner::measure(s.as_ref(), &buffer, budget, Cache::Hot)))
+        .collect::<Vec<_>>();
+    serde_json::to_string(&results).unwrap()
+}
@@ -0,0 +1,63 @@
+//! Throughput against corpus length, for each strategy.
+//!
+//! The benches in src/lib.rs each parse one corpus of one size, which hides
+//! whatever a strategy pays once per run rather than once per message:
+//! setting up a sink, warming a cache, starting a thread. Here each strategy
+//! parses the first 10, 100, 1000 and 10000 messages of the same session,
+//! and criterion reports messages per second for each, so a strategy with a
+//! fixed cost shows up as a curve that rises with length and then flattens,
+//! where one without is flat throughout. The session is the generated
+//! presets interleaved, so that every prefix has a bit of everything.
+//!
+//! Run it with
+//!
+//!     cargo bench -p xi-der-strategies --bench corpus_length
+//!
+//! and add `-- NAME` to run just the group for strategy NAME.
+
+#[macro_use]
+extern crate criterion;
+extern crate xi_der_corpus as corpus;
+extern crate xi_der_strategies as strategies;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+
+use corpus::generate::Preset;
+use strategies::sink::Sink;
+use strategies::strategy::{self, Strategy};
+
+const LENGTHS: [usize; 4] = [10, 100, 1000, 10_000];
+
+fn session() -> Vec<String> {
+    let longest = LENGTHS[LENGTHS.len() - 1];
+    let presets = Preset::all().iter().map(|p| p.generate(longest, 1)).collect::<Vec<_>>();
+    let mut lines = corpus::interleave::interleave(&presets, 1);
+    lines.truncate(longest);
+    lines
+}
+
+fn parse_all(strategy: &dyn Strategy, lines: &[String]) -> Sink {
+    let mut sink = Sink::new();
+    for line in lines {
+        // as the harness does, a line a strategy can't parse is skipped
+        let _ = strategy.parse_into(line, &mut sink);
+    }
+    sink
+}
+
+fn corpus_length(c: &mut Criterion) {
+    let lines = session();
+    for strategy in strategy::all() {
+        let mut group = c.benchmark_group(strategy.name());
+        for &len in &LENGTHS {
+            group.throughput(Throughput::Elements(len as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(len), &lines[..len], |b, lines| {
+                b.iter(|| parse_all(strategy.as_ref(), lines))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, corpus_length);
+criterion_main!(benches);
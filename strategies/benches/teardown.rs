@@ -0,0 +1,75 @@
+//! The end of a batch: getting rid of the messages once they're handled.
+//!
+//! xi handles a batch of messages on its event loop, and whatever it parsed
+//! them into is dropped there too, before the next frame. Owned messages
+//! free each of their strings; borrowed ones own little but the odd
+//! unescaped string, so dropping them is mostly freeing the `Vec`; and an
+//! arena is cleared, keeping its slots for the next batch, which frees only
+//! what the messages owned. Only the teardown is timed: each iteration gets
+//! a freshly parsed batch, and the benches in src/lib.rs time parsing. Run
+//! it with
+//!
+//!     cargo bench -p xi-der-strategies --bench teardown
+
+#[macro_use]
+extern crate criterion;
+extern crate serde_json;
+extern crate xi_der_corpus as corpus;
+extern crate xi_der_strategies as strategies;
+
+use std::collections::VecDeque;
+
+use criterion::{BatchSize, Criterion, Throughput};
+
+use corpus::generate::Preset;
+use strategies::arena::{self, Arena};
+use strategies::{rpc2, rpc3};
+
+const MESSAGES: usize = 5000;
+
+/// The lines of `preset` that both rpc2 and rpc3 parse as notifications, so
+/// that every bench drops the same messages.
+fn batch(preset: Preset) -> Vec<String> {
+    preset.generate(MESSAGES, 1).into_iter().filter(|line| {
+        serde_json::from_str::<rpc2::CoreNotification>(line).is_ok()
+            && serde_json::from_str::<rpc3::CoreNotification>(line).is_ok()
+    }).collect()
+}
+
+fn teardown(c: &mut Criterion) {
+    for &preset in &[Preset::CursorBurst, Preset::FileSession] {
+        let lines = batch(preset);
+        let mut group = c.benchmark_group(format!("teardown/{}", preset.name()));
+        group.throughput(Throughput::Elements(lines.len() as u64));
+        // a batch is too big to set up many of at once
+        group.bench_function("owned", |b| b.iter_batched(
+            || lines.iter().map(|l| serde_json::from_str::<rpc2::CoreNotification>(l).unwrap())
+                .collect::<Vec<_>>(),
+            drop,
+            BatchSize::PerIteration,
+        ));
+        group.bench_function("borrowed", |b| b.iter_batched(
+            || lines.iter().map(|l| serde_json::from_str::<rpc3::CoreNotification>(l).unwrap())
+                .collect::<Vec<_>>(),
+            drop,
+            BatchSize::PerIteration,
+        ));
+        group.bench_function("arena", |b| b.iter_batched(
+            || {
+                let mut arena = Arena::new();
+                assert_eq!(arena::parse_lines(&lines, &mut arena, &mut VecDeque::new()), 0);
+                arena
+            },
+            // returned, so that freeing the slots themselves isn't timed
+            |mut arena| {
+                arena.clear();
+                arena
+            },
+            BatchSize::PerIteration,
+        ));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, teardown);
+criterion_main!(benches);
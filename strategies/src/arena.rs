@@ -0,0 +1,209 @@
+//! Parsed messages in an arena, with indices on the queue.
+//!
+//! A queue of `CoreNotification`s moves each message at least twice, into
+//! the queue and out of it, and the enum is as big as its biggest variant.
+//! Here the messages are parsed into an arena once and stay put, and the
+//! queue carries an `Index` of eight bytes. The arena can hold the borrowing
+//! rpc3 types, as long as the lines they borrow from outlive it, which is
+//! the same arrangement `scoped` makes with the handler thread.
+//!
+//! Slots are reused once their message is handled, so an index carries the
+//! generation of the slot it was given out for, and one that's outlived its
+//! message finds nothing rather than whatever took its place.
+
+use std::collections::VecDeque;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crossbeam;
+#[cfg(not(target_arch = "wasm32"))]
+use crossbeam::channel;
+use serde_json;
+
+use dispatch::{self, Counter};
+use rpc3;
+#[cfg(not(target_arch = "wasm32"))]
+use scoped::QUEUE_DEPTH;
+
+/// A handle to a value in an `Arena`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Index {
+    slot: u32,
+    generation: u32,
+}
+
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    /// The slots that are empty, to be reused before the arena grows.
+    free: Vec<u32>,
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena { slots: Vec::new(), free: Vec::new() }
+    }
+
+    pub fn insert(&mut self, value: T) -> Index {
+        match self.free.pop() {
+            Some(slot) => {
+                let entry = &mut self.slots[slot as usize];
+                entry.value = Some(value);
+                Index { slot, generation: entry.generation }
+            }
+            None => {
+                let slot = self.slots.len() as u32;
+                self.slots.push(Slot { generation: 0, value: Some(value) });
+                Index { slot, generation: 0 }
+            }
+        }
+    }
+
+    /// The value at `index`, if it hasn't been removed.
+    pub fn get(&self, index: Index) -> Option<&T> {
+        self.slots.get(index.slot as usize)
+            .filter(|s| s.generation == index.generation)
+            .and_then(|s| s.value.as_ref())
+    }
+
+    /// Takes the value at `index` out of the arena, freeing its slot.
+    pub fn remove(&mut self, index: Index) -> Option<T> {
+        let entry = self.slots.get_mut(index.slot as usize)
+            .filter(|s| s.generation == index.generation)?;
+        let value = entry.value.take()?;
+        entry.generation = entry.generation.wrapping_add(1);
+        self.free.push(index.slot);
+        Some(value)
+    }
+
+    /// Removes everything, keeping the slots for reuse.
+    pub fn clear(&mut self) {
+        for (i, entry) in self.slots.iter_mut().enumerate() {
+            if entry.value.take().is_some() {
+                entry.generation = entry.generation.wrapping_add(1);
+                self.free.push(i as u32);
+            }
+        }
+    }
+
+    /// The number of values in the arena.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Arena<T> {
+        Arena::new()
+    }
+}
+
+/// Parses each of `lines` into `arena`, queueing their indices. Returns the
+/// number of lines that failed to parse.
+pub fn parse_lines<'a>(lines: &'a [String], arena: &mut Arena<rpc3::CoreNotification<'a>>,
+                       queue: &mut VecDeque<Index>) -> usize {
+    let mut errors = 0;
+    for line in lines {
+        match serde_json::from_str::<rpc3::CoreNotification>(line) {
+            Ok(msg) => queue.push_back(arena.insert(msg)),
+            Err(_) => errors += 1,
+        }
+    }
+    errors
+}
+
+/// Dispatches the messages in `queue`, in order, removing each from `arena`
+/// once it's handled.
+pub fn dispatch_queue(arena: &mut Arena<rpc3::CoreNotification>, queue: &mut VecDeque<Index>,
+                      handler: &mut Counter) {
+    while let Some(index) = queue.pop_front() {
+        if let Some(msg) = arena.remove(index) {
+            dispatch::match_rpc3(&msg, handler);
+        }
+    }
+}
+
+/// Dispatches the messages in `queue` on a scoped handler thread, which
+/// borrows `arena` and is sent only their indices.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn dispatch_scoped(arena: &Arena<rpc3::CoreNotification>, queue: &mut VecDeque<Index>)
+    -> Counter
+{
+    let (tx, rx) = channel::bounded::<Index>(QUEUE_DEPTH);
+    crossbeam::scope(|scope| {
+        let handler = scope.spawn(move |_| {
+            let mut handler = Counter::default();
+            for index in rx {
+                if let Some(msg) = arena.get(index) {
+                    dispatch::match_rpc3(msg, &mut handler);
+                }
+            }
+            handler
+        });
+        for index in queue.drain(..) {
+            if tx.send(index).is_err() {
+                break;
+            }
+        }
+        drop(tx);
+        handler.join().expect("handler thread panicked")
+    }).expect("scoped thread panicked")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use super::*;
+    use TEST_JSON;
+
+    #[test]
+    fn generations() {
+        let mut arena = Arena::new();
+        let a = arena.insert("a");
+        let b = arena.insert("b");
+        assert_eq!(arena.remove(a), Some("a"));
+        assert_eq!(arena.remove(a), None);
+        // a's slot is reused, but a still finds nothing
+        let c = arena.insert("c");
+        assert_eq!((arena.get(a), arena.get(c)), (None, Some(&"c")));
+        arena.clear();
+        assert!(arena.is_empty());
+        assert_eq!((arena.get(b), arena.get(c)), (None, None));
+        arena.insert("d");
+        assert_eq!((arena.len(), arena.slots.len()), (1, 2));
+        assert!(mem::size_of::<Index>() < mem::size_of::<rpc3::CoreNotification>());
+    }
+
+    #[test]
+    fn same_calls_as_one_thread() {
+        let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        let mut expected = Counter::default();
+        for line in &lines {
+            let msg = serde_json::from_str::<rpc3::CoreNotification>(line).unwrap();
+            dispatch::match_rpc3(&msg, &mut expected);
+        }
+
+        let mut arena = Arena::new();
+        let mut queue = VecDeque::new();
+        assert_eq!(parse_lines(&lines, &mut arena, &mut queue), 0);
+        let mut handler = Counter::default();
+        dispatch_queue(&mut arena, &mut queue, &mut handler);
+        assert_eq!(handler, expected);
+        assert!(arena.is_empty() && queue.is_empty());
+
+        assert_eq!(parse_lines(&lines, &mut arena, &mut queue), 0);
+        assert_eq!(dispatch_scoped(&arena, &mut queue), expected);
+        assert_eq!(arena.len(), lines.len());
+
+        let bad = vec![lines[0].clone(), "{".to_owned()];
+        assert_eq!(parse_lines(&bad, &mut Arena::new(), &mut VecDeque::new()), 1);
+    }
+}
@@ -0,0 +1,155 @@
+//! Binary payloads, carried in an RPC as base64.
+//!
+//! JSON has no bytes, so a plugin sending core something binary, an image to
+//! preview or what it put on the clipboard, sends it as a base64 string in
+//! the params of a plugin RPC. rpc2 reads those params into a `Value`,
+//! copying the string, and leaves decoding it to whoever handles the RPC.
+//! The types here read a `show_image` RPC's params directly, either decoding
+//! the image as it's deserialized (`Image`), or borrowing the base64 and
+//! decoding it only when asked (`LazyImage`), which is free for a core that
+//! only forwards it, and doesn't copy it first either way. Base64 has no
+//! characters JSON needs to escape, but `/` may be escaped anyway, as `\/`,
+//! and then the string has to be unescaped into a copy.
+//!
+//! As in rpc2, the envelope's method and the plugin command aren't checked.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::{DecodeError, Engine};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde_json;
+
+/// A plugin RPC, with its params as `P`.
+#[derive(Deserialize, Debug)]
+pub struct PluginRpc<'a, P> {
+    pub view_id: &'a str,
+    pub receiver: &'a str,
+    #[serde(borrow)]
+    pub rpc: Rpc<'a, P>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Rpc<'a, P> {
+    pub method: &'a str,
+    pub params: P,
+}
+
+#[derive(Deserialize)]
+struct Envelope<'a, P> {
+    #[serde(borrow)]
+    params: PluginRpc<'a, P>,
+}
+
+/// `show_image`'s params, with the image decoded as it's read.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Image {
+    pub mime: String,
+    #[serde(deserialize_with = "decode")]
+    pub data: Vec<u8>,
+}
+
+/// `show_image`'s params, with the image left as base64.
+#[derive(Deserialize, Debug)]
+pub struct LazyImage<'a> {
+    #[serde(borrow)]
+    pub mime: Cow<'a, str>,
+    #[serde(borrow)]
+    pub data: Base64<'a>,
+}
+
+/// A base64 string, borrowed from the input unless it was escaped.
+#[derive(Deserialize, Debug)]
+pub struct Base64<'a>(#[serde(borrow)] Cow<'a, str>);
+
+impl<'a> Base64<'a> {
+    pub fn raw(&self) -> &str {
+        &self.0
+    }
+
+    pub fn decode(&self) -> Result<Vec<u8>, DecodeError> {
+        STANDARD.decode(self.0.as_bytes())
+    }
+}
+
+/// Reads a plugin RPC whose params are `P`.
+pub fn parse<'a, P: Deserialize<'a>>(json: &'a str) -> serde_json::Result<PluginRpc<'a, P>> {
+    serde_json::from_str::<Envelope<P>>(json).map(|env| env.params)
+}
+
+/// A `show_image` plugin RPC carrying `data`, for the tests and benches.
+pub fn show_image(mime: &str, data: &[u8]) -> String {
+    json!({"method": "plugin", "params": {
+        "command": "plugin_rpc",
+        "view_id": "view-id-1",
+        "receiver": "image-preview",
+        "rpc": {
+            "method": "show_image",
+            "params": {"mime": mime, "data": STANDARD.encode(data)},
+            "rpc_type": "notification",
+        },
+    }}).to_string()
+}
+
+/// Decodes a base64 string as it's deserialized, without copying it out of
+/// the input first.
+fn decode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    struct DecodeVisitor;
+
+    impl<'de> Visitor<'de> for DecodeVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a base64 string")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+            STANDARD.decode(v.as_bytes()).map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_str(DecodeVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpc2;
+
+    #[test]
+    fn images() {
+        let data = (0..1000u32).map(|i| (i * 7 % 256) as u8).collect::<Vec<_>>();
+        let json = show_image("image/png", &data);
+
+        let eager = parse::<Image>(&json).unwrap();
+        assert_eq!((eager.view_id, eager.receiver, eager.rpc.method),
+                   ("view-id-1", "image-preview", "show_image"));
+        assert_eq!(eager.rpc.params, Image { mime: "image/png".into(), data: data.clone() });
+
+        let lazy = parse::<LazyImage>(&json).unwrap();
+        assert!(match lazy.rpc.params.data.0 { Cow::Borrowed(_) => true, _ => false });
+        assert_eq!(lazy.rpc.params.data.decode().unwrap(), data);
+
+        // rpc2 takes the params as they come
+        match serde_json::from_str::<rpc2::CoreNotification>(&json).unwrap() {
+            rpc2::CoreNotification::Plugin(rpc2::PluginNotification::PluginRpc { rpc, .. }) => {
+                let base64 = rpc.params["data"].as_str().unwrap();
+                assert_eq!(base64, lazy.rpc.params.data.raw());
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // escaped, and copied
+        let escaped = json.replace('/', "\\/");
+        let lazy = parse::<LazyImage>(&escaped).unwrap();
+        assert!(match lazy.rpc.params.data.0 { Cow::Owned(_) => true, _ => false });
+        assert_eq!(lazy.rpc.params.data.decode().unwrap(), data);
+        assert_eq!(parse::<Image>(&escaped).unwrap().rpc.params.data, data);
+
+        // not base64: an error when it's read, or only when it's decoded
+        let bad = json.replacen("\"data\":\"", "\"data\":\"!", 1);
+        assert!(parse::<Image>(&bad).is_err());
+        assert!(parse::<LazyImage>(&bad).unwrap().rpc.params.data.decode().is_err());
+    }
+}
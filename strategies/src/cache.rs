@@ -0,0 +1,146 @@
+//! Caching the parse of edits' params, keyed by their raw text.
+//!
+//! Much of what a frontend sends is the same few messages over and over: a
+//! held arrow key sends the same `move_down`, with the same params, dozens
+//! of times a second. `ParamsCache` keeps the typed parse of each edit's
+//! params it's seen, behind an `Rc`, and hands out the same one whenever
+//! the raw params are the same again, so that a hit costs reading the
+//! envelope and hashing the params. Params longer than `MAX_PARAMS`, such
+//! as pastes, aren't kept, and when the cache is full it's emptied rather
+//! than evicting anything in particular.
+//!
+//! How often it hits is up to the corpus: almost always in a cursor burst,
+//! rarely in a scroll storm, whose positions keep changing. The tests below
+//! say how often for each of the generated presets, and the `cached_*`
+//! benches what that's worth.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde_json;
+
+use rpc2::{EditCommand, EditNotification};
+use scratch::Reset;
+
+/// The longest params that are kept.
+pub const MAX_PARAMS: usize = 256;
+
+/// The most entries kept at once.
+pub const CAPACITY: usize = 1024;
+
+#[derive(Debug, Default)]
+pub struct ParamsCache {
+    edits: HashMap<Box<str>, Rc<EditCommand<EditNotification>>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl ParamsCache {
+    pub fn new() -> ParamsCache {
+        ParamsCache::default()
+    }
+
+    /// The edit whose params are `params`, from the cache if they've been
+    /// seen before.
+    pub fn edit(&mut self, params: &str)
+        -> serde_json::Result<Rc<EditCommand<EditNotification>>>
+    {
+        if let Some(edit) = self.edits.get(params) {
+            self.hits += 1;
+            return Ok(edit.clone());
+        }
+        self.misses += 1;
+        let edit = Rc::new(serde_json::from_str(params)?);
+        if params.len() <= MAX_PARAMS {
+            if self.edits.len() >= CAPACITY {
+                self.edits.clear();
+            }
+            self.edits.insert(params.into(), Rc::clone(&edit));
+        }
+        Ok(edit)
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// The fraction of lookups that hit, or 0 if there haven't been any.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl Reset for ParamsCache {
+    fn prepare(&mut self) {
+        self.edits.reserve(CAPACITY);
+    }
+
+    fn reset(&mut self) {
+        self.edits.clear();
+        self.hits = 0;
+        self.misses = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corpus::generate::Preset;
+    use rpc4::Envelope;
+    use scratch::Scratch;
+
+    fn hit_rate(lines: &[String]) -> f64 {
+        let mut cache = ParamsCache::new();
+        for line in lines {
+            let env = Envelope::parse(line).unwrap();
+            if env.method == "edit" {
+                cache.edit(env.params.get()).unwrap();
+            }
+        }
+        cache.hit_rate()
+    }
+
+    #[test]
+    fn hit_rates() {
+        let rate = |preset: Preset| hit_rate(&preset.generate(5000, 1));
+        assert!(rate(Preset::CursorBurst) > 0.99);
+        assert!(rate(Preset::ScrollStorm) < 0.5);
+        // no edits at all
+        assert_eq!(rate(Preset::FileSession), 0.0);
+    }
+
+    #[test]
+    fn same_edit() {
+        let mut cache = ParamsCache::new();
+        let params = r#"{"view_id":"view-id-1","method":"move_down","params":[]}"#;
+        let first = cache.edit(params).unwrap();
+        assert!(Rc::ptr_eq(&first, &cache.edit(params).unwrap()));
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+        assert!(cache.edit(r#"{"view_id":"view-id-1","method":"fly"}"#).is_err());
+        let long = format!(r#"{{"view_id":"v","method":"insert","params":{{"chars":"{}"}}}}"#,
+                           "a".repeat(MAX_PARAMS));
+        cache.edit(&long).unwrap();
+        cache.edit(&long).unwrap();
+        assert_eq!(cache.misses(), 4);
+    }
+
+    #[test]
+    fn reset() {
+        let scratch = Scratch::new(ParamsCache::new());
+        scratch.prepare();
+        let params = r#"{"view_id":"view-id-1","method":"move_down","params":[]}"#;
+        let first = scratch.get().edit(params).unwrap();
+        assert!(Rc::ptr_eq(&first, &scratch.get().edit(params).unwrap()));
+        scratch.reset();
+        let mut cache = scratch.get();
+        assert!(!Rc::ptr_eq(&first, &cache.edit(params).unwrap()));
+        assert_eq!((cache.hits(), cache.misses()), (0, 1));
+    }
+}
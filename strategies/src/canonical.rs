@@ -0,0 +1,144 @@
+//! Canonical JSON, for comparing what two serializers wrote.
+//!
+//! Two outputs can be the same message and still differ byte for byte: rpc2
+//! writes an edit's keys sorted, rpc3 in declaration order, and an input
+//! might write `1.0` for `1` or `\/` for `/`. Written canonically, the same
+//! message is always the same text: object keys sorted by their bytes, no
+//! whitespace, strings escaped only where JSON requires it, integers as
+//! integers, including floats without a fraction that fit in one, and other
+//! floats in their shortest round-tripping digits, laid out by one rule of
+//! our own rather than by whichever serde_json is in the build. Anything
+//! that still differs is a real difference.
+
+use std::fmt::Write;
+
+use serde::Serialize;
+use serde_json::{self, Number, Value};
+
+/// `value`, serialized canonically.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    Ok(write(&serde_json::to_value(value)?))
+}
+
+/// `json`, rewritten canonically.
+pub fn canonicalize(json: &str) -> serde_json::Result<String> {
+    Ok(write(&serde_json::from_str::<Value>(json)?))
+}
+
+/// `value`, written canonically.
+pub fn write(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out
+}
+
+fn write_value(value: &Value, out: &mut String) {
+    match *value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if b { "true" } else { "false" }),
+        Value::Number(ref n) => write_number(n, out),
+        Value::String(ref s) => write_string(s, out),
+        Value::Array(ref values) => {
+            out.push('[');
+            for (idx, value) in values.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_value(value, out);
+            }
+            out.push(']');
+        }
+        Value::Object(ref map) => {
+            // sorted here rather than trusting the map's order, which is
+            // insertion order if anything in the build enables preserve_order
+            let mut members = map.iter().collect::<Vec<_>>();
+            members.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (idx, (key, value)) in members.into_iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write_value(value, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// 2^53, up to which every integer is exactly a float.
+const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+fn write_number(n: &Number, out: &mut String) {
+    if let Some(u) = n.as_u64() {
+        let _ = write!(out, "{}", u);
+    } else if let Some(i) = n.as_i64() {
+        let _ = write!(out, "{}", i);
+    } else if let Some(f) = n.as_f64() {
+        if f.fract() == 0.0 && f.abs() <= MAX_EXACT {
+            // and -0.0 as 0
+            let _ = write!(out, "{}", f as i64);
+        } else {
+            write_float(f, out);
+        }
+    }
+}
+
+/// `f` in plain decimal if its exponent is from -7 to 20, and as digits
+/// and an exponent, as in `1.5e-8` or `1e300`, otherwise. The digits are
+/// the shortest that read back as `f`, which is what `{:e}` writes.
+fn write_float(f: f64, out: &mut String) {
+    let sci = format!("{:e}", f.abs());
+    let (mantissa, exp) = sci.split_at(sci.find('e').unwrap_or(sci.len()));
+    let exp = exp.get(1..).and_then(|e| e.parse::<i32>().ok()).unwrap_or(0);
+    if f.is_sign_negative() {
+        out.push('-');
+    }
+    if exp < -7 || exp > 20 {
+        let _ = write!(out, "{}e{}", mantissa, exp);
+        return;
+    }
+    let digits = mantissa.replace('.', "");
+    if exp < 0 {
+        out.push_str("0.");
+        out.extend((1..-exp).map(|_| '0'));
+        out.push_str(&digits);
+    } else {
+        let point = exp as usize + 1;
+        if digits.len() <= point {
+            out.push_str(&digits);
+            out.extend((digits.len()..point).map(|_| '0'));
+        } else {
+            let _ = write!(out, "{}.{}", &digits[..point], &digits[point..]);
+        }
+    }
+}
+
+fn write_string(s: &str, out: &mut String) {
+    // serde_json escapes only quotes, backslashes and control characters
+    out.push_str(&Value::String(s.to_owned()).to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical() {
+        let json = r#"{ "b": [1.0, -0.0, 2.5, 1e3, -7], "a": "\/é\n",
+                        "c": {"y": null, "x": true} }"#;
+        assert_eq!(canonicalize(json).unwrap(),
+                   r#"{"a":"/é\n","b":[1,0,2.5,1000,-7],"c":{"x":true,"y":null}}"#);
+        assert_eq!(canonicalize(&canonicalize(json).unwrap()).unwrap(),
+                   canonicalize(json).unwrap());
+        assert_eq!(canonicalize("1e300").unwrap(), "1e300");
+        for &(json, canonical) in &[("0.1", "0.1"), ("-2.5E-3", "-0.0025"), ("123.456", "123.456"),
+                                    ("1.5e-8", "1.5e-8"), ("1e-7", "0.0000001"),
+                                    ("-1.25e20", "-125000000000000000000"), ("1e21", "1e21"),
+                                    ("2.5e-300", "2.5e-300")] {
+            assert_eq!(canonicalize(json).unwrap(), canonical, "{}", json);
+        }
+        assert_eq!(to_string(&vec![(1, "a")]).unwrap(), r#"[[1,"a"]]"#);
+    }
+}
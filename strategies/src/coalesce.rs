@@ -0,0 +1,132 @@
+//! Merging runs of inserts before they're parsed.
+//!
+//! While typing, a frontend sends an `insert` per keystroke, and when core
+//! falls behind they pile up in its input. The proposal modeled here is to
+//! scan the waiting messages and merge consecutive inserts into the same
+//! view into one, so that a burst of typing costs one typed parse and one
+//! edit instead of dozens. The scan reads each message onto a tape, which
+//! is cheap next to typed parsing; whether it pays for itself is what the
+//! `*_inserts` benches are for.
+//!
+//! Only notifications of exactly the shape xi-mac sends are merged: an edit
+//! without an id, whose params are a view id, the method and the chars.
+//! Anything else, including batches, ends a run and passes through as is.
+
+use std::borrow::Cow;
+
+use tape::{Op, Tape};
+
+/// Returns `lines` with each run of consecutive inserts into the same view
+/// merged into a single insert. Messages that aren't merged are borrowed.
+pub fn coalesce<'a>(lines: &'a [String]) -> Vec<Cow<'a, str>> {
+    let mut out = Vec::with_capacity(lines.len());
+    let mut run: Option<Run<'a>> = None;
+    for line in lines {
+        let insert = as_insert(line);
+        if let (Some(r), Some((view, chars))) = (run.as_mut(), insert) {
+            if r.view == view {
+                r.chars.push_str(chars);
+                r.count += 1;
+                continue;
+            }
+        }
+        if let Some(r) = run.take() {
+            out.push(r.finish());
+        }
+        match insert {
+            Some((view, chars)) => {
+                run = Some(Run { first: line, view, chars: chars.to_owned(), count: 1 })
+            }
+            None => out.push(Cow::Borrowed(line.as_str())),
+        }
+    }
+    out.extend(run.map(Run::finish));
+    out
+}
+
+/// Inserts waiting to be merged.
+struct Run<'a> {
+    first: &'a str,
+    /// The raw view id, with any escapes left in.
+    view: &'a str,
+    /// The raw chars of every insert so far. JSON escapes never span more
+    /// than one character, so the raw strings can be joined as they are.
+    chars: String,
+    count: usize,
+}
+
+impl<'a> Run<'a> {
+    fn finish(self) -> Cow<'a, str> {
+        if self.count == 1 {
+            return Cow::Borrowed(self.first);
+        }
+        Cow::Owned(format!(concat!(r#"{{"method":"edit","params":{{"view_id":"{}","#,
+                                   r#""method":"insert","params":{{"chars":"{}"}}}}}}"#),
+                           self.view, self.chars))
+    }
+}
+
+/// The raw view id and chars of `line`, if it's a plain insert.
+fn as_insert(line: &str) -> Option<(&str, &str)> {
+    let tape = Tape::parse(line).ok()?;
+    let root = tape.root();
+    if root.op() != Op::Object || root.len() != 2 || !root.get("method")?.str_eq("edit") {
+        return None;
+    }
+    let params = root.get("params")?;
+    if params.op() != Op::Object || params.len() != 3
+        || !params.get("method")?.str_eq("insert")
+    {
+        return None;
+    }
+    let inner = params.get("params")?;
+    if inner.op() != Op::Object || inner.len() != 1 {
+        return None;
+    }
+    Some((params.get("view_id")?.as_raw_str()?, inner.get("chars")?.as_raw_str()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+
+    fn insert(view: &str, chars: &str) -> String {
+        json!({"method": "edit", "params": {"view_id": view, "method": "insert",
+               "params": {"chars": chars}}}).to_string()
+    }
+
+    fn chars(line: &str) -> String {
+        let val = serde_json::from_str::<Value>(line).unwrap();
+        val["params"]["params"]["chars"].as_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn merges_runs() {
+        let scroll = r#"{"method":"edit","params":{"view_id":"a","method":"scroll","params":[0,9]}}"#;
+        let lines = vec![insert("a", "h"), insert("a", "\"i\"\n"), insert("b", "x"),
+                         scroll.to_owned(), insert("a", "é"), insert("a", "!")];
+        let merged = coalesce(&lines);
+        assert_eq!(merged.len(), 4);
+        assert_eq!(chars(&merged[0]), "h\"i\"\n");
+        assert!(merged[0].contains(r#""view_id":"a""#));
+        assert_eq!(merged[1], lines[2]);
+        assert_eq!(merged[2], scroll);
+        assert_eq!(chars(&merged[3]), "é!");
+        // a lone insert is passed through untouched
+        match coalesce(&lines[2..3])[0] {
+            Cow::Borrowed(line) => assert_eq!(line, lines[2]),
+            Cow::Owned(_) => panic!("copied a lone insert"),
+        }
+    }
+
+    #[test]
+    fn leaves_others_alone() {
+        let with_id = r#"{"id":1,"method":"edit","params":{"view_id":"a","method":"insert","params":{"chars":"x"}}}"#;
+        let extra = r#"{"method":"edit","params":{"view_id":"a","method":"insert","params":{"chars":"x","n":1}}}"#;
+        let lines = vec![insert("a", "x"), with_id.to_owned(), extra.to_owned(),
+                         format!("[{}]", insert("a", "x")), insert("a", "x")];
+        assert_eq!(coalesce(&lines), lines);
+    }
+}
@@ -0,0 +1,266 @@
+//! One message for every variant of the rpc2 types, for catching the
+//! variants that some strategies don't know about.
+//!
+//! rpc2 is the reference: the other strategies are only interesting if they
+//! handle everything it does. When a variant is added there, add it to the
+//! lists below; the `_covers_*` functions stop compiling as a reminder.
+
+use serde_json::{self, Value};
+
+use error::ParseError;
+use rpc2::{self, CoreNotification, CoreRequest, EditCommand, EditNotification, EditRequest,
+           GestureType, LineRange, MouseAction, PlaceholderRpc, PluginNotification, RpcType};
+use strategy::Strategy;
+
+/// A serialized message, and a name for it like "edit/insert".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub json: String,
+}
+
+/// A sample that a strategy failed to parse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    pub strategy: &'static str,
+    pub sample: String,
+    pub error: ParseError,
+}
+
+const VIEW_ID: &str = "view-id-1";
+
+fn edit<T>(cmd: T) -> EditCommand<T> {
+    EditCommand { view_id: VIEW_ID.into(), undo_group: None, annotation: None, cmd }
+}
+
+pub fn edit_notifications() -> Vec<EditNotification> {
+    use rpc2::EditNotification::*;
+    vec![
+        Insert { chars: "fn main() {\n\t\"hi\"\n}".into() },
+        DeleteForward,
+        DeleteBackward,
+        DeleteWordForward,
+        DeleteWordBackward,
+        DeleteToEndOfParagraph,
+        DeleteToBeginningOfLine,
+        InsertNewline,
+        InsertTab,
+        MoveUp,
+        MoveUpAndModifySelection,
+        MoveDown,
+        MoveDownAndModifySelection,
+        MoveLeft,
+        MoveLeftAndModifySelection,
+        MoveRight,
+        MoveRightAndModifySelection,
+        MoveWordLeft,
+        MoveWordLeftAndModifySelection,
+        MoveWordRight,
+        MoveWordRightAndModifySelection,
+        MoveToBeginningOfParagraph,
+        MoveToEndOfParagraph,
+        MoveToLeftEndOfLine,
+        MoveToLeftEndOfLineAndModifySelection,
+        MoveToRightEndOfLine,
+        MoveToRightEndOfLineAndModifySelection,
+        MoveToBeginningOfDocument,
+        MoveToBeginningOfDocumentAndModifySelection,
+        MoveToEndOfDocument,
+        MoveToEndOfDocumentAndModifySelection,
+        ScrollPageUp,
+        PageUpAndModifySelection,
+        ScrollPageDown,
+        PageDownAndModifySelection,
+        SelectAll,
+        AddSelectionAbove,
+        AddSelectionBelow,
+        Scroll(LineRange { start: 3, end: 13 }),
+        GotoLine { line: 42 },
+        RequestLines(LineRange { start: 12, end: 13 }),
+        Yank,
+        Transpose,
+        Click(MouseAction { line: 3, column: 10, flags: 0, click_count: Some(1) }),
+        Drag(MouseAction { line: 5, column: 34, flags: 0, click_count: None }),
+        Gesture { line: 2, column: 7, ty: GestureType::WordSelect },
+        Undo,
+        Redo,
+        FindNext { wrap_around: true, allow_same: false },
+        FindPrevious { wrap_around: false },
+        DebugRewrap,
+        DebugPrintSpans,
+        SetMarkedText { chars: "日本".into(), selection: (2, 2), replacement: Some((10, 12)) },
+        UnmarkText,
+    ]
+}
+
+pub fn edit_requests() -> Vec<EditRequest> {
+    vec![
+        EditRequest::Cut,
+        EditRequest::Copy,
+        EditRequest::Find { chars: Some("main".into()), case_sensitive: false },
+    ]
+}
+
+pub fn plugin_notifications() -> Vec<PluginNotification> {
+    vec![
+        PluginNotification::Start { view_id: VIEW_ID.into(), plugin_name: "syntect".into() },
+        PluginNotification::Stop { view_id: VIEW_ID.into(), plugin_name: "syntect".into() },
+        PluginNotification::PluginRpc {
+            view_id: VIEW_ID.into(),
+            receiver: "syntect".into(),
+            rpc: PlaceholderRpc {
+                method: "custom_command".into(),
+                params: json!({"arg": [1, 2]}),
+                rpc_type: RpcType::Notification,
+            },
+        },
+    ]
+}
+
+/// Every core notification, including every edit and plugin notification.
+pub fn core_notifications() -> Vec<CoreNotification> {
+    let mut all = edit_notifications().into_iter()
+        .map(|cmd| CoreNotification::Edit(edit(cmd)))
+        .collect::<Vec<_>>();
+    all.extend(plugin_notifications().into_iter().map(CoreNotification::Plugin));
+    all.push(CoreNotification::CloseView { view_id: VIEW_ID.into() });
+    all.push(CoreNotification::Save { view_id: VIEW_ID.into(), file_path: "src/main.rs".into() });
+    all.push(CoreNotification::SetTheme { theme_name: "InspiredGitHub".into() });
+    all.push(CoreNotification::ClientStarted(rpc2::EmptyStruct {}));
+    all
+}
+
+/// Every core request, including every edit request.
+pub fn core_requests() -> Vec<CoreRequest> {
+    let mut all = edit_requests().into_iter()
+        .map(|cmd| CoreRequest::Edit(edit(cmd)))
+        .collect::<Vec<_>>();
+    all.push(CoreRequest::NewView { file_path: Some("src/lib.rs".into()) });
+    all
+}
+
+/// Every message, serialized as it would be on the wire. Requests are given
+/// ids.
+pub fn samples() -> Vec<Sample> {
+    let notifications = core_notifications().iter()
+        .map(|msg| serde_json::to_value(msg).unwrap())
+        .collect::<Vec<_>>();
+    let requests = core_requests().iter().enumerate()
+        .map(|(i, msg)| {
+            let mut val = serde_json::to_value(msg).unwrap();
+            val["id"] = json!(i);
+            val
+        })
+        .collect::<Vec<_>>();
+    notifications.into_iter().chain(requests)
+        .map(|val| Sample { name: sample_name(&val), json: val.to_string() })
+        .collect()
+}
+
+fn sample_name(val: &Value) -> String {
+    let method = val["method"].as_str().unwrap_or_default();
+    match method {
+        "edit" => format!("edit/{}", val["params"]["method"].as_str().unwrap_or_default()),
+        "plugin" => format!("plugin/{}", val["params"]["command"].as_str().unwrap_or_default()),
+        _ => method.to_owned(),
+    }
+}
+
+/// Runs each strategy over every sample, returning the failures.
+pub fn check(strategies: &[Box<dyn Strategy>]) -> Vec<Gap> {
+    let samples = samples();
+    let mut gaps = Vec::new();
+    for strategy in strategies {
+        for sample in &samples {
+            if let Err(error) = strategy.parse(&sample.json) {
+                gaps.push(Gap { strategy: strategy.name(), sample: sample.name.clone(), error });
+            }
+        }
+    }
+    gaps
+}
+
+/// Prints the gaps for each strategy.
+pub fn print_gaps(strategies: &[Box<dyn Strategy>], gaps: &[Gap]) {
+    let total = samples().len();
+    for strategy in strategies {
+        let missing = gaps.iter().filter(|g| g.strategy == strategy.name()).collect::<Vec<_>>();
+        println!("{:<14}{:>4}/{} parsed", strategy.name(), total - missing.len(), total);
+        for gap in missing {
+            println!("    {:<40}{}", gap.sample, gap.error);
+        }
+    }
+}
+
+// These are never called. Their matches have no wildcards, so they fail to
+// compile when a variant is added to rpc2 without being added above.
+
+fn _covers_edit_notifications(msg: &EditNotification) {
+    use rpc2::EditNotification::*;
+    match *msg {
+        Insert { .. } | DeleteForward | DeleteBackward | DeleteWordForward | DeleteWordBackward
+        | DeleteToEndOfParagraph | DeleteToBeginningOfLine | InsertNewline | InsertTab | MoveUp
+        | MoveUpAndModifySelection | MoveDown | MoveDownAndModifySelection | MoveLeft
+        | MoveLeftAndModifySelection | MoveRight | MoveRightAndModifySelection | MoveWordLeft
+        | MoveWordLeftAndModifySelection | MoveWordRight | MoveWordRightAndModifySelection
+        | MoveToBeginningOfParagraph | MoveToEndOfParagraph | MoveToLeftEndOfLine
+        | MoveToLeftEndOfLineAndModifySelection | MoveToRightEndOfLine
+        | MoveToRightEndOfLineAndModifySelection | MoveToBeginningOfDocument
+        | MoveToBeginningOfDocumentAndModifySelection | MoveToEndOfDocument
+        | MoveToEndOfDocumentAndModifySelection | ScrollPageUp | PageUpAndModifySelection
+        | ScrollPageDown | PageDownAndModifySelection | SelectAll | AddSelectionAbove
+        | AddSelectionBelow | Scroll(_) | GotoLine { .. } | RequestLines(_) | Yank | Transpose
+        | Click(_) | Drag(_) | Gesture { .. } | Undo | Redo | FindNext { .. }
+        | FindPrevious { .. } | DebugRewrap | DebugPrintSpans | SetMarkedText { .. }
+        | UnmarkText => (),
+    }
+}
+
+fn _covers_edit_requests(msg: &EditRequest) {
+    match *msg {
+        EditRequest::Cut | EditRequest::Copy | EditRequest::Find { .. } => (),
+    }
+}
+
+fn _covers_plugin_notifications(msg: &PluginNotification) {
+    match *msg {
+        PluginNotification::Start { .. } | PluginNotification::Stop { .. }
+        | PluginNotification::PluginRpc { .. } => (),
+    }
+}
+
+fn _covers_core(notification: &CoreNotification, request: &CoreRequest) {
+    match *notification {
+        CoreNotification::Edit(_) | CoreNotification::Plugin(_)
+        | CoreNotification::CloseView { .. } | CoreNotification::Save { .. }
+        | CoreNotification::SetTheme { .. } | CoreNotification::ClientStarted(_) => (),
+    }
+    match *request {
+        CoreRequest::Edit(_) | CoreRequest::NewView { .. } => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strategy::{FutureSerde, Hand, Serde, Tape};
+
+    #[test]
+    fn samples_are_distinct() {
+        let samples = samples();
+        let mut names = samples.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), samples.len());
+        assert_eq!(samples.len(), edit_notifications().len() + edit_requests().len()
+                   + plugin_notifications().len() + 5);
+    }
+
+    // xi-core is expected to have gaps; the strategies here shouldn't
+    #[test]
+    fn no_gaps() {
+        let strategies: Vec<Box<dyn Strategy>> =
+            vec![Box::new(Serde), Box::new(FutureSerde), Box::new(Hand), Box::new(Tape)];
+        assert_eq!(check(&strategies), vec![]);
+    }
+}
@@ -0,0 +1,161 @@
+//! How well each strategy explains what's wrong with a message.
+//!
+//! When a frontend sends something core doesn't understand, the error
+//! message is usually all anyone has to go on. serde_derive's messages for
+//! adjacently tagged enums are notoriously unhelpful ("unknown variant",
+//! with no word of where), so this feeds a fixed set of malformed messages
+//! to each strategy and puts what they say side by side.
+
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+
+use error::ParseError;
+use strategy::Strategy;
+
+/// A malformed message, and a name for what's wrong with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Case {
+    pub name: &'static str,
+    pub json: &'static str,
+}
+
+/// What a strategy made of a case.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Rejected(ParseError),
+    Accepted,
+    Panicked,
+}
+
+/// Each strategy's verdict on each case.
+#[derive(Debug)]
+pub struct Report {
+    pub strategies: Vec<&'static str>,
+    pub cases: Vec<Case>,
+    /// Indexed by case, then by strategy.
+    pub verdicts: Vec<Vec<Verdict>>,
+}
+
+macro_rules! case {
+    ($name:expr, $json:expr) => { Case { name: $name, json: $json } }
+}
+
+/// The malformed messages, from broken framing inwards.
+pub fn cases() -> Vec<Case> {
+    vec![
+        case!("truncated", r#"{"method":"edit","params":{"view_id":"view-id-1","method":"#),
+        case!("trailing_garbage", r#"{"method":"client_started","params":{}} }"#),
+        case!("not_an_object", r#""client_started""#),
+        case!("empty_batch", "[]"),
+        case!("missing_method", r#"{"params":{}}"#),
+        case!("method_not_a_string", r#"{"method":7,"params":{}}"#),
+        case!("missing_params", r#"{"method":"set_theme"}"#),
+        case!("unknown_method", r#"{"method":"frobnicate","params":{}}"#),
+        case!("unknown_edit_method",
+              r#"{"method":"edit","params":{"view_id":"view-id-1","method":"frobnicate","params":[]}}"#),
+        case!("edit_missing_view_id",
+              r#"{"method":"edit","params":{"method":"insert","params":{"chars":"a"}}}"#),
+        case!("insert_missing_chars",
+              r#"{"method":"edit","params":{"view_id":"view-id-1","method":"insert","params":{}}}"#),
+        case!("insert_chars_not_a_string",
+              r#"{"method":"edit","params":{"view_id":"view-id-1","method":"insert","params":{"chars":3}}}"#),
+        case!("scroll_one_line",
+              r#"{"method":"edit","params":{"view_id":"view-id-1","method":"scroll","params":[3]}}"#),
+        case!("click_negative_line",
+              r#"{"method":"edit","params":{"view_id":"view-id-1","method":"click","params":[-3,10,0,1]}}"#),
+        case!("unknown_gesture",
+              r#"{"method":"edit","params":{"view_id":"view-id-1","method":"gesture","params":{"line":2,"column":7,"ty":"quadruple_select"}}}"#),
+        case!("save_missing_file_path", r#"{"method":"save","params":{"view_id":"view-id-1"}}"#),
+        case!("bad_message_in_batch",
+              r#"[{"method":"client_started","params":{}},{"method":"set_theme","params":{"theme":"x"}}]"#),
+    ]
+}
+
+/// Feeds every case to every strategy.
+pub fn run(strategies: &[Box<dyn Strategy>]) -> Report {
+    let cases = cases();
+    // as in mutate, panics are expected, and reported separately
+    let prev_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| ()));
+    let verdicts = cases.iter().map(|case| {
+        strategies.iter().map(|s| verdict(s.as_ref(), case.json)).collect()
+    }).collect();
+    panic::set_hook(prev_hook);
+    Report { strategies: strategies.iter().map(|s| s.name()).collect(), cases, verdicts }
+}
+
+/// Feeds `json` to `strategy`, catching any panic.
+fn verdict(strategy: &dyn Strategy, json: &str) -> Verdict {
+    match panic::catch_unwind(AssertUnwindSafe(|| strategy.parse(json))) {
+        Ok(Err(e)) => Verdict::Rejected(e),
+        Ok(Ok(())) => Verdict::Accepted,
+        Err(_) => Verdict::Panicked,
+    }
+}
+
+impl Verdict {
+    fn kind(&self) -> &'static str {
+        match *self {
+            Verdict::Rejected(ref e) => e.kind(),
+            Verdict::Accepted => "ACCEPTED",
+            Verdict::Panicked => "PANICKED",
+        }
+    }
+
+    fn message(&self) -> &str {
+        match *self {
+            Verdict::Rejected(ref e) => e.message(),
+            _ => "",
+        }
+    }
+}
+
+impl Report {
+    pub fn print(&self) {
+        for (case, verdicts) in self.cases.iter().zip(self.verdicts.iter()) {
+            println!("{}: {}", case.name, case.json);
+            for (strategy, verdict) in self.strategies.iter().zip(verdicts.iter()) {
+                println!("    {:<14}{:<16}{}", strategy, verdict.kind(), verdict.message());
+            }
+            println!();
+        }
+    }
+
+    /// The report as a Markdown table per case, for pasting into issues.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        for (case, verdicts) in self.cases.iter().zip(self.verdicts.iter()) {
+            let _ = writeln!(out, "### {}\n\n```json\n{}\n```\n", case.name, case.json);
+            out.push_str("| strategy | kind | message |\n|---|---|---|\n");
+            for (strategy, verdict) in self.strategies.iter().zip(verdicts.iter()) {
+                let message = verdict.message().replace('|', "\\|").replace('\n', " ");
+                let _ = writeln!(out, "| {} | {} | {} |", strategy, verdict.kind(), message);
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strategy::{FutureSerde, Hand, Serde, Tape};
+
+    #[test]
+    fn cases_are_malformed() {
+        let strategies: Vec<Box<dyn Strategy>> =
+            vec![Box::new(Serde), Box::new(FutureSerde), Box::new(Hand), Box::new(Tape)];
+        let report = run(&strategies);
+        for (case, verdicts) in report.cases.iter().zip(report.verdicts.iter()) {
+            assert!(verdicts.iter().all(|v| *v != Verdict::Panicked), "{}", case.name);
+            // accepting one is a finding; nobody accepting it means it isn't malformed
+            assert!(verdicts.iter().any(|v| v.kind() != "ACCEPTED"), "{}", case.name);
+        }
+        let truncated = &report.verdicts[0];
+        assert!(truncated.iter().all(|v| v.kind() == "frame"));
+        let markdown = report.to_markdown();
+        assert_eq!(markdown.matches("### ").count(), cases().len());
+        assert_eq!(markdown.matches("| tape |").count(), cases().len());
+    }
+}
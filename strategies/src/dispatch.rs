@@ -0,0 +1,225 @@
+//! Getting from a parsed notification to a called handler.
+//!
+//! Constructing the enum isn't the end of the story: core then has to
+//! branch on it. This compares a plain `match` against a table of function
+//! pointers indexed by variant, for the rpc2 and rpc3 types. The tape and
+//! xi-core strategies don't produce one of these enums, so they're left out.
+
+use rpc2;
+use rpc3;
+
+/// The receiver of notifications. `E` is the parsed edit command.
+pub trait Handler<E> {
+    fn edit(&mut self, cmd: &E);
+    fn plugin(&mut self, cmd: &rpc2::PluginNotification);
+    fn close_view(&mut self, view_id: &str);
+    fn save(&mut self, view_id: &str, file_path: &str);
+    fn set_theme(&mut self, theme_name: &str);
+    fn client_started(&mut self);
+    fn new_view(&mut self, file_path: Option<&str>);
+}
+
+/// A notification enum whose variants can be numbered, for table dispatch.
+pub trait Variant {
+    /// The number of variants.
+    const COUNT: usize;
+
+    /// The index of this value's variant, in declaration order.
+    fn index(&self) -> usize;
+}
+
+/// Calls the handler for `msg` with a `match`.
+pub fn match_rpc2<H>(msg: &rpc2::CoreNotification, handler: &mut H)
+    where H: Handler<rpc2::EditCommand<rpc2::EditNotification>>
+{
+    use rpc2::CoreNotification::*;
+    match *msg {
+        Edit(ref cmd) => handler.edit(cmd),
+        Plugin(ref cmd) => handler.plugin(cmd),
+        CloseView { ref view_id } => handler.close_view(view_id.as_str()),
+        Save { ref view_id, ref file_path } => handler.save(view_id.as_str(), file_path),
+        SetTheme { ref theme_name } => handler.set_theme(theme_name),
+        ClientStarted(_) => handler.client_started(),
+    }
+}
+
+/// Calls the handler for `msg` with a `match`.
+pub fn match_rpc3<'a, H>(msg: &rpc3::CoreNotification<'a>, handler: &mut H)
+    where H: Handler<rpc3::EditNotification<'a>>
+{
+    use rpc3::CoreNotification::*;
+    match *msg {
+        Edit(ref cmd) => handler.edit(cmd),
+        Plugin(ref cmd) => handler.plugin(cmd),
+        CloseView { view_id } => handler.close_view(view_id),
+        Save { view_id, file_path } => handler.save(view_id, file_path),
+        SetTheme { theme_name } => handler.set_theme(theme_name),
+        ClientStarted(_) => handler.client_started(),
+        NewView { file_path } => handler.new_view(file_path),
+    }
+}
+
+/// A table of handler functions, indexed by variant.
+pub struct Table<N, H> {
+    entries: Vec<fn(&N, &mut H)>,
+}
+
+impl<N: Variant, H> Table<N, H> {
+    /// Calls the handler for `msg` through the table.
+    pub fn dispatch(&self, msg: &N, handler: &mut H) {
+        (self.entries[msg.index()])(msg, handler)
+    }
+}
+
+// Each entry is only ever called with its own variant; the `if let`s are
+// there to get at the fields.
+
+impl<H> Table<rpc2::CoreNotification, H>
+    where H: Handler<rpc2::EditCommand<rpc2::EditNotification>>
+{
+    pub fn rpc2() -> Self {
+        use rpc2::CoreNotification::*;
+        let entries: [fn(&rpc2::CoreNotification, &mut H); 6] = [
+            |msg, h| if let Edit(ref cmd) = *msg { h.edit(cmd) },
+            |msg, h| if let Plugin(ref cmd) = *msg { h.plugin(cmd) },
+            |msg, h| if let CloseView { ref view_id } = *msg { h.close_view(view_id.as_str()) },
+            |msg, h| if let Save { ref view_id, ref file_path } = *msg {
+                h.save(view_id.as_str(), file_path)
+            },
+            |msg, h| if let SetTheme { ref theme_name } = *msg { h.set_theme(theme_name) },
+            |_, h| h.client_started(),
+        ];
+        debug_assert_eq!(entries.len(), rpc2::CoreNotification::COUNT);
+        Table { entries: entries.to_vec() }
+    }
+}
+
+impl<'a, H> Table<rpc3::CoreNotification<'a>, H>
+    where H: Handler<rpc3::EditNotification<'a>>
+{
+    pub fn rpc3() -> Self {
+        use rpc3::CoreNotification::*;
+        let entries: [fn(&rpc3::CoreNotification<'a>, &mut H); 7] = [
+            |msg, h| if let Edit(ref cmd) = *msg { h.edit(cmd) },
+            |msg, h| if let Plugin(ref cmd) = *msg { h.plugin(cmd) },
+            |msg, h| if let CloseView { view_id } = *msg { h.close_view(view_id) },
+            |msg, h| if let Save { view_id, file_path } = *msg { h.save(view_id, file_path) },
+            |msg, h| if let SetTheme { theme_name } = *msg { h.set_theme(theme_name) },
+            |_, h| h.client_started(),
+            |msg, h| if let NewView { file_path } = *msg { h.new_view(file_path) },
+        ];
+        debug_assert_eq!(entries.len(), rpc3::CoreNotification::COUNT);
+        Table { entries: entries.to_vec() }
+    }
+}
+
+impl Variant for rpc2::CoreNotification {
+    const COUNT: usize = 6;
+
+    fn index(&self) -> usize {
+        use rpc2::CoreNotification::*;
+        match *self {
+            Edit(_) => 0,
+            Plugin(_) => 1,
+            CloseView { .. } => 2,
+            Save { .. } => 3,
+            SetTheme { .. } => 4,
+            ClientStarted(_) => 5,
+        }
+    }
+}
+
+impl<'a> Variant for rpc3::CoreNotification<'a> {
+    const COUNT: usize = 7;
+
+    fn index(&self) -> usize {
+        use rpc3::CoreNotification::*;
+        match *self {
+            Edit(_) => 0,
+            Plugin(_) => 1,
+            CloseView { .. } => 2,
+            Save { .. } => 3,
+            SetTheme { .. } => 4,
+            ClientStarted(_) => 5,
+            NewView { .. } => 6,
+        }
+    }
+}
+
+/// A handler that counts its calls and the bytes of the strings it's
+/// passed, so that there's something for dispatch to do.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Counter {
+    pub calls: usize,
+    pub bytes: usize,
+}
+
+impl<E> Handler<E> for Counter {
+    fn edit(&mut self, _cmd: &E) {
+        self.calls += 1;
+    }
+
+    fn plugin(&mut self, _cmd: &rpc2::PluginNotification) {
+        self.calls += 1;
+    }
+
+    fn close_view(&mut self, view_id: &str) {
+        self.calls += 1;
+        self.bytes += view_id.len();
+    }
+
+    fn save(&mut self, view_id: &str, file_path: &str) {
+        self.calls += 1;
+        self.bytes += view_id.len() + file_path.len();
+    }
+
+    fn set_theme(&mut self, theme_name: &str) {
+        self.calls += 1;
+        self.bytes += theme_name.len();
+    }
+
+    fn client_started(&mut self) {
+        self.calls += 1;
+    }
+
+    fn new_view(&mut self, file_path: Option<&str>) {
+        self.calls += 1;
+        self.bytes += file_path.map(str::len).unwrap_or(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+    use TEST_JSON;
+
+    #[test]
+    fn table_matches_match() {
+        let (mut matched, mut tabled) = (Counter::default(), Counter::default());
+        let table = Table::rpc3();
+        for json in TEST_JSON.lines() {
+            let msg = serde_json::from_str::<rpc3::CoreNotification>(json).unwrap();
+            match_rpc3(&msg, &mut matched);
+            table.dispatch(&msg, &mut tabled);
+        }
+        assert_eq!(matched.calls, TEST_JSON.lines().count());
+        assert!(matched.bytes > 0);
+        assert_eq!(matched, tabled);
+
+        let (mut matched, mut tabled) = (Counter::default(), Counter::default());
+        let table = Table::rpc2();
+        for json in TEST_JSON.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            if val.get("id").is_some() {
+                continue;
+            }
+            let msg = serde_json::from_value::<rpc2::CoreNotification>(val).unwrap();
+            match_rpc2(&msg, &mut matched);
+            table.dispatch(&msg, &mut tabled);
+        }
+        assert!(matched.calls > 0);
+        assert_eq!(matched, tabled);
+    }
+}
@@ -0,0 +1,206 @@
+//! Why a strategy rejected a message.
+//!
+//! The strategies fail in very different ways: serde has its own error type
+//! and messages, xi-core has another, and the tape validator its own again.
+//! Each of them is reported as a `ParseError`, so that tooling comparing
+//! strategies can tell, say, an unknown method from malformed params without
+//! matching on message text.
+//!
+//! Where a strategy's own error doesn't say which it was (serde_derive's
+//! errors generally don't), the message is classified by running it through
+//! the tape validator, which does, and the strategy's own message is kept.
+//! That only happens once a message has already been rejected, so it costs
+//! nothing on the success path.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use serde_json::{self, Value};
+#[cfg(feature = "serde_path_to_error")]
+use serde_path_to_error;
+
+use tape::{self, Op, Tape};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input isn't JSON.
+    Frame(String),
+    /// The input is JSON, but not a JSON-RPC message, or a batch of them.
+    Envelope(String),
+    /// A well-formed message with a method we don't know. Edit methods are
+    /// qualified, as in "edit/insert".
+    UnknownMethod { method: String, message: String },
+    /// A known method, with params that don't fit it.
+    BadParams { method: String, message: String },
+    /// The input couldn't be read.
+    Io(String),
+}
+
+impl ParseError {
+    /// A short name for the kind of error, for grouping.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            ParseError::Frame(_) => "frame",
+            ParseError::Envelope(_) => "envelope",
+            ParseError::UnknownMethod { .. } => "unknown_method",
+            ParseError::BadParams { .. } => "bad_params",
+            ParseError::Io(_) => "io",
+        }
+    }
+
+    /// Converts a serde_json error from parsing `json`.
+    pub fn from_json(json: &str, err: serde_json::Error) -> ParseError {
+        use serde_json::error::Category;
+        match err.classify() {
+            Category::Io => ParseError::Io(err.to_string()),
+            Category::Syntax | Category::Eof => ParseError::Frame(err.to_string()),
+            Category::Data => ParseError::classify(json, err.to_string()),
+        }
+    }
+
+    /// As `from_json`, for an error that knows the path to the field that
+    /// failed, which is put at the front of the message.
+    #[cfg(feature = "serde_path_to_error")]
+    pub fn from_path(json: &str, err: serde_path_to_error::Error<serde_json::Error>)
+        -> ParseError
+    {
+        let path = err.path().to_string();
+        let err = ParseError::from_json(json, err.into_inner());
+        let message = format!("{}: {}", path, err.message());
+        err.with_message(message)
+    }
+
+    /// Converts an error from converting `val` to a message type.
+    pub fn from_value<E: fmt::Display>(val: &Value, err: E) -> ParseError {
+        ParseError::classify(&val.to_string(), err.to_string())
+    }
+
+    /// Works out what kind of error `message` was, by validating `json`.
+    ///
+    /// If `json` validates, the strategy was stricter than the validator,
+    /// and the error is put down to the params of the first message.
+    pub fn classify(json: &str, message: String) -> ParseError {
+        match ParseError::validate(json) {
+            Err(e) => e.with_message(message),
+            Ok(method) => ParseError::BadParams { method, message },
+        }
+    }
+
+    /// Validates `json`, returning the first message's qualified method.
+    fn validate(json: &str) -> Result<String, ParseError> {
+        let tape = Tape::parse(json).map_err(|e| ParseError::Frame(e.to_string()))?;
+        let root = tape.root();
+        let nodes = if root.op() == Op::Array {
+            if root.is_empty() {
+                return Err(ParseError::Envelope("empty batch".into()));
+            }
+            root.children().collect()
+        } else {
+            vec![root]
+        };
+        let mut first_method = None;
+        for node in nodes {
+            let msg = tape::Message::from_node(node)?;
+            msg.validate()?;
+            first_method.get_or_insert_with(|| msg.qualified_method());
+        }
+        Ok(first_method.unwrap_or_default())
+    }
+
+    /// The same kind of error, with `message` instead.
+    fn with_message(self, message: String) -> ParseError {
+        match self {
+            ParseError::Frame(_) => ParseError::Frame(message),
+            ParseError::Envelope(_) => ParseError::Envelope(message),
+            ParseError::UnknownMethod { method, .. } => {
+                ParseError::UnknownMethod { method, message }
+            }
+            ParseError::BadParams { method, .. } => ParseError::BadParams { method, message },
+            ParseError::Io(_) => ParseError::Io(message),
+        }
+    }
+
+    /// The strategy's own description of the error.
+    pub fn message(&self) -> &str {
+        match *self {
+            ParseError::Frame(ref message) | ParseError::Envelope(ref message)
+            | ParseError::Io(ref message) => message,
+            ParseError::UnknownMethod { ref message, .. }
+            | ParseError::BadParams { ref message, .. } => message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Frame(ref msg) => write!(f, "invalid JSON: {}", msg),
+            ParseError::Envelope(ref msg) => write!(f, "invalid message: {}", msg),
+            ParseError::UnknownMethod { ref method, ref message } => {
+                write!(f, "unknown method '{}': {}", method, message)
+            }
+            ParseError::BadParams { ref method, ref message } => {
+                write!(f, "bad params for '{}': {}", method, message)
+            }
+            ParseError::Io(ref msg) => write!(f, "io error: {}", msg),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> ParseError {
+        ParseError::Io(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind_of(json: &str) -> &'static str {
+        ParseError::classify(json, "rejected".into()).kind()
+    }
+
+    #[test]
+    fn classifies() {
+        assert_eq!(kind_of(r#"{"method":"#), "frame");
+        assert_eq!(kind_of(r#"{"params":{}}"#), "envelope");
+        assert_eq!(kind_of("[]"), "envelope");
+        assert_eq!(kind_of("3"), "envelope");
+        assert_eq!(ParseError::classify(r#"{"method":"frobnicate","params":{}}"#, "no".into()),
+                   ParseError::UnknownMethod { method: "frobnicate".into(),
+                                               message: "no".into() });
+        let edit = r#"{"method":"edit","params":{"view_id":"v","method":"frobnicate"}}"#;
+        match ParseError::classify(edit, "no".into()) {
+            ParseError::UnknownMethod { method, .. } => assert_eq!(method, "edit/frobnicate"),
+            other => panic!("{:?}", other),
+        }
+        let scroll = r#"{"method":"edit","params":{"view_id":"v","method":"scroll","params":[1]}}"#;
+        assert_eq!(kind_of(scroll), "bad_params");
+        // valid as far as the validator is concerned
+        let valid = r#"[{"method":"client_started","params":{}}]"#;
+        assert_eq!(ParseError::classify(valid, "too strict".into()),
+                   ParseError::BadParams { method: "client_started".into(),
+                                           message: "too strict".into() });
+    }
+
+    #[test]
+    fn from_serde_json() {
+        let json = r#"{"method":"edit","params":{"view_id":"v","method":"insert","params":{}}}"#;
+        let err = serde_json::from_str::<::rpc2::CoreNotification>(json).unwrap_err();
+        let message = err.to_string();
+        match ParseError::from_json(json, err) {
+            ParseError::BadParams { method, message: m } => {
+                assert_eq!(method, "edit/insert");
+                // serde's message, not the validator's
+                assert_eq!(m, message);
+            }
+            other => panic!("{:?}", other),
+        }
+        let err = serde_json::from_str::<Value>("{").unwrap_err();
+        assert_eq!(ParseError::from_json("{", err).kind(), "frame");
+    }
+}
@@ -0,0 +1,94 @@
+//! A fast path for commands without params, matched as bytes.
+//!
+//! Most of what a frontend sends while the cursor is moving is edits like
+//! `move_down`, whose params are `[]`, and these are always laid out the
+//! same way: xi-mac writes the envelope's fields in one order and with no
+//! whitespace. `parse` checks a message against that layout a piece at a
+//! time, and looks the command up by name; no deserializer is involved.
+//! Anything laid out otherwise, or with params to read, or with escapes in
+//! its strings, is left to a full parse, so the fast path is only ever an
+//! optimization: it never accepts a message the full parse wouldn't.
+//!
+//! How much it covers is up to the corpus; the tests below check that it's
+//! most of a cursor burst.
+
+use rpc5::{CoreNotification, EditNotification};
+
+const EDIT: &str = r#"{"method":"edit","params":{"view_id":""#;
+const METHOD: &str = r#","method":""#;
+const CLIENT_STARTED: &str = r#"{"method":"client_started","params":{}}"#;
+
+/// `json` as a notification, if it's one without params, laid out as xi-mac
+/// lays it out.
+pub fn parse(json: &str) -> Option<CoreNotification<'_>> {
+    if json == CLIENT_STARTED {
+        return Some(CoreNotification::ClientStarted);
+    }
+    let rest = strip_prefix(json, EDIT)?;
+    let (view_id, rest) = string(rest)?;
+    let rest = strip_prefix(rest, METHOD)?;
+    let (method, rest) = string(rest)?;
+    match rest {
+        r#","params":[]}}"# | r#","params":{}}}"# => (),
+        _ => return None,
+    }
+    Some(CoreNotification::Edit {
+        view_id,
+        undo_group: None,
+        annotation: None,
+        cmd: EditNotification::without_params(method)?,
+    })
+}
+
+fn strip_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.starts_with(prefix) {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// The contents of a string, up to its closing quote, and what's after the
+/// quote; `None` if it has escapes.
+fn string(s: &str) -> Option<(&str, &str)> {
+    let end = s.bytes().position(|b| b == b'"' || b == b'\\' || b < 0x20)?;
+    if s.as_bytes()[end] != b'"' {
+        return None;
+    }
+    Some((&s[..end], &s[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corpus::generate::Preset;
+
+    #[test]
+    fn fast() {
+        let msg = concat!(r#"{"method":"edit","params":{"view_id":"view-id-1","#,
+                          r#""method":"move_up","params":[]}}"#);
+        match parse(msg) {
+            Some(CoreNotification::Edit { view_id: "view-id-1", cmd, .. }) => {
+                assert_eq!(cmd, EditNotification::MoveUp)
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(parse(&msg.replace("[]", "{}")).is_some());
+        assert!(parse(r#"{"method":"client_started","params":{}}"#).is_some());
+
+        // all left to a full parse
+        assert!(parse(&msg.replace("[]", "[1]")).is_none());
+        assert!(parse(&msg.replace("move_up", "insert")).is_none());
+        assert!(parse(&msg.replace("move_up", "move\\u005fup")).is_none());
+        assert!(parse(&msg.replace(",", ", ")).is_none());
+        assert!(parse(&format!("{} ", msg)).is_none());
+        assert!(parse(&msg.replace("view-id-1", "view\"")).is_none());
+    }
+
+    #[test]
+    fn covers_cursor_burst() {
+        let lines = Preset::CursorBurst.generate(5000, 1);
+        let fast = lines.iter().filter(|line| parse(line).is_some()).count();
+        assert!(fast as f64 > 0.8 * lines.len() as f64);
+    }
+}
@@ -0,0 +1,567 @@
+//! Edits read straight into the events xi-core handles them as.
+//!
+//! xi-core doesn't dispatch on the RPC enums. It converts each edit command
+//! into an `EventDomain`, sorting it by what it touches, and turning the
+//! dozens of `move_*` and `delete_*` commands into a few events carrying a
+//! `Movement`, and that's what the editor and view match on. These types
+//! are a stand-in for that layer (edit_types.rs and movement.rs in
+//! xi-editor), which the xi-core this builds against predates. As there,
+//! the line ranges and mouse actions are the RPC's own.
+//!
+//! `EventDomain::from_params` reads a command from its params into them
+//! directly, without the RPC enum in between, which is what the `internal`
+//! strategy measures: whether the RPC layer is worth keeping, or the
+//! parser should know about the editor's types. An edit's undo group and
+//! annotation are protocol 2's, and not xi-core's, and are left out.
+//!
+//! The other strategies would have to convert their edits into events after
+//! parsing, with `EventDomain::from_rpc2` and the like, which is where a
+//! representation that borrows might give back what it saved: xi-core's
+//! events own their text. The `*_events` benches measure that.
+
+use serde::de;
+use serde_json::{self, value::RawValue};
+
+use rpc2::{self, GestureType, LineRange, MouseAction};
+use rpc3;
+use rpc5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    Left,
+    Right,
+    LeftWord,
+    RightWord,
+    LeftOfLine,
+    RightOfLine,
+    Up,
+    Down,
+    UpPage,
+    DownPage,
+    StartOfParagraph,
+    EndOfParagraph,
+    StartOfDocument,
+    EndOfDocument,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EventDomain {
+    View(ViewEvent),
+    Buffer(BufferEvent),
+    Special(SpecialEvent),
+}
+
+/// Events that change the view: the selections, and what's visible.
+#[derive(Debug, PartialEq)]
+pub enum ViewEvent {
+    Move(Movement),
+    ModifySelection(Movement),
+    SelectAll,
+    AddSelectionAbove,
+    AddSelectionBelow,
+    Scroll(LineRange),
+    Click(MouseAction),
+    Drag(MouseAction),
+    Gesture { line: u64, column: u64, ty: GestureType },
+    GotoLine { line: u64 },
+    FindNext { wrap_around: bool, allow_same: bool },
+    FindPrevious { wrap_around: bool },
+}
+
+/// Events that change the text.
+#[derive(Debug, PartialEq)]
+pub enum BufferEvent {
+    /// `kill` puts what was deleted where a yank finds it.
+    Delete { movement: Movement, kill: bool },
+    Backspace,
+    Transpose,
+    Undo,
+    Redo,
+    Insert(String),
+    InsertNewline,
+    InsertTab,
+    Yank,
+    SetMarkedText { chars: String, selection: (u64, u64), replacement: Option<(u64, u64)> },
+    UnmarkText,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SpecialEvent {
+    DebugRewrap,
+    DebugPrintSpans,
+    RequestLines(LineRange),
+}
+
+/// An edit, as xi-core dispatches it.
+#[derive(Debug, PartialEq)]
+pub struct Edit<'a> {
+    pub view_id: &'a str,
+    pub event: EventDomain,
+}
+
+/// The params of an edit, down to its command's.
+#[derive(Deserialize)]
+struct EditParams<'a> {
+    view_id: &'a str,
+    method: &'a str,
+    #[serde(borrow, default)]
+    params: Option<&'a RawValue>,
+}
+
+#[derive(Deserialize)]
+struct Chars {
+    chars: String,
+}
+
+#[derive(Deserialize)]
+struct Line {
+    line: u64,
+}
+
+#[derive(Deserialize)]
+struct GestureParams {
+    line: u64,
+    column: u64,
+    ty: GestureType,
+}
+
+#[derive(Deserialize)]
+struct FindNextParams {
+    wrap_around: bool,
+    allow_same: bool,
+}
+
+#[derive(Deserialize)]
+struct FindPreviousParams {
+    wrap_around: bool,
+}
+
+#[derive(Deserialize)]
+struct MarkedText {
+    chars: String,
+    selection: (u64, u64),
+    replacement: Option<(u64, u64)>,
+}
+
+impl<'a> Edit<'a> {
+    /// Reads an edit from its params, for parsers that have read the
+    /// envelope.
+    pub fn from_params(params: &'a str) -> serde_json::Result<Edit<'a>> {
+        let p = serde_json::from_str::<EditParams>(params)?;
+        Ok(Edit { view_id: p.view_id, event: EventDomain::from_params(p.method, p.params)? })
+    }
+}
+
+impl EventDomain {
+    /// Reads the command `method` from its params.
+    pub fn from_params(method: &str, params: Option<&RawValue>) -> serde_json::Result<Self> {
+        use self::BufferEvent::*;
+        use self::EventDomain::*;
+        use self::ViewEvent::*;
+        if let Some(event) = EventDomain::without_params(method) {
+            return Ok(event);
+        }
+        let params = params
+            .ok_or_else(|| <serde_json::Error as de::Error>::missing_field("params"))?
+            .get();
+        Ok(match method {
+            "insert" => Buffer(Insert(serde_json::from_str::<Chars>(params)?.chars)),
+            "scroll" => View(Scroll(serde_json::from_str(params)?)),
+            "request_lines" => {
+                Special(SpecialEvent::RequestLines(serde_json::from_str(params)?))
+            }
+            "click" => View(Click(serde_json::from_str(params)?)),
+            "drag" => View(Drag(serde_json::from_str(params)?)),
+            "gesture" => {
+                let GestureParams { line, column, ty } = serde_json::from_str(params)?;
+                View(Gesture { line, column, ty })
+            }
+            "goto_line" => View(GotoLine { line: serde_json::from_str::<Line>(params)?.line }),
+            "find_next" => {
+                let FindNextParams { wrap_around, allow_same } = serde_json::from_str(params)?;
+                View(FindNext { wrap_around, allow_same })
+            }
+            "find_previous" => {
+                let FindPreviousParams { wrap_around } = serde_json::from_str(params)?;
+                View(FindPrevious { wrap_around })
+            }
+            "set_marked_text" => {
+                let MarkedText { chars, selection, replacement } = serde_json::from_str(params)?;
+                Buffer(SetMarkedText { chars, selection, replacement })
+            }
+            _ => return Err(de::Error::custom(format_args!("unknown edit method `{}`", method))),
+        })
+    }
+
+    /// The commands whose params, if any, are ignored.
+    fn without_params(method: &str) -> Option<Self> {
+        use self::BufferEvent::*;
+        use self::EventDomain::*;
+        use self::Movement::*;
+        use self::ViewEvent::*;
+        let delete = |movement, kill| Buffer(Delete { movement, kill });
+        Some(match method {
+            "move_up" => View(Move(Up)),
+            "move_down" => View(Move(Down)),
+            "move_left" => View(Move(Left)),
+            "move_right" => View(Move(Right)),
+            "move_word_left" => View(Move(LeftWord)),
+            "move_word_right" => View(Move(RightWord)),
+            "move_to_beginning_of_paragraph" => View(Move(StartOfParagraph)),
+            "move_to_end_of_paragraph" => View(Move(EndOfParagraph)),
+            "move_to_left_end_of_line" => View(Move(LeftOfLine)),
+            "move_to_right_end_of_line" => View(Move(RightOfLine)),
+            "move_to_beginning_of_document" => View(Move(StartOfDocument)),
+            "move_to_end_of_document" => View(Move(EndOfDocument)),
+            "scroll_page_up" => View(Move(UpPage)),
+            "scroll_page_down" => View(Move(DownPage)),
+            "move_up_and_modify_selection" => View(ModifySelection(Up)),
+            "move_down_and_modify_selection" => View(ModifySelection(Down)),
+            "move_left_and_modify_selection" => View(ModifySelection(Left)),
+            "move_right_and_modify_selection" => View(ModifySelection(Right)),
+            "move_word_left_and_modify_selection" => View(ModifySelection(LeftWord)),
+            "move_word_right_and_modify_selection" => View(ModifySelection(RightWord)),
+            "move_to_left_end_of_line_and_modify_selection" => {
+                View(ModifySelection(LeftOfLine))
+            }
+            "move_to_right_end_of_line_and_modify_selection" => {
+                View(ModifySelection(RightOfLine))
+            }
+            "move_to_beginning_of_document_and_modify_selection" => {
+                View(ModifySelection(StartOfDocument))
+            }
+            "move_to_end_of_document_and_modify_selection" => {
+                View(ModifySelection(EndOfDocument))
+            }
+            "page_up_and_modify_selection" => View(ModifySelection(UpPage)),
+            "page_down_and_modify_selection" => View(ModifySelection(DownPage)),
+            "select_all" => View(SelectAll),
+            "add_selection_above" => View(AddSelectionAbove),
+            "add_selection_below" => View(AddSelectionBelow),
+            "delete_forward" => delete(Right, false),
+            "delete_backward" => Buffer(Backspace),
+            "delete_word_forward" => delete(RightWord, false),
+            "delete_word_backward" => delete(LeftWord, false),
+            "delete_to_end_of_paragraph" => delete(EndOfParagraph, true),
+            "delete_to_beginning_of_line" => delete(LeftOfLine, false),
+            "insert_newline" => Buffer(InsertNewline),
+            "insert_tab" => Buffer(InsertTab),
+            "yank" => Buffer(Yank),
+            "transpose" => Buffer(Transpose),
+            "undo" => Buffer(Undo),
+            "redo" => Buffer(Redo),
+            "unmark_text" => Buffer(UnmarkText),
+            "debug_rewrap" => Special(SpecialEvent::DebugRewrap),
+            "debug_print_spans" => Special(SpecialEvent::DebugPrintSpans),
+            _ => return None,
+        })
+    }
+}
+
+impl EventDomain {
+    pub fn from_rpc2(cmd: rpc2::EditNotification) -> Self {
+        use rpc2::EditNotification as E;
+        use self::BufferEvent::*;
+        use self::EventDomain::*;
+        use self::Movement::*;
+        use self::ViewEvent::*;
+        let delete = |movement, kill| Buffer(Delete { movement, kill });
+        match cmd {
+            E::Insert { chars } => Buffer(Insert(chars)),
+            E::DeleteForward => delete(Right, false),
+            E::DeleteBackward => Buffer(Backspace),
+            E::DeleteWordForward => delete(RightWord, false),
+            E::DeleteWordBackward => delete(LeftWord, false),
+            E::DeleteToEndOfParagraph => delete(EndOfParagraph, true),
+            E::DeleteToBeginningOfLine => delete(LeftOfLine, false),
+            E::InsertNewline => Buffer(InsertNewline),
+            E::InsertTab => Buffer(InsertTab),
+            E::MoveUp => View(Move(Up)),
+            E::MoveUpAndModifySelection => View(ModifySelection(Up)),
+            E::MoveDown => View(Move(Down)),
+            E::MoveDownAndModifySelection => View(ModifySelection(Down)),
+            E::MoveLeft => View(Move(Left)),
+            E::MoveLeftAndModifySelection => View(ModifySelection(Left)),
+            E::MoveRight => View(Move(Right)),
+            E::MoveRightAndModifySelection => View(ModifySelection(Right)),
+            E::MoveWordLeft => View(Move(LeftWord)),
+            E::MoveWordLeftAndModifySelection => View(ModifySelection(LeftWord)),
+            E::MoveWordRight => View(Move(RightWord)),
+            E::MoveWordRightAndModifySelection => View(ModifySelection(RightWord)),
+            E::MoveToBeginningOfParagraph => View(Move(StartOfParagraph)),
+            E::MoveToEndOfParagraph => View(Move(EndOfParagraph)),
+            E::MoveToLeftEndOfLine => View(Move(LeftOfLine)),
+            E::MoveToLeftEndOfLineAndModifySelection => View(ModifySelection(LeftOfLine)),
+            E::MoveToRightEndOfLine => View(Move(RightOfLine)),
+            E::MoveToRightEndOfLineAndModifySelection => View(ModifySelection(RightOfLine)),
+            E::MoveToBeginningOfDocument => View(Move(StartOfDocument)),
+            E::MoveToBeginningOfDocumentAndModifySelection => {
+                View(ModifySelection(StartOfDocument))
+            }
+            E::MoveToEndOfDocument => View(Move(EndOfDocument)),
+            E::MoveToEndOfDocumentAndModifySelection => View(ModifySelection(EndOfDocument)),
+            E::ScrollPageUp => View(Move(UpPage)),
+            E::PageUpAndModifySelection => View(ModifySelection(UpPage)),
+            E::ScrollPageDown => View(Move(DownPage)),
+            E::PageDownAndModifySelection => View(ModifySelection(DownPage)),
+            E::SelectAll => View(SelectAll),
+            E::AddSelectionAbove => View(AddSelectionAbove),
+            E::AddSelectionBelow => View(AddSelectionBelow),
+            E::Scroll(range) => View(Scroll(range)),
+            E::GotoLine { line } => View(GotoLine { line }),
+            E::RequestLines(range) => Special(SpecialEvent::RequestLines(range)),
+            E::Yank => Buffer(Yank),
+            E::Transpose => Buffer(Transpose),
+            E::Click(action) => View(Click(action)),
+            E::Drag(action) => View(Drag(action)),
+            E::Gesture { line, column, ty } => View(Gesture { line, column, ty }),
+            E::Undo => Buffer(Undo),
+            E::Redo => Buffer(Redo),
+            E::FindNext { wrap_around, allow_same } => View(FindNext { wrap_around, allow_same }),
+            E::FindPrevious { wrap_around } => View(FindPrevious { wrap_around }),
+            E::DebugRewrap => Special(SpecialEvent::DebugRewrap),
+            E::DebugPrintSpans => Special(SpecialEvent::DebugPrintSpans),
+            E::SetMarkedText { chars, selection, replacement } => {
+                Buffer(SetMarkedText { chars, selection, replacement })
+            }
+            E::UnmarkText => Buffer(UnmarkText),
+        }
+    }
+
+    /// `None` for cut, copy and find, which xi-core handles as requests.
+    /// Marked text borrowed from the input is copied here.
+    pub fn from_rpc3(cmd: rpc3::EditNotification) -> Option<Self> {
+        use rpc3::EditNotification as E;
+        use self::BufferEvent::*;
+        use self::EventDomain::*;
+        use self::Movement::*;
+        use self::ViewEvent::*;
+        let delete = |movement, kill| Buffer(Delete { movement, kill });
+        let range = |p: rpc3::RequestLinesParams| LineRange { start: p.0 as u64, end: p.1 as u64 };
+        Some(match cmd {
+            E::Insert { params, .. } => Buffer(Insert(params.chars)),
+            E::RequestLines { params, .. } => Special(SpecialEvent::RequestLines(range(params))),
+            E::Scroll { params, .. } => View(Scroll(range(params))),
+            E::MoveWordRight { .. } => View(Move(RightWord)),
+            E::MoveWordLeft { .. } => View(Move(LeftWord)),
+            E::DeleteForward { .. } => delete(Right, false),
+            E::DeleteBackward { .. } => Buffer(Backspace),
+            E::InsertNewline { .. } => Buffer(InsertNewline),
+            E::InsertTab { .. } => Buffer(InsertTab),
+            E::Click { params: rpc3::ClickParams(line, column, flags, count), .. } => {
+                View(Click(MouseAction {
+                    line: line as u64,
+                    column: column as u64,
+                    flags: flags as u64,
+                    click_count: Some(count as u64),
+                }))
+            }
+            E::Drag { params: rpc3::DragParams(line, column, flags), .. } => {
+                View(Drag(MouseAction {
+                    line: line as u64,
+                    column: column as u64,
+                    flags: flags as u64,
+                    click_count: None,
+                }))
+            }
+            E::Gesture { params, .. } => {
+                View(Gesture { line: params.line, column: params.column, ty: params.ty })
+            }
+            E::Cut { .. } | E::Copy { .. } | E::Find { .. } => return None,
+            E::DeleteWordForward => delete(RightWord, false),
+            E::DeleteWordBackward => delete(LeftWord, false),
+            E::DeleteToEndOfParagraph => delete(EndOfParagraph, true),
+            E::DeleteToBeginningOfLine => delete(LeftOfLine, false),
+            E::MoveUp => View(Move(Up)),
+            E::MoveUpAndModifySelection => View(ModifySelection(Up)),
+            E::MoveDown => View(Move(Down)),
+            E::MoveDownAndModifySelection => View(ModifySelection(Down)),
+            E::MoveLeft => View(Move(Left)),
+            E::MoveLeftAndModifySelection => View(ModifySelection(Left)),
+            E::MoveRight => View(Move(Right)),
+            E::MoveRightAndModifySelection => View(ModifySelection(Right)),
+            E::MoveWordLeftAndModifySelection => View(ModifySelection(LeftWord)),
+            E::MoveWordRightAndModifySelection => View(ModifySelection(RightWord)),
+            E::MoveToBeginningOfParagraph => View(Move(StartOfParagraph)),
+            E::MoveToEndOfParagraph => View(Move(EndOfParagraph)),
+            E::MoveToLeftEndOfLine => View(Move(LeftOfLine)),
+            E::MoveToLeftEndOfLineAndModifySelection => View(ModifySelection(LeftOfLine)),
+            E::MoveToRightEndOfLine => View(Move(RightOfLine)),
+            E::MoveToRightEndOfLineAndModifySelection => View(ModifySelection(RightOfLine)),
+            E::MoveToBeginningOfDocument => View(Move(StartOfDocument)),
+            E::MoveToBeginningOfDocumentAndModifySelection => {
+                View(ModifySelection(StartOfDocument))
+            }
+            E::MoveToEndOfDocument => View(Move(EndOfDocument)),
+            E::MoveToEndOfDocumentAndModifySelection => View(ModifySelection(EndOfDocument)),
+            E::ScrollPageUp => View(Move(UpPage)),
+            E::PageUpAndModifySelection => View(ModifySelection(UpPage)),
+            E::ScrollPageDown => View(Move(DownPage)),
+            E::PageDownAndModifySelection => View(ModifySelection(DownPage)),
+            E::SelectAll => View(SelectAll),
+            E::AddSelectionAbove => View(AddSelectionAbove),
+            E::AddSelectionBelow => View(AddSelectionBelow),
+            E::GotoLine { params, .. } => View(GotoLine { line: params.line }),
+            E::Yank => Buffer(Yank),
+            E::Transpose => Buffer(Transpose),
+            E::Undo => Buffer(Undo),
+            E::Redo => Buffer(Redo),
+            E::FindNext { params, .. } => View(FindNext {
+                wrap_around: params.wrap_around,
+                allow_same: params.allow_same,
+            }),
+            E::FindPrevious { params, .. } => {
+                View(FindPrevious { wrap_around: params.wrap_around })
+            }
+            E::DebugRewrap => Special(SpecialEvent::DebugRewrap),
+            E::DebugPrintSpans => Special(SpecialEvent::DebugPrintSpans),
+            E::SetMarkedText { params, .. } => Buffer(SetMarkedText {
+                chars: params.chars.into_owned(),
+                selection: params.selection,
+                replacement: params.replacement,
+            }),
+            E::UnmarkText { .. } => Buffer(UnmarkText),
+        })
+    }
+
+    /// `None` for cut, copy and find, as for rpc3.
+    pub fn from_rpc5(cmd: rpc5::EditNotification) -> Option<Self> {
+        use rpc5::EditNotification as E;
+        use self::BufferEvent::*;
+        use self::EventDomain::*;
+        use self::Movement::*;
+        use self::ViewEvent::*;
+        let delete = |movement, kill| Buffer(Delete { movement, kill });
+        Some(match cmd {
+            E::Insert { chars } => Buffer(Insert(chars)),
+            E::RequestLines(start, end) => {
+                Special(SpecialEvent::RequestLines(LineRange { start, end }))
+            }
+            E::Scroll(start, end) => View(Scroll(LineRange { start, end })),
+            E::Click(line, column, flags, count) => {
+                View(Click(MouseAction { line, column, flags, click_count: Some(count) }))
+            }
+            E::Drag(line, column, flags) => {
+                View(Drag(MouseAction { line, column, flags, click_count: None }))
+            }
+            E::Gesture { line, column, ty } => View(Gesture { line, column, ty }),
+            E::GotoLine { line } => View(GotoLine { line }),
+            E::FindNext { wrap_around, allow_same } => View(FindNext { wrap_around, allow_same }),
+            E::FindPrevious { wrap_around } => View(FindPrevious { wrap_around }),
+            E::Find { .. } | E::Cut | E::Copy => return None,
+            E::MoveWordRight => View(Move(RightWord)),
+            E::MoveWordLeft => View(Move(LeftWord)),
+            E::DeleteForward => delete(Right, false),
+            E::DeleteBackward => Buffer(Backspace),
+            E::InsertNewline => Buffer(InsertNewline),
+            E::InsertTab => Buffer(InsertTab),
+            E::DeleteWordForward => delete(RightWord, false),
+            E::DeleteWordBackward => delete(LeftWord, false),
+            E::DeleteToEndOfParagraph => delete(EndOfParagraph, true),
+            E::DeleteToBeginningOfLine => delete(LeftOfLine, false),
+            E::MoveUp => View(Move(Up)),
+            E::MoveUpAndModifySelection => View(ModifySelection(Up)),
+            E::MoveDown => View(Move(Down)),
+            E::MoveDownAndModifySelection => View(ModifySelection(Down)),
+            E::MoveLeft => View(Move(Left)),
+            E::MoveLeftAndModifySelection => View(ModifySelection(Left)),
+            E::MoveRight => View(Move(Right)),
+            E::MoveRightAndModifySelection => View(ModifySelection(Right)),
+            E::MoveWordLeftAndModifySelection => View(ModifySelection(LeftWord)),
+            E::MoveWordRightAndModifySelection => View(ModifySelection(RightWord)),
+            E::MoveToBeginningOfParagraph => View(Move(StartOfParagraph)),
+            E::MoveToEndOfParagraph => View(Move(EndOfParagraph)),
+            E::MoveToLeftEndOfLine => View(Move(LeftOfLine)),
+            E::MoveToLeftEndOfLineAndModifySelection => View(ModifySelection(LeftOfLine)),
+            E::MoveToRightEndOfLine => View(Move(RightOfLine)),
+            E::MoveToRightEndOfLineAndModifySelection => View(ModifySelection(RightOfLine)),
+            E::MoveToBeginningOfDocument => View(Move(StartOfDocument)),
+            E::MoveToBeginningOfDocumentAndModifySelection => {
+                View(ModifySelection(StartOfDocument))
+            }
+            E::MoveToEndOfDocument => View(Move(EndOfDocument)),
+            E::MoveToEndOfDocumentAndModifySelection => View(ModifySelection(EndOfDocument)),
+            E::ScrollPageUp => View(Move(UpPage)),
+            E::PageUpAndModifySelection => View(ModifySelection(UpPage)),
+            E::ScrollPageDown => View(Move(DownPage)),
+            E::PageDownAndModifySelection => View(ModifySelection(DownPage)),
+            E::SelectAll => View(SelectAll),
+            E::AddSelectionAbove => View(AddSelectionAbove),
+            E::AddSelectionBelow => View(AddSelectionBelow),
+            E::Yank => Buffer(Yank),
+            E::Transpose => Buffer(Transpose),
+            E::Undo => Buffer(Undo),
+            E::Redo => Buffer(Redo),
+            E::DebugRewrap => Special(SpecialEvent::DebugRewrap),
+            E::DebugPrintSpans => Special(SpecialEvent::DebugPrintSpans),
+            E::SetMarkedText { chars, selection, replacement } => {
+                Buffer(SetMarkedText { chars, selection, replacement })
+            }
+            E::UnmarkText => Buffer(UnmarkText),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corpus::generate::Preset;
+    use peek;
+    use rpc4;
+
+    #[test]
+    fn events() {
+        let edit = |params: &str| Edit::from_params(params).map(|edit| edit.event);
+        assert_eq!(edit(r#"{"view_id":"view-id-1","method":"move_word_left","params":[]}"#)
+                       .unwrap(),
+                   EventDomain::View(ViewEvent::Move(Movement::LeftWord)));
+        assert_eq!(edit(r#"{"view_id":"view-id-1","method":"delete_to_end_of_paragraph"}"#)
+                       .unwrap(),
+                   EventDomain::Buffer(BufferEvent::Delete {
+                       movement: Movement::EndOfParagraph,
+                       kill: true,
+                   }));
+        assert_eq!(edit(r#"{"view_id":"v","method":"insert","params":{"chars":"a"}}"#).unwrap(),
+                   EventDomain::Buffer(BufferEvent::Insert("a".into())));
+        assert_eq!(edit(r#"{"view_id":"v","method":"request_lines","params":[0,10]}"#).unwrap(),
+                   EventDomain::Special(SpecialEvent::RequestLines(LineRange {
+                       start: 0,
+                       end: 10,
+                   })));
+        // cut and copy are requests
+        assert!(edit(r#"{"view_id":"v","method":"cut","params":[]}"#).is_err());
+        assert!(edit(r#"{"view_id":"v","method":"insert","params":[]}"#).is_err());
+        assert!(edit(r#"{"view_id":"v","method":"insert"}"#).is_err());
+    }
+
+    #[test]
+    fn conversions_agree() {
+        let mut lines = Preset::CursorBurst.generate(500, 1);
+        lines.extend(Preset::ScrollStorm.generate(500, 1));
+        for json in lines.iter().filter(|json| !peek::has_id(json)) {
+            let env = rpc4::Envelope::parse(json).unwrap();
+            if env.method != "edit" {
+                continue;
+            }
+            let event = Edit::from_params(env.params.get()).unwrap().event;
+            match serde_json::from_str(json).unwrap() {
+                rpc2::CoreNotification::Edit(edit) => {
+                    assert_eq!(EventDomain::from_rpc2(edit.cmd), event, "{}", json)
+                }
+                other => panic!("{:?}", other),
+            }
+            match serde_json::from_str(json).unwrap() {
+                rpc3::CoreNotification::Edit(cmd) => {
+                    assert_eq!(EventDomain::from_rpc3(cmd).as_ref(), Some(&event), "{}", json)
+                }
+                other => panic!("{:?}", other),
+            }
+            match serde_json::from_str(json).unwrap() {
+                rpc5::CoreNotification::Edit { cmd, .. } => {
+                    assert_eq!(EventDomain::from_rpc5(cmd).as_ref(), Some(&event), "{}", json)
+                }
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,2804 @@
+//! I would like to benchmark a few different ways of deserializing RPCs:
+//!
+//! 1. from a &str, with borrows, right into some type.
+//! 2. from a &str, no borrows, into a type
+//! 3. from a &str into a Value, into a type
+//!
+//! Those are all of messages a frontend sends core. The messages going the
+//! other ways, to a frontend and between core and its plugins, are benched
+//! in modules named for their direction (`s2c`, `p2c` and `c2p`; see
+//! `corpus::Direction`), so that `cargo bench s2c::` runs one group.
+
+#![feature(test)]
+
+extern crate test;
+
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+#[macro_use]
+extern crate serde_derive;
+extern crate base64;
+
+extern crate xi_der_corpus as corpus;
+
+#[cfg(all(feature = "xi-core", not(target_arch = "wasm32")))]
+extern crate xi_core_lib;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate crossbeam;
+
+#[cfg(feature = "no-panic")]
+extern crate no_panic;
+#[cfg(feature = "json5")]
+extern crate json5;
+#[cfg(feature = "serde_path_to_error")]
+extern crate serde_path_to_error;
+#[cfg(feature = "unbounded_depth")]
+extern crate serde_stacker;
+#[cfg(all(feature = "jemalloc", not(target_arch = "wasm32")))]
+extern crate tikv_jemallocator;
+#[cfg(all(feature = "mimalloc", not(target_arch = "wasm32")))]
+extern crate mimalloc;
+
+pub mod rpc2;
+pub mod rpc3;
+pub mod rpc4;
+pub mod rpc5;
+pub mod split;
+pub mod strategy;
+pub mod error;
+pub mod tape;
+pub mod dispatch;
+pub mod coverage;
+pub mod unescape;
+pub mod mutate;
+pub mod optional;
+pub mod diagnostics;
+pub mod coalesce;
+pub mod cache;
+pub mod scratch;
+pub mod fast_path;
+pub mod internal;
+pub mod reassemble;
+pub mod route;
+pub mod paths;
+pub mod sink;
+pub mod parser;
+pub mod arena;
+pub mod unified;
+pub mod peek;
+pub mod wire;
+pub mod canonical;
+pub mod binary;
+pub mod validate;
+pub mod reference_v1;
+pub mod reference_v2;
+#[cfg(feature = "unchecked")]
+pub mod unchecked;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod scoped;
+
+use test::Bencher;
+
+#[cfg(all(feature = "jemalloc", not(target_arch = "wasm32")))]
+#[global_allocator]
+static ALLOC: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc"), not(target_arch = "wasm32")))]
+#[global_allocator]
+static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+/// The global allocator, as selected by cargo feature.
+pub const ALLOCATOR: &str = if cfg!(target_arch = "wasm32") {
+    "system"
+} else if cfg!(feature = "jemalloc") {
+    "jemalloc"
+} else if cfg!(feature = "mimalloc") {
+    "mimalloc"
+} else {
+    "system"
+};
+
+use serde_json::Value;
+
+#[cfg(all(test, feature = "xi-core", not(target_arch = "wasm32")))]
+use xi_core_lib::rpc::Request;
+#[cfg(all(test, not(feature = "xi-core"), not(target_arch = "wasm32")))]
+use reference_v2::Request;
+
+#[cfg(test)]
+use std::collections::VecDeque;
+#[cfg(test)]
+use std::sync::OnceLock;
+
+#[cfg(test)]
+use sink::Sink;
+#[cfg(test)]
+use corpus::{chunked, Buffer, ANNOTATED_JSON, GESTURE_JSON, IDS_JSON, IME_JSON, TEST_JSON};
+#[cfg(all(test, feature = "json5"))]
+use corpus::RELAXED_JSON;
+#[cfg(test)]
+use strategy::{parse_rpc_request, rpc2_from_str, rpc2_from_value, Strategy};
+#[cfg(all(test, feature = "serde_path_to_error"))]
+use strategy::rpc2_from_value_tracked;
+
+
+//const TEST_JSON: &str = r#"{"method":"client_started","params":{}}
+//{"method":"set_theme","params":{"theme_name":"InspiredGitHub"}}
+//{"id":0,"method":"new_view","params":{}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"insert","params":{"chars":"\/\/ Copyright 2016 Google Inc. All rights reserved.\n\/\/\n\/\/ Licensed under the Apache License, Version 2.0 (the \"License\");\n\/\/ you may not use this file except in compliance with the License.\n\/\/ You may obtain a copy of the License at\n\/\/\n\/\/     http:\/\/www.apache.org\/licenses\/LICENSE-2.0\n\/\/\n\/\/ Unless required by applicable law or agreed to in writing, software\n\/\/ distributed under the License is distributed on an \"AS IS\" BASIS,\n\/\/ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.\n\/\/ See the License for the specific language governing permissions and\n\/\/ limitations under the License."}}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"request_lines","params":[12,13]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"move_to_right_end_of_line","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"move_to_left_end_of_line","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"scroll","params":[3,13]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"move_to_end_of_document","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"move_to_beginning_of_document","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"scroll","params":[0,10]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"move_word_right","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"move_word_left","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"move_word_right_and_modify_selection","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"delete_backward","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"delete_forward","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"insert_newline","params":[]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"scroll","params":[1,11]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"drag","params":[5,34,0]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"click","params":[3,10,0,1]}}
+//{"method":"edit","params":{"view_id":"view-id-1","method":"goto_line","params":{"line":1}}}
+//{"method":"close_view","params":{"view_id":"view-id-1"}}"#;
+
+
+/// TEST_JSON, copied once into a buffer that every bench over it shares,
+/// rather than each reading it from the binary, where the first to run
+/// would pay to fault in its pages. See `corpus::buffer`.
+#[cfg(test)]
+fn test_json() -> &'static Buffer {
+    static BUFFER: OnceLock<Buffer> = OnceLock::new();
+    BUFFER.get_or_init(|| Buffer::new(TEST_JSON.lines()))
+}
+
+#[cfg(all(feature = "xi-core", not(target_arch = "wasm32")))]
+#[bench]
+fn borrow(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            match parse_rpc_request(&val) {
+                Some((_id, method, params)) => {
+                    test::black_box(Request::from_json(method, params).unwrap());
+                }
+                None => eprintln!("invalid RPC request")
+            }
+        }
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcCall {
+    pub method: String,
+    pub params: Value,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[bench]
+fn own(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let mut val = serde_json::from_str::<Value>(json).unwrap();
+            test::black_box(val.as_object_mut().map(|obj| obj.remove("id")));
+            let rpc: RpcCall = serde_json::from_value(val).unwrap();
+            test::black_box(Request::from_json(&rpc.method, &rpc.params).unwrap());
+        }
+    })
+}
+
+/// `RpcCall` plus the id, so the envelope can be parsed straight from a string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcCallWithId {
+    pub id: Option<Value>,
+    pub method: String,
+    pub params: Value,
+}
+
+/// Like `own`, but straight from the string, with no intermediate `Value`.
+#[cfg(not(target_arch = "wasm32"))]
+#[bench]
+fn own_direct(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let rpc: RpcCallWithId = serde_json::from_str(json).unwrap();
+            test::black_box(&rpc.id);
+            test::black_box(Request::from_json(&rpc.method, &rpc.params).unwrap());
+        }
+    })
+}
+
+/// Like `own`, but the `Value` is only used to find the id; the envelope is
+/// then parsed again from the string.
+#[cfg(not(target_arch = "wasm32"))]
+#[bench]
+fn own_peek_id(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            test::black_box(val.get("id"));
+            let rpc: RpcCall = serde_json::from_str(json).unwrap();
+            test::black_box(Request::from_json(&rpc.method, &rpc.params).unwrap());
+        }
+    })
+}
+
+/// Like `own`, with xi-core's parser as it was before it borrowed.
+#[bench]
+fn reference_v1(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let mut val = serde_json::from_str::<Value>(json).unwrap();
+            test::black_box(val.as_object_mut().map(|obj| obj.remove("id")));
+            let rpc: RpcCall = serde_json::from_value(val).unwrap();
+            test::black_box(reference_v1::Request::from_json(&rpc.method, &rpc.params).unwrap());
+        }
+    })
+}
+
+/// Like `own`, with the snapshot of xi-core's current parser, so that it
+/// can be compared with `reference_v1` with or without the xi-core feature.
+#[bench]
+fn reference_v2(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let mut val = serde_json::from_str::<Value>(json).unwrap();
+            test::black_box(val.as_object_mut().map(|obj| obj.remove("id")));
+            let rpc: RpcCall = serde_json::from_value(val).unwrap();
+            test::black_box(reference_v2::Request::from_json(&rpc.method, &rpc.params).unwrap());
+        }
+    })
+}
+
+#[bench]
+fn serde(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            if let Err(e) = rpc2_from_value(val, &mut sink) {
+                panic!("{:?}\n{}", e, json);
+            }
+        }
+        test::black_box(sink);
+    })
+}
+
+/// As `serde`, finding the id with `peek::has_id` instead of in a `Value`.
+#[bench]
+fn scan_id(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            rpc2_from_str(json, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+/// As `serde`, into `unified::CoreMessage`, without branching on the id.
+#[bench]
+fn unified(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            sink.consume(&unified::from_value(val).unwrap().message);
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            match serde_json::from_str::<rpc3::CoreNotification>(json) {
+                Ok(msg) => sink.consume(&msg),
+                Err(e) => panic!("{:?}\n{}", e, json),
+            }
+        }
+        test::black_box(sink);
+    })
+}
+
+// the depth check that serde_json makes on entering every object and array:
+// turning it off alone is only safe for a corpus as shallow as this one, and
+// with serde_stacker, which checks the stack instead, safe for any
+
+#[cfg(feature = "unbounded_depth")]
+#[bench]
+fn future_serde_no_limit(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            let mut de = serde_json::Deserializer::from_str(json);
+            de.disable_recursion_limit();
+            let msg: rpc3::CoreNotification = serde::Deserialize::deserialize(&mut de).unwrap();
+            sink.consume(&msg);
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(feature = "unbounded_depth")]
+#[bench]
+fn future_serde_unbounded(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            let mut de = serde_json::Deserializer::from_str(json);
+            de.disable_recursion_limit();
+            let de = serde_stacker::Deserializer::new(&mut de);
+            let msg: rpc3::CoreNotification = serde::Deserialize::deserialize(de).unwrap();
+            sink.consume(&msg);
+        }
+        test::black_box(sink);
+    })
+}
+
+/// As `future_serde`, with one `Deserializer` for the whole corpus.
+#[bench]
+fn future_serde_stream(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        strategy::FutureSerdeStream.parse_into(buffer.as_str(), &mut sink).unwrap();
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn tape(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            let tape = tape::Tape::parse(json).unwrap();
+            let msg = tape::Message::from_tape(&tape).unwrap();
+            msg.validate().unwrap();
+            sink.consume(&msg);
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(feature = "unchecked")]
+#[bench]
+fn unchecked(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            strategy::Unchecked.parse_into(json, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn serde_gestures(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in GESTURE_JSON.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            sink.consume(&serde_json::from_value::<rpc2::CoreNotification>(val).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_gestures(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in GESTURE_JSON.lines() {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+// the success path, with and without tracking the path to each field: this
+// is what always-on better diagnostics would cost xi-core
+#[cfg(feature = "serde_path_to_error")]
+#[bench]
+fn serde_path(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            rpc2_from_value_tracked(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(feature = "serde_path_to_error")]
+#[bench]
+fn future_serde_path(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            let mut de = serde_json::Deserializer::from_str(json);
+            let msg = serde_path_to_error::deserialize::<_, rpc3::CoreNotification>(&mut de);
+            sink.consume(&msg.unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(feature = "serde_path_to_error")]
+#[bench]
+fn future_serde_path_gestures(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in GESTURE_JSON.lines() {
+            let mut de = serde_json::Deserializer::from_str(json);
+            let msg = serde_path_to_error::deserialize::<_, rpc3::CoreNotification>(&mut de);
+            sink.consume(&msg.unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(feature = "json5")]
+#[bench]
+fn json5_strict(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            let val = json5::from_str::<Value>(json).unwrap();
+            rpc2_from_value(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(feature = "json5")]
+#[bench]
+fn json5_relaxed(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in RELAXED_JSON.lines() {
+            let val = json5::from_str::<Value>(json).unwrap();
+            rpc2_from_value(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn hand(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            sink.consume(&serde_json::from_str::<rpc5::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn hand_gestures(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in GESTURE_JSON.lines() {
+            sink.consume(&serde_json::from_str::<rpc5::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn split(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            sink.consume(&split::Message::parse(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn ignored_any(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in buffer.lines() {
+            sink.consume(&serde_json::from_str::<route::Envelope>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+// relaying, as core does to plugins: read as little as it takes to decide
+// where a message goes, and pass the line on as it came; compare with
+// forward_reserialized, which writes out what it parsed
+
+/// Where a relay sends a message: edits to the view's plugins, plugin
+/// commands to the plugin host, the rest to core.
+#[cfg(test)]
+fn destination(method: &str) -> usize {
+    match method {
+        "edit" => 0,
+        "plugin" => 1,
+        _ => 2,
+    }
+}
+
+/// Appends each line of TEST_JSON to the output `destination` picks for it.
+#[cfg(test)]
+fn bench_forward<F: Fn(&str) -> usize>(b: &mut Bencher, destination: F) {
+    let buffer = test_json();
+    let mut outputs = vec![Vec::new(); 3];
+    b.iter(|| {
+        outputs.iter_mut().for_each(Vec::clear);
+        for json in buffer.lines() {
+            let out = &mut outputs[destination(json)];
+            out.extend_from_slice(json.as_bytes());
+            out.push(b'\n');
+        }
+        test::black_box(&outputs);
+    })
+}
+
+#[bench]
+fn forward_ignored_any(b: &mut Bencher) {
+    bench_forward(b, |json| {
+        destination(&serde_json::from_str::<route::Envelope>(json).unwrap().method)
+    });
+}
+
+#[bench]
+fn forward_value(b: &mut Bencher) {
+    bench_forward(b, |json| {
+        let val = serde_json::from_str::<Value>(json).unwrap();
+        destination(val["method"].as_str().unwrap())
+    });
+}
+
+#[bench]
+fn forward_tape(b: &mut Bencher) {
+    bench_forward(b, |json| {
+        let tape = tape::Tape::parse(json).unwrap();
+        destination(tape::Message::from_tape(&tape).unwrap().method.as_raw_str().unwrap())
+    });
+}
+
+#[cfg(feature = "unchecked")]
+#[bench]
+fn forward_unchecked(b: &mut Bencher) {
+    bench_forward(b, |json| destination(unchecked::envelope(json).unwrap().0));
+}
+
+#[bench]
+fn forward_reserialized(b: &mut Bencher) {
+    let buffer = test_json();
+    let mut outputs = vec![Vec::new(); 3];
+    b.iter(|| {
+        outputs.iter_mut().for_each(Vec::clear);
+        for json in buffer.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            let out = &mut outputs[destination(val["method"].as_str().unwrap())];
+            serde_json::to_writer(&mut *out, &val).unwrap();
+            out.push(b'\n');
+        }
+        test::black_box(&outputs);
+    })
+}
+
+// as xi-core would, keep each header for routing once the line is gone;
+// compare with split
+
+#[bench]
+fn split_kept_headers(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        let mut headers = Vec::new();
+        for json in buffer.lines() {
+            let msg = split::Message::parse(json).unwrap();
+            sink.consume(&msg);
+            headers.push(msg.header);
+        }
+        test::black_box((sink, headers));
+    })
+}
+
+// marked text mixes a string with tuples in one params object
+
+#[bench]
+fn serde_ime(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in IME_JSON.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            rpc2_from_value(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_ime(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in IME_JSON.lines() {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn hand_ime(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in IME_JSON.lines() {
+            sink.consume(&serde_json::from_str::<rpc5::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn split_ime(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in IME_JSON.lines() {
+            sink.consume(&split::Message::parse(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+// the basic corpus again, with protocol 2's undo groups and annotations on
+// the edits; compare with the TEST_JSON benches of the same strategy
+
+#[bench]
+fn future_serde_annotated(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in ANNOTATED_JSON.lines() {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn hand_annotated(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in ANNOTATED_JSON.lines() {
+            sink.consume(&serde_json::from_str::<rpc5::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(test)]
+fn parse_lazy_edits(corpus: &str) {
+    for json in corpus.lines() {
+        let env = rpc4::Envelope::parse(json).unwrap();
+        if let Some(edit) = env.edit() {
+            test::black_box(edit.unwrap());
+        }
+    }
+}
+
+#[bench]
+fn lazy_edits(b: &mut Bencher) {
+    let buffer = test_json();
+    b.iter(|| parse_lazy_edits(buffer.as_str()))
+}
+
+#[bench]
+fn lazy_edits_annotated(b: &mut Bencher) {
+    b.iter(|| parse_lazy_edits(ANNOTATED_JSON))
+}
+
+#[bench]
+fn serde_ids(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in IDS_JSON.lines() {
+            let mut val = serde_json::from_str::<Value>(json).unwrap();
+            let id = val.as_object_mut().and_then(|obj| obj.remove("id")).unwrap();
+            test::black_box(serde_json::from_value::<rpc2::RpcId>(id).unwrap());
+            sink.consume(&serde_json::from_value::<rpc2::CoreRequest>(val).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn scan_id_ids(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in IDS_JSON.lines() {
+            rpc2_from_str(json, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn unified_ids(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in IDS_JSON.lines() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            let envelope = unified::from_value(val).unwrap();
+            test::black_box(envelope.id.unwrap());
+            sink.consume(&envelope.message);
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_ids(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in IDS_JSON.lines() {
+            let peek = serde_json::from_str::<rpc3::IdPeek>(json).unwrap();
+            test::black_box(peek.id.unwrap());
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn tape_ids(b: &mut Bencher) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in IDS_JSON.lines() {
+            let tape = tape::Tape::parse(json).unwrap();
+            let msg = tape::Message::from_tape(&tape).unwrap();
+            msg.validate().unwrap();
+            test::black_box(msg.id.unwrap());
+            sink.consume(&msg);
+        }
+        test::black_box(sink);
+    })
+}
+
+/// What core might answer an edit request with. The text is the same for
+/// every cut and copy, and find always finds the same few matches, so that
+/// only the response's construction is timed, and not the search.
+#[cfg(test)]
+fn respond(request: &rpc2::EditRequest) -> rpc2::EditResult {
+    match *request {
+        rpc2::EditRequest::Cut | rpc2::EditRequest::Copy => {
+            rpc2::EditResult::Text(Some("fn main() {\n    println!(\"hello\");\n}\n".into()))
+        }
+        rpc2::EditRequest::Find { chars: None, .. } => rpc2::EditResult::Matches(Vec::new()),
+        rpc2::EditRequest::Find { chars: Some(ref chars), .. } => {
+            let len = chars.len() as u64;
+            rpc2::EditResult::Matches((0..8).map(|i| (i * 120, i * 120 + len)).collect())
+        }
+    }
+}
+
+/// Parses each of the requests in the ids corpus for the edit `method`,
+/// answers it, and serializes the answer, as core does for a frontend.
+#[cfg(test)]
+fn bench_request_response(b: &mut Bencher, method: &str) {
+    let needle = format!(r#""method":"{}""#, method);
+    let lines = IDS_JSON.lines().filter(|l| l.contains(&needle)).collect::<Vec<_>>();
+    assert!(!lines.is_empty());
+    let mut buf = Vec::new();
+    b.iter(|| {
+        buf.clear();
+        for json in lines.iter() {
+            let mut val = serde_json::from_str::<Value>(json).unwrap();
+            let id = val.as_object_mut().and_then(|obj| obj.remove("id")).unwrap();
+            let id = serde_json::from_value::<rpc2::RpcId>(id).unwrap();
+            let result = match serde_json::from_value::<rpc2::CoreRequest>(val).unwrap() {
+                rpc2::CoreRequest::Edit(edit) => respond(&edit.cmd),
+                other => panic!("not an edit: {:?}", other),
+            };
+            serde_json::to_writer(&mut buf, &rpc2::Response { id, result }).unwrap();
+            buf.push(b'\n');
+        }
+        test::black_box(&buf);
+    })
+}
+
+#[bench]
+fn request_response_copy(b: &mut Bencher) {
+    bench_request_response(b, "copy");
+}
+
+#[bench]
+fn request_response_cut(b: &mut Bencher) {
+    bench_request_response(b, "cut");
+}
+
+#[bench]
+fn request_response_find(b: &mut Bencher) {
+    bench_request_response(b, "find");
+}
+
+#[bench]
+fn serde_dispatch_match(b: &mut Bencher) {
+    let mut handler = dispatch::Counter::default();
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let mut val = serde_json::from_str::<Value>(json).unwrap();
+            let id = val.as_object_mut().and_then(|obj| obj.remove("id"));
+            if id.is_some() {
+                test::black_box(serde_json::from_value::<rpc2::CoreRequest>(val).unwrap());
+            } else {
+                let msg = serde_json::from_value::<rpc2::CoreNotification>(val).unwrap();
+                dispatch::match_rpc2(&msg, &mut handler);
+            }
+        }
+    });
+    test::black_box(handler);
+}
+
+#[bench]
+fn serde_dispatch_table(b: &mut Bencher) {
+    let mut handler = dispatch::Counter::default();
+    let table = dispatch::Table::rpc2();
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let mut val = serde_json::from_str::<Value>(json).unwrap();
+            let id = val.as_object_mut().and_then(|obj| obj.remove("id"));
+            if id.is_some() {
+                test::black_box(serde_json::from_value::<rpc2::CoreRequest>(val).unwrap());
+            } else {
+                let msg = serde_json::from_value::<rpc2::CoreNotification>(val).unwrap();
+                table.dispatch(&msg, &mut handler);
+            }
+        }
+    });
+    test::black_box(handler);
+}
+
+#[bench]
+fn future_serde_dispatch_match(b: &mut Bencher) {
+    let mut handler = dispatch::Counter::default();
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let msg = serde_json::from_str::<rpc3::CoreNotification>(json).unwrap();
+            dispatch::match_rpc3(&msg, &mut handler);
+        }
+    });
+    test::black_box(handler);
+}
+
+#[bench]
+fn future_serde_dispatch_table(b: &mut Bencher) {
+    let mut handler = dispatch::Counter::default();
+    let table = dispatch::Table::rpc3();
+    let buffer = test_json();
+    b.iter(|| {
+        for json in buffer.lines() {
+            let msg = serde_json::from_str::<rpc3::CoreNotification>(json).unwrap();
+            table.dispatch(&msg, &mut handler);
+        }
+    });
+    test::black_box(handler);
+}
+
+#[cfg(test)]
+fn test_batches() -> Vec<String> {
+    let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+    corpus::batch(&lines, 4)
+}
+
+/// Key auto-repeat: thousands of envelopes with nothing in them.
+#[cfg(test)]
+fn cursor_burst() -> Vec<String> {
+    corpus::generate::Preset::CursorBurst.generate(5000, 1)
+}
+
+#[bench]
+fn serde_cursor_burst(b: &mut Bencher) {
+    let lines = cursor_burst();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            rpc2_from_value(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_cursor_burst(b: &mut Bencher) {
+    let lines = cursor_burst();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+/// As `future_serde_cursor_burst`, with one `Deserializer` for the whole
+/// burst rather than one for each line.
+#[bench]
+fn future_serde_stream_cursor_burst(b: &mut Bencher) {
+    let text = cursor_burst().join("\n");
+    b.iter(|| {
+        let mut sink = Sink::new();
+        strategy::FutureSerdeStream.parse_into(&text, &mut sink).unwrap();
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn hand_cursor_burst(b: &mut Bencher) {
+    let lines = cursor_burst();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            sink.consume(&serde_json::from_str::<rpc5::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn tape_cursor_burst(b: &mut Bencher) {
+    let lines = cursor_burst();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let tape = tape::Tape::parse(json).unwrap();
+            let msg = tape::Message::from_tape(&tape).unwrap();
+            msg.validate().unwrap();
+            sink.consume(&msg);
+        }
+        test::black_box(sink);
+    })
+}
+
+/// Momentum scrolling: a scroll and a request_lines per frame.
+#[cfg(test)]
+fn scroll_storm() -> Vec<String> {
+    corpus::generate::Preset::ScrollStorm.generate(5000, 1)
+}
+
+#[bench]
+fn serde_scroll_storm(b: &mut Bencher) {
+    let lines = scroll_storm();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            rpc2_from_value(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_scroll_storm(b: &mut Bencher) {
+    let lines = scroll_storm();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn hand_scroll_storm(b: &mut Bencher) {
+    let lines = scroll_storm();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            sink.consume(&serde_json::from_str::<rpc5::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn tape_scroll_storm(b: &mut Bencher) {
+    let lines = scroll_storm();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let tape = tape::Tape::parse(json).unwrap();
+            let msg = tape::Message::from_tape(&tape).unwrap();
+            msg.validate().unwrap();
+            sink.consume(&msg);
+        }
+        test::black_box(sink);
+    })
+}
+
+// plugin RPCs with params nested 64 deep, which every strategy parses, and
+// 500 deep, past serde_json's limit: the strategies that parse the params
+// give up at the limit, and those that keep them raw skip all of them
+
+#[cfg(test)]
+fn bench_deep(b: &mut Bencher, strategy: &dyn strategy::Strategy, depth: usize) {
+    let lines = corpus::generate::deep_nesting(depth, 1000, 1);
+    b.bytes = lines.iter().map(|l| l.len() as u64).sum();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let _ = test::black_box(strategy.parse_into(json, &mut sink));
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn serde_deep_64(b: &mut Bencher) {
+    bench_deep(b, &strategy::Serde, 64);
+}
+
+#[bench]
+fn serde_deep_500(b: &mut Bencher) {
+    bench_deep(b, &strategy::Serde, 500);
+}
+
+#[bench]
+fn future_serde_deep_64(b: &mut Bencher) {
+    bench_deep(b, &strategy::FutureSerde, 64);
+}
+
+#[bench]
+fn future_serde_deep_500(b: &mut Bencher) {
+    bench_deep(b, &strategy::FutureSerde, 500);
+}
+
+#[cfg(feature = "unbounded_depth")]
+#[bench]
+fn future_serde_unbounded_deep_64(b: &mut Bencher) {
+    bench_deep(b, &strategy::FutureSerdeUnbounded, 64);
+}
+
+/// All of the params, where `future_serde_deep_500` gives up at the limit.
+#[cfg(feature = "unbounded_depth")]
+#[bench]
+fn future_serde_unbounded_deep_500(b: &mut Bencher) {
+    bench_deep(b, &strategy::FutureSerdeUnbounded, 500);
+}
+
+#[bench]
+fn hand_deep_64(b: &mut Bencher) {
+    bench_deep(b, &strategy::Hand, 64);
+}
+
+#[bench]
+fn hand_deep_500(b: &mut Bencher) {
+    bench_deep(b, &strategy::Hand, 500);
+}
+
+#[bench]
+fn tape_deep_64(b: &mut Bencher) {
+    bench_deep(b, &strategy::Tape, 64);
+}
+
+#[bench]
+fn tape_deep_500(b: &mut Bencher) {
+    bench_deep(b, &strategy::Tape, 500);
+}
+
+#[bench]
+fn ignored_any_deep_500(b: &mut Bencher) {
+    bench_deep(b, &strategy::IgnoredAny, 500);
+}
+
+// the params of edits cached by their raw text, as they'd be in a
+// long-running core: the cache is filled by the first pass, and kept for the
+// rest; see `cache` for the hit rates
+
+#[cfg(test)]
+fn bench_cached_params(b: &mut Bencher, lines: &[String]) {
+    let strategy = strategy::CachedParams::default();
+    strategy.prepare();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in lines {
+            strategy.parse_into(json, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn cached_params(b: &mut Bencher) {
+    bench_cached_params(b, &TEST_JSON.lines().map(String::from).collect::<Vec<_>>());
+}
+
+#[bench]
+fn cached_params_cursor_burst(b: &mut Bencher) {
+    bench_cached_params(b, &cursor_burst());
+}
+
+#[bench]
+fn cached_params_scroll_storm(b: &mut Bencher) {
+    bench_cached_params(b, &scroll_storm());
+}
+
+// as above, with the cache reset after every pass, as the harness resets it
+// between runs, so that every pass fills it from empty. The difference from
+// the benches above is what a cold cache costs; see `scratch`
+
+#[cfg(test)]
+fn bench_cached_params_reset(b: &mut Bencher, lines: &[String]) {
+    let strategy = strategy::CachedParams::default();
+    strategy.prepare();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in lines {
+            strategy.parse_into(json, &mut sink).unwrap();
+        }
+        strategy.reset();
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn cached_params_reset_cursor_burst(b: &mut Bencher) {
+    bench_cached_params_reset(b, &cursor_burst());
+}
+
+#[bench]
+fn cached_params_reset_scroll_storm(b: &mut Bencher) {
+    bench_cached_params_reset(b, &scroll_storm());
+}
+
+// commands without params matched as bytes, and everything else parsed as
+// `hand` would; see `fast_path`. Compare with the `hand_*` benches: the fast
+// path covers most of a cursor burst, and little of a scroll storm
+
+#[cfg(test)]
+fn bench_fast_path(b: &mut Bencher, lines: &[String]) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in lines {
+            strategy::FastPath.parse_into(json, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn fast_path(b: &mut Bencher) {
+    bench_fast_path(b, &TEST_JSON.lines().map(String::from).collect::<Vec<_>>());
+}
+
+#[bench]
+fn fast_path_cursor_burst(b: &mut Bencher) {
+    bench_fast_path(b, &cursor_burst());
+}
+
+#[bench]
+fn fast_path_scroll_storm(b: &mut Bencher) {
+    bench_fast_path(b, &scroll_storm());
+}
+
+// internal reads edits into xi-core's events rather than rpc2's enums; the
+// cursor burst is nearly all edits, and compared with scan_id it's the cost
+// of the RPC layer
+
+#[cfg(test)]
+fn bench_internal(b: &mut Bencher, lines: &[String]) {
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in lines {
+            strategy::Internal.parse_into(json, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn internal(b: &mut Bencher) {
+    bench_internal(b, &TEST_JSON.lines().map(String::from).collect::<Vec<_>>());
+}
+
+#[bench]
+fn internal_cursor_burst(b: &mut Bencher) {
+    bench_internal(b, &cursor_burst());
+}
+
+// each of the RPC types parsed and then converted into xi-core's events,
+// which are consumed as `internal` consumes them: the difference from
+// `internal_cursor_burst` is what the RPC layer costs, and from the plain
+// `*_cursor_burst` benches roughly what the conversion does. rpc3's borrowed
+// marked text has to be copied into an event.
+
+#[bench]
+fn serde_events_cursor_burst(b: &mut Bencher) {
+    let lines = cursor_burst();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            // requests have no events
+            if val.get("id").is_some() {
+                rpc2_from_value(val, &mut sink).unwrap();
+                continue;
+            }
+            match serde_json::from_value::<rpc2::CoreNotification>(val).unwrap() {
+                rpc2::CoreNotification::Edit(edit) => sink.consume(&internal::Edit {
+                    view_id: edit.view_id.as_str(),
+                    event: internal::EventDomain::from_rpc2(edit.cmd),
+                }),
+                other => sink.consume(&other),
+            }
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_events_cursor_burst(b: &mut Bencher) {
+    let lines = cursor_burst();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            match serde_json::from_str::<rpc3::CoreNotification>(json).unwrap() {
+                rpc3::CoreNotification::Edit(cmd) => {
+                    // rpc3 drops most edits' view ids, and the sink doesn't hash them
+                    let event = internal::EventDomain::from_rpc3(cmd).unwrap();
+                    sink.consume(&internal::Edit { view_id: "", event });
+                }
+                other => sink.consume(&other),
+            }
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn hand_events_cursor_burst(b: &mut Bencher) {
+    let lines = cursor_burst();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            match serde_json::from_str::<rpc5::CoreNotification>(json).unwrap() {
+                rpc5::CoreNotification::Edit { view_id, cmd, .. } => {
+                    let event = internal::EventDomain::from_rpc5(cmd).unwrap();
+                    sink.consume(&internal::Edit { view_id, event });
+                }
+                other => sink.consume(&other),
+            }
+        }
+        test::black_box(sink);
+    })
+}
+
+// with RPC logging on, xi writes every message it's sent to its log. That's
+// either the line as it came in, which any strategy can do, or what was
+// parsed written back out, which needs types that serialize. The log here
+// is a buffer, so the numbers are a floor: a file adds its writes on top.
+// Compare with the plain *_cursor_burst benches
+
+#[cfg(test)]
+fn bench_logged<F>(b: &mut Bencher, mut parse_and_log: F)
+    where F: FnMut(&str, &mut Sink, &mut Vec<u8>)
+{
+    let lines = cursor_burst();
+    let mut log = Vec::new();
+    b.iter(|| {
+        log.clear();
+        let mut sink = Sink::new();
+        for json in &lines {
+            parse_and_log(json, &mut sink, &mut log);
+        }
+        test::black_box((sink, &log));
+    })
+}
+
+#[cfg(test)]
+fn bench_log_raw<S: Strategy>(b: &mut Bencher, strategy: S) {
+    bench_logged(b, |json, sink, log| {
+        strategy.parse_into(json, sink).unwrap();
+        log.extend_from_slice(json.as_bytes());
+        log.push(b'\n');
+    })
+}
+
+#[bench]
+fn log_raw_serde(b: &mut Bencher) {
+    bench_log_raw(b, strategy::Serde);
+}
+
+#[bench]
+fn log_raw_future_serde(b: &mut Bencher) {
+    bench_log_raw(b, strategy::FutureSerde);
+}
+
+#[bench]
+fn log_raw_hand(b: &mut Bencher) {
+    bench_log_raw(b, strategy::Hand);
+}
+
+#[bench]
+fn log_raw_fast_path(b: &mut Bencher) {
+    bench_log_raw(b, strategy::FastPath);
+}
+
+/// The `Value` serde parsed through, as xi-rpc would log it.
+#[bench]
+fn log_value_serde(b: &mut Bencher) {
+    bench_logged(b, |json, sink, log| {
+        let val = serde_json::from_str::<Value>(json).unwrap();
+        serde_json::to_writer(&mut *log, &val).unwrap();
+        log.push(b'\n');
+        rpc2_from_value(val, sink).unwrap();
+    })
+}
+
+/// A parsed message with the id it came with, so that a reserialized log
+/// holds the same messages as a raw one.
+#[cfg(test)]
+#[derive(Serialize)]
+struct Logged<I, T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<I>,
+    #[serde(flatten)]
+    msg: T,
+}
+
+#[bench]
+fn log_reserialized_serde(b: &mut Bencher) {
+    fn reserialize<T>(id: Option<Value>, val: Value, sink: &mut Sink, log: &mut Vec<u8>)
+        where T: serde::de::DeserializeOwned + serde::Serialize + sink::Consume
+    {
+        let msg = serde_json::from_value::<T>(val).unwrap();
+        serde_json::to_writer(&mut *log, &Logged { id, msg: &msg }).unwrap();
+        log.push(b'\n');
+        sink.consume(&msg);
+    }
+
+    bench_logged(b, |json, sink, log| {
+        let mut val = serde_json::from_str::<Value>(json).unwrap();
+        // split on the id as `rpc2_from_value` does
+        match val.as_object_mut().and_then(|obj| obj.remove("id")) {
+            id @ Some(_) => reserialize::<rpc2::CoreRequest>(id, val, sink, log),
+            None => reserialize::<rpc2::CoreNotification>(None, val, sink, log),
+        }
+    })
+}
+
+#[bench]
+fn log_reserialized_future_serde(b: &mut Bencher) {
+    bench_logged(b, |json, sink, log| {
+        let id = serde_json::from_str::<rpc3::IdPeek>(json).unwrap().id;
+        let msg = serde_json::from_str::<rpc3::CoreNotification>(json).unwrap();
+        serde_json::to_writer(&mut *log, &Logged { id, msg: &msg }).unwrap();
+        log.push(b'\n');
+        sink.consume(&msg);
+    })
+}
+
+/// Opening, saving and closing files: messages that are mostly path. rpc2
+/// copies each path into a `String`, where rpc3 and rpc5 borrow it.
+#[cfg(test)]
+fn file_session() -> Vec<String> {
+    corpus::generate::Preset::FileSession.generate(5000, 1)
+}
+
+#[bench]
+fn serde_file_session(b: &mut Bencher) {
+    let lines = file_session();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            rpc2_from_value(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_file_session(b: &mut Bencher) {
+    let lines = file_session();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn hand_file_session(b: &mut Bencher) {
+    let lines = file_session();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            sink.consume(&serde_json::from_str::<rpc5::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn split_file_session(b: &mut Bencher) {
+    let lines = file_session();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            sink.consume(&split::Message::parse(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn tape_file_session(b: &mut Bencher) {
+    let lines = file_session();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let tape = tape::Tape::parse(json).unwrap();
+            let msg = tape::Message::from_tape(&tape).unwrap();
+            msg.validate().unwrap();
+            sink.consume(&msg);
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(test)]
+fn bench_paths<'a, P>(b: &mut Bencher, lines: &'a [String])
+    where P: AsRef<std::path::Path> + serde::Deserialize<'a>
+{
+    b.iter(|| {
+        for json in lines {
+            let msg = serde_json::from_str::<paths::FileMessage<P>>(json).unwrap();
+            test::black_box(msg.file_path());
+        }
+    })
+}
+
+#[bench]
+fn paths_string(b: &mut Bencher) {
+    bench_paths::<String>(b, &file_session());
+}
+
+#[bench]
+fn paths_pathbuf(b: &mut Bencher) {
+    bench_paths::<std::path::PathBuf>(b, &file_session());
+}
+
+#[bench]
+fn paths_borrowed(b: &mut Bencher) {
+    bench_paths::<&std::path::Path>(b, &file_session());
+}
+
+/// A line of code typed into one view, an insert per keystroke, with the
+/// frontend asking for the new line at the end of each line.
+#[cfg(test)]
+fn typing() -> Vec<String> {
+    let text = "fn main() {\n    println!(\"hello, world\");\n}\n";
+    let mut lines = Vec::new();
+    for (i, line) in text.split_terminator('\n').enumerate() {
+        for c in line.chars().chain(Some('\n')) {
+            lines.push(json!({"method": "edit", "params": {"view_id": "view-id-1",
+                "method": "insert", "params": {"chars": c.to_string()}}}).to_string());
+        }
+        lines.push(json!({"method": "edit", "params": {"view_id": "view-id-1",
+            "method": "request_lines", "params": [i, i + 2]}}).to_string());
+    }
+    lines
+}
+
+#[bench]
+fn serde_inserts(b: &mut Bencher) {
+    let lines = typing();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            rpc2_from_value(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn serde_inserts_coalesced(b: &mut Bencher) {
+    let lines = typing();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in coalesce::coalesce(&lines) {
+            let val = serde_json::from_str::<Value>(&json).unwrap();
+            rpc2_from_value(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_inserts(b: &mut Bencher) {
+    let lines = typing();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_inserts_coalesced(b: &mut Bencher) {
+    let lines = typing();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in coalesce::coalesce(&lines) {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(&json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+/// TEST_JSON with a few big pastes, of a quarter of a megabyte each, into
+/// the view it opens.
+#[cfg(test)]
+fn paste_session() -> Vec<String> {
+    let text = TEST_JSON.repeat(256 * 1024 / TEST_JSON.len());
+    let paste = json!({"method": "edit", "params": {"view_id": "view-id-1",
+        "method": "insert", "params": {"chars": text}}}).to_string();
+    let mut lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+    for i in 0..4 {
+        lines.insert(3 + i * 4, paste.clone());
+    }
+    lines
+}
+
+/// Parses the longest of `lines`, as the worst a single message costs.
+#[cfg(test)]
+fn bench_longest(b: &mut Bencher, lines: &[String]) {
+    let json = lines.iter().max_by_key(|line| line.len()).unwrap();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        strategy::Serde.parse_into(json, &mut sink).unwrap();
+        test::black_box(sink);
+    })
+}
+
+/// Parses the session with its pastes in chunks of `size` bytes, and puts
+/// them back together.
+#[cfg(test)]
+fn bench_paste_chunked(b: &mut Bencher, size: usize) {
+    let json = corpus::paste::chunk(&paste_session(), size).join("\n");
+    b.iter(|| {
+        let mut sink = Sink::new();
+        strategy::Reassemble.parse_into(&json, &mut sink).unwrap();
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn paste_whole(b: &mut Bencher) {
+    let lines = paste_session();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            rpc2_from_value(val, &mut sink).unwrap();
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn paste_chunked_4k(b: &mut Bencher) {
+    bench_paste_chunked(b, 4 * 1024);
+}
+
+#[bench]
+fn paste_chunked_64k(b: &mut Bencher) {
+    bench_paste_chunked(b, 64 * 1024);
+}
+
+#[bench]
+fn paste_worst_whole(b: &mut Bencher) {
+    bench_longest(b, &paste_session());
+}
+
+#[bench]
+fn paste_worst_chunk_4k(b: &mut Bencher) {
+    bench_longest(b, &corpus::paste::chunk(&paste_session(), 4 * 1024));
+}
+
+#[bench]
+fn serde_batch(b: &mut Bencher) {
+    let batches = test_batches();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &batches {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            let res = strategy::for_each_message(val, |val| {
+                rpc2_from_value(val, &mut sink).map_err(|e| error::ParseError::from_json(json, e))
+            });
+            assert!(res.is_ok());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn future_serde_batch(b: &mut Bencher) {
+    let batches = test_batches();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &batches {
+            sink.consume(&serde_json::from_str::<Vec<rpc3::CoreNotification>>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[bench]
+fn scoped_batch(b: &mut Bencher) {
+    let batches = test_batches();
+    b.iter(|| {
+        for json in &batches {
+            let msgs = serde_json::from_str::<Vec<rpc3::CoreNotification>>(json).unwrap();
+            test::black_box(scoped::dispatch_all(msgs));
+        }
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[bench]
+fn scoped_pipeline(b: &mut Bencher) {
+    let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+    b.iter(|| {
+        let (handler, errors) = scoped::dispatch_lines(&lines);
+        assert_eq!(errors, 0);
+        test::black_box(handler);
+    })
+}
+
+/// Parse, enqueue and dispatch, with the messages themselves on the queue.
+#[bench]
+fn enum_queue(b: &mut Bencher) {
+    let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+    let mut queue = VecDeque::new();
+    b.iter(|| {
+        for line in &lines {
+            queue.push_back(serde_json::from_str::<rpc3::CoreNotification>(line).unwrap());
+        }
+        let mut handler = dispatch::Counter::default();
+        while let Some(msg) = queue.pop_front() {
+            dispatch::match_rpc3(&msg, &mut handler);
+        }
+        test::black_box(handler);
+    })
+}
+
+/// As `enum_queue`, with the messages in an arena and their indices on the
+/// queue.
+#[bench]
+fn arena_queue(b: &mut Bencher) {
+    let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+    let mut arena = arena::Arena::new();
+    let mut queue = VecDeque::new();
+    b.iter(|| {
+        assert_eq!(arena::parse_lines(&lines, &mut arena, &mut queue), 0);
+        let mut handler = dispatch::Counter::default();
+        arena::dispatch_queue(&mut arena, &mut queue, &mut handler);
+        test::black_box(handler);
+    })
+}
+
+/// As `scoped_pipeline`, sending the handler thread indices into an arena.
+#[cfg(not(target_arch = "wasm32"))]
+#[bench]
+fn arena_scoped(b: &mut Bencher) {
+    let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+    let mut arena = arena::Arena::new();
+    let mut queue = VecDeque::new();
+    b.iter(|| {
+        assert_eq!(arena::parse_lines(&lines, &mut arena, &mut queue), 0);
+        test::black_box(arena::dispatch_scoped(&arena, &mut queue));
+        arena.clear();
+    })
+}
+
+#[bench]
+fn tape_batch(b: &mut Bencher) {
+    let batches = test_batches();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &batches {
+            let tape = tape::Tape::parse(json).unwrap();
+            for node in tape.root().children() {
+                let msg = tape::Message::from_node(node).unwrap();
+                msg.validate().unwrap();
+                sink.consume(&msg);
+            }
+        }
+        test::black_box(sink);
+    })
+}
+
+/// A large paste: the still-escaped chars of TEST_JSON's insert, repeated,
+/// as a JSON string literal.
+#[cfg(test)]
+fn make_paste() -> String {
+    let line = TEST_JSON.lines().find(|l| l.contains(r#""method":"insert""#)).unwrap();
+    let start = line.find(r#""chars":""#).unwrap() + r#""chars":""#.len();
+    let end = line.rfind(r#""}}}"#).unwrap();
+    format!("\"{}\"", line[start..end].repeat(100))
+}
+
+#[bench]
+fn unescape_serde(b: &mut Bencher) {
+    let paste = make_paste();
+    b.bytes = paste.len() as u64;
+    b.iter(|| serde_json::from_str::<String>(&paste).unwrap())
+}
+
+#[bench]
+fn unescape_tape(b: &mut Bencher) {
+    let paste = make_paste();
+    b.bytes = paste.len() as u64;
+    b.iter(|| tape::unescape(&paste[1..paste.len() - 1]).unwrap())
+}
+
+#[bench]
+fn unescape_simd(b: &mut Bencher) {
+    let paste = make_paste();
+    b.bytes = paste.len() as u64;
+    b.iter(|| unescape::unescape(&paste[1..paste.len() - 1]).unwrap())
+}
+
+/// Leaving the string escaped: only finding where it ends.
+#[bench]
+fn unescape_deferred(b: &mut Bencher) {
+    let paste = make_paste();
+    b.bytes = paste.len() as u64;
+    b.iter(|| assert_eq!(unescape::string_end(paste.as_bytes()), Some(paste.len() - 1)))
+}
+
+/// Inserts of a large paste, many times over.
+#[cfg(test)]
+fn make_paste_inserts() -> Vec<String> {
+    let paste = make_paste();
+    let prefix = r#"{"method":"edit","params":{"view_id":"view-id-1","method":"insert","params":{"chars":"#;
+    (0..20).map(|_| format!("{}{}}}}}}}", prefix, paste)).collect()
+}
+
+#[bench]
+fn insert_eager(b: &mut Bencher) {
+    let inserts = make_paste_inserts();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &inserts {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+#[cfg(test)]
+fn parse_lazy_insert(json: &str) -> rpc4::LazyStr {
+    let env = rpc4::Envelope::parse(json).unwrap();
+    let edit = env.edit().unwrap().unwrap();
+    edit.insert().unwrap().unwrap().chars
+}
+
+#[bench]
+fn insert_lazy(b: &mut Bencher) {
+    let inserts = make_paste_inserts();
+    b.iter(|| {
+        for json in &inserts {
+            test::black_box(parse_lazy_insert(json).raw().len());
+        }
+    })
+}
+
+#[bench]
+fn insert_lazy_access(b: &mut Bencher) {
+    let inserts = make_paste_inserts();
+    b.iter(|| {
+        for json in &inserts {
+            test::black_box(parse_lazy_insert(json).get().unwrap());
+        }
+    })
+}
+
+/// An update with `n` selected regions, as after a select-all-occurrences.
+#[cfg(test)]
+fn make_selection_update(n: u64) -> rpc2::ClientNotification {
+    let regions = (0..n).map(|i| rpc2::SelRegion {
+        start: i * 80 + 12,
+        end: i * 80 + 20,
+        horiz: None,
+        affinity: rpc2::Affinity::Downstream,
+    }).collect();
+    rpc2::ClientNotification::Update {
+        view_id: "view-id-1".into(),
+        update: rpc2::Update { selection: rpc2::Selection(regions), pristine: false },
+    }
+}
+
+/// A highlighting update with `n` spans, roughly one per token.
+#[cfg(test)]
+fn make_update_spans(n: u64) -> rpc2::PluginCoreNotification {
+    let spans = (0..n).map(|i| rpc2::StyleSpan(i * 6, 1 + i % 5, 2 + i % 11)).collect();
+    rpc2::PluginCoreNotification::UpdateSpans {
+        view_id: "view-id-1".into(),
+        start: 0,
+        len: n * 6,
+        spans,
+        rev: 1,
+    }
+}
+
+/// A theme's worth of style definitions.
+#[cfg(test)]
+fn make_def_styles(n: u64) -> Vec<rpc2::ClientNotification> {
+    (0..n).map(|i| rpc2::ClientNotification::DefStyle(rpc2::StyleDef {
+        id: i + 2,
+        fg_color: Some(0xff00_0000 | (i as u32 * 0x0001_0101)),
+        bg_color: if i % 4 == 0 { Some(0xffff_ffff) } else { None },
+        weight: if i % 3 == 0 { Some(700) } else { None },
+        italic: if i % 5 == 0 { Some(true) } else { None },
+        underline: None,
+    })).collect()
+}
+
+/// Parses the built-in corpora of messages sent in `strategy`'s direction,
+/// so that the directions can be compared with each other.
+#[cfg(test)]
+fn bench_direction<S: strategy::Strategy>(b: &mut Bencher, strategy: S) {
+    let corpora = strategy.direction().corpora();
+    let buffer = Buffer::new(corpora.iter().flat_map(|c| c.lines()));
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for line in buffer.lines() {
+            strategy.parse_into(line, &mut sink).unwrap();
+        }
+        test::black_box(sink)
+    })
+}
+
+/// What a frontend sends core. The benches outside these modules are all of
+/// these too; this is the one to compare with the other directions'.
+#[cfg(test)]
+mod c2s {
+    use super::*;
+
+    #[bench]
+    fn corpus(b: &mut Bencher) {
+        bench_direction(b, strategy::Serde);
+    }
+}
+
+/// What core sends a frontend.
+#[cfg(test)]
+mod s2c {
+    use super::*;
+
+    #[bench]
+    fn corpus(b: &mut Bencher) {
+        bench_direction(b, strategy::SerdeToClient);
+    }
+
+    #[bench]
+    fn serialize_selection_100(b: &mut Bencher) {
+        let update = make_selection_update(100);
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            serde_json::to_writer(&mut buf, &update).unwrap();
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn serialize_selection_1000(b: &mut Bencher) {
+        let update = make_selection_update(1000);
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            serde_json::to_writer(&mut buf, &update).unwrap();
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn serialize_def_style(b: &mut Bencher) {
+        let msgs = make_def_styles(64);
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            for msg in msgs.iter() {
+                serde_json::to_writer(&mut buf, msg).unwrap();
+                buf.push(b'\n');
+            }
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn deserialize_def_style(b: &mut Bencher) {
+        let mut json = Vec::new();
+        corpus::write_lines(&make_def_styles(64), &mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        b.iter(|| {
+            for line in json.lines() {
+                test::black_box(serde_json::from_str::<rpc2::ClientNotification>(line).unwrap());
+            }
+        })
+    }
+}
+
+/// What a plugin sends core.
+#[cfg(test)]
+mod p2c {
+    use super::*;
+
+    #[bench]
+    fn corpus(b: &mut Bencher) {
+        bench_direction(b, strategy::SerdeFromPlugin);
+    }
+
+    #[bench]
+    fn serialize_update_spans(b: &mut Bencher) {
+        let msg = make_update_spans(5000);
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            serde_json::to_writer(&mut buf, &msg).unwrap();
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn deserialize_update_spans(b: &mut Bencher) {
+        let json = serde_json::to_string(&make_update_spans(5000)).unwrap();
+        b.iter(|| {
+            serde_json::from_str::<rpc2::PluginCoreNotification>(&json).unwrap()
+        })
+    }
+}
+
+/// What core sends a plugin.
+#[cfg(test)]
+mod c2p {
+    use super::*;
+
+    #[bench]
+    fn corpus(b: &mut Bencher) {
+        bench_direction(b, strategy::SerdeToPlugin);
+    }
+
+    #[bench]
+    fn corpus_future_serde(b: &mut Bencher) {
+        bench_direction(b, strategy::FutureSerdeToPlugin);
+    }
+
+    fn session() -> &'static [String] {
+        static SESSION: OnceLock<Vec<String>> = OnceLock::new();
+        SESSION.get_or_init(|| corpus::generate::plugin_session(5000, 1))
+    }
+
+    /// Requests are `T`s, and notifications `U`s.
+    fn deserialize_session<T, U>(b: &mut Bencher)
+        where T: serde::Deserialize<'static>, U: serde::Deserialize<'static>
+    {
+        let session = session();
+        b.iter(|| {
+            for line in session {
+                if peek::has_id(line) {
+                    test::black_box(serde_json::from_str::<T>(line).unwrap());
+                } else {
+                    test::black_box(serde_json::from_str::<U>(line).unwrap());
+                }
+            }
+        })
+    }
+
+    #[bench]
+    fn deserialize_session_owned(b: &mut Bencher) {
+        deserialize_session::<rpc2::HostRequest, rpc2::HostNotification>(b);
+    }
+
+    #[bench]
+    fn deserialize_session_borrowed(b: &mut Bencher) {
+        deserialize_session::<rpc3::HostRequest, rpc3::HostNotification>(b);
+    }
+
+    #[bench]
+    fn serialize_session(b: &mut Bencher) {
+        let requests = session().iter().filter(|line| peek::has_id(line))
+            .map(|line| serde_json::from_str::<rpc2::HostRequest>(line).unwrap())
+            .collect::<Vec<_>>();
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            for msg in requests.iter() {
+                serde_json::to_writer(&mut buf, msg).unwrap();
+                buf.push(b'\n');
+            }
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn serialize_initialize(b: &mut Bencher) {
+        let msg = serde_json::from_str::<rpc2::HostNotification>(&session()[0]).unwrap();
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            serde_json::to_writer(&mut buf, &msg).unwrap();
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn serialize_ping(b: &mut Bencher) {
+        let msg = rpc2::HostNotification::Ping(rpc2::EmptyStruct {});
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            serde_json::to_writer(&mut buf, &msg).unwrap();
+            test::black_box(&buf);
+        })
+    }
+
+    /// Opening a file sends all of it to each plugin, in one insert.
+    #[bench]
+    fn serialize_initial_update(b: &mut Bencher) {
+        let text = TEST_JSON.repeat(64);
+        let new_len = text.len() as u64;
+        let update = rpc2::HostRequest::Update(rpc2::PluginUpdate {
+            view_id: "view-id-1".into(),
+            delta: Some(rpc2::RopeDelta {
+                els: vec![rpc2::DeltaElement::Insert(text)],
+                base_len: 0,
+            }),
+            new_len,
+            rev: 1,
+            edit_type: "insert".into(),
+            author: "core".into(),
+        });
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            serde_json::to_writer(&mut buf, &update).unwrap();
+            test::black_box(&buf);
+        })
+    }
+}
+
+/// The edits in the annotated corpus, which has undo groups and annotations
+/// on some of them, many times over.
+#[cfg(test)]
+fn make_edits() -> Vec<rpc2::EditCommand<rpc2::EditNotification>> {
+    corpus::ANNOTATED_JSON.lines().cycle().take(1000)
+        .filter_map(|l| match serde_json::from_str::<rpc2::CoreNotification>(l) {
+            Ok(rpc2::CoreNotification::Edit(edit)) => Some(edit),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `EditCommand`'s `Serialize` impl, which goes through a `Value`.
+#[bench]
+fn edit_serialize_value(b: &mut Bencher) {
+    let edits = make_edits();
+    let mut buf = Vec::new();
+    b.iter(|| {
+        buf.clear();
+        for edit in edits.iter() {
+            serde_json::to_writer(&mut buf, edit).unwrap();
+        }
+        test::black_box(&buf);
+    })
+}
+
+#[bench]
+fn edit_serialize_flattened(b: &mut Bencher) {
+    let edits = make_edits();
+    let mut buf = Vec::new();
+    b.iter(|| {
+        buf.clear();
+        for edit in edits.iter() {
+            serde_json::to_writer(&mut buf, &edit.flattened()).unwrap();
+        }
+        test::black_box(&buf);
+    })
+}
+
+/// The notifications of the test and annotated corpora, or of
+/// `paste_session`, whose pastes are all quotes and newlines to be escaped
+/// again.
+#[cfg(test)]
+fn round_trip_lines(pastes: bool) -> Vec<String> {
+    let lines = if pastes {
+        paste_session()
+    } else {
+        TEST_JSON.lines().chain(ANNOTATED_JSON.lines()).map(String::from).collect()
+    };
+    lines.into_iter().filter(|line| !peek::has_id(line)).collect()
+}
+
+/// Writing out the borrowed rpc3 types, parsed ahead of time.
+#[bench]
+fn future_serialize(b: &mut Bencher) {
+    let lines = round_trip_lines(false);
+    let messages = lines.iter()
+        .map(|line| serde_json::from_str::<rpc3::CoreNotification>(line).unwrap())
+        .collect::<Vec<_>>();
+    let mut buf = Vec::new();
+    b.iter(|| {
+        buf.clear();
+        for msg in &messages {
+            serde_json::to_writer(&mut buf, msg).unwrap();
+        }
+        test::black_box(&buf);
+    })
+}
+
+#[bench]
+fn serde_serialize(b: &mut Bencher) {
+    let messages = round_trip_lines(false).iter()
+        .map(|line| serde_json::from_str::<rpc2::CoreNotification>(line).unwrap())
+        .collect::<Vec<_>>();
+    let mut buf = Vec::new();
+    b.iter(|| {
+        buf.clear();
+        for msg in &messages {
+            serde_json::to_writer(&mut buf, msg).unwrap();
+        }
+        test::black_box(&buf);
+    })
+}
+
+/// Parsing each line and writing it back out, as a proxy forwarding what it
+/// understood would.
+#[cfg(test)]
+fn bench_future_round_trip(b: &mut Bencher, lines: &[String]) {
+    let mut buf = Vec::new();
+    b.iter(|| {
+        buf.clear();
+        for json in lines {
+            let msg = serde_json::from_str::<rpc3::CoreNotification>(json).unwrap();
+            serde_json::to_writer(&mut buf, &msg).unwrap();
+        }
+        test::black_box(&buf);
+    })
+}
+
+#[cfg(test)]
+fn bench_serde_round_trip(b: &mut Bencher, lines: &[String]) {
+    let mut buf = Vec::new();
+    b.iter(|| {
+        buf.clear();
+        for json in lines {
+            let msg = serde_json::from_str::<rpc2::CoreNotification>(json).unwrap();
+            serde_json::to_writer(&mut buf, &msg).unwrap();
+        }
+        test::black_box(&buf);
+    })
+}
+
+#[bench]
+fn future_round_trip(b: &mut Bencher) {
+    bench_future_round_trip(b, &round_trip_lines(false));
+}
+
+#[bench]
+fn serde_round_trip(b: &mut Bencher) {
+    bench_serde_round_trip(b, &round_trip_lines(false));
+}
+
+#[bench]
+fn future_round_trip_pastes(b: &mut Bencher) {
+    bench_future_round_trip(b, &round_trip_lines(true));
+}
+
+#[bench]
+fn serde_round_trip_pastes(b: &mut Bencher) {
+    bench_serde_round_trip(b, &round_trip_lines(true));
+}
+
+/// The style definitions from `make_def_styles`, as `T`. About half of the
+/// optional fields are unset.
+#[cfg(test)]
+fn make_optional_defs<T>() -> Vec<T>
+    where T: for<'a> From<&'a rpc2::StyleDef>
+{
+    make_def_styles(64).iter()
+        .filter_map(|msg| match *msg {
+            rpc2::ClientNotification::DefStyle(ref def) => Some(T::from(def)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn bench_optional_serialize<T>(b: &mut Bencher)
+    where T: for<'a> From<&'a rpc2::StyleDef> + serde::Serialize
+{
+    let defs = make_optional_defs::<T>();
+    let mut buf = Vec::new();
+    b.iter(|| {
+        buf.clear();
+        for def in defs.iter() {
+            serde_json::to_writer(&mut buf, def).unwrap();
+            buf.push(b'\n');
+        }
+        test::black_box(&buf);
+    })
+}
+
+#[cfg(test)]
+fn bench_optional_from_str<T>(b: &mut Bencher)
+    where T: for<'a> From<&'a rpc2::StyleDef> + serde::Serialize + serde::de::DeserializeOwned
+{
+    let lines = make_optional_defs::<T>().iter()
+        .map(|def| serde_json::to_string(def).unwrap())
+        .collect::<Vec<_>>();
+    b.iter(|| {
+        for json in lines.iter() {
+            test::black_box(serde_json::from_str::<T>(json).unwrap());
+        }
+    })
+}
+
+/// As in the serde strategy: a `Value` first, then the type.
+#[cfg(test)]
+fn bench_optional_from_value<T>(b: &mut Bencher)
+    where T: for<'a> From<&'a rpc2::StyleDef> + serde::Serialize + serde::de::DeserializeOwned
+{
+    let lines = make_optional_defs::<T>().iter()
+        .map(|def| serde_json::to_string(def).unwrap())
+        .collect::<Vec<_>>();
+    b.iter(|| {
+        for json in lines.iter() {
+            let val = serde_json::from_str::<Value>(json).unwrap();
+            test::black_box(serde_json::from_value::<T>(val).unwrap());
+        }
+    })
+}
+
+#[bench]
+fn optional_null_serialize(b: &mut Bencher) {
+    bench_optional_serialize::<rpc2::StyleDef>(b);
+}
+
+#[bench]
+fn optional_sparse_serialize(b: &mut Bencher) {
+    bench_optional_serialize::<optional::SparseStyleDef>(b);
+}
+
+#[bench]
+fn optional_dense_serialize(b: &mut Bencher) {
+    bench_optional_serialize::<optional::DenseStyleDef>(b);
+}
+
+#[bench]
+fn optional_null_from_str(b: &mut Bencher) {
+    bench_optional_from_str::<rpc2::StyleDef>(b);
+}
+
+#[bench]
+fn optional_sparse_from_str(b: &mut Bencher) {
+    bench_optional_from_str::<optional::SparseStyleDef>(b);
+}
+
+#[bench]
+fn optional_dense_from_str(b: &mut Bencher) {
+    bench_optional_from_str::<optional::DenseStyleDef>(b);
+}
+
+#[bench]
+fn optional_null_from_value(b: &mut Bencher) {
+    bench_optional_from_value::<rpc2::StyleDef>(b);
+}
+
+#[bench]
+fn optional_sparse_from_value(b: &mut Bencher) {
+    bench_optional_from_value::<optional::SparseStyleDef>(b);
+}
+
+#[bench]
+fn optional_dense_from_value(b: &mut Bencher) {
+    bench_optional_from_value::<optional::DenseStyleDef>(b);
+}
+
+/// Feeds TEST_JSON through `reassembler` in small random chunks, parsing each
+/// reassembled line as in `future_serde`.
+#[cfg(test)]
+fn bench_chunked<R: chunked::Reassembler>(b: &mut Bencher, mut reassembler: R) {
+    let buffer = test_json();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        let source = chunked::ChunkedReader::new(buffer.as_str().as_bytes(), 42, 64);
+        reassembler.read_lines(source, |json| {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }).unwrap();
+        test::black_box(sink);
+    })
+}
+
+#[bench]
+fn chunked_string(b: &mut Bencher) {
+    bench_chunked(b, chunked::StringAccumulator::new());
+}
+
+#[bench]
+fn chunked_ring(b: &mut Bencher) {
+    bench_chunked(b, chunked::RingBuffer::new());
+}
+
+#[bench]
+fn chunked_scan(b: &mut Bencher) {
+    bench_chunked(b, chunked::ScanningBuffer::new());
+}
+
+/// Decompresses TEST_JSON (repeated, so there's something to compress) and
+/// parses each line as in `future_serde`, as a recorded session would be.
+#[cfg(test)]
+fn bench_compressed(b: &mut Bencher, compression: corpus::Compression) {
+    use std::io::BufRead;
+
+    let data = TEST_JSON.repeat(100);
+    let compressed = compression.compress(data.as_bytes()).unwrap();
+    b.bytes = data.len() as u64;
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for line in compression.decoder(&compressed[..]).unwrap().lines() {
+            let line = line.unwrap();
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(&line).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+/// The same pipeline, without the decompression, for comparison.
+#[bench]
+fn compressed_none(b: &mut Bencher) {
+    bench_compressed(b, corpus::Compression::None);
+}
+
+#[cfg(feature = "gzip")]
+#[bench]
+fn compressed_gzip(b: &mut Bencher) {
+    bench_compressed(b, corpus::Compression::Gzip);
+}
+
+#[cfg(feature = "zstd")]
+#[bench]
+fn compressed_zstd(b: &mut Bencher) {
+    bench_compressed(b, corpus::Compression::Zstd);
+}
+
+#[cfg(feature = "lz4")]
+#[bench]
+fn compressed_lz4(b: &mut Bencher) {
+    bench_compressed(b, corpus::Compression::Lz4);
+}
+
+// framing a corpus, a message a line or by balancing braces, and the second
+// of the same corpus pretty-printed, as some debugging clients send it; see
+// `corpus::framing`. `future_serde_pretty` is then what parsing around the
+// whitespace costs, against `future_serde`
+
+#[cfg(test)]
+fn pretty_json() -> String {
+    TEST_JSON.lines().map(|line| {
+        serde_json::to_string_pretty(&serde_json::from_str::<Value>(line).unwrap()).unwrap()
+    }).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+fn bench_framing(b: &mut Bencher, framing: corpus::Framing, data: &str) {
+    b.bytes = data.len() as u64;
+    b.iter(|| test::black_box(framing.read(data.as_bytes()).unwrap()))
+}
+
+#[bench]
+fn framing_lines(b: &mut Bencher) {
+    bench_framing(b, corpus::Framing::Lines, &TEST_JSON.repeat(100));
+}
+
+#[bench]
+fn framing_braces(b: &mut Bencher) {
+    bench_framing(b, corpus::Framing::Braces, &TEST_JSON.repeat(100));
+}
+
+#[bench]
+fn framing_braces_pretty(b: &mut Bencher) {
+    bench_framing(b, corpus::Framing::Braces, &(pretty_json() + "\n").repeat(100));
+}
+
+#[bench]
+fn future_serde_pretty(b: &mut Bencher) {
+    let messages = corpus::Framing::Braces.read(pretty_json().as_bytes()).unwrap();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &messages {
+            sink.consume(&serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+        }
+        test::black_box(sink);
+    })
+}
+
+// whether to compress big messages on the way through the pipe: each is
+// compressed on its own, as a sender would before writing it, and
+// decompressed and parsed at the other end. With `b.bytes` set, compare the
+// MB/s with the raw benches' and the channel's own; compressing pays off when
+// the time it adds is less than what sending the bytes it saves takes
+
+#[cfg(test)]
+fn bench_wire<F: Fn(&str)>(b: &mut Bencher, messages: &[String],
+                           compression: corpus::Compression, parse: F) {
+    use std::io::Read;
+
+    b.bytes = messages.iter().map(|m| m.len() as u64).sum();
+    let mut text = String::new();
+    b.iter(|| {
+        for json in messages {
+            let sent = compression.compress(json.as_bytes()).unwrap();
+            text.clear();
+            compression.decoder(&sent[..]).unwrap().read_to_string(&mut text).unwrap();
+            parse(&text);
+        }
+    })
+}
+
+#[cfg(test)]
+fn bench_wire_paste(b: &mut Bencher, compression: corpus::Compression) {
+    bench_wire(b, &make_paste_inserts(), compression, |json| {
+        test::black_box(serde_json::from_str::<rpc3::CoreNotification>(json).unwrap());
+    })
+}
+
+/// A plugin's first update, with the whole of a file in one insert.
+#[cfg(test)]
+fn bench_wire_update(b: &mut Bencher, compression: corpus::Compression) {
+    let text = TEST_JSON.repeat(64);
+    let update = rpc2::HostRequest::Update(rpc2::PluginUpdate {
+        view_id: "view-id-1".into(),
+        delta: Some(rpc2::RopeDelta { els: vec![rpc2::DeltaElement::Insert(text)], base_len: 0 }),
+        new_len: 64 * TEST_JSON.len() as u64,
+        rev: 1,
+        edit_type: "insert".into(),
+        author: "core".into(),
+    });
+    let messages = vec![serde_json::to_string(&update).unwrap(); 4];
+    bench_wire(b, &messages, compression, |json| {
+        test::black_box(serde_json::from_str::<rpc2::HostRequest>(json).unwrap());
+    })
+}
+
+#[bench]
+fn wire_raw_paste(b: &mut Bencher) {
+    bench_wire_paste(b, corpus::Compression::None);
+}
+
+#[cfg(feature = "gzip")]
+#[bench]
+fn wire_gzip_paste(b: &mut Bencher) {
+    bench_wire_paste(b, corpus::Compression::Gzip);
+}
+
+#[cfg(feature = "zstd")]
+#[bench]
+fn wire_zstd_paste(b: &mut Bencher) {
+    bench_wire_paste(b, corpus::Compression::Zstd);
+}
+
+#[cfg(feature = "lz4")]
+#[bench]
+fn wire_lz4_paste(b: &mut Bencher) {
+    bench_wire_paste(b, corpus::Compression::Lz4);
+}
+
+#[bench]
+fn wire_raw_update(b: &mut Bencher) {
+    bench_wire_update(b, corpus::Compression::None);
+}
+
+#[cfg(feature = "gzip")]
+#[bench]
+fn wire_gzip_update(b: &mut Bencher) {
+    bench_wire_update(b, corpus::Compression::Gzip);
+}
+
+#[cfg(feature = "zstd")]
+#[bench]
+fn wire_zstd_update(b: &mut Bencher) {
+    bench_wire_update(b, corpus::Compression::Zstd);
+}
+
+#[cfg(feature = "lz4")]
+#[bench]
+fn wire_lz4_update(b: &mut Bencher) {
+    bench_wire_update(b, corpus::Compression::Lz4);
+}
+
+// a plugin RPC carrying a 256KB image as base64: decoded as it's read,
+// borrowed and decoded after, borrowed and never decoded (as a core that
+// only forwards it would), and through rpc2's `Value` params
+
+#[cfg(test)]
+fn make_image_rpc() -> String {
+    let data = (0..256 * 1024u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect::<Vec<_>>();
+    binary::show_image("image/png", &data)
+}
+
+#[bench]
+fn base64_eager(b: &mut Bencher) {
+    let json = make_image_rpc();
+    b.bytes = json.len() as u64;
+    b.iter(|| binary::parse::<binary::Image>(&json).unwrap().rpc.params.data.len())
+}
+
+#[bench]
+fn base64_deferred(b: &mut Bencher) {
+    let json = make_image_rpc();
+    b.bytes = json.len() as u64;
+    b.iter(|| {
+        let rpc = binary::parse::<binary::LazyImage>(&json).unwrap();
+        rpc.rpc.params.data.decode().unwrap().len()
+    })
+}
+
+#[bench]
+fn base64_deferred_unused(b: &mut Bencher) {
+    let json = make_image_rpc();
+    b.bytes = json.len() as u64;
+    b.iter(|| binary::parse::<binary::LazyImage>(&json).unwrap().rpc.params.data.raw().len())
+}
+
+#[bench]
+fn base64_rpc2(b: &mut Bencher) {
+    let json = make_image_rpc();
+    b.bytes = json.len() as u64;
+    b.iter(|| match serde_json::from_str::<rpc2::CoreNotification>(&json).unwrap() {
+        rpc2::CoreNotification::Plugin(rpc2::PluginNotification::PluginRpc { rpc, .. }) => {
+            use base64::Engine;
+            let data = rpc.params["data"].as_str().unwrap();
+            base64::engine::general_purpose::STANDARD.decode(data).unwrap().len()
+        }
+        _ => unreachable!(),
+    })
+}
+
+// every strategy over every corpus of a frontend's messages, a bench each,
+// named strategy::corpus, so that `cargo bench hand::ime` runs one cell,
+// `cargo bench matrix::hand::` a strategy's row, and `cargo bench ::ime` a
+// corpus's column. A new strategy needs a line in `matrix!` below, and a
+// test fails until it has one. As in the harness, lines a strategy can't
+// parse are skipped rather than failing the bench
+
+/// The lines of the corpus that the matrix calls `name`: one of the
+/// built-in corpora, or a generated preset.
+#[cfg(test)]
+fn matrix_corpus(name: &str) -> Vec<String> {
+    let text = match name {
+        "basic" => TEST_JSON,
+        "gestures" => GESTURE_JSON,
+        "ids" => IDS_JSON,
+        "ime" => IME_JSON,
+        "annotated" => ANNOTATED_JSON,
+        preset => {
+            let preset = corpus::generate::Preset::from_name(preset).expect("no such corpus");
+            return preset.generate(5000, 1);
+        }
+    };
+    text.lines().map(String::from).collect()
+}
+
+#[cfg(test)]
+fn bench_cell(b: &mut Bencher, strategy: &dyn Strategy, corpus: &str) {
+    let lines = matrix_corpus(corpus);
+    b.bytes = lines.iter().map(|l| l.len() as u64).sum();
+    strategy.prepare();
+    b.iter(|| {
+        let mut sink = Sink::new();
+        for json in &lines {
+            let _ = strategy.parse_into(json, &mut sink);
+        }
+        test::black_box(sink);
+    });
+    strategy.reset();
+}
+
+/// A bench for each corpus, of one strategy.
+#[cfg(test)]
+macro_rules! matrix_row {
+    ($strategy:expr; $($corpus:ident),*) => {
+        $(
+            #[bench]
+            fn $corpus(b: &mut Bencher) {
+                bench_cell(b, &$strategy, stringify!($corpus));
+            }
+        )*
+    };
+}
+
+/// A module of benches for each strategy, named as the strategy is, and
+/// `strategies()`, the names of those there are in this build.
+#[cfg(test)]
+macro_rules! matrix {
+    ($($(#[$attr:meta])* $name:ident => $strategy:expr,)*) => {
+        $(
+            $(#[$attr])*
+            mod $name {
+                use super::*;
+
+                matrix_row!($strategy; basic, gestures, ids, ime, annotated, cursor_burst,
+                            scroll_storm, file_session);
+            }
+        )*
+
+        fn strategies() -> Vec<&'static str> {
+            let mut names = Vec::new();
+            $(
+                $(#[$attr])*
+                names.push(stringify!($name));
+            )*
+            names
+        }
+    };
+}
+
+#[cfg(test)]
+mod matrix {
+    use super::*;
+
+    matrix! {
+        #[cfg(all(feature = "xi-core", not(target_arch = "wasm32")))]
+        borrow => strategy::Borrow,
+        #[cfg(not(target_arch = "wasm32"))]
+        own => strategy::Own,
+        #[cfg(not(target_arch = "wasm32"))]
+        own_direct => strategy::OwnDirect,
+        #[cfg(not(target_arch = "wasm32"))]
+        own_peek_id => strategy::OwnPeekId,
+        #[cfg(not(target_arch = "wasm32"))]
+        reference_v1 => strategy::ReferenceV1,
+        #[cfg(not(target_arch = "wasm32"))]
+        reference_v2 => strategy::ReferenceV2,
+        serde => strategy::Serde,
+        scan_id => strategy::ScanId,
+        cached_params => strategy::CachedParams::default(),
+        fast_path => strategy::FastPath,
+        internal => strategy::Internal,
+        reassemble => strategy::Reassemble,
+        #[cfg(feature = "serde_path_to_error")]
+        serde_path => strategy::SerdePath,
+        #[cfg(feature = "json5")]
+        json5 => strategy::Json5,
+        future_serde => strategy::FutureSerde,
+        future_serde_stream => strategy::FutureSerdeStream,
+        #[cfg(feature = "serde_path_to_error")]
+        future_serde_path => strategy::FutureSerdePath,
+        #[cfg(feature = "unbounded_depth")]
+        future_serde_unbounded => strategy::FutureSerdeUnbounded,
+        hand => strategy::Hand,
+        #[cfg(feature = "unchecked")]
+        unchecked => strategy::Unchecked,
+        split => strategy::Split,
+        #[cfg(not(target_arch = "wasm32"))]
+        scoped => strategy::Scoped,
+        tape => strategy::Tape,
+        ignored_any => strategy::IgnoredAny,
+    }
+
+    #[test]
+    fn every_strategy() {
+        let names = strategies();
+        for strategy in strategy::all().into_iter().chain(strategy::routers()) {
+            assert!(names.contains(&strategy.name()), "{} isn't in the matrix", strategy.name());
+        }
+        assert_eq!(names.len(), strategy::all().len() + strategy::routers().len());
+    }
+
+    #[test]
+    fn corpora() {
+        for name in &["basic", "ime", "cursor_burst", "file_session"] {
+            assert!(!matrix_corpus(name).is_empty(), "{}", name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_tagging {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum ExternalTag {
+        Blue,
+        Other(String),
+        Yellowish { how_yellow: f64 },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    #[serde(tag = "method")]
+    enum InternalTag {
+        Blue,
+        Other(String),
+        Yellowish { how_yellow: f64 },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    #[serde(tag = "method", content = "params")]
+    enum AdjacentlyTag {
+        Blue,
+        Other(String),
+        Yellowish { how_yellow: f64 },
+    }
+
+    #[test]
+    fn test_external() {
+        let blue = r#"{"blue": {}}"#;
+        let yellow = r#"{"yellowish": {"how_yellow": 1.4}}"#;
+        let x = serde_json::from_str::<ExternalTag>(&blue).unwrap();
+        let x = serde_json::from_str::<ExternalTag>(&yellow).unwrap();
+    }
+
+    #[test]
+    fn test_internal() {
+        let blue = r#"{"method": "blue"}"#;
+        let yellow = r#"{"method": "yellowish", "how_yellow": 1.4}"#;
+        let x = serde_json::from_str::<InternalTag>(&blue);
+        assert!(x.is_ok());
+        let x = serde_json::from_str::<InternalTag>(&yellow);
+        assert!(x.is_ok());
+    }
+
+    #[test]
+    fn test_adjacent() {
+        let blue = r#"{"method": "blue", "params": {}}"#;
+        let yellow = r#"{"method": "yellowish", "params": {"how_yellow": 1.4}}"#;
+        let x = serde_json::from_str::<AdjacentlyTag>(&blue).unwrap();
+        let x = serde_json::from_str::<AdjacentlyTag>(&yellow).unwrap();
+    }
+}
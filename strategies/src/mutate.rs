@@ -0,0 +1,317 @@
+//! Structured mutations of corpus lines, for scoring how strategies cope
+//! with malformed input.
+//!
+//! Each mutation is applied to a line, and the result is fed to every
+//! strategy. A strategy can reject the mutated message, accept it, or panic.
+//! Apart from `ShuffleKeys`, every mutation changes the meaning of a message,
+//! so acceptance means the strategy silently accepted something it was not
+//! sent. (This overcounts a little: dropping an optional field is benign.)
+
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+
+use serde_json::{self, Value};
+
+use corpus::rng::XorShift;
+use strategy::Strategy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    /// Removes a field from some object in the message.
+    DropField,
+    /// Replaces some value in the message with a value of another type.
+    ChangeType,
+    /// Reorders the keys of every object in the message.
+    ShuffleKeys,
+    /// Cuts the message off partway through.
+    Truncate,
+}
+
+/// What a strategy did with a mutated message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Rejected,
+    Accepted,
+    Panicked,
+}
+
+/// The outcomes of one strategy against one kind of mutation.
+#[derive(Debug, Clone, Default)]
+pub struct Tally {
+    pub rejected: usize,
+    pub accepted: usize,
+    pub panicked: usize,
+}
+
+/// The results of a mutation run, per strategy and mutation.
+#[derive(Debug)]
+pub struct Report {
+    pub strategies: Vec<&'static str>,
+    pub mutations: Vec<Mutation>,
+    /// Indexed by strategy, then by mutation.
+    pub tallies: Vec<Vec<Tally>>,
+}
+
+impl Mutation {
+    pub fn all() -> Vec<Mutation> {
+        vec![Mutation::DropField, Mutation::ChangeType, Mutation::ShuffleKeys, Mutation::Truncate]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Mutation::DropField => "drop_field",
+            Mutation::ChangeType => "change_type",
+            Mutation::ShuffleKeys => "shuffle_keys",
+            Mutation::Truncate => "truncate",
+        }
+    }
+
+    /// Whether the mutated message means the same thing as the original.
+    pub fn preserves_semantics(&self) -> bool {
+        *self == Mutation::ShuffleKeys
+    }
+
+    /// Applies this mutation to `line`, returning `None` if it cannot be
+    /// applied (for instance, dropping a field from a message without any).
+    pub fn apply(&self, line: &str, rng: &mut XorShift) -> Option<String> {
+        if *self == Mutation::Truncate {
+            return truncate(line, rng);
+        }
+
+        let mut value = serde_json::from_str::<Value>(line).ok()?;
+        match *self {
+            Mutation::DropField => {
+                let fields = pointers(&value, true);
+                if fields.is_empty() {
+                    return None;
+                }
+                let field = &fields[rng.range(0, fields.len() - 1)];
+                let split = field.rfind('/').unwrap();
+                let key = unescape(&field[split + 1..]);
+                value.pointer_mut(&field[..split])
+                    .and_then(Value::as_object_mut)
+                    .and_then(|obj| obj.remove(&key))?;
+                Some(value.to_string())
+            }
+            Mutation::ChangeType => {
+                let all = pointers(&value, false);
+                let target = &all[rng.range(0, all.len() - 1)];
+                let slot = value.pointer_mut(target)?;
+                *slot = other_type(slot);
+                Some(value.to_string())
+            }
+            Mutation::ShuffleKeys => {
+                let mut out = String::with_capacity(line.len());
+                write_shuffled(&value, rng, &mut out);
+                Some(out)
+            }
+            Mutation::Truncate => unreachable!(),
+        }
+    }
+}
+
+impl Tally {
+    fn record(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Rejected => self.rejected += 1,
+            Outcome::Accepted => self.accepted += 1,
+            Outcome::Panicked => self.panicked += 1,
+        }
+    }
+}
+
+/// Feeds `message` to `strategy`, catching any panic.
+pub fn check(strategy: &dyn Strategy, message: &str) -> Outcome {
+    match panic::catch_unwind(AssertUnwindSafe(|| strategy.parse(message))) {
+        Ok(Ok(())) => Outcome::Accepted,
+        Ok(Err(_)) => Outcome::Rejected,
+        Err(_) => Outcome::Panicked,
+    }
+}
+
+/// Applies `count` instances of each mutation to each line, and feeds the
+/// results to each strategy.
+pub fn run(lines: &[String], strategies: &[Box<dyn Strategy>], count: usize, seed: u64) -> Report {
+    let mutations = Mutation::all();
+    let mut tallies = vec![vec![Tally::default(); mutations.len()]; strategies.len()];
+    let mut rng = XorShift::new(seed);
+
+    // we expect panics; don't spam stderr with them
+    let prev_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| ()));
+
+    for line in lines {
+        for (m_idx, mutation) in mutations.iter().enumerate() {
+            for _ in 0..count {
+                let mutated = match mutation.apply(line, &mut rng) {
+                    Some(m) => m,
+                    None => break,
+                };
+                for (s_idx, strategy) in strategies.iter().enumerate() {
+                    tallies[s_idx][m_idx].record(check(strategy.as_ref(), &mutated));
+                }
+            }
+        }
+    }
+
+    panic::set_hook(prev_hook);
+    Report {
+        strategies: strategies.iter().map(|s| s.name()).collect(),
+        mutations,
+        tallies,
+    }
+}
+
+impl Report {
+    pub fn print(&self) {
+        println!("{:<14}{:<14}{:>10}{:>10}{:>10}",
+                 "strategy", "mutation", "rejected", "panicked", "accepted");
+        for (strategy, tallies) in self.strategies.iter().zip(self.tallies.iter()) {
+            for (mutation, tally) in self.mutations.iter().zip(tallies.iter()) {
+                let note = if mutation.preserves_semantics() { " (should accept)" } else { "" };
+                println!("{:<14}{:<14}{:>10}{:>10}{:>10}{}", strategy, mutation.name(),
+                         tally.rejected, tally.panicked, tally.accepted, note);
+            }
+        }
+    }
+}
+
+/// Cuts `line` at a random char boundary, keeping at least one char.
+fn truncate(line: &str, rng: &mut XorShift) -> Option<String> {
+    let boundaries = line.char_indices().skip(1).map(|(i, _)| i).collect::<Vec<_>>();
+    if boundaries.is_empty() {
+        return None;
+    }
+    let end = boundaries[rng.range(0, boundaries.len() - 1)];
+    Some(line[..end].to_owned())
+}
+
+/// Returns JSON pointers to every value in `value`, including the root.
+/// If `fields_only` is true, returns only pointers to object members.
+fn pointers(value: &Value, fields_only: bool) -> Vec<String> {
+    fn walk(value: &Value, path: &mut String, fields_only: bool,
+            is_field: bool, out: &mut Vec<String>) {
+        if is_field || !fields_only {
+            out.push(path.clone());
+        }
+        let len = path.len();
+        match *value {
+            Value::Object(ref obj) => for (key, child) in obj {
+                path.push('/');
+                path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                walk(child, path, fields_only, true, out);
+                path.truncate(len);
+            },
+            Value::Array(ref arr) => for (i, child) in arr.iter().enumerate() {
+                write!(path, "/{}", i).unwrap();
+                walk(child, path, fields_only, false, out);
+                path.truncate(len);
+            },
+            _ => (),
+        }
+    }
+
+    let mut out = Vec::new();
+    walk(value, &mut String::new(), fields_only, false, &mut out);
+    out
+}
+
+fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+/// Returns a value of a different JSON type from `value`.
+fn other_type(value: &Value) -> Value {
+    match *value {
+        Value::Null => json!(0),
+        Value::Bool(b) => json!(b.to_string()),
+        Value::Number(ref n) => json!(n.to_string()),
+        Value::String(ref s) => json!(s.len()),
+        Value::Array(ref arr) => json!({ "len": arr.len() }),
+        Value::Object(ref obj) => Value::Array(obj.values().cloned().collect()),
+    }
+}
+
+/// Serializes `value`, emitting the keys of each object in random order.
+fn write_shuffled(value: &Value, rng: &mut XorShift, out: &mut String) {
+    match *value {
+        Value::Object(ref obj) => {
+            let mut entries = obj.iter().collect::<Vec<_>>();
+            // fisher-yates
+            for i in (1..entries.len()).rev() {
+                entries.swap(i, rng.range(0, i));
+            }
+            out.push('{');
+            for (i, &(key, child)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::to_string(key).unwrap());
+                out.push(':');
+                write_shuffled(child, rng, out);
+            }
+            out.push('}');
+        }
+        Value::Array(ref arr) => {
+            out.push('[');
+            for (i, child) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_shuffled(child, rng, out);
+            }
+            out.push(']');
+        }
+        ref other => out.push_str(&other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = r#"{"method":"edit","params":{"view_id":"view-id-1","method":"scroll","params":[3,13]}}"#;
+
+    #[test]
+    fn shuffle_preserves_value() {
+        let original = serde_json::from_str::<Value>(LINE).unwrap();
+        let mut rng = XorShift::new(7);
+        for _ in 0..20 {
+            let shuffled = Mutation::ShuffleKeys.apply(LINE, &mut rng).unwrap();
+            assert_eq!(serde_json::from_str::<Value>(&shuffled).unwrap(), original);
+        }
+    }
+
+    #[test]
+    fn drop_field_removes_one_field() {
+        let before = pointers(&serde_json::from_str(LINE).unwrap(), true).len();
+        let mut rng = XorShift::new(7);
+        for _ in 0..20 {
+            let dropped = Mutation::DropField.apply(LINE, &mut rng).unwrap();
+            let value = serde_json::from_str::<Value>(&dropped).unwrap();
+            assert!(pointers(&value, true).len() < before);
+        }
+        assert!(Mutation::DropField.apply("[1,2]", &mut rng).is_none());
+    }
+
+    #[test]
+    fn change_type_changes_value() {
+        let original = serde_json::from_str::<Value>(LINE).unwrap();
+        let mut rng = XorShift::new(7);
+        for _ in 0..20 {
+            let changed = Mutation::ChangeType.apply(LINE, &mut rng).unwrap();
+            assert_ne!(serde_json::from_str::<Value>(&changed).unwrap(), original);
+        }
+    }
+
+    #[test]
+    fn truncate_is_prefix() {
+        let mut rng = XorShift::new(7);
+        for _ in 0..20 {
+            let truncated = Mutation::Truncate.apply("h\u{e9}llo", &mut rng).unwrap();
+            assert!(truncated.len() < "h\u{e9}llo".len());
+            assert!("h\u{e9}llo".starts_with(&truncated));
+        }
+        assert!(Mutation::Truncate.apply("x", &mut rng).is_none());
+    }
+}
@@ -0,0 +1,117 @@
+//! Two ways of writing optional fields, for comparing their costs.
+//!
+//! Much of xi's protocol is optional params: style definitions, click
+//! counts, find options. They can be left out of the message when unset
+//! (`Option<T>` with `skip_serializing_if`), or always sent, with a default
+//! standing in for "unset". The first makes for smaller messages, but
+//! serde's handling of missing fields, and of `Option` in a `Value`, is a
+//! different code path from a plain field's.
+//!
+//! `rpc2::StyleDef` is a third way: `Option<T>`, sent as `null`.
+
+use rpc2::StyleDef;
+
+/// A `StyleDef` that leaves out its unset fields.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SparseStyleDef {
+    pub id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fg_color: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg_color: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underline: Option<bool>,
+}
+
+/// A `StyleDef` that always sends every field. Colors are ARGB, so a color
+/// of 0 is fully transparent, which is as good as unset.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct DenseStyleDef {
+    pub id: u64,
+    pub fg_color: u32,
+    pub bg_color: u32,
+    pub weight: u16,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Default for DenseStyleDef {
+    fn default() -> DenseStyleDef {
+        DenseStyleDef { id: 0, fg_color: 0, bg_color: 0, weight: 400, italic: false,
+                        underline: false }
+    }
+}
+
+// so that all three can be built from the same definitions
+impl<'a> From<&'a StyleDef> for StyleDef {
+    fn from(def: &StyleDef) -> StyleDef {
+        def.clone()
+    }
+}
+
+impl<'a> From<&'a StyleDef> for SparseStyleDef {
+    fn from(def: &StyleDef) -> SparseStyleDef {
+        SparseStyleDef {
+            id: def.id,
+            fg_color: def.fg_color,
+            bg_color: def.bg_color,
+            weight: def.weight,
+            italic: def.italic,
+            underline: def.underline,
+        }
+    }
+}
+
+impl<'a> From<&'a StyleDef> for DenseStyleDef {
+    fn from(def: &StyleDef) -> DenseStyleDef {
+        let unset = DenseStyleDef::default();
+        DenseStyleDef {
+            id: def.id,
+            fg_color: def.fg_color.unwrap_or(unset.fg_color),
+            bg_color: def.bg_color.unwrap_or(unset.bg_color),
+            weight: def.weight.unwrap_or(unset.weight),
+            italic: def.italic.unwrap_or(unset.italic),
+            underline: def.underline.unwrap_or(unset.underline),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+
+    fn def() -> StyleDef {
+        StyleDef { id: 3, fg_color: Some(0xff00_00ff), bg_color: None, weight: Some(700),
+                   italic: None, underline: None }
+    }
+
+    #[test]
+    fn sparse_leaves_out_unset() {
+        let json = serde_json::to_string(&SparseStyleDef::from(&def())).unwrap();
+        assert_eq!(json, r#"{"id":3,"fg_color":4278190335,"weight":700}"#);
+        let reread = serde_json::from_str::<SparseStyleDef>(&json).unwrap();
+        assert_eq!(reread, SparseStyleDef::from(&def()));
+        let val = serde_json::from_str::<Value>(&json).unwrap();
+        assert_eq!(serde_json::from_value::<SparseStyleDef>(val).unwrap(), reread);
+    }
+
+    #[test]
+    fn dense_sends_everything() {
+        let dense = DenseStyleDef::from(&def());
+        let val = serde_json::to_value(&dense).unwrap();
+        assert_eq!(val.as_object().unwrap().len(), 6);
+        assert_eq!(val["weight"], json!(700));
+        assert_eq!(val["italic"], json!(false));
+        assert_eq!(serde_json::from_value::<DenseStyleDef>(val).unwrap(), dense);
+        // and fills in the defaults for anything missing
+        let partial = serde_json::from_str::<DenseStyleDef>(r#"{"id":3}"#).unwrap();
+        assert_eq!(partial, DenseStyleDef { id: 3, ..DenseStyleDef::default() });
+    }
+}
@@ -0,0 +1,87 @@
+//! Running a strategy over a corpus, one line at a time, for tools that
+//! want the strategies without the bench loops around them: log analyzers,
+//! or anything replaying a corpus. `CorpusParser::line` locates errors, as
+//! well as the messages that parsed.
+
+use std::iter::Enumerate;
+use std::str::Lines;
+
+use error::ParseError;
+use sink::Sink;
+use strategy::Strategy;
+
+/// An iterator over the results of parsing each line of a corpus.
+///
+/// Blank lines are skipped, as `corpus::read_lines` skips them.
+pub struct CorpusParser<'a, S: ?Sized + 'a> {
+    strategy: &'a S,
+    lines: Enumerate<Lines<'a>>,
+    line: usize,
+}
+
+/// A line that the strategy parsed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Message<'a> {
+    /// The line's number in the corpus, from 1.
+    pub line: usize,
+    pub json: &'a str,
+    /// What the strategy made of it: the number of messages on the line,
+    /// more than one for a batch, and their checksum. Strategies that can't
+    /// feed a sink leave it empty.
+    pub parsed: Sink,
+}
+
+impl<'a, S: Strategy + ?Sized> CorpusParser<'a, S> {
+    pub fn new(strategy: &'a S, corpus: &'a str) -> CorpusParser<'a, S> {
+        CorpusParser { strategy, lines: corpus.lines().enumerate(), line: 0 }
+    }
+
+    /// The number of the line last returned, from 1, so that errors can be
+    /// located too.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl<'a, S: Strategy + ?Sized> Iterator for CorpusParser<'a, S> {
+    type Item = Result<Message<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (i, json) = self.lines.by_ref().find(|&(_, l)| !l.trim().is_empty())?;
+        self.line = i + 1;
+        let mut parsed = Sink::new();
+        Some(self.strategy.parse_into(json, &mut parsed)
+             .map(|()| Message { line: self.line, json, parsed }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strategy::{self, Hand, Tape};
+    use TEST_JSON;
+
+    #[test]
+    fn parses_each_line() {
+        for strategy in strategy::all() {
+            let messages = CorpusParser::new(strategy.as_ref(), TEST_JSON)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(messages.len(), TEST_JSON.lines().count(), "{}", strategy.name());
+            assert_eq!(messages[2].line, 3);
+            assert_eq!(messages[2].json, TEST_JSON.lines().nth(2).unwrap());
+        }
+    }
+
+    #[test]
+    fn errors_are_located() {
+        let corpus = "{\"method\":\"client_started\",\"params\":{}}\n\n  \n{\n";
+        let mut parser = CorpusParser::new(&Tape, corpus);
+        let first = parser.next().unwrap().unwrap();
+        assert_eq!((first.line, first.parsed.messages()), (1, 1));
+        assert_eq!(parser.next().unwrap().unwrap_err().kind(), "frame");
+        assert_eq!(parser.line(), 4);
+        assert!(parser.next().is_none());
+        assert_eq!(CorpusParser::new(&Hand, "").count(), 0);
+    }
+}
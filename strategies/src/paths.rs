@@ -0,0 +1,96 @@
+//! Three types for `file_path`, for deciding which one xi-core should store.
+//!
+//! rpc2 reads paths into `String`s, and xi-core turns them into `PathBuf`s
+//! when it touches the file system. Reading straight into a `PathBuf` saves
+//! the second copy. Borrowing a `&Path` saves both, but, like a `&str`, only
+//! works for paths with nothing escaped in them, which rules out Windows
+//! paths.
+//!
+//! Whichever is used, a path on the wire is a JSON string, and so valid
+//! Unicode, while on unix a file name can be any bytes. serde refuses to
+//! serialize a `PathBuf` that isn't UTF-8, and serde_json refuses the lone
+//! surrogate escapes that some encoders use for such bytes, so a file with
+//! a name that isn't UTF-8 can't be named over RPC at all. That's the policy
+//! the tests pin down: a lossy conversion would have core open or save some
+//! other file.
+
+use std::path::{Path, PathBuf};
+
+/// The messages that carry a path, with the path read into a `P`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum FileMessage<'a, P> {
+    NewView { file_path: Option<P> },
+    Save { view_id: &'a str, file_path: P },
+    CloseView { view_id: &'a str },
+}
+
+/// What rpc2 does.
+pub type StringPaths<'a> = FileMessage<'a, String>;
+pub type OwnedPaths<'a> = FileMessage<'a, PathBuf>;
+pub type BorrowedPaths<'a> = FileMessage<'a, &'a Path>;
+
+impl<'a, P: AsRef<Path>> FileMessage<'a, P> {
+    pub fn file_path(&self) -> Option<&Path> {
+        match *self {
+            FileMessage::NewView { ref file_path } => file_path.as_ref().map(|p| p.as_ref()),
+            FileMessage::Save { ref file_path, .. } => Some(file_path.as_ref()),
+            FileMessage::CloseView { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+    use corpus::generate::Preset;
+
+    #[test]
+    fn all_three_agree() {
+        for line in Preset::FileSession.generate(500, 2) {
+            let string = serde_json::from_str::<StringPaths>(&line).unwrap();
+            let owned = serde_json::from_str::<OwnedPaths>(&line).unwrap();
+            let borrowed = serde_json::from_str::<BorrowedPaths>(&line).unwrap();
+            assert_eq!(string.file_path(), owned.file_path());
+            assert_eq!(string.file_path(), borrowed.file_path());
+            // and each writes out what it read
+            let json = serde_json::to_string(&owned).unwrap();
+            assert_eq!(serde_json::from_str::<OwnedPaths>(&json).unwrap(), owned);
+            let json = serde_json::to_string(&borrowed).unwrap();
+            assert_eq!(serde_json::from_str::<BorrowedPaths>(&json).unwrap(), borrowed);
+        }
+    }
+
+    #[test]
+    fn escaped_paths() {
+        let json = r#"{"method":"save","params":{"view_id":"v","file_path":"C:\\src\\lib.rs"}}"#;
+        let owned = serde_json::from_str::<OwnedPaths>(json).unwrap();
+        assert_eq!(owned.file_path(), Some(Path::new(r"C:\src\lib.rs")));
+        assert!(serde_json::from_str::<BorrowedPaths>(json).is_err());
+    }
+
+    #[test]
+    fn invalid_utf8_is_refused() {
+        // a lone surrogate, as some encoders write a byte that isn't UTF-8
+        let json = r#"{"method":"save","params":{"view_id":"v","file_path":"/tmp/\udcff.txt"}}"#;
+        assert!(serde_json::from_str::<StringPaths>(json).is_err());
+        assert!(serde_json::from_str::<OwnedPaths>(json).is_err());
+        assert!(serde_json::from_str::<BorrowedPaths>(json).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_utf8_is_not_sent() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = PathBuf::from(OsStr::from_bytes(b"/tmp/\xff.txt"));
+        let msg = OwnedPaths::Save { view_id: "v", file_path: path.clone() };
+        assert!(serde_json::to_string(&msg).is_err());
+        // rather than sending the name of some other file
+        assert_ne!(Path::new(&*path.to_string_lossy()), path);
+    }
+}
@@ -0,0 +1,85 @@
+//! Whether a message has an id, from its text.
+//!
+//! rpc2 has separate enums for requests and notifications, so a message's id
+//! has to be found before it can be parsed into either. The serde strategy
+//! parses into a `Value` and removes the id from it; `has_id` instead scans
+//! the line once, keeping track of how deeply it's nested and skipping over
+//! strings, and looks for an `"id"` key in the outermost object, so that
+//! the message can then be parsed straight from the string.
+//!
+//! Keys aren't unescaped, so an id written as `"\u0069d"` isn't found, and
+//! the message is taken for a notification. No frontend writes its keys that
+//! way.
+
+/// Whether the object in `json` has an id, even a null one. Anything that
+/// isn't an object doesn't, and nor does anything after an unclosed string.
+pub fn has_id(json: &str) -> bool {
+    let bytes = json.as_bytes();
+    let mut depth = 0u32;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let end = string_end(bytes, i + 1);
+                if depth == 1 && &bytes[i + 1..end] == b"id" && is_key(bytes, end + 1) {
+                    return true;
+                }
+                i = end;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
+
+/// The index of the quote that ends the string starting at `start`, or the
+/// end of `bytes` if it isn't closed.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Whether the string ending before `after` is a key: whether the next thing
+/// after it is a colon.
+fn is_key(bytes: &[u8], after: usize) -> bool {
+    bytes.get(after..).unwrap_or_default().iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .map_or(false, |&b| b == b':')
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+    use corpus::{IDS_JSON, TEST_JSON};
+
+    #[test]
+    fn agrees_with_value() {
+        for line in TEST_JSON.lines().chain(IDS_JSON.lines()) {
+            let val = serde_json::from_str::<Value>(line).unwrap();
+            assert_eq!(has_id(line), val.get("id").is_some(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn only_top_level_keys() {
+        assert!(has_id(r#"{ "method":"a", "id" : null }"#));
+        assert!(!has_id(r#"{"method":"edit","params":{"id":1}}"#));
+        assert!(!has_id(r#"{"method":"id","params":["id"]}"#));
+        assert!(!has_id(r#"{"method":"\"id\":","params":{}}"#));
+        assert!(!has_id(r#"[{"id":1}]"#));
+        assert!(!has_id(r#"{"method":"a\"#));
+        assert!(!has_id(""));
+    }
+}
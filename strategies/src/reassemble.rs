@@ -0,0 +1,94 @@
+//! Putting pastes split by `corpus::paste::chunk` back together.
+//!
+//! The chunks of a paste are consecutive inserts into the same view with
+//! the same undo group. A `Reassembler` holds on to an insert with an undo
+//! group until it sees a message that doesn't continue it, appending the
+//! chars of any that do, so that what reaches the sink is the paste as it
+//! was sent whole. Inserts without an undo group, like keystrokes, pass
+//! straight through. Inserts that were sent separately into the same undo
+//! group can't be told from chunks, and are joined too; xi's frontends
+//! don't send any.
+
+use rpc2::{CoreNotification, EditCommand, EditNotification};
+use sink::Sink;
+
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    pending: Option<EditCommand<EditNotification>>,
+}
+
+impl Reassembler {
+    pub fn new() -> Reassembler {
+        Reassembler::default()
+    }
+
+    /// Feeds `msg` to `sink`, or holds on to it if it might be continued.
+    pub fn push(&mut self, msg: CoreNotification, sink: &mut Sink) {
+        let edit = match msg {
+            CoreNotification::Edit(edit) => edit,
+            other => {
+                self.flush(sink);
+                sink.consume(&other);
+                return;
+            }
+        };
+        if let Some(ref mut pending) = self.pending {
+            if let (&mut EditNotification::Insert { chars: ref mut all },
+                    &EditNotification::Insert { ref chars }) = (&mut pending.cmd, &edit.cmd)
+            {
+                if pending.view_id == edit.view_id && pending.undo_group == edit.undo_group {
+                    all.push_str(chars);
+                    return;
+                }
+            }
+        }
+        self.flush(sink);
+        match edit.cmd {
+            EditNotification::Insert { .. } if edit.undo_group.is_some() => {
+                self.pending = Some(edit)
+            }
+            _ => sink.consume(&CoreNotification::Edit(edit)),
+        }
+    }
+
+    /// Feeds the insert being held on to, if there is one, to `sink`.
+    pub fn flush(&mut self, sink: &mut Sink) {
+        if let Some(edit) = self.pending.take() {
+            sink.consume(&CoreNotification::Edit(edit));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use corpus::paste;
+    use sink::Sink;
+    use strategy::{Reassemble, Serde, Strategy};
+    use TEST_JSON;
+
+    #[test]
+    fn same_as_whole() {
+        let mut lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        let paste = json!({"method": "edit", "params": {"view_id": "view-id-1",
+            "method": "insert", "params": {"chars": TEST_JSON}}});
+        lines.insert(3, paste.to_string());
+        lines.push(paste.to_string());
+
+        let mut whole = Sink::new();
+        for line in &lines {
+            Serde.parse_into(line, &mut whole).unwrap();
+        }
+        let chunked = paste::chunk(&lines, 64);
+        assert!(chunked.len() > lines.len() + TEST_JSON.len() / 64);
+        let mut reassembled = Sink::new();
+        Reassemble.parse_into(&chunked.join("\n"), &mut reassembled).unwrap();
+        assert_eq!(reassembled, whole);
+
+        // a line at a time, nothing is held over
+        let mut apart = Sink::new();
+        for line in &chunked {
+            Reassemble.parse_into(line, &mut apart).unwrap();
+        }
+        assert_eq!(apart.messages(), chunked.len() as u64);
+    }
+}
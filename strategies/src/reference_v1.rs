@@ -0,0 +1,189 @@
+//! xi-core's `rpc::Request::from_json` as it was before it borrowed.
+//!
+//! This version copied every string it read out of the params into the
+//! `Request`, and looked each field up through small helpers on the
+//! params' map, taking it apart with `as_object` and `as_array` once per
+//! command. It accepts the same methods as `reference_v2`, and parses them
+//! into the same variants, so that the `reference_v1` and `reference_v2`
+//! strategies differ only in how xi-core went about it. Like `reference_v2`,
+//! it's written to xi-core's shape rather than copied from it.
+
+use serde_json::value::{Map, Value};
+
+use tape::{GESTURE_TYPES, PLAIN_EDIT_METHODS};
+
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    Edit { view_id: String, edit_command: EditCommand },
+    NewView { file_path: Option<String> },
+    CloseView { view_id: String },
+    Save { view_id: String, file_path: String },
+    SetTheme { theme_name: String },
+    ClientStarted,
+    Plugin { view_id: String, command: String },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EditCommand {
+    Insert { chars: String },
+    RequestLines(u64, u64),
+    Scroll(u64, u64),
+    Click(u64, u64, u64, Option<u64>),
+    Drag(u64, u64, u64),
+    Gesture { line: u64, column: u64, ty: String },
+    GotoLine { line: u64 },
+    FindNext { wrap_around: bool, allow_same: bool },
+    FindPrevious { wrap_around: bool },
+    Find { chars: Option<String>, case_sensitive: bool },
+    SetMarkedText { chars: String, selection: (u64, u64), replacement: Option<(u64, u64)> },
+    /// One of the methods without params, by name.
+    Plain(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnknownMethod(String),
+    MalformedParams(String),
+}
+
+impl Request {
+    pub fn from_json(method: &str, params: &Value) -> Result<Request, Error> {
+        let malformed = || Error::MalformedParams(method.to_owned());
+        if method == "client_started" {
+            return Ok(Request::ClientStarted);
+        }
+        let dict = params.as_object().ok_or_else(malformed)?;
+        let field = |key: &str| dict_get_string(dict, key).ok_or_else(malformed);
+        Ok(match method {
+            "edit" => {
+                let view_id = field("view_id")?;
+                let edit_method = field("method")?;
+                Request::Edit {
+                    view_id,
+                    edit_command: EditCommand::from_json(&edit_method, dict.get("params"))?,
+                }
+            }
+            "new_view" => Request::NewView {
+                file_path: match dict.get("file_path") {
+                    None | Some(&Value::Null) => None,
+                    Some(_) => Some(field("file_path")?),
+                },
+            },
+            "close_view" => Request::CloseView { view_id: field("view_id")? },
+            "save" => Request::Save { view_id: field("view_id")?, file_path: field("file_path")? },
+            "set_theme" => Request::SetTheme { theme_name: field("theme_name")? },
+            "plugin" => Request::Plugin { view_id: field("view_id")?, command: field("command")? },
+            other => return Err(Error::UnknownMethod(other.to_owned())),
+        })
+    }
+}
+
+impl EditCommand {
+    fn from_json(method: &str, params: Option<&Value>) -> Result<EditCommand, Error> {
+        use self::EditCommand::*;
+        let malformed = || Error::MalformedParams(format!("edit/{}", method));
+        if PLAIN_EDIT_METHODS.contains(&method) {
+            return Ok(Plain(method.to_owned()));
+        }
+        let params = params.ok_or_else(malformed)?;
+        match method {
+            "request_lines" | "scroll" | "click" | "drag" => {
+                let arr = params.as_array().ok_or_else(malformed)?;
+                let get = |i: usize| arr_get_u64(arr, i).ok_or_else(malformed);
+                return Ok(match (method, arr.len()) {
+                    ("request_lines", 2) => RequestLines(get(0)?, get(1)?),
+                    ("scroll", 2) => Scroll(get(0)?, get(1)?),
+                    ("click", 3) => Click(get(0)?, get(1)?, get(2)?, None),
+                    ("click", 4) => Click(get(0)?, get(1)?, get(2)?, Some(get(3)?)),
+                    ("drag", 3) | ("drag", 4) => Drag(get(0)?, get(1)?, get(2)?),
+                    _ => return Err(malformed()),
+                });
+            }
+            _ => {}
+        }
+        let dict = params.as_object().ok_or_else(malformed)?;
+        let get_u64 = |key: &str| dict_get_u64(dict, key).ok_or_else(malformed);
+        let get_bool = |key: &str| dict_get_bool(dict, key).ok_or_else(malformed);
+        let get_string = |key: &str| dict_get_string(dict, key).ok_or_else(malformed);
+        let get_pair = |key: &str| -> Result<(u64, u64), Error> {
+            let arr = dict.get(key).and_then(Value::as_array).ok_or_else(malformed)?;
+            match (arr.len(), arr_get_u64(arr, 0), arr_get_u64(arr, 1)) {
+                (2, Some(first), Some(second)) => Ok((first, second)),
+                _ => Err(malformed()),
+            }
+        };
+        Ok(match method {
+            "insert" => Insert { chars: get_string("chars")? },
+            "gesture" => {
+                let ty = get_string("ty")?;
+                if !GESTURE_TYPES.contains(&ty.as_str()) {
+                    return Err(malformed());
+                }
+                Gesture { line: get_u64("line")?, column: get_u64("column")?, ty }
+            }
+            "goto_line" => GotoLine { line: get_u64("line")? },
+            "find_next" => FindNext {
+                wrap_around: get_bool("wrap_around")?,
+                allow_same: get_bool("allow_same")?,
+            },
+            "find_previous" => FindPrevious { wrap_around: get_bool("wrap_around")? },
+            "find" => Find {
+                chars: match dict.get("chars") {
+                    None | Some(&Value::Null) => None,
+                    Some(_) => Some(get_string("chars")?),
+                },
+                case_sensitive: get_bool("case_sensitive")?,
+            },
+            "set_marked_text" => SetMarkedText {
+                chars: get_string("chars")?,
+                selection: get_pair("selection")?,
+                replacement: match dict.get("replacement") {
+                    None | Some(&Value::Null) => None,
+                    Some(_) => Some(get_pair("replacement")?),
+                },
+            },
+            other => return Err(Error::UnknownMethod(format!("edit/{}", other))),
+        })
+    }
+}
+
+fn dict_get_string(dict: &Map<String, Value>, key: &str) -> Option<String> {
+    dict.get(key).and_then(Value::as_str).map(String::from)
+}
+
+fn dict_get_u64(dict: &Map<String, Value>, key: &str) -> Option<u64> {
+    dict.get(key).and_then(Value::as_u64)
+}
+
+fn dict_get_bool(dict: &Map<String, Value>, key: &str) -> Option<bool> {
+    dict.get(key).and_then(Value::as_bool)
+}
+
+fn arr_get_u64(arr: &[Value], idx: usize) -> Option<u64> {
+    arr.get(idx).and_then(Value::as_u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+    use reference_v2;
+    use strategy::parse_rpc_request;
+    use {GESTURE_JSON, IME_JSON, TEST_JSON};
+
+    #[test]
+    fn same_as_v2() {
+        for line in TEST_JSON.lines().chain(IME_JSON.lines()).chain(GESTURE_JSON.lines()) {
+            let val = serde_json::from_str::<Value>(line).unwrap();
+            let (_, method, params) = parse_rpc_request(&val).unwrap();
+            // the variants are the same, and `&str` and `String` debug alike
+            let v1 = Request::from_json(method, params).unwrap();
+            let v2 = reference_v2::Request::from_json(method, params).unwrap();
+            assert_eq!(format!("{:?}", v1), format!("{:?}", v2));
+        }
+        let bad = json!({"view_id": "view-id-1", "method": "scroll", "params": [1]});
+        assert_eq!(Request::from_json("edit", &bad),
+                   Err(Error::MalformedParams("edit/scroll".into())));
+    }
+}
@@ -0,0 +1,182 @@
+//! A stand-in for xi-core's `rpc::Request::from_json` as it is now, for
+//! building without xi-core.
+//!
+//! xi-core is a git dependency on a branch: it takes minutes to build, and
+//! breaks whenever its nightly does. The `own` strategies only need it for
+//! the one function, which walks a message's params `Value` by hand into a
+//! `Request` borrowing its strings from it. This is that function, written
+//! to the same shape, and accepting the same methods, so that they do much
+//! the same work without the `xi-core` feature. It isn't xi-core's code, so
+//! `borrow`, which is there to time xi-core itself, still needs the feature.
+//!
+//! `reference_v1` is the version before it, for comparing the two.
+
+use serde_json::Value;
+
+use tape::{GESTURE_TYPES, PLAIN_EDIT_METHODS};
+
+/// What missing params are read as.
+static NULL: Value = Value::Null;
+
+#[derive(Debug, PartialEq)]
+pub enum Request<'a> {
+    Edit { view_id: &'a str, edit_command: EditCommand<'a> },
+    NewView { file_path: Option<&'a str> },
+    CloseView { view_id: &'a str },
+    Save { view_id: &'a str, file_path: &'a str },
+    SetTheme { theme_name: &'a str },
+    ClientStarted,
+    Plugin { view_id: &'a str, command: &'a str },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EditCommand<'a> {
+    Insert { chars: &'a str },
+    RequestLines(u64, u64),
+    Scroll(u64, u64),
+    Click(u64, u64, u64, Option<u64>),
+    Drag(u64, u64, u64),
+    Gesture { line: u64, column: u64, ty: &'a str },
+    GotoLine { line: u64 },
+    FindNext { wrap_around: bool, allow_same: bool },
+    FindPrevious { wrap_around: bool },
+    Find { chars: Option<&'a str>, case_sensitive: bool },
+    SetMarkedText { chars: &'a str, selection: (u64, u64), replacement: Option<(u64, u64)> },
+    /// One of the methods without params, by name.
+    Plain(&'a str),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnknownMethod(String),
+    MalformedParams(String),
+}
+
+impl<'a> Request<'a> {
+    pub fn from_json(method: &str, params: &'a Value) -> Result<Request<'a>, Error> {
+        let malformed = || Error::MalformedParams(method.to_owned());
+        let field = |key: &str| get_str(params, key).ok_or_else(malformed);
+        Ok(match method {
+            "edit" => {
+                let edit_params = params.get("params").unwrap_or(&NULL);
+                Request::Edit {
+                    view_id: field("view_id")?,
+                    edit_command: EditCommand::from_json(field("method")?, edit_params)?,
+                }
+            }
+            "new_view" => Request::NewView {
+                file_path: match params.get("file_path") {
+                    None | Some(&Value::Null) => None,
+                    Some(path) => Some(path.as_str().ok_or_else(malformed)?),
+                },
+            },
+            "close_view" => Request::CloseView { view_id: field("view_id")? },
+            "save" => Request::Save { view_id: field("view_id")?, file_path: field("file_path")? },
+            "set_theme" => Request::SetTheme { theme_name: field("theme_name")? },
+            "client_started" => Request::ClientStarted,
+            "plugin" => Request::Plugin { view_id: field("view_id")?, command: field("command")? },
+            other => return Err(Error::UnknownMethod(other.to_owned())),
+        })
+    }
+}
+
+impl<'a> EditCommand<'a> {
+    fn from_json(method: &'a str, params: &'a Value) -> Result<EditCommand<'a>, Error> {
+        use self::EditCommand::*;
+        let malformed = || Error::MalformedParams(format!("edit/{}", method));
+        let get_u64 = |key: &str| params.get(key).and_then(Value::as_u64).ok_or_else(malformed);
+        let get_bool = |key: &str| params.get(key).and_then(Value::as_bool).ok_or_else(malformed);
+        let pair = |val: &Value| match u64s(val) {
+            Some(ref v) if v.len() == 2 => Ok((v[0], v[1])),
+            _ => Err(malformed()),
+        };
+        Ok(match method {
+            "insert" => Insert { chars: get_str(params, "chars").ok_or_else(malformed)? },
+            "request_lines" => {
+                let (first, last) = pair(params)?;
+                RequestLines(first, last)
+            }
+            "scroll" => {
+                let (first, last) = pair(params)?;
+                Scroll(first, last)
+            }
+            "click" => match u64s(params).as_deref() {
+                Some(&[line, column, flags]) => Click(line, column, flags, None),
+                Some(&[line, column, flags, count]) => Click(line, column, flags, Some(count)),
+                _ => return Err(malformed()),
+            },
+            "drag" => match u64s(params).as_deref() {
+                Some(&[line, column, flags]) | Some(&[line, column, flags, _]) => {
+                    Drag(line, column, flags)
+                }
+                _ => return Err(malformed()),
+            },
+            "gesture" => Gesture {
+                line: get_u64("line")?,
+                column: get_u64("column")?,
+                ty: get_str(params, "ty").filter(|ty| GESTURE_TYPES.contains(ty))
+                    .ok_or_else(malformed)?,
+            },
+            "goto_line" => GotoLine { line: get_u64("line")? },
+            "find_next" => FindNext {
+                wrap_around: get_bool("wrap_around")?,
+                allow_same: get_bool("allow_same")?,
+            },
+            "find_previous" => FindPrevious { wrap_around: get_bool("wrap_around")? },
+            "find" => Find {
+                chars: match params.get("chars") {
+                    None | Some(&Value::Null) => None,
+                    Some(chars) => Some(chars.as_str().ok_or_else(malformed)?),
+                },
+                case_sensitive: get_bool("case_sensitive")?,
+            },
+            "set_marked_text" => SetMarkedText {
+                chars: get_str(params, "chars").ok_or_else(malformed)?,
+                selection: pair(params.get("selection").unwrap_or(&NULL))?,
+                replacement: match params.get("replacement") {
+                    None | Some(&Value::Null) => None,
+                    Some(r) => Some(pair(r)?),
+                },
+            },
+            plain if PLAIN_EDIT_METHODS.contains(&plain) => Plain(plain),
+            other => return Err(Error::UnknownMethod(format!("edit/{}", other))),
+        })
+    }
+}
+
+fn get_str<'a>(params: &'a Value, key: &str) -> Option<&'a str> {
+    params.get(key).and_then(Value::as_str)
+}
+
+/// The elements of an array of integers.
+fn u64s(val: &Value) -> Option<Vec<u64>> {
+    val.as_array()?.iter().map(Value::as_u64).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+    use strategy::parse_rpc_request;
+    use {IME_JSON, TEST_JSON};
+
+    #[test]
+    fn parses_corpus() {
+        for line in TEST_JSON.lines().chain(IME_JSON.lines()) {
+            let val = serde_json::from_str::<Value>(line).unwrap();
+            let (_, method, params) = parse_rpc_request(&val).unwrap();
+            assert!(Request::from_json(method, params).is_ok(), "{}", line);
+        }
+        let val = json!({"view_id": "view-id-1", "method": "click", "params": [3, 10, 0]});
+        assert_eq!(Request::from_json("edit", &val),
+                   Ok(Request::Edit {
+                       view_id: "view-id-1",
+                       edit_command: EditCommand::Click(3, 10, 0, None),
+                   }));
+        let bad = json!({"view_id": "view-id-1", "method": "scroll", "params": [1]});
+        assert_eq!(Request::from_json("edit", &bad),
+                   Err(Error::MalformedParams("edit/scroll".into())));
+        assert!(Request::from_json("frobnicate", &json!({})).is_err());
+    }
+}
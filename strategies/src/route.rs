@@ -0,0 +1,55 @@
+//! The envelope alone, as a router would read it.
+//!
+//! Something that only forwards messages, as core does a plugin's RPCs,
+//! needs the method, to know where to send a message, and the id, to match
+//! up the answer, but not the params. `Envelope` skips them with serde's
+//! `IgnoredAny`, which still scans them to find where they end, and checks
+//! that they're well-formed, but builds nothing. That's as little as a serde
+//! strategy can do with a message, so the others' times are best read as
+//! what they add to it.
+
+use std::borrow::Cow;
+
+use serde::de::{Deserialize, Deserializer, IgnoredAny};
+
+use rpc3::RpcId;
+
+#[derive(Deserialize, Debug)]
+pub struct Envelope<'a> {
+    /// `None` if the field is missing, and `Some(RpcId::Null)` if it's
+    /// present but null.
+    #[serde(borrow, default, deserialize_with = "present")]
+    pub id: Option<RpcId<'a>>,
+    #[serde(borrow)]
+    pub method: Cow<'a, str>,
+    pub params: IgnoredAny,
+}
+
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where D: Deserializer<'de>, T: Deserialize<'de>
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+    use TEST_JSON;
+
+    #[test]
+    fn envelopes() {
+        for line in TEST_JSON.lines() {
+            let env = serde_json::from_str::<Envelope>(line).unwrap();
+            let val = serde_json::from_str::<Value>(line).unwrap();
+            assert_eq!(env.method, val["method"].as_str().unwrap());
+            assert_eq!(env.id.is_some(), val.get("id").is_some());
+        }
+        let env = serde_json::from_str::<Envelope>(
+            r#"{"id":null,"method":"frobnicate","params":{"nonsense":[1,{}]}}"#).unwrap();
+        assert_eq!((env.id, env.method), (Some(RpcId::Null), Cow::Borrowed("frobnicate")));
+        assert!(serde_json::from_str::<Envelope>(r#"{"method":"edit"}"#).is_err());
+        assert!(serde_json::from_str::<Envelope>(r#"{"method":"edit","params":[}"#).is_err());
+    }
+}
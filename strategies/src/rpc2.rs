@@ -0,0 +1,726 @@
+// Copyright 2016 Google Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! RPC handling for communications with front-end.
+
+use std::fmt;
+use serde_json::{self, Value};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{self, Serialize, Serializer};
+
+
+// =============================================================================
+//  Command types
+// =============================================================================
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EmptyStruct {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum CoreNotification {
+    Edit(EditCommand<EditNotification>),
+    Plugin(PluginNotification),
+    CloseView { view_id: ViewIdentifier },
+    Save { view_id: ViewIdentifier, file_path: String },
+    SetTheme { theme_name: String },
+    ClientStarted(EmptyStruct),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum CoreRequest {
+    Edit(EditCommand<EditRequest>),
+    NewView { file_path: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditCommand<T> {
+    pub view_id: ViewIdentifier,
+    /// Edits with the same undo group are undone together. (Protocol 2.)
+    pub undo_group: Option<u64>,
+    /// A description of the edit, for the undo menu. (Protocol 2.)
+    pub annotation: Option<String>,
+    pub cmd: T,
+}
+
+/// An enum representing touch and mouse gestures applied to the text.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GestureType {
+    PointSelect,
+    ToggleSel,
+    RangeSelect,
+    LineSelect,
+    WordSelect,
+    MultiLineSelect,
+    MultiWordSelect,
+}
+
+// NOTE:
+// Several core protocol commands use a params array to pass arguments
+// which are named, internally. these two types use custom Serialize /
+// Deserialize impls to accomodate this.
+#[derive(PartialEq, Eq, Debug)]
+pub struct LineRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct MouseAction {
+    pub line: u64,
+    pub column: u64,
+    pub flags: u64,
+    pub click_count: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum EditNotification {
+    Insert { chars: String },
+    DeleteForward,
+    DeleteBackward,
+    DeleteWordForward,
+    DeleteWordBackward,
+    DeleteToEndOfParagraph,
+    DeleteToBeginningOfLine,
+    InsertNewline,
+    InsertTab,
+    MoveUp,
+    MoveUpAndModifySelection,
+    MoveDown,
+    MoveDownAndModifySelection,
+    MoveLeft,
+    MoveLeftAndModifySelection,
+    MoveRight,
+    MoveRightAndModifySelection,
+    MoveWordLeft,
+    MoveWordLeftAndModifySelection,
+    MoveWordRight,
+    MoveWordRightAndModifySelection,
+    MoveToBeginningOfParagraph,
+    MoveToEndOfParagraph,
+    MoveToLeftEndOfLine,
+    MoveToLeftEndOfLineAndModifySelection,
+    MoveToRightEndOfLine,
+    MoveToRightEndOfLineAndModifySelection,
+    MoveToBeginningOfDocument,
+    MoveToBeginningOfDocumentAndModifySelection,
+    MoveToEndOfDocument,
+    MoveToEndOfDocumentAndModifySelection,
+    ScrollPageUp,
+    PageUpAndModifySelection,
+    ScrollPageDown,
+    PageDownAndModifySelection,
+    SelectAll,
+    AddSelectionAbove,
+    AddSelectionBelow,
+    Scroll(LineRange),
+    GotoLine { line: u64 },
+    RequestLines(LineRange),
+    Yank,
+    Transpose,
+    Click(MouseAction),
+    Drag(MouseAction),
+    Gesture { line: u64, column: u64, ty: GestureType},
+    Undo,
+    Redo,
+    FindNext { wrap_around: bool, allow_same: bool },
+    FindPrevious { wrap_around: bool },
+    DebugRewrap,
+    DebugPrintSpans,
+    /// Input method composition: `chars` is the text being composed,
+    /// `selection` the range selected within it, and `replacement` the
+    /// range of the document it replaces, if not the selection.
+    SetMarkedText { chars: String, selection: (u64, u64), replacement: Option<(u64, u64)> },
+    UnmarkText,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum EditRequest {
+    Cut,
+    Copy,
+    Find { chars: Option<String>, case_sensitive: bool },
+}
+
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "command")]
+#[serde(rename_all = "snake_case")]
+pub enum PluginNotification {
+    Start { view_id: ViewIdentifier, plugin_name: String },
+    Stop { view_id: ViewIdentifier, plugin_name: String },
+    PluginRpc { view_id: ViewIdentifier, receiver: String, rpc: PlaceholderRpc },
+}
+
+// =============================================================================
+//  Client notifications
+// =============================================================================
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum ClientNotification {
+    Update { view_id: ViewIdentifier, update: Update },
+    DefStyle(StyleDef),
+    /// Something the user should be told, such as a failed save.
+    Alert { msg: String },
+    AvailableThemes { themes: Vec<String> },
+    AvailablePlugins { view_id: ViewIdentifier, plugins: Vec<PluginDescription> },
+    /// The settings that changed, or all of them for a new view. Any of
+    /// xi's settings can be here, so they're left as JSON.
+    ConfigChanged { view_id: ViewIdentifier, changes: serde_json::Map<String, Value> },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PluginDescription {
+    pub name: String,
+    pub running: bool,
+}
+
+/// Notifications sent from a plugin to core.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum PluginCoreNotification {
+    UpdateSpans { view_id: ViewIdentifier, start: u64, len: u64, spans: Vec<StyleSpan>, rev: u64 },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Update {
+    pub selection: Selection,
+    pub pristine: bool,
+}
+
+/// A set of selected regions. With multiple cursors there may be hundreds.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Selection(pub Vec<SelRegion>);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SelRegion {
+    pub start: u64,
+    pub end: u64,
+    /// The horizontal position to use when moving vertically, if not the start.
+    pub horiz: Option<u64>,
+    pub affinity: Affinity,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Affinity {
+    Downstream,
+    Upstream,
+}
+
+/// A styled region, sent as `[start, len, style_id]`.
+///
+/// Syntax highlighting sends thousands of these per message.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StyleSpan(pub u64, pub u64, pub u64);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct StyleDef {
+    pub id: u64,
+    pub fg_color: Option<u32>,
+    pub bg_color: Option<u32>,
+    pub weight: Option<u16>,
+    pub italic: Option<bool>,
+    pub underline: Option<bool>,
+}
+
+// =============================================================================
+//  Responses
+// =============================================================================
+
+/// Core's answer to a request, sent back with the request's id.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Response<T> {
+    pub id: RpcId,
+    pub result: T,
+}
+
+/// The result of an `EditRequest`: for cut and copy, the text, or null if
+/// the selection was empty; for find, the start and end of each match.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum EditResult {
+    Text(Option<String>),
+    Matches(Vec<(u64, u64)>),
+}
+
+// =============================================================================
+//  Plugin notifications and requests
+// =============================================================================
+
+/// Notifications sent from core to a plugin. Plugins call core their host.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum HostNotification {
+    Ping(EmptyStruct),
+    Initialize { plugin_id: u64, buffer_info: Vec<BufferInfo> },
+    DidSave { view_id: ViewIdentifier, path: String },
+    DidClose { view_id: ViewIdentifier },
+    Shutdown(EmptyStruct),
+}
+
+/// Requests sent from core to a plugin. Core waits for the plugin to answer
+/// an update before sending the next, so that spans can't arrive for a
+/// revision the plugin hasn't seen.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum HostRequest {
+    Update(PluginUpdate),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BufferInfo {
+    pub buffer_id: u64,
+    pub path: Option<String>,
+    pub views: Vec<ViewIdentifier>,
+    pub rev: u64,
+    pub buf_size: u64,
+    pub nb_lines: u64,
+    pub syntax: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PluginUpdate {
+    pub view_id: ViewIdentifier,
+    /// `None` if the edit was too big to be worth sending; the plugin has
+    /// to ask for the text it needs.
+    pub delta: Option<RopeDelta>,
+    pub new_len: u64,
+    pub rev: u64,
+    pub edit_type: String,
+    pub author: String,
+}
+
+/// An edit, as the ranges of the old text to keep and the new text between
+/// them. Pasting a file sends all of it in one insert.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RopeDelta {
+    pub els: Vec<DeltaElement>,
+    pub base_len: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeltaElement {
+    /// The start and end of a range of the old text.
+    Copy(u64, u64),
+    Insert(String),
+}
+
+// Serialize / Deserialize
+
+impl<T: Serialize> Serialize for EditCommand<T>
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let mut v = serde_json::to_value(&self.cmd).map_err(ser::Error::custom)?;
+        // view_id lives alongside the edit method, as in the deserialize impl
+        v["view_id"] = json!(self.view_id);
+        if let Some(group) = self.undo_group {
+            v["undo_group"] = json!(group);
+        }
+        if let Some(ref annotation) = self.annotation {
+            v["annotation"] = json!(annotation);
+        }
+        v.serialize(serializer)
+    }
+}
+
+impl<T: Serialize> EditCommand<T> {
+    /// This command, to be serialized without going through a `Value`.
+    ///
+    /// The impl above builds a `Value` of the edit and then serializes that,
+    /// allocating a map and a string for each key on the way, for every edit
+    /// that's echoed or logged. This writes the edit's fields into the same
+    /// `SerializeMap` as view_id, through `#[serde(flatten)]`, serde's one
+    /// public way of handing a map to another type's impl. The keys come out
+    /// in another order, but it's the same object.
+    pub fn flattened(&self) -> FlatEditCommand<'_, T> {
+        FlatEditCommand {
+            cmd: &self.cmd,
+            view_id: &self.view_id,
+            undo_group: self.undo_group,
+            annotation: self.annotation.as_ref().map(String::as_str),
+        }
+    }
+}
+
+/// An `EditCommand`, borrowed to be serialized; see `EditCommand::flattened`.
+#[derive(Serialize, Debug)]
+pub struct FlatEditCommand<'a, T: 'a> {
+    #[serde(flatten)]
+    cmd: &'a T,
+    view_id: &'a ViewIdentifier,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    undo_group: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    annotation: Option<&'a str>,
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for EditCommand<T>
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        #[derive(Deserialize)]
+        struct InnerId {
+            view_id: ViewIdentifier,
+            #[serde(default)]
+            undo_group: Option<u64>,
+            #[serde(default)]
+            annotation: Option<String>,
+        }
+
+        let mut v = Value::deserialize(deserializer)?;
+        let helper = InnerId::deserialize(&v).map_err(de::Error::custom)?;
+        let InnerId { view_id, undo_group, annotation } = helper;
+        // if params are empty, remove them
+        let remove_params = match v.get("params") {
+            Some(&Value::Object(ref obj)) => obj.is_empty(),
+            Some(&Value::Array(ref arr)) => arr.is_empty(),
+            Some(_) => return Err(de::Error::custom("'params' field, if present, must be object or array.")),
+            None => false,
+        };
+
+        if remove_params {
+            v.as_object_mut().map(|v| v.remove("params"));
+        }
+
+        let cmd = T::deserialize(v).map_err(de::Error::custom)?;
+        Ok(EditCommand { view_id, undo_group, annotation, cmd })
+    }
+}
+
+impl Serialize for MouseAction
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        // drag has no click count; omit it rather than sending null
+        let v = match self.click_count {
+            Some(count) => json!([self.line, self.column, self.flags, count]),
+            None => json!([self.line, self.column, self.flags]),
+        };
+        v.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MouseAction
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let v: Vec<u64> = Vec::deserialize(deserializer)?;
+        if v.len() < 3 || v.len() > 4 {
+            return Err(de::Error::invalid_length(v.len(), &"3 or 4 elements"));
+        }
+        let click_count = if v.len() == 4 { Some(v[3]) } else { None };
+        Ok(MouseAction { line: v[0], column: v[1], flags: v[2], click_count: click_count })
+    }
+}
+
+impl Serialize for LineRange
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let as_tup = (self.start, self.end);
+        let v = serde_json::to_value(&as_tup).map_err(ser::Error::custom)?;
+        v.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LineRange
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        #[derive(Deserialize)]
+        struct TwoTuple(u64, u64);
+
+        let tup = TwoTuple::deserialize(deserializer)?;
+        Ok(LineRange { start: tup.0, end: tup.1 })
+    }
+}
+
+/// ViewIdentifiers are the primary means of routing messages between xi-core and a client view.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ViewIdentifier(String);
+
+impl ViewIdentifier {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a str> for ViewIdentifier {
+    fn from(s: &'a str) -> Self {
+        ViewIdentifier(s.to_owned())
+    }
+}
+
+/// The id of a request. JSON-RPC allows numbers, strings, and null; xi
+/// frontends have only sent integers so far, but nothing stops them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RpcId {
+    Number(u64),
+    String(String),
+    Null,
+}
+
+impl Serialize for RpcId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        match *self {
+            RpcId::Number(n) => serializer.serialize_u64(n),
+            RpcId::String(ref s) => serializer.serialize_str(s),
+            RpcId::Null => serializer.serialize_unit(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RpcId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_any(RpcIdVisitor)
+    }
+}
+
+struct RpcIdVisitor;
+
+impl<'de> de::Visitor<'de> for RpcIdVisitor {
+    type Value = RpcId;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a non-negative integer, a string, or null")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<RpcId, E> {
+        Ok(RpcId::Number(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<RpcId, E> {
+        if v < 0 {
+            Err(E::invalid_value(de::Unexpected::Signed(v), &self))
+        } else {
+            Ok(RpcId::Number(v as u64))
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<RpcId, E> {
+        Ok(RpcId::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<RpcId, E> {
+        Ok(RpcId::String(v))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<RpcId, E> {
+        Ok(RpcId::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<RpcId, E> {
+        Ok(RpcId::Null)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// A placeholder type which can represent a generic RPC.
+///
+/// This is the type used for custom plugin commands, which may have arbitrary
+/// method names and parameters.
+pub struct PlaceholderRpc {
+    pub method: String,
+    pub params: Value,
+    pub rpc_type: RpcType,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RpcType {
+    Notification, Request
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::*;
+    use corpus::{self, ANNOTATED_JSON, TEST_JSON, TO_CLIENT_JSON, TO_PLUGIN_JSON};
+
+    #[test]
+    fn update_round_trip() {
+        let regions = (0..300).map(|i| SelRegion {
+            start: i * 10,
+            end: i * 10 + 4,
+            horiz: if i % 2 == 0 { None } else { Some(3) },
+            affinity: Affinity::Downstream,
+        }).collect();
+        let update = ClientNotification::Update {
+            view_id: "view-id-1".into(),
+            update: Update { selection: Selection(regions), pristine: false },
+        };
+        let json = serde_json::to_string(&update).unwrap();
+        assert!(json.starts_with(r#"{"method":"update","params":"#));
+        assert_eq!(serde_json::from_str::<ClientNotification>(&json).unwrap(), update);
+    }
+
+    #[test]
+    fn short_mouse_action() {
+        let json = r#"{"method":"click","params":[3,10]}"#;
+        assert!(serde_json::from_str::<EditNotification>(json).is_err());
+    }
+
+    #[test]
+    fn id_shapes() {
+        for &(json, ref id) in &[("0", RpcId::Number(0)),
+                                 ("18446744073709551615", RpcId::Number(u64::MAX)),
+                                 (r#""req-1""#, RpcId::String("req-1".into())),
+                                 (r#""\u00e9""#, RpcId::String("\u{e9}".into())),
+                                 ("null", RpcId::Null)] {
+            assert_eq!(&serde_json::from_str::<RpcId>(json).unwrap(), id);
+            let value = serde_json::from_str::<Value>(json).unwrap();
+            assert_eq!(&serde_json::from_value::<RpcId>(value).unwrap(), id);
+            assert_eq!(serde_json::from_str::<RpcId>(&serde_json::to_string(id).unwrap()).unwrap(), *id);
+        }
+        for bad in &["-1", "1.5", "true", "[]", "{}"] {
+            assert!(serde_json::from_str::<RpcId>(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn spans_round_trip() {
+        let spans = PluginCoreNotification::UpdateSpans {
+            view_id: "view-id-1".into(),
+            start: 0,
+            len: 40,
+            spans: vec![StyleSpan(0, 4, 2), StyleSpan(5, 12, 3)],
+            rev: 7,
+        };
+        let json = serde_json::to_string(&spans).unwrap();
+        assert!(json.contains(r#""spans":[[0,4,2],[5,12,3]]"#));
+        assert_eq!(serde_json::from_str::<PluginCoreNotification>(&json).unwrap(), spans);
+
+        let def = ClientNotification::DefStyle(StyleDef {
+            id: 2, fg_color: Some(0xff00_00ff), bg_color: None,
+            weight: Some(700), italic: None, underline: Some(true),
+        });
+        let json = serde_json::to_string(&def).unwrap();
+        assert_eq!(serde_json::from_str::<ClientNotification>(&json).unwrap(), def);
+    }
+
+    #[test]
+    fn housekeeping() {
+        for line in TO_CLIENT_JSON.lines() {
+            let msg = serde_json::from_str::<ClientNotification>(line).unwrap();
+            assert_eq!(serde_json::to_value(&msg).unwrap(),
+                       serde_json::from_str::<Value>(line).unwrap());
+        }
+        let json = r#"{"method":"config_changed","params":{"view_id":"view-id-2","changes":{}}}"#;
+        match serde_json::from_str::<ClientNotification>(json).unwrap() {
+            ClientNotification::ConfigChanged { ref changes, .. } => assert!(changes.is_empty()),
+            other => panic!("expected config_changed, got {:?}", other),
+        }
+        let json = r#"{"method":"available_plugins","params":{"view_id":"v","plugins":[{}]}}"#;
+        assert!(serde_json::from_str::<ClientNotification>(json).is_err());
+    }
+
+    #[test]
+    fn host_messages() {
+        for line in TO_PLUGIN_JSON.lines() {
+            let mut val = serde_json::from_str::<Value>(line).unwrap();
+            if val.as_object_mut().and_then(|obj| obj.remove("id")).is_some() {
+                let req = serde_json::from_value::<HostRequest>(val.clone()).unwrap();
+                assert_eq!(serde_json::to_value(&req).unwrap(), val);
+            } else {
+                let msg = serde_json::from_value::<HostNotification>(val.clone()).unwrap();
+                assert_eq!(serde_json::to_value(&msg).unwrap(), val);
+            }
+        }
+        let json = r#"{"els":[{"copy":[0,52]},{"insert":"\n"}],"base_len":60}"#;
+        let delta = serde_json::from_str::<RopeDelta>(json).unwrap();
+        assert_eq!(delta.els, vec![DeltaElement::Copy(0, 52), DeltaElement::Insert("\n".into())]);
+    }
+
+    #[test]
+    fn edit_responses() {
+        let copied = Response { id: RpcId::Number(42), result: EditResult::Text(None) };
+        assert_eq!(serde_json::to_string(&copied).unwrap(), r#"{"id":42,"result":null}"#);
+        let found = Response {
+            id: RpcId::String("req-1".into()),
+            result: EditResult::Matches(vec![(3, 10), (40, 47)]),
+        };
+        let json = serde_json::to_string(&found).unwrap();
+        assert_eq!(json, r#"{"id":"req-1","result":[[3,10],[40,47]]}"#);
+        assert_eq!(serde_json::from_str::<Response<EditResult>>(&json).unwrap(), found);
+    }
+
+    #[test]
+    fn flattened_edits() {
+        let edits = ANNOTATED_JSON.lines()
+            .filter_map(|l| serde_json::from_str::<CoreNotification>(l).ok())
+            .filter_map(|msg| match msg {
+                CoreNotification::Edit(edit) => Some(edit),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(edits.iter().any(|e| e.undo_group.is_some()));
+        for edit in &edits {
+            let flat = serde_json::to_value(&edit.flattened()).unwrap();
+            assert_eq!(flat, serde_json::to_value(edit).unwrap());
+            assert_eq!(&serde_json::from_value::<EditCommand<EditNotification>>(flat).unwrap(),
+                       edit);
+        }
+    }
+
+    #[test]
+    fn corpus_round_trip() {
+        let messages = TEST_JSON.lines()
+            .map(|l| serde_json::from_str::<Value>(l).unwrap())
+            .filter(|v| v.get("id").is_none())
+            .map(|v| serde_json::from_value::<CoreNotification>(v).unwrap())
+            .collect::<Vec<_>>();
+        assert!(!messages.is_empty());
+
+        let name = format!("der_bench_round_trip_rpc2_{}.jsonl", process::id());
+        let path = env::temp_dir().join(name);
+        corpus::write_jsonl(&messages, &path).unwrap();
+        let lines = corpus::read_jsonl(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(lines.len(), messages.len());
+        for (line, msg) in lines.iter().zip(messages.iter()) {
+            let reread = serde_json::from_str::<CoreNotification>(line).unwrap();
+            assert_eq!(&reread, msg);
+        }
+    }
+}
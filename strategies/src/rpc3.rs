@@ -0,0 +1,465 @@
+//! A coarse implementation of what this would look like if we were just
+//! borrowing directly.
+
+
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer};
+
+use rpc2;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EmptyStruct {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum CoreNotification<'a> {
+    Edit(EditNotification<'a>),
+    Plugin(rpc2::PluginNotification),
+    CloseView { view_id: &'a str },
+    Save { view_id: &'a str, file_path: &'a str },
+    SetTheme { theme_name: &'a str },
+    ClientStarted(EmptyStruct),
+    NewView { file_path: Option<&'a str> },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+//pub struct InsertParams<'a> { chars: &'a str }
+pub struct InsertParams { pub chars: String }
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RequestLinesParams(pub usize, pub usize);
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ClickParams(pub usize, pub usize, pub usize, pub usize);
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct DragParams(pub usize, pub usize, pub usize);
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GestureParams { pub line: u64, pub column: u64, pub ty: rpc2::GestureType }
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GotoLineParams { pub line: u64 }
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct FindNextParams { pub wrap_around: bool, pub allow_same: bool }
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct FindPreviousParams { pub wrap_around: bool }
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct MarkedTextParams<'a> {
+    #[serde(borrow)]
+    pub chars: Cow<'a, str>,
+    pub selection: (u64, u64),
+    #[serde(default)]
+    pub replacement: Option<(u64, u64)>,
+}
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct FindParams<'a> {
+    #[serde(borrow)]
+    pub chars: Option<Cow<'a, str>>,
+    case_sensitive: bool,
+}
+
+
+// The edits that change the text carry protocol 2's optional undo group and
+// annotation. Other edits are free to send them too, and they're ignored.
+// Unset, they're left out when serializing, as rpc2 leaves them out; edits
+// without a view id here are written without one.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method")]
+pub enum EditNotification<'a> {
+    Insert {
+        view_id: &'a str,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        undo_group: Option<u64>,
+        #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+        annotation: Option<Cow<'a, str>>,
+        params: InsertParams,
+    },
+    RequestLines { view_id: &'a str, params: RequestLinesParams },
+    Scroll { view_id: &'a str, params: RequestLinesParams },
+    MoveWordRight { view_id: &'a str },
+    MoveWordLeft { view_id: &'a str },
+    DeleteForward {
+        view_id: &'a str,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        undo_group: Option<u64>,
+        #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+        annotation: Option<Cow<'a, str>>,
+    },
+    DeleteBackward {
+        view_id: &'a str,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        undo_group: Option<u64>,
+        #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+        annotation: Option<Cow<'a, str>>,
+    },
+    InsertNewline {
+        view_id: &'a str,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        undo_group: Option<u64>,
+        #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+        annotation: Option<Cow<'a, str>>,
+    },
+    InsertTab {
+        view_id: &'a str,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        undo_group: Option<u64>,
+        #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+        annotation: Option<Cow<'a, str>>,
+    },
+    Click { view_id: &'a str, params: ClickParams },
+    Drag { view_id: &'a str, params: DragParams },
+    Gesture { view_id: &'a str, params: GestureParams },
+    Cut { view_id: &'a str },
+    Copy { view_id: &'a str },
+    Find { view_id: &'a str, #[serde(borrow)] params: FindParams<'a> },
+    DeleteWordForward,
+    DeleteWordBackward,
+    DeleteToEndOfParagraph,
+    DeleteToBeginningOfLine,
+    MoveUp,
+    MoveUpAndModifySelection,
+    MoveDown,
+    MoveDownAndModifySelection,
+    MoveLeft,
+    MoveLeftAndModifySelection,
+    MoveRight,
+    MoveRightAndModifySelection,
+    MoveWordLeftAndModifySelection,
+    MoveWordRightAndModifySelection,
+    MoveToBeginningOfParagraph,
+    MoveToEndOfParagraph,
+    MoveToLeftEndOfLine,
+    MoveToLeftEndOfLineAndModifySelection,
+    MoveToRightEndOfLine,
+    MoveToRightEndOfLineAndModifySelection,
+    MoveToBeginningOfDocument,
+    MoveToBeginningOfDocumentAndModifySelection,
+    MoveToEndOfDocument,
+    MoveToEndOfDocumentAndModifySelection,
+    ScrollPageUp,
+    PageUpAndModifySelection,
+    ScrollPageDown,
+    PageDownAndModifySelection,
+    SelectAll,
+    AddSelectionAbove,
+    AddSelectionBelow,
+    GotoLine { view_id: &'a str, params: GotoLineParams },
+    Yank,
+    Transpose,
+    Undo,
+    Redo,
+    FindNext { view_id: &'a str, params: FindNextParams },
+    FindPrevious { view_id: &'a str, params: FindPreviousParams },
+    DebugRewrap,
+    DebugPrintSpans,
+    SetMarkedText { view_id: &'a str, #[serde(borrow)] params: MarkedTextParams<'a> },
+    UnmarkText { view_id: &'a str },
+}
+
+/// What core sends a plugin, as rpc2 has it, borrowing the ids and the
+/// names. Paths and inserted text can have escapes, so they're `Cow`s.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum HostNotification<'a> {
+    Ping(EmptyStruct),
+    Initialize {
+        plugin_id: u64,
+        #[serde(borrow)]
+        buffer_info: Vec<BufferInfo<'a>>,
+    },
+    DidSave {
+        view_id: &'a str,
+        #[serde(borrow)]
+        path: Cow<'a, str>,
+    },
+    DidClose { view_id: &'a str },
+    Shutdown(EmptyStruct),
+}
+
+/// rpc2's `HostRequest`, borrowing.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum HostRequest<'a> {
+    Update(#[serde(borrow)] PluginUpdate<'a>),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BufferInfo<'a> {
+    pub buffer_id: u64,
+    #[serde(borrow)]
+    pub path: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub views: Vec<&'a str>,
+    pub rev: u64,
+    pub buf_size: u64,
+    pub nb_lines: u64,
+    pub syntax: &'a str,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PluginUpdate<'a> {
+    pub view_id: &'a str,
+    #[serde(borrow)]
+    pub delta: Option<RopeDelta<'a>>,
+    pub new_len: u64,
+    pub rev: u64,
+    pub edit_type: &'a str,
+    pub author: &'a str,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RopeDelta<'a> {
+    #[serde(borrow)]
+    pub els: Vec<DeltaElement<'a>>,
+    pub base_len: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeltaElement<'a> {
+    Copy(u64, u64),
+    Insert(#[serde(borrow)] Cow<'a, str>),
+}
+
+/// The id of a request, borrowed from the input where possible; string ids
+/// containing escapes have to be copied.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum RpcId<'a> {
+    Number(u64),
+    String(Cow<'a, str>),
+    Null,
+}
+
+/// Just the id of a message, for deciding whether it's a request before
+/// parsing the rest.
+///
+/// `id` is `None` if the field is missing, and `Some(RpcId::Null)` if it's
+/// present but null.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct IdPeek<'a> {
+    #[serde(borrow, default, deserialize_with = "present")]
+    pub id: Option<RpcId<'a>>,
+}
+
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where D: Deserializer<'de>, T: Deserialize<'de>
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RpcId<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_any(RpcIdVisitor(PhantomData))
+    }
+}
+
+struct RpcIdVisitor<'a>(PhantomData<&'a ()>);
+
+impl<'de: 'a, 'a> de::Visitor<'de> for RpcIdVisitor<'a> {
+    type Value = RpcId<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a non-negative integer, a string, or null")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<RpcId<'a>, E> {
+        Ok(RpcId::Number(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<RpcId<'a>, E> {
+        if v < 0 {
+            Err(E::invalid_value(de::Unexpected::Signed(v), &self))
+        } else {
+            Ok(RpcId::Number(v as u64))
+        }
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<RpcId<'a>, E> {
+        Ok(RpcId::String(Cow::Borrowed(v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<RpcId<'a>, E> {
+        Ok(RpcId::String(Cow::Owned(v.to_owned())))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<RpcId<'a>, E> {
+        Ok(RpcId::String(Cow::Owned(v)))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<RpcId<'a>, E> {
+        Ok(RpcId::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<RpcId<'a>, E> {
+        Ok(RpcId::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+    use corpus::{self, ANNOTATED_JSON, GESTURE_JSON, IME_JSON, TEST_JSON, TO_PLUGIN_JSON};
+    use corpus::generate;
+    use serde_json::Value;
+
+    use canonical;
+    use peek;
+
+    #[test]
+    fn id_shapes() {
+        let peek = |json| serde_json::from_str::<IdPeek>(json).unwrap().id;
+        assert_eq!(peek(r#"{"id":7,"method":"new_view"}"#), Some(RpcId::Number(7)));
+        assert_eq!(peek(r#"{"method":"new_view","id":null}"#), Some(RpcId::Null));
+        assert_eq!(peek(r#"{"method":"new_view"}"#), None);
+
+        match peek(r#"{"id":"req-1"}"#) {
+            Some(RpcId::String(Cow::Borrowed("req-1"))) => (),
+            other => panic!("expected a borrowed id, got {:?}", other),
+        }
+        match peek(r#"{"id":"req-\u00e9"}"#) {
+            Some(RpcId::String(Cow::Owned(ref s))) if s == "req-\u{e9}" => (),
+            other => panic!("expected an owned id, got {:?}", other),
+        }
+
+        assert!(serde_json::from_str::<IdPeek>(r#"{"id":-1}"#).is_err());
+        assert!(serde_json::from_str::<IdPeek>(r#"{"id":[0]}"#).is_err());
+    }
+
+    #[test]
+    fn gestures() {
+        let mut messages = Vec::new();
+        for line in GESTURE_JSON.lines() {
+            assert!(serde_json::from_str::<CoreNotification>(line).is_ok(), "{}", line);
+            messages.push(serde_json::from_str::<rpc2::CoreNotification>(line).unwrap());
+        }
+
+        let mut out = Vec::new();
+        corpus::write_lines(&messages, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        for (line, msg) in out.lines().zip(messages.iter()) {
+            assert_eq!(&serde_json::from_str::<rpc2::CoreNotification>(line).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn marked_text() {
+        for line in IME_JSON.lines().skip(1) {
+            assert!(serde_json::from_str::<CoreNotification>(line).is_ok(), "{}", line);
+            assert!(serde_json::from_str::<rpc2::CoreNotification>(line).is_ok(), "{}", line);
+        }
+        let json = r#"{"method":"edit","params":{"view_id":"v","method":"set_marked_text",
+                       "params":{"chars":"日本語","selection":[0,3]}}}"#;
+        match serde_json::from_str::<CoreNotification>(json).unwrap() {
+            CoreNotification::Edit(EditNotification::SetMarkedText { params, .. }) => {
+                assert_eq!(params.chars, Cow::Borrowed("日本語"));
+                assert_eq!(params.selection, (0, 3));
+                assert_eq!(params.replacement, None);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn annotations() {
+        for line in ANNOTATED_JSON.lines() {
+            assert!(serde_json::from_str::<CoreNotification>(line).is_ok(), "{}", line);
+            // rpc2 has new_view as a request, and no annotations on those
+            if peek::has_id(line) {
+                continue;
+            }
+            // rpc2 writes the fields back out as it read them
+            let msg = serde_json::from_str::<rpc2::CoreNotification>(line).unwrap();
+            let json = serde_json::to_string(&msg).unwrap();
+            assert_eq!(serde_json::from_str::<rpc2::CoreNotification>(&json).unwrap(), msg);
+        }
+        let json = r#"{"method":"edit","params":{"view_id":"v","undo_group":3,
+                       "annotation":"pasted \"x\"","method":"delete_backward","params":[]}}"#;
+        let edit = serde_json::from_str::<CoreNotification>(json).unwrap();
+        match edit {
+            CoreNotification::Edit(EditNotification::DeleteBackward {
+                undo_group, annotation, ..
+            }) => {
+                assert_eq!(undo_group, Some(3));
+                assert_eq!(annotation.as_ref().map(|a| a.as_ref()), Some("pasted \"x\""));
+            }
+            other => panic!("{:?}", other),
+        }
+        match serde_json::from_str::<rpc2::CoreNotification>(json).unwrap() {
+            rpc2::CoreNotification::Edit(edit) => {
+                assert_eq!(edit.undo_group, Some(3));
+                assert_eq!(edit.annotation.as_ref().map(|a| a.as_str()), Some("pasted \"x\""));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn serializes_as_rpc2() {
+        let escaped = concat!(r#"{"method":"edit","params":{"view_id":"v","method":"insert","#,
+                              r#""annotation":"pasted \"x\"\n","params":{"chars":"\t\u00e9\""}}}"#);
+        let lines = TEST_JSON.lines().chain(ANNOTATED_JSON.lines()).chain(IME_JSON.lines())
+            .chain(Some(escaped))
+            .filter(|line| !peek::has_id(line));
+        for line in lines {
+            let msg = serde_json::from_str::<CoreNotification>(line).unwrap();
+            let json = serde_json::to_string(&msg).unwrap();
+            assert_eq!(serde_json::from_str::<CoreNotification>(&json).unwrap(), msg);
+
+            let rpc2_json = serde_json::to_string(
+                &serde_json::from_str::<rpc2::CoreNotification>(line).unwrap()).unwrap();
+            match msg {
+                // rpc2 writes an edit's fields through a `Value`, sorted
+                CoreNotification::Edit(_) => {
+                    let mut expected = serde_json::from_str::<Value>(&rpc2_json).unwrap();
+                    if !json.contains(r#""view_id""#) {
+                        expected["params"].as_object_mut().unwrap().remove("view_id");
+                    }
+                    assert_eq!(canonical::canonicalize(&json).unwrap(),
+                               canonical::write(&expected), "{}", line);
+                }
+                _ => assert_eq!(json, rpc2_json),
+            }
+        }
+    }
+
+    #[test]
+    fn host_messages() {
+        let session = generate::plugin_session(200, 5);
+        let lines = TO_PLUGIN_JSON.lines().chain(session.iter().map(String::as_str));
+        for line in lines {
+            let json = if peek::has_id(line) {
+                let req = serde_json::from_str::<HostRequest>(line).unwrap();
+                let rpc2_req = serde_json::from_str::<rpc2::HostRequest>(line).unwrap();
+                assert_eq!(serde_json::to_string(&req).unwrap(),
+                           serde_json::to_string(&rpc2_req).unwrap());
+                serde_json::to_string(&req).unwrap()
+            } else {
+                let msg = serde_json::from_str::<HostNotification>(line).unwrap();
+                let rpc2_msg = serde_json::from_str::<rpc2::HostNotification>(line).unwrap();
+                assert_eq!(serde_json::to_string(&msg).unwrap(),
+                           serde_json::to_string(&rpc2_msg).unwrap());
+                serde_json::to_string(&msg).unwrap()
+            };
+            // the id isn't part of the message
+            let mut expected = serde_json::from_str::<Value>(line).unwrap();
+            expected.as_object_mut().unwrap().remove("id");
+            assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), expected);
+        }
+
+        let json = r#"{"els":[{"copy":[0,52]},{"insert":"fn"},{"insert":"\n"}],"base_len":60}"#;
+        let delta = serde_json::from_str::<RopeDelta>(json).unwrap();
+        match delta.els[..] {
+            [DeltaElement::Copy(0, 52), DeltaElement::Insert(Cow::Borrowed("fn")),
+             DeltaElement::Insert(Cow::Owned(ref newline))] if newline == "\n" => (),
+            ref other => panic!("unexpected elements {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Like rpc3, but dispatching one level at a time, and leaving the chars of
+//! an insert escaped until someone asks for them.
+//!
+//! Much of what xi does with inserted text (measuring it, forwarding it to
+//! plugins) doesn't need it decoded right away. Serde buffers the contents
+//! of internally tagged enums, which loses the raw input, so here params are
+//! kept as `RawValue`s and parsed once the method is known.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use serde_json::{self, value::RawValue};
+
+use unescape;
+
+/// The outer envelope of a message.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Envelope<'a> {
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<&'a RawValue>,
+    pub method: &'a str,
+    #[serde(borrow)]
+    pub params: &'a RawValue,
+}
+
+/// The params of an `edit`, which are themselves a method and params.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Edit<'a> {
+    pub view_id: &'a str,
+    /// Protocol 2's optional undo group and annotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undo_group: Option<u64>,
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<LazyStr<'a>>,
+    pub method: &'a str,
+    #[serde(borrow)]
+    pub params: &'a RawValue,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct InsertParams<'a> {
+    #[serde(borrow)]
+    pub chars: LazyStr<'a>,
+}
+
+impl<'a> Envelope<'a> {
+    pub fn parse(json: &'a str) -> serde_json::Result<Envelope<'a>> {
+        serde_json::from_str(json)
+    }
+
+    /// The params of an edit, if this is one.
+    pub fn edit(&self) -> Option<serde_json::Result<Edit<'a>>> {
+        if self.method == "edit" {
+            Some(serde_json::from_str(self.params.get()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Edit<'a> {
+    /// The params of an insert, if this is one.
+    pub fn insert(&self) -> Option<serde_json::Result<InsertParams<'a>>> {
+        if self.method == "insert" {
+            Some(serde_json::from_str(self.params.get()))
+        } else {
+            None
+        }
+    }
+}
+
+/// A JSON string, left escaped until it's asked for.
+#[derive(Clone, Copy)]
+pub struct LazyStr<'a>(&'a RawValue);
+
+impl<'a> LazyStr<'a> {
+    /// The contents of the string, still escaped.
+    pub fn raw(&self) -> &'a str {
+        let json = self.0.get();
+        &json[1..json.len() - 1]
+    }
+
+    /// Whether decoding the string will need to allocate.
+    pub fn is_escaped(&self) -> bool {
+        self.raw().contains('\\')
+    }
+
+    /// Decodes the string, borrowing if there are no escapes.
+    ///
+    /// serde_json has already checked that the string is valid JSON, so this
+    /// only fails on unpaired surrogates, which serde_json allows through.
+    pub fn get(&self) -> Option<Cow<'a, str>> {
+        let raw = self.raw();
+        if self.is_escaped() {
+            unescape::unescape(raw).map(Cow::Owned)
+        } else {
+            Some(Cow::Borrowed(raw))
+        }
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for LazyStr<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let raw = <&'de RawValue>::deserialize(deserializer)?;
+        if raw.get().starts_with('"') {
+            Ok(LazyStr(raw))
+        } else {
+            Err(de::Error::invalid_type(de::Unexpected::Other(raw.get()), &"a string"))
+        }
+    }
+}
+
+/// Forwards the string exactly as it arrived.
+impl<'a> Serialize for LazyStr<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'a> PartialEq for LazyStr<'a> {
+    fn eq(&self, other: &LazyStr<'a>) -> bool {
+        self.raw() == other.raw()
+    }
+}
+
+impl<'a> fmt::Debug for LazyStr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LazyStr({})", self.0.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_chars(json: &str) -> LazyStr {
+        let env = Envelope::parse(json).unwrap();
+        let edit = env.edit().unwrap().unwrap();
+        edit.insert().unwrap().unwrap().chars
+    }
+
+    #[test]
+    fn lazy_insert() {
+        let json = r#"{"method":"edit","params":{"view_id":"v","method":"insert","params":{"chars":"a\nb\/"}}}"#;
+        let chars = insert_chars(json);
+        assert_eq!(chars.raw(), r#"a\nb\/"#);
+        assert!(chars.is_escaped());
+        assert_eq!(chars.get(), Some(Cow::Owned("a\nb/".into())));
+
+        // params before method, which rpc3 would have to buffer
+        let json = r#"{"params":{"params":{"chars":"plain"},"method":"insert","view_id":"v"},"method":"edit"}"#;
+        let chars = insert_chars(json);
+        assert_eq!(chars.get(), Some(Cow::Borrowed("plain")));
+
+        let params = InsertParams { chars };
+        assert_eq!(serde_json::to_string(&params).unwrap(), r#"{"chars":"plain"}"#);
+    }
+
+    #[test]
+    fn not_a_string() {
+        for params in &[r#"{"chars":3}"#, r#"{"chars":null}"#, r#"{"chars":["a"]}"#] {
+            assert!(serde_json::from_str::<InsertParams>(params).is_err(), "{}", params);
+        }
+        let json = r#"{"method":"close_view","params":{"view_id":"v"}}"#;
+        assert!(Envelope::parse(json).unwrap().edit().is_none());
+    }
+}
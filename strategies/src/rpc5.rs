@@ -0,0 +1,494 @@
+//! rpc3, without serde_derive: every `Deserialize` impl here is written by
+//! hand.
+//!
+//! The derived impl for rpc3's internally tagged, 50-odd variant
+//! `EditNotification` is enormous: a field visitor per variant, and the
+//! whole message buffered into serde's `Content` in case the tag comes
+//! last. Here the method is matched as a string, params are kept as
+//! `RawValue`s until it's known what they should be (as in rpc4), and a
+//! single visitor handles the fields of every params object. Compare the
+//! instruction cache behavior with `der_bench counters`.
+//!
+//! A plugin's RPC is left unparsed.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde_json::{self, value::RawValue};
+
+use rpc2::GestureType;
+
+#[derive(Debug)]
+pub enum CoreNotification<'a> {
+    /// `undo_group` and `annotation` are protocol 2's, and optional.
+    Edit {
+        view_id: &'a str,
+        undo_group: Option<u64>,
+        annotation: Option<&'a str>,
+        cmd: EditNotification,
+    },
+    Plugin(PluginNotification<'a>),
+    CloseView { view_id: &'a str },
+    Save { view_id: &'a str, file_path: &'a str },
+    SetTheme { theme_name: &'a str },
+    ClientStarted,
+    NewView { file_path: Option<&'a str> },
+}
+
+#[derive(Debug)]
+pub enum PluginNotification<'a> {
+    Start { view_id: &'a str, plugin_name: &'a str },
+    Stop { view_id: &'a str, plugin_name: &'a str },
+    PluginRpc { view_id: &'a str, receiver: &'a str, rpc: &'a RawValue },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EditNotification {
+    Insert { chars: String },
+    RequestLines(u64, u64),
+    Scroll(u64, u64),
+    Click(u64, u64, u64, u64),
+    Drag(u64, u64, u64),
+    Gesture { line: u64, column: u64, ty: GestureType },
+    GotoLine { line: u64 },
+    FindNext { wrap_around: bool, allow_same: bool },
+    FindPrevious { wrap_around: bool },
+    Find { chars: Option<String>, case_sensitive: bool },
+    MoveWordRight,
+    MoveWordLeft,
+    DeleteForward,
+    DeleteBackward,
+    InsertNewline,
+    InsertTab,
+    Cut,
+    Copy,
+    DeleteWordForward,
+    DeleteWordBackward,
+    DeleteToEndOfParagraph,
+    DeleteToBeginningOfLine,
+    MoveUp,
+    MoveUpAndModifySelection,
+    MoveDown,
+    MoveDownAndModifySelection,
+    MoveLeft,
+    MoveLeftAndModifySelection,
+    MoveRight,
+    MoveRightAndModifySelection,
+    MoveWordLeftAndModifySelection,
+    MoveWordRightAndModifySelection,
+    MoveToBeginningOfParagraph,
+    MoveToEndOfParagraph,
+    MoveToLeftEndOfLine,
+    MoveToLeftEndOfLineAndModifySelection,
+    MoveToRightEndOfLine,
+    MoveToRightEndOfLineAndModifySelection,
+    MoveToBeginningOfDocument,
+    MoveToBeginningOfDocumentAndModifySelection,
+    MoveToEndOfDocument,
+    MoveToEndOfDocumentAndModifySelection,
+    ScrollPageUp,
+    PageUpAndModifySelection,
+    ScrollPageDown,
+    PageDownAndModifySelection,
+    SelectAll,
+    AddSelectionAbove,
+    AddSelectionBelow,
+    Yank,
+    Transpose,
+    Undo,
+    Redo,
+    DebugRewrap,
+    DebugPrintSpans,
+    SetMarkedText { chars: String, selection: (u64, u64), replacement: Option<(u64, u64)> },
+    UnmarkText,
+}
+
+impl<'a> CoreNotification<'a> {
+    /// Reads the message `method` from its raw params, for parsers that have
+    /// already read the envelope.
+    pub fn from_params(method: &str, params: &'a str) -> serde_json::Result<Self> {
+        use self::CoreNotification::*;
+        let p = serde_json::from_str::<Fields>(params)?;
+        Ok(match method {
+            "edit" => {
+                let method = required(p.method, "method")?;
+                Edit {
+                    view_id: required(p.view_id, "view_id")?,
+                    undo_group: p.undo_group,
+                    annotation: p.annotation,
+                    cmd: EditNotification::from_params(method, p.params)?,
+                }
+            }
+            "plugin" => Plugin(PluginNotification::from_fields(p)?),
+            "close_view" => CloseView { view_id: required(p.view_id, "view_id")? },
+            "save" => Save {
+                view_id: required(p.view_id, "view_id")?,
+                file_path: required(p.file_path, "file_path")?,
+            },
+            "set_theme" => SetTheme { theme_name: required(p.theme_name, "theme_name")? },
+            "client_started" => ClientStarted,
+            "new_view" => NewView { file_path: p.file_path },
+            _ => return Err(de::Error::custom(format_args!("unknown method `{}`", method))),
+        })
+    }
+}
+
+impl<'a> PluginNotification<'a> {
+    fn from_fields(p: Fields<'a>) -> serde_json::Result<Self> {
+        use self::PluginNotification::*;
+        let view_id = required(p.view_id, "view_id")?;
+        Ok(match required(p.command, "command")? {
+            "start" => Start { view_id, plugin_name: required(p.plugin_name, "plugin_name")? },
+            "stop" => Stop { view_id, plugin_name: required(p.plugin_name, "plugin_name")? },
+            "plugin_rpc" => PluginRpc {
+                view_id,
+                receiver: required(p.receiver, "receiver")?,
+                rpc: required(p.rpc, "rpc")?,
+            },
+            command => {
+                return Err(de::Error::custom(format_args!("unknown command `{}`", command)))
+            }
+        })
+    }
+}
+
+impl EditNotification {
+    /// Reads the command `method` from its params, for parsers that have
+    /// already read the rest of the edit.
+    pub fn from_params(method: &str, params: Option<&RawValue>) -> serde_json::Result<Self> {
+        use self::EditNotification::*;
+        if let Some(cmd) = EditNotification::without_params(method) {
+            return Ok(cmd);
+        }
+        let params = required(params, "params")?.get();
+        Ok(match method {
+            "request_lines" => {
+                let (first, last) = serde_json::from_str(params)?;
+                RequestLines(first, last)
+            }
+            "scroll" => {
+                let (first, last) = serde_json::from_str(params)?;
+                Scroll(first, last)
+            }
+            "click" => {
+                let (line, column, flags, count) = serde_json::from_str(params)?;
+                Click(line, column, flags, count)
+            }
+            "drag" => {
+                let (line, column, flags) = serde_json::from_str(params)?;
+                Drag(line, column, flags)
+            }
+            _ => {
+                let p = serde_json::from_str::<Fields>(params)?;
+                match method {
+                    "insert" => Insert { chars: required(p.chars, "chars")? },
+                    "gesture" => Gesture {
+                        line: required(p.line, "line")?,
+                        column: required(p.column, "column")?,
+                        ty: gesture_type(required(p.ty, "ty")?)?,
+                    },
+                    "goto_line" => GotoLine { line: required(p.line, "line")? },
+                    "find_next" => FindNext {
+                        wrap_around: required(p.wrap_around, "wrap_around")?,
+                        allow_same: required(p.allow_same, "allow_same")?,
+                    },
+                    "find_previous" => {
+                        FindPrevious { wrap_around: required(p.wrap_around, "wrap_around")? }
+                    }
+                    "find" => Find {
+                        chars: p.chars,
+                        case_sensitive: required(p.case_sensitive, "case_sensitive")?,
+                    },
+                    "set_marked_text" => SetMarkedText {
+                        chars: required(p.chars, "chars")?,
+                        selection: required(p.selection, "selection")?,
+                        replacement: p.replacement,
+                    },
+                    _ => {
+                        return Err(de::Error::custom(
+                            format_args!("unknown edit method `{}`", method)))
+                    }
+                }
+            }
+        })
+    }
+
+    /// The commands whose params, if any, are ignored.
+    pub fn without_params(method: &str) -> Option<Self> {
+        use self::EditNotification::*;
+        Some(match method {
+            "move_word_right" => MoveWordRight,
+            "move_word_left" => MoveWordLeft,
+            "delete_forward" => DeleteForward,
+            "delete_backward" => DeleteBackward,
+            "insert_newline" => InsertNewline,
+            "insert_tab" => InsertTab,
+            "cut" => Cut,
+            "copy" => Copy,
+            "delete_word_forward" => DeleteWordForward,
+            "delete_word_backward" => DeleteWordBackward,
+            "delete_to_end_of_paragraph" => DeleteToEndOfParagraph,
+            "delete_to_beginning_of_line" => DeleteToBeginningOfLine,
+            "move_up" => MoveUp,
+            "move_up_and_modify_selection" => MoveUpAndModifySelection,
+            "move_down" => MoveDown,
+            "move_down_and_modify_selection" => MoveDownAndModifySelection,
+            "move_left" => MoveLeft,
+            "move_left_and_modify_selection" => MoveLeftAndModifySelection,
+            "move_right" => MoveRight,
+            "move_right_and_modify_selection" => MoveRightAndModifySelection,
+            "move_word_left_and_modify_selection" => MoveWordLeftAndModifySelection,
+            "move_word_right_and_modify_selection" => MoveWordRightAndModifySelection,
+            "move_to_beginning_of_paragraph" => MoveToBeginningOfParagraph,
+            "move_to_end_of_paragraph" => MoveToEndOfParagraph,
+            "move_to_left_end_of_line" => MoveToLeftEndOfLine,
+            "move_to_left_end_of_line_and_modify_selection" =>
+                MoveToLeftEndOfLineAndModifySelection,
+            "move_to_right_end_of_line" => MoveToRightEndOfLine,
+            "move_to_right_end_of_line_and_modify_selection" =>
+                MoveToRightEndOfLineAndModifySelection,
+            "move_to_beginning_of_document" => MoveToBeginningOfDocument,
+            "move_to_beginning_of_document_and_modify_selection" =>
+                MoveToBeginningOfDocumentAndModifySelection,
+            "move_to_end_of_document" => MoveToEndOfDocument,
+            "move_to_end_of_document_and_modify_selection" => MoveToEndOfDocumentAndModifySelection,
+            "scroll_page_up" => ScrollPageUp,
+            "page_up_and_modify_selection" => PageUpAndModifySelection,
+            "scroll_page_down" => ScrollPageDown,
+            "page_down_and_modify_selection" => PageDownAndModifySelection,
+            "select_all" => SelectAll,
+            "add_selection_above" => AddSelectionAbove,
+            "add_selection_below" => AddSelectionBelow,
+            "yank" => Yank,
+            "transpose" => Transpose,
+            "undo" => Undo,
+            "redo" => Redo,
+            "debug_rewrap" => DebugRewrap,
+            "debug_print_spans" => DebugPrintSpans,
+            "unmark_text" => UnmarkText,
+            _ => return None,
+        })
+    }
+}
+
+fn gesture_type(ty: &str) -> serde_json::Result<GestureType> {
+    Ok(match ty {
+        "point_select" => GestureType::PointSelect,
+        "toggle_sel" => GestureType::ToggleSel,
+        "range_select" => GestureType::RangeSelect,
+        "line_select" => GestureType::LineSelect,
+        "word_select" => GestureType::WordSelect,
+        "multi_line_select" => GestureType::MultiLineSelect,
+        "multi_word_select" => GestureType::MultiWordSelect,
+        _ => return Err(de::Error::custom(format_args!("unknown gesture type `{}`", ty))),
+    })
+}
+
+fn required<T>(field: Option<T>, name: &'static str) -> serde_json::Result<T> {
+    field.ok_or_else(|| de::Error::missing_field(name))
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for CoreNotification<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let msg = Fields::deserialize(deserializer)?;
+        let method = msg.method.ok_or_else(|| de::Error::missing_field("method"))?;
+        let params = msg.params.ok_or_else(|| de::Error::missing_field("params"))?;
+        CoreNotification::from_params(method, params.get()).map_err(de::Error::custom)
+    }
+}
+
+/// The fields of every params object, and of the message itself. Each
+/// message uses a few of them; anything else is skipped.
+///
+/// A null `chars`, `file_path`, `replacement`, `undo_group` or `annotation`
+/// is the same as a missing one.
+#[derive(Default)]
+struct Fields<'a> {
+    view_id: Option<&'a str>,
+    undo_group: Option<u64>,
+    annotation: Option<&'a str>,
+    method: Option<&'a str>,
+    params: Option<&'a RawValue>,
+    chars: Option<String>,
+    line: Option<u64>,
+    column: Option<u64>,
+    ty: Option<&'a str>,
+    wrap_around: Option<bool>,
+    allow_same: Option<bool>,
+    case_sensitive: Option<bool>,
+    selection: Option<(u64, u64)>,
+    replacement: Option<(u64, u64)>,
+    file_path: Option<&'a str>,
+    theme_name: Option<&'a str>,
+    command: Option<&'a str>,
+    plugin_name: Option<&'a str>,
+    receiver: Option<&'a str>,
+    rpc: Option<&'a RawValue>,
+}
+
+enum Field {
+    ViewId,
+    UndoGroup,
+    Annotation,
+    Method,
+    Params,
+    Chars,
+    Line,
+    Column,
+    Ty,
+    WrapAround,
+    AllowSame,
+    CaseSensitive,
+    Selection,
+    Replacement,
+    FilePath,
+    ThemeName,
+    Command,
+    PluginName,
+    Receiver,
+    Rpc,
+    Other,
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Fields<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_map(FieldsVisitor(PhantomData))
+    }
+}
+
+struct FieldsVisitor<'a>(PhantomData<&'a ()>);
+
+impl<'de: 'a, 'a> Visitor<'de> for FieldsVisitor<'a> {
+    type Value = Fields<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an object")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Fields<'a>, A::Error>
+        where A: MapAccess<'de>
+    {
+        let mut f = Fields::default();
+        while let Some(field) = map.next_key::<Field>()? {
+            match field {
+                Field::ViewId => f.view_id = Some(map.next_value()?),
+                Field::UndoGroup => f.undo_group = map.next_value()?,
+                Field::Annotation => f.annotation = map.next_value()?,
+                Field::Method => f.method = Some(map.next_value()?),
+                Field::Params => f.params = Some(map.next_value()?),
+                Field::Chars => f.chars = map.next_value()?,
+                Field::Line => f.line = Some(map.next_value()?),
+                Field::Column => f.column = Some(map.next_value()?),
+                Field::Ty => f.ty = Some(map.next_value()?),
+                Field::WrapAround => f.wrap_around = Some(map.next_value()?),
+                Field::AllowSame => f.allow_same = Some(map.next_value()?),
+                Field::CaseSensitive => f.case_sensitive = Some(map.next_value()?),
+                Field::Selection => f.selection = Some(map.next_value()?),
+                Field::Replacement => f.replacement = map.next_value()?,
+                Field::FilePath => f.file_path = map.next_value()?,
+                Field::ThemeName => f.theme_name = Some(map.next_value()?),
+                Field::Command => f.command = Some(map.next_value()?),
+                Field::PluginName => f.plugin_name = Some(map.next_value()?),
+                Field::Receiver => f.receiver = Some(map.next_value()?),
+                Field::Rpc => f.rpc = Some(map.next_value()?),
+                Field::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(f)
+    }
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+struct FieldVisitor;
+
+impl<'de> Visitor<'de> for FieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a field name")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Field, E> {
+        Ok(match v {
+            "view_id" => Field::ViewId,
+            "undo_group" => Field::UndoGroup,
+            "annotation" => Field::Annotation,
+            "method" => Field::Method,
+            "params" => Field::Params,
+            "chars" => Field::Chars,
+            "line" => Field::Line,
+            "column" => Field::Column,
+            "ty" => Field::Ty,
+            "wrap_around" => Field::WrapAround,
+            "allow_same" => Field::AllowSame,
+            "case_sensitive" => Field::CaseSensitive,
+            "selection" => Field::Selection,
+            "replacement" => Field::Replacement,
+            "file_path" => Field::FilePath,
+            "theme_name" => Field::ThemeName,
+            "command" => Field::Command,
+            "plugin_name" => Field::PluginName,
+            "receiver" => Field::Receiver,
+            "rpc" => Field::Rpc,
+            _ => Field::Other,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corpus::{ANNOTATED_JSON, GESTURE_JSON, IDS_JSON, IME_JSON, TEST_JSON};
+    use rpc3;
+
+    #[test]
+    fn agrees_with_rpc3() {
+        let corpus = TEST_JSON.lines().chain(GESTURE_JSON.lines()).chain(IDS_JSON.lines())
+            .chain(IME_JSON.lines()).chain(ANNOTATED_JSON.lines());
+        for json in corpus {
+            assert!(serde_json::from_str::<rpc3::CoreNotification>(json).is_ok());
+            assert!(serde_json::from_str::<CoreNotification>(json).is_ok(), "{}", json);
+        }
+        for json in &[
+            r#"{"method":"frobnicate","params":{}}"#,
+            r#"{"params":{"view_id":"v"}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"scroll","params":[1]}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"insert","params":{}}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"gesture",
+                "params":{"line":1,"column":2,"ty":"squiggle"}}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"set_marked_text",
+                "params":{"chars":"に","selection":[1]}}}"#,
+        ] {
+            assert!(serde_json::from_str::<rpc3::CoreNotification>(json).is_err());
+            assert!(serde_json::from_str::<CoreNotification>(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn params_in_any_order() {
+        let json = r#"{"params":{"params":{"chars":"a\nb"},"method":"insert","undo_group":4,
+                       "view_id":"v"},"id":3,"method":"edit"}"#;
+        match serde_json::from_str::<CoreNotification>(json).unwrap() {
+            CoreNotification::Edit { view_id, undo_group, annotation, cmd } => {
+                assert_eq!(view_id, "v");
+                assert_eq!((undo_group, annotation), (Some(4), None));
+                assert_eq!(cmd, EditNotification::Insert { chars: "a\nb".into() });
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
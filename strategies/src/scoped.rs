@@ -0,0 +1,83 @@
+//! Handing borrowed messages to another thread.
+//!
+//! The usual objection to the borrowing rpc3 types is that xi reads on one
+//! thread and handles on another, and a `CoreNotification<'a>` can't outlive
+//! the buffer it borrows from. That rules out `thread::spawn`, which wants
+//! `'static`, but not scoped threads: if the reader keeps a batch of lines
+//! alive for the length of a scope, a handler thread in that scope can be
+//! sent messages that borrow from them. Nothing is copied, and the borrow
+//! checker sees to it that the lines outlive the handler.
+
+use crossbeam;
+use crossbeam::channel;
+use serde_json;
+
+use dispatch::{self, Counter};
+use rpc3;
+
+/// How many parsed messages can be waiting for the handler.
+pub const QUEUE_DEPTH: usize = 64;
+
+/// Parses each of `lines` on this thread, sending the messages to be
+/// dispatched on a scoped handler thread as they're parsed. Returns the
+/// handler, and the number of lines that failed to parse.
+pub fn dispatch_lines(lines: &[String]) -> (Counter, usize) {
+    let (tx, rx) = channel::bounded::<rpc3::CoreNotification>(QUEUE_DEPTH);
+    crossbeam::scope(|scope| {
+        let handler = scope.spawn(move |_| {
+            let mut handler = Counter::default();
+            for msg in rx {
+                dispatch::match_rpc3(&msg, &mut handler);
+            }
+            handler
+        });
+        let mut errors = 0;
+        for line in lines {
+            match serde_json::from_str::<rpc3::CoreNotification>(line) {
+                Ok(msg) => if tx.send(msg).is_err() {
+                    break;
+                },
+                Err(_) => errors += 1,
+            }
+        }
+        drop(tx);
+        (handler.join().expect("handler thread panicked"), errors)
+    }).expect("scoped thread panicked")
+}
+
+/// Dispatches `msgs`, which were parsed on this thread, on a scoped handler
+/// thread.
+pub fn dispatch_all(msgs: Vec<rpc3::CoreNotification>) -> Counter {
+    crossbeam::scope(|scope| {
+        scope.spawn(move |_| {
+            let mut handler = Counter::default();
+            for msg in &msgs {
+                dispatch::match_rpc3(msg, &mut handler);
+            }
+            handler
+        }).join().expect("handler thread panicked")
+    }).expect("scoped thread panicked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TEST_JSON;
+
+    #[test]
+    fn same_calls_as_one_thread() {
+        let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        let mut expected = Counter::default();
+        let mut msgs = Vec::new();
+        for line in &lines {
+            let msg = serde_json::from_str::<rpc3::CoreNotification>(line).unwrap();
+            dispatch::match_rpc3(&msg, &mut expected);
+            msgs.push(msg);
+        }
+        assert_eq!(dispatch_lines(&lines), (expected.clone(), 0));
+        assert_eq!(dispatch_all(msgs), expected);
+
+        let bad = vec![lines[0].clone(), "{".to_owned(), lines[1].clone()];
+        assert_eq!(dispatch_lines(&bad).1, 1);
+    }
+}
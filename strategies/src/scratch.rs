@@ -0,0 +1,51 @@
+//! State a strategy keeps from one message to the next.
+//!
+//! Strategies are called through `&self`, from the harness, the benches and
+//! the tests alike, so one that keeps a cache, an arena or an interner keeps
+//! it in a `Scratch`, which lends it out mutably for the length of a parse.
+//! That makes the strategy `!Sync`, so that a thread can't share another's:
+//! each makes an instance of its own, as the harness's `measure_contended`
+//! does, and so has scratch of its own, without a `thread_local!` or a
+//! static.
+//!
+//! What's in it matters to the timings, so it has a lifecycle that the
+//! harness drives through `Strategy::prepare` and `Strategy::reset`: ready
+//! before a run, and emptied after it, so that a second run over the same
+//! instance starts as the first did rather than with the first's state.
+
+use std::cell::{RefCell, RefMut};
+
+/// Something kept in a `Scratch`.
+pub trait Reset {
+    /// Gets ready for a run, allocating up front whatever it can, so that
+    /// the first messages don't pay for it.
+    fn prepare(&mut self) {}
+
+    /// Forgets everything from the last run. Allocations can be kept.
+    fn reset(&mut self);
+}
+
+#[derive(Debug, Default)]
+pub struct Scratch<T> {
+    state: RefCell<T>,
+}
+
+impl<T: Reset> Scratch<T> {
+    pub fn new(state: T) -> Scratch<T> {
+        Scratch { state: RefCell::new(state) }
+    }
+
+    /// The state, for one parse. Panics if it's already lent out, which
+    /// only a strategy that parses reentrantly would do.
+    pub fn get(&self) -> RefMut<'_, T> {
+        self.state.borrow_mut()
+    }
+
+    pub fn prepare(&self) {
+        self.state.borrow_mut().prepare();
+    }
+
+    pub fn reset(&self) {
+        self.state.borrow_mut().reset();
+    }
+}
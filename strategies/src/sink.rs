@@ -0,0 +1,455 @@
+//! Somewhere for the benches to put what they parse.
+//!
+//! A bench that binds its result to an unused variable, or only checks that
+//! it's `Ok`, leaves the optimizer free to delete whatever work it can prove
+//! has no effect, and it can prove more for some strategies than for others.
+//! Instead, the benches feed each message to a `Sink`, which hashes a few of
+//! its fields, and hand the sink to `test::black_box`.
+//!
+//! The fields are the ones a handler would look at first, and ones every
+//! strategy keeps: the method, the view id of messages addressed to a view
+//! (rpc3 drops the view id of most edits, so edits' aren't included), and
+//! the length of the message's payload: the chars of an insert, a path, a
+//! theme or plugin name. So every strategy hashes the same stream.
+//!
+//! The messages going the other ways, to a frontend and between core and a
+//! plugin, have a payload too: the regions of an update's selection, the
+//! themes, plugins or settings a frontend is told about, the spans from a
+//! plugin, the text inserted by a plugin's update.
+
+use internal;
+use rpc2;
+use rpc3;
+use rpc5;
+use route;
+use split;
+use tape;
+use unified;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// A running hash of the messages fed to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sink {
+    hash: u64,
+    messages: u64,
+}
+
+/// A parsed message, or several, that can be fed to a `Sink`.
+pub trait Consume {
+    fn consume(&self, sink: &mut Sink);
+}
+
+impl Sink {
+    pub fn new() -> Sink {
+        Sink { hash: FNV_OFFSET, messages: 0 }
+    }
+
+    pub fn consume<M: Consume + ?Sized>(&mut self, msg: &M) {
+        msg.consume(self);
+    }
+
+    /// Hashes one message's fields.
+    pub fn message(&mut self, method: &str, view_id: Option<&str>, payload: usize) {
+        self.bytes(method.as_bytes());
+        // 0xff never appears in UTF-8, so it can't be confused with the text
+        self.bytes(&[0xff]);
+        self.bytes(view_id.unwrap_or_default().as_bytes());
+        self.bytes(&[0xff]);
+        self.bytes(&(payload as u64).to_le_bytes());
+        self.messages += 1;
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.hash ^= u64::from(b);
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// The number of messages consumed.
+    pub fn messages(&self) -> u64 {
+        self.messages
+    }
+}
+
+impl Default for Sink {
+    fn default() -> Sink {
+        Sink::new()
+    }
+}
+
+impl<T: Consume> Consume for [T] {
+    fn consume(&self, sink: &mut Sink) {
+        for msg in self {
+            msg.consume(sink);
+        }
+    }
+}
+
+impl<T: Consume> Consume for Vec<T> {
+    fn consume(&self, sink: &mut Sink) {
+        self[..].consume(sink)
+    }
+}
+
+impl Consume for rpc2::CoreNotification {
+    fn consume(&self, sink: &mut Sink) {
+        use rpc2::CoreNotification::*;
+        match *self {
+            Edit(ref edit) => edit.consume(sink),
+            Plugin(ref plugin) => plugin.consume(sink),
+            CloseView { ref view_id } => sink.message("close_view", Some(view_id.as_str()), 0),
+            Save { ref view_id, ref file_path } => {
+                sink.message("save", Some(view_id.as_str()), file_path.len())
+            }
+            SetTheme { ref theme_name } => sink.message("set_theme", None, theme_name.len()),
+            ClientStarted(_) => sink.message("client_started", None, 0),
+        }
+    }
+}
+
+impl Consume for rpc2::EditCommand<rpc2::EditNotification> {
+    fn consume(&self, sink: &mut Sink) {
+        use rpc2::EditNotification::{Insert, SetMarkedText};
+        let payload = match self.cmd {
+            Insert { ref chars } | SetMarkedText { ref chars, .. } => chars.len(),
+            _ => 0,
+        };
+        sink.message("edit", None, payload)
+    }
+}
+
+impl Consume for rpc2::PluginNotification {
+    fn consume(&self, sink: &mut Sink) {
+        use rpc2::PluginNotification::*;
+        let (view_id, name) = match *self {
+            Start { ref view_id, ref plugin_name } | Stop { ref view_id, ref plugin_name } => {
+                (view_id, plugin_name)
+            }
+            PluginRpc { ref view_id, ref receiver, .. } => (view_id, receiver),
+        };
+        sink.message("plugin", Some(view_id.as_str()), name.len())
+    }
+}
+
+impl Consume for rpc2::CoreRequest {
+    fn consume(&self, sink: &mut Sink) {
+        match *self {
+            rpc2::CoreRequest::Edit(ref edit) => {
+                let payload = match edit.cmd {
+                    rpc2::EditRequest::Find { chars: Some(ref chars), .. } => chars.len(),
+                    _ => 0,
+                };
+                sink.message("edit", None, payload)
+            }
+            rpc2::CoreRequest::NewView { ref file_path } => {
+                sink.message("new_view", None, file_path.as_ref().map_or(0, |p| p.len()))
+            }
+        }
+    }
+}
+
+impl Consume for rpc2::ClientNotification {
+    fn consume(&self, sink: &mut Sink) {
+        match *self {
+            rpc2::ClientNotification::Update { ref view_id, ref update } => {
+                sink.message("update", Some(view_id.as_str()), update.selection.0.len())
+            }
+            rpc2::ClientNotification::DefStyle(_) => sink.message("def_style", None, 0),
+            rpc2::ClientNotification::Alert { ref msg } => sink.message("alert", None, msg.len()),
+            rpc2::ClientNotification::AvailableThemes { ref themes } => {
+                sink.message("available_themes", None, themes.len())
+            }
+            rpc2::ClientNotification::AvailablePlugins { ref view_id, ref plugins } => {
+                sink.message("available_plugins", Some(view_id.as_str()), plugins.len())
+            }
+            rpc2::ClientNotification::ConfigChanged { ref view_id, ref changes } => {
+                sink.message("config_changed", Some(view_id.as_str()), changes.len())
+            }
+        }
+    }
+}
+
+impl Consume for rpc2::PluginCoreNotification {
+    fn consume(&self, sink: &mut Sink) {
+        let rpc2::PluginCoreNotification::UpdateSpans { ref view_id, ref spans, .. } = *self;
+        sink.message("update_spans", Some(view_id.as_str()), spans.len())
+    }
+}
+
+impl Consume for rpc2::HostNotification {
+    fn consume(&self, sink: &mut Sink) {
+        use rpc2::HostNotification::*;
+        match *self {
+            Ping(_) => sink.message("ping", None, 0),
+            Initialize { ref buffer_info, .. } => {
+                sink.message("initialize", None, buffer_info.len())
+            }
+            DidSave { ref view_id, ref path } => {
+                sink.message("did_save", Some(view_id.as_str()), path.len())
+            }
+            DidClose { ref view_id } => sink.message("did_close", Some(view_id.as_str()), 0),
+            Shutdown(_) => sink.message("shutdown", None, 0),
+        }
+    }
+}
+
+impl Consume for rpc2::HostRequest {
+    fn consume(&self, sink: &mut Sink) {
+        let rpc2::HostRequest::Update(ref update) = *self;
+        let inserted = update.delta.as_ref().map_or(0, |delta| {
+            delta.els.iter().map(|el| match *el {
+                rpc2::DeltaElement::Insert(ref text) => text.len(),
+                rpc2::DeltaElement::Copy(..) => 0,
+            }).sum()
+        });
+        sink.message("update", Some(update.view_id.as_str()), inserted)
+    }
+}
+
+impl Consume for unified::CoreMessage {
+    fn consume(&self, sink: &mut Sink) {
+        use rpc2::EditNotification::{Insert, SetMarkedText};
+        use rpc2::EditRequest::Find;
+        use unified::{CoreMessage, Edit};
+        match *self {
+            CoreMessage::Edit(ref edit) => {
+                let payload = match edit.cmd {
+                    Edit::Notification(Insert { ref chars })
+                    | Edit::Notification(SetMarkedText { ref chars, .. })
+                    | Edit::Request(Find { chars: Some(ref chars), .. }) => chars.len(),
+                    _ => 0,
+                };
+                sink.message("edit", None, payload)
+            }
+            CoreMessage::Plugin(ref plugin) => plugin.consume(sink),
+            CoreMessage::CloseView { ref view_id } => {
+                sink.message("close_view", Some(view_id.as_str()), 0)
+            }
+            CoreMessage::Save { ref view_id, ref file_path } => {
+                sink.message("save", Some(view_id.as_str()), file_path.len())
+            }
+            CoreMessage::SetTheme { ref theme_name } => {
+                sink.message("set_theme", None, theme_name.len())
+            }
+            CoreMessage::ClientStarted(_) => sink.message("client_started", None, 0),
+            CoreMessage::NewView { ref file_path } => {
+                sink.message("new_view", None, file_path.as_ref().map_or(0, |p| p.len()))
+            }
+        }
+    }
+}
+
+impl<'a> Consume for rpc3::CoreNotification<'a> {
+    fn consume(&self, sink: &mut Sink) {
+        use rpc3::CoreNotification::*;
+        use rpc3::EditNotification::{Find, Insert, SetMarkedText};
+        match *self {
+            Edit(ref edit) => {
+                let payload = match *edit {
+                    Insert { ref params, .. } => params.chars.len(),
+                    SetMarkedText { ref params, .. } => params.chars.len(),
+                    Find { ref params, .. } => params.chars.as_ref().map_or(0, |c| c.len()),
+                    _ => 0,
+                };
+                sink.message("edit", None, payload)
+            }
+            Plugin(ref plugin) => plugin.consume(sink),
+            CloseView { view_id } => sink.message("close_view", Some(view_id), 0),
+            Save { view_id, file_path } => sink.message("save", Some(view_id), file_path.len()),
+            SetTheme { theme_name } => sink.message("set_theme", None, theme_name.len()),
+            ClientStarted(_) => sink.message("client_started", None, 0),
+            NewView { file_path } => sink.message("new_view", None, file_path.map_or(0, str::len)),
+        }
+    }
+}
+
+impl<'a> Consume for rpc3::HostNotification<'a> {
+    fn consume(&self, sink: &mut Sink) {
+        use rpc3::HostNotification::*;
+        match *self {
+            Ping(_) => sink.message("ping", None, 0),
+            Initialize { ref buffer_info, .. } => {
+                sink.message("initialize", None, buffer_info.len())
+            }
+            DidSave { view_id, ref path } => sink.message("did_save", Some(view_id), path.len()),
+            DidClose { view_id } => sink.message("did_close", Some(view_id), 0),
+            Shutdown(_) => sink.message("shutdown", None, 0),
+        }
+    }
+}
+
+impl<'a> Consume for rpc3::HostRequest<'a> {
+    fn consume(&self, sink: &mut Sink) {
+        let rpc3::HostRequest::Update(ref update) = *self;
+        let inserted = update.delta.as_ref().map_or(0, |delta| {
+            delta.els.iter().map(|el| match *el {
+                rpc3::DeltaElement::Insert(ref text) => text.len(),
+                rpc3::DeltaElement::Copy(..) => 0,
+            }).sum()
+        });
+        sink.message("update", Some(update.view_id), inserted)
+    }
+}
+
+impl<'a> Consume for rpc5::CoreNotification<'a> {
+    fn consume(&self, sink: &mut Sink) {
+        use rpc5::CoreNotification::*;
+        use rpc5::EditNotification::{Find, Insert, SetMarkedText};
+        use rpc5::PluginNotification::{PluginRpc, Start, Stop};
+        match *self {
+            Edit { ref cmd, .. } => {
+                let payload = match *cmd {
+                    Insert { ref chars } | SetMarkedText { ref chars, .. } => chars.len(),
+                    Find { ref chars, .. } => chars.as_ref().map_or(0, |c| c.len()),
+                    _ => 0,
+                };
+                sink.message("edit", None, payload)
+            }
+            Plugin(Start { view_id, plugin_name }) | Plugin(Stop { view_id, plugin_name }) => {
+                sink.message("plugin", Some(view_id), plugin_name.len())
+            }
+            Plugin(PluginRpc { view_id, receiver, .. }) => {
+                sink.message("plugin", Some(view_id), receiver.len())
+            }
+            CloseView { view_id } => sink.message("close_view", Some(view_id), 0),
+            Save { view_id, file_path } => sink.message("save", Some(view_id), file_path.len()),
+            SetTheme { theme_name } => sink.message("set_theme", None, theme_name.len()),
+            ClientStarted => sink.message("client_started", None, 0),
+            NewView { file_path } => sink.message("new_view", None, file_path.map_or(0, str::len)),
+        }
+    }
+}
+
+impl<'a> Consume for internal::Edit<'a> {
+    fn consume(&self, sink: &mut Sink) {
+        use internal::BufferEvent::{Insert, SetMarkedText};
+        use internal::EventDomain::Buffer;
+        let payload = match self.event {
+            Buffer(Insert(ref chars)) | Buffer(SetMarkedText { ref chars, .. }) => chars.len(),
+            _ => 0,
+        };
+        sink.message("edit", None, payload)
+    }
+}
+
+impl<'a> Consume for route::Envelope<'a> {
+    fn consume(&self, sink: &mut Sink) {
+        sink.message(&self.method, None, 0)
+    }
+}
+
+impl<'a> Consume for split::Message<'a> {
+    fn consume(&self, sink: &mut Sink) {
+        use split::{Edit, Payload};
+        let header = &self.header;
+        let payload = match self.payload {
+            Payload::Edit { cmd: Edit::Insert { ref chars }, .. }
+            | Payload::Edit { cmd: Edit::SetMarkedText { ref chars, .. }, .. } => chars.len(),
+            Payload::Edit { cmd: Edit::Find { ref chars, .. }, .. } => {
+                chars.as_ref().map_or(0, |c| c.len())
+            }
+            Payload::Path(ref path) => path.as_ref().map_or(0, |p| p.len()),
+            Payload::Theme(ref name) | Payload::Plugin { ref name, .. } => name.len(),
+            Payload::Edit { .. } | Payload::Empty => 0,
+        };
+        let view_id = match header.method {
+            "edit" => None,
+            _ => header.view_id.as_ref().map(|v| v.as_str()),
+        };
+        sink.message(header.method, view_id, payload)
+    }
+}
+
+/// The tape leaves strings escaped, so this unescapes the ones it measures,
+/// as a handler would have to.
+impl<'t, 'a> Consume for tape::Message<'t, 'a> {
+    fn consume(&self, sink: &mut Sink) {
+        let p = &self.params;
+        let len = |key| p.get(key).and_then(|n| n.as_str()).map_or(0, |s| s.len());
+        let method = self.method.as_str().unwrap_or_default();
+        let view_id = p.get("view_id").and_then(|n| n.as_str());
+        match method.as_ref() {
+            "edit" => {
+                let inner = p.get("params");
+                let payload = inner.and_then(|i| i.get("chars")).and_then(|c| c.as_str())
+                    .map_or(0, |c| c.len());
+                sink.message("edit", None, payload)
+            }
+            "close_view" => sink.message("close_view", view_id.as_ref().map(|v| v.as_ref()), 0),
+            "save" => sink.message("save", view_id.as_ref().map(|v| v.as_ref()), len("file_path")),
+            "plugin" => {
+                let payload = len("plugin_name") + len("receiver");
+                sink.message("plugin", view_id.as_ref().map(|v| v.as_ref()), payload)
+            }
+            "set_theme" => sink.message("set_theme", None, len("theme_name")),
+            "new_view" => sink.message("new_view", None, len("file_path")),
+            other => sink.message(other, None, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+    use corpus::{generate, TO_PLUGIN_JSON};
+    use peek;
+    use {GESTURE_JSON, IME_JSON, TEST_JSON};
+
+    fn rpc2_message(line: &str, sink: &mut Sink) {
+        let mut val = serde_json::from_str::<Value>(line).unwrap();
+        if val.as_object_mut().and_then(|obj| obj.remove("id")).is_some() {
+            sink.consume(&serde_json::from_value::<rpc2::CoreRequest>(val).unwrap());
+        } else {
+            sink.consume(&serde_json::from_value::<rpc2::CoreNotification>(val).unwrap());
+        }
+    }
+
+    #[test]
+    fn strategies_agree() {
+        for corpus in &[TEST_JSON, GESTURE_JSON, IME_JSON] {
+            let (mut a, mut b, mut c, mut d) = (Sink::new(), Sink::new(), Sink::new(), Sink::new());
+            let mut e = Sink::new();
+            for line in corpus.lines() {
+                rpc2_message(line, &mut a);
+                b.consume(&serde_json::from_str::<rpc3::CoreNotification>(line).unwrap());
+                c.consume(&serde_json::from_str::<rpc5::CoreNotification>(line).unwrap());
+                let tape = tape::Tape::parse(line).unwrap();
+                d.consume(&tape::Message::from_tape(&tape).unwrap());
+                e.consume(&split::Message::parse(line).unwrap());
+            }
+            assert_eq!(a.messages(), corpus.lines().count() as u64);
+            assert_eq!(a, b);
+            assert_eq!(a, c);
+            assert_eq!(a, d);
+            assert_eq!(a, e);
+            assert_ne!(a, Sink::new());
+        }
+    }
+
+    #[test]
+    fn host_messages_agree() {
+        let session = generate::plugin_session(200, 9);
+        let lines = TO_PLUGIN_JSON.lines().chain(session.iter().map(String::as_str));
+        let (mut a, mut b) = (Sink::new(), Sink::new());
+        for line in lines {
+            if peek::has_id(line) {
+                a.consume(&serde_json::from_str::<rpc2::HostRequest>(line).unwrap());
+                b.consume(&serde_json::from_str::<rpc3::HostRequest>(line).unwrap());
+            } else {
+                a.consume(&serde_json::from_str::<rpc2::HostNotification>(line).unwrap());
+                b.consume(&serde_json::from_str::<rpc3::HostNotification>(line).unwrap());
+            }
+        }
+        assert_eq!(a, b);
+        assert_ne!(a, Sink::new());
+    }
+}
@@ -0,0 +1,306 @@
+//! An owned header, and a borrowed payload.
+//!
+//! xi-core keeps a message's header after the line it arrived on is gone: the
+//! id, to answer a request once the work is done, and the method and view id,
+//! to route it. The payload it mostly consumes right away: inserted text goes
+//! into the rope, a path is opened. So here the header is owned, and cheap to
+//! keep, while the payload's strings stay borrowed from the input wherever
+//! they have nothing to unescape.
+//!
+//! The envelope and params are read a level at a time, as in rpc4, and the
+//! edits without anything bulky in them are left to rpc5.
+
+use std::borrow::Cow;
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde_json::{self, value::RawValue};
+
+use rpc2::{RpcId, ViewIdentifier};
+use rpc3::EmptyStruct;
+use rpc5;
+
+/// What xi-core keeps to route a message, and to answer it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// `None` if the field is missing; a request with a null id is
+    /// `Some(RpcId::Null)`.
+    pub id: Option<RpcId>,
+    pub method: &'static str,
+    /// The view the message is addressed to, if it's addressed to one.
+    pub view_id: Option<ViewIdentifier>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Message<'a> {
+    pub header: Header,
+    pub payload: Payload<'a>,
+}
+
+/// What's consumed on arrival, borrowed where possible.
+#[derive(Debug, PartialEq)]
+pub enum Payload<'a> {
+    /// `client_started` and `close_view` carry nothing beyond the header.
+    Empty,
+    /// `undo_group` and `annotation` are protocol 2's, and optional.
+    Edit { undo_group: Option<u64>, annotation: Option<Cow<'a, str>>, cmd: Edit<'a> },
+    /// The path of a `save`, or of a `new_view`, which needn't have one.
+    Path(Option<Cow<'a, str>>),
+    Theme(Cow<'a, str>),
+    /// `name` is the plugin to start or stop, or the receiver of an RPC.
+    /// The RPC is forwarded as it came, as its JSON text.
+    Plugin { command: PluginCommand, name: Cow<'a, str>, rpc: Option<&'a str> },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Edit<'a> {
+    Insert { chars: Cow<'a, str> },
+    SetMarkedText { chars: Cow<'a, str>, selection: (u64, u64), replacement: Option<(u64, u64)> },
+    Find { chars: Option<Cow<'a, str>>, case_sensitive: bool },
+    /// The commands with nothing bulky in them.
+    Other(rpc5::EditNotification),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginCommand {
+    Start,
+    Stop,
+    PluginRpc,
+}
+
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    #[serde(default, deserialize_with = "present")]
+    id: Option<RpcId>,
+    method: &'a str,
+    #[serde(borrow)]
+    params: &'a RawValue,
+}
+
+#[derive(Deserialize)]
+struct EditParams<'a> {
+    view_id: ViewIdentifier,
+    #[serde(default)]
+    undo_group: Option<u64>,
+    #[serde(borrow, default)]
+    annotation: Option<Cow<'a, str>>,
+    method: &'a str,
+    #[serde(borrow, default)]
+    params: Option<&'a RawValue>,
+}
+
+#[derive(Deserialize)]
+struct InsertParams<'a> {
+    #[serde(borrow)]
+    chars: Cow<'a, str>,
+}
+
+#[derive(Deserialize)]
+struct MarkedTextParams<'a> {
+    #[serde(borrow)]
+    chars: Cow<'a, str>,
+    selection: (u64, u64),
+    #[serde(default)]
+    replacement: Option<(u64, u64)>,
+}
+
+#[derive(Deserialize)]
+struct FindParams<'a> {
+    #[serde(borrow, default)]
+    chars: Option<Cow<'a, str>>,
+    case_sensitive: bool,
+}
+
+#[derive(Deserialize)]
+struct ViewParams {
+    view_id: ViewIdentifier,
+}
+
+#[derive(Deserialize)]
+struct SaveParams<'a> {
+    view_id: ViewIdentifier,
+    #[serde(borrow)]
+    file_path: Cow<'a, str>,
+}
+
+#[derive(Deserialize)]
+struct NewViewParams<'a> {
+    #[serde(borrow, default)]
+    file_path: Option<Cow<'a, str>>,
+}
+
+#[derive(Deserialize)]
+struct ThemeParams<'a> {
+    #[serde(borrow)]
+    theme_name: Cow<'a, str>,
+}
+
+#[derive(Deserialize)]
+struct PluginParams<'a> {
+    command: &'a str,
+    view_id: ViewIdentifier,
+    #[serde(borrow, default)]
+    plugin_name: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    receiver: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    rpc: Option<&'a RawValue>,
+}
+
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where D: Deserializer<'de>, T: Deserialize<'de>
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+fn required<T>(field: Option<T>, name: &'static str) -> serde_json::Result<T> {
+    field.ok_or_else(|| de::Error::missing_field(name))
+}
+
+impl<'a> Message<'a> {
+    pub fn parse(json: &'a str) -> serde_json::Result<Message<'a>> {
+        serde_json::from_str(json)
+    }
+
+    fn from_envelope(env: Envelope<'a>) -> serde_json::Result<Message<'a>> {
+        let params = env.params.get();
+        let (method, view_id, payload) = match env.method {
+            "edit" => {
+                let p = serde_json::from_str::<EditParams>(params)?;
+                let cmd = Edit::from_params(p.method, p.params)?;
+                let payload = Payload::Edit {
+                    undo_group: p.undo_group,
+                    annotation: p.annotation,
+                    cmd,
+                };
+                ("edit", Some(p.view_id), payload)
+            }
+            "plugin" => {
+                let p = serde_json::from_str::<PluginParams>(params)?;
+                let (command, name) = match p.command {
+                    "start" => (PluginCommand::Start, required(p.plugin_name, "plugin_name")?),
+                    "stop" => (PluginCommand::Stop, required(p.plugin_name, "plugin_name")?),
+                    "plugin_rpc" => {
+                        required(p.rpc, "rpc")?;
+                        (PluginCommand::PluginRpc, required(p.receiver, "receiver")?)
+                    }
+                    command => {
+                        return Err(de::Error::custom(
+                            format_args!("unknown command `{}`", command)))
+                    }
+                };
+                let rpc = p.rpc.map(RawValue::get);
+                ("plugin", Some(p.view_id), Payload::Plugin { command, name, rpc })
+            }
+            "close_view" => {
+                let p = serde_json::from_str::<ViewParams>(params)?;
+                ("close_view", Some(p.view_id), Payload::Empty)
+            }
+            "save" => {
+                let p = serde_json::from_str::<SaveParams>(params)?;
+                ("save", Some(p.view_id), Payload::Path(Some(p.file_path)))
+            }
+            "set_theme" => {
+                let p = serde_json::from_str::<ThemeParams>(params)?;
+                ("set_theme", None, Payload::Theme(p.theme_name))
+            }
+            "client_started" => {
+                serde_json::from_str::<EmptyStruct>(params)?;
+                ("client_started", None, Payload::Empty)
+            }
+            "new_view" => {
+                let p = serde_json::from_str::<NewViewParams>(params)?;
+                ("new_view", None, Payload::Path(p.file_path))
+            }
+            method => return Err(de::Error::custom(format_args!("unknown method `{}`", method))),
+        };
+        Ok(Message { header: Header { id: env.id, method, view_id }, payload })
+    }
+}
+
+impl<'a> Edit<'a> {
+    fn from_params(method: &str, params: Option<&'a RawValue>) -> serde_json::Result<Self> {
+        let raw = || required(params, "params").map(|p| p.get());
+        Ok(match method {
+            "insert" => {
+                let p = serde_json::from_str::<InsertParams>(raw()?)?;
+                Edit::Insert { chars: p.chars }
+            }
+            "set_marked_text" => {
+                let p = serde_json::from_str::<MarkedTextParams>(raw()?)?;
+                Edit::SetMarkedText {
+                    chars: p.chars,
+                    selection: p.selection,
+                    replacement: p.replacement,
+                }
+            }
+            "find" => {
+                let p = serde_json::from_str::<FindParams>(raw()?)?;
+                Edit::Find { chars: p.chars, case_sensitive: p.case_sensitive }
+            }
+            _ => Edit::Other(rpc5::EditNotification::from_params(method, params)?),
+        })
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Message<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let env = Envelope::deserialize(deserializer)?;
+        Message::from_envelope(env).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corpus::{ANNOTATED_JSON, GESTURE_JSON, IDS_JSON, IME_JSON, TEST_JSON};
+    use rpc3;
+
+    #[test]
+    fn agrees_with_rpc3() {
+        let corpus = TEST_JSON.lines().chain(GESTURE_JSON.lines()).chain(IDS_JSON.lines())
+            .chain(IME_JSON.lines()).chain(ANNOTATED_JSON.lines());
+        for json in corpus {
+            assert!(serde_json::from_str::<rpc3::CoreNotification>(json).is_ok());
+            assert!(Message::parse(json).is_ok(), "{}", json);
+        }
+        for json in &[
+            r#"{"method":"frobnicate","params":{}}"#,
+            r#"{"method":"close_view","params":{}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"insert","params":{}}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"scroll","params":[1]}}"#,
+            r#"{"method":"plugin","params":{"command":"start","view_id":"v"}}"#,
+        ] {
+            assert!(serde_json::from_str::<rpc3::CoreNotification>(json).is_err());
+            assert!(Message::parse(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn header_and_payload() {
+        let json = r#"{"id":7,"method":"edit","params":{"view_id":"view-id-1","method":"insert",
+                       "params":{"chars":"plain"}}}"#;
+        let msg = Message::parse(json).unwrap();
+        assert_eq!(msg.header, Header {
+            id: Some(RpcId::Number(7)),
+            method: "edit",
+            view_id: Some("view-id-1".into()),
+        });
+        match msg.payload {
+            Payload::Edit { cmd: Edit::Insert { chars: Cow::Borrowed(chars) }, .. } => {
+                assert_eq!(chars, "plain");
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // escaped text has to be copied, but is still the payload's
+        let json = r#"{"method":"save","params":{"view_id":"v","file_path":"C:\\a.txt"}}"#;
+        let msg = Message::parse(json).unwrap();
+        assert_eq!(msg.header.id, None);
+        assert_eq!(msg.payload, Payload::Path(Some(Cow::Owned(r"C:\a.txt".into()))));
+
+        let json = r#"{"id":null,"method":"client_started","params":{}}"#;
+        assert_eq!(Message::parse(json).unwrap().header.id, Some(RpcId::Null));
+    }
+}
@@ -0,0 +1,1374 @@
+//! The deserialization strategies under comparison, behind a common
+//! interface so that they can be run over arbitrary corpora.
+//!
+//! Each strategy mirrors one of the benches in lib.rs, except that failures
+//! are returned instead of unwrapped, as a `ParseError`.
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, value::RawValue, Value};
+#[cfg(feature = "no-panic")]
+use no_panic::no_panic;
+#[cfg(feature = "json5")]
+use json5;
+#[cfg(feature = "serde_path_to_error")]
+use serde_path_to_error;
+#[cfg(feature = "unbounded_depth")]
+use serde_stacker;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::hint::black_box;
+
+#[cfg(all(feature = "xi-core", not(target_arch = "wasm32")))]
+use xi_core_lib::rpc::Request;
+#[cfg(all(not(feature = "xi-core"), not(target_arch = "wasm32")))]
+use reference_v2::Request;
+
+use corpus::Direction;
+
+use cache::ParamsCache;
+use error::ParseError;
+use fast_path;
+use internal;
+use peek;
+use reassemble::Reassembler;
+#[cfg(not(target_arch = "wasm32"))]
+use reference_v1;
+#[cfg(not(target_arch = "wasm32"))]
+use reference_v2;
+use rpc2;
+use rpc3;
+use rpc4;
+use rpc5;
+use route;
+use scratch::Scratch;
+#[cfg(not(target_arch = "wasm32"))]
+use scoped;
+use sink::{Consume, Sink};
+use split;
+use tape;
+#[cfg(feature = "unchecked")]
+use unchecked;
+use validate;
+#[cfg(not(target_arch = "wasm32"))]
+use {RpcCall, RpcCallWithId};
+
+// The hand-written envelope parsing below is on every message's path. With
+// the no-panic feature, a release build fails to link if the optimizer can't
+// prove these functions panic-free; panic landing pads inhibit inlining,
+// which would make the benches unrepresentative:
+//
+//     cargo test -p xi-der-strategies --release --features no-panic
+
+pub trait Strategy {
+    /// A short name, used in reports.
+    fn name(&self) -> &'static str;
+
+    /// Parses a single message, discarding the result.
+    fn parse(&self, json: &str) -> Result<(), ParseError> {
+        self.parse_into(json, &mut Sink::new())
+    }
+
+    /// Parses a single message, and feeds what it parsed to `sink`.
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError>;
+
+    /// Whether `parse_into` feeds the sink, so that its checksum can be
+    /// compared with other strategies'. xi-core's `Request` can't be looked
+    /// into, so the strategies built on it don't.
+    fn checksums(&self) -> bool {
+        true
+    }
+
+    /// Which way the messages it parses go. Most strategies are for what a
+    /// frontend sends core.
+    fn direction(&self) -> Direction {
+        Direction::C2S
+    }
+
+    /// Gets whatever the strategy keeps between messages ready for a run;
+    /// see `scratch`. Most strategies keep nothing.
+    fn prepare(&self) {}
+
+    /// Forgets whatever the strategy kept from the last run, so that the
+    /// next starts afresh.
+    fn reset(&self) {}
+
+    /// What else there is to know about it when choosing one; see
+    /// `Traits`. The default is `Serde`'s.
+    fn traits(&self) -> Traits {
+        Traits::default()
+    }
+}
+
+/// What choosing a strategy would commit xi-core to, besides its speed.
+/// Reports show these next to the timings, since the fastest strategy is
+/// often the one that asks the most of whoever maintains it.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Traits {
+    /// Whether the code here that it runs has `unsafe` blocks. serde_json's
+    /// and the other dependencies' aren't counted.
+    pub uses_unsafe: bool,
+    /// Whether it parses a message without allocating, as long as none of
+    /// its strings have escapes.
+    pub allocation_free: bool,
+    /// Whether what it parses borrows its strings from the input, rather
+    /// than copying them out.
+    pub borrows: bool,
+    pub maintainability: Maintainability,
+}
+
+/// How much of the protocol would have to be kept up to date by hand.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Maintainability {
+    /// Parsing written by hand: every new method or field is code to write
+    /// and test.
+    Low,
+    /// Derived types, with some of the parsing done by hand around them.
+    Medium,
+    /// Derived types, as xi-core's are: a new method is a new variant.
+    High,
+}
+
+impl Default for Traits {
+    fn default() -> Traits {
+        Traits {
+            uses_unsafe: false,
+            allocation_free: false,
+            borrows: false,
+            maintainability: Maintainability::High,
+        }
+    }
+}
+
+impl Traits {
+    /// Derived types that borrow, as `FutureSerde`'s do.
+    fn borrowing() -> Traits {
+        Traits { borrows: true, ..Traits::default() }
+    }
+
+    /// Owned types, some of them parsed by hand.
+    fn partly_by_hand() -> Traits {
+        Traits { maintainability: Maintainability::Medium, ..Traits::default() }
+    }
+
+    /// Parsed by hand, borrowing, as `Hand` does.
+    fn by_hand() -> Traits {
+        Traits { borrows: true, maintainability: Maintainability::Low, ..Traits::default() }
+    }
+}
+
+impl Maintainability {
+    pub fn name(self) -> &'static str {
+        match self {
+            Maintainability::Low => "low",
+            Maintainability::Medium => "medium",
+            Maintainability::High => "high",
+        }
+    }
+}
+
+/// Parse into a `Value`, and let xi-core borrow from it.
+#[cfg(all(feature = "xi-core", not(target_arch = "wasm32")))]
+pub struct Borrow;
+
+/// Parse into a `Value`, then into an owned envelope.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Own;
+
+/// Parse the envelope directly from the string, skipping the `Value`.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct OwnDirect;
+
+/// Parse into a `Value` just to look at the id, then parse the envelope
+/// again from the string.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct OwnPeekId;
+
+/// As `Own`, with xi-core's parser as it was before it borrowed; see
+/// `reference_v1`.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ReferenceV1;
+
+/// As `Own`, with the snapshot of xi-core's current parser, whether or not
+/// the `xi-core` feature is on; see `reference_v2`.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ReferenceV2;
+
+/// Parse into a `Value`, then into the rpc2 types.
+pub struct Serde;
+
+/// As `Serde`, but finding the id by scanning the string, and parsing
+/// straight from it; see `peek`.
+pub struct ScanId;
+
+/// As `ScanId`, with the parse of each edit's params cached by their raw
+/// text; see `cache`. Each instance has a cache of its own, which lasts
+/// until it's reset.
+#[derive(Default)]
+pub struct CachedParams {
+    cache: Scratch<ParamsCache>,
+}
+
+/// As `Hand`, with commands without params matched as bytes first; see
+/// `fast_path`.
+pub struct FastPath;
+
+/// As `ScanId`, with edits read straight into the events xi-core handles
+/// them as, rather than into an RPC enum first; see `internal`.
+pub struct Internal;
+
+/// As `Serde`, putting inserts that were split into chunks back together;
+/// see `reassemble`. Like `FutureSerdeStream` it can be given several
+/// messages at once, which is the only way a paste's chunks meet.
+pub struct Reassemble;
+
+/// As `Serde`, with a JSON5 parser, which also accepts comments, trailing
+/// commas, unquoted keys and so on. It doesn't borrow, so the target is the
+/// same as `Serde`'s.
+#[cfg(feature = "json5")]
+pub struct Json5;
+
+/// As `Serde`, tracking the path to the field being deserialized, so that
+/// errors can say where they happened.
+#[cfg(feature = "serde_path_to_error")]
+pub struct SerdePath;
+
+/// Parse directly from the string into the borrowing rpc3 types.
+pub struct FutureSerde;
+
+/// As `FutureSerde`, with one `Deserializer` for everything it's given,
+/// which can be several messages separated by whitespace, as a whole corpus
+/// is. Given a line at a time, it makes a `Deserializer` for each, as
+/// `FutureSerde` does.
+pub struct FutureSerdeStream;
+
+/// As `FutureSerde`, tracking the path as `SerdePath` does.
+#[cfg(feature = "serde_path_to_error")]
+pub struct FutureSerdePath;
+
+/// As `FutureSerde`, with serde_json's recursion limit turned off, and
+/// serde_stacker growing the stack instead when it runs low, so that params
+/// nested any depth parse. Dropping them still recurses, though.
+#[cfg(feature = "unbounded_depth")]
+pub struct FutureSerdeUnbounded;
+
+/// As `FutureSerde`, with hand-written impls instead of serde_derive's.
+pub struct Hand;
+
+/// As `Hand`, reading the envelope without bounds checks; see `unchecked`.
+#[cfg(feature = "unchecked")]
+pub struct Unchecked;
+
+/// Own the header of each message, and borrow its payload.
+pub struct Split;
+
+/// As `FutureSerde`, then dispatch the borrowed messages on another thread,
+/// in a scope. Each call spawns a thread, so this is meant for batches.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Scoped;
+
+/// Parse the envelope alone, skipping the params; see `route`. It accepts
+/// methods and params that the others reject, so it isn't one of `all`,
+/// but one of `routers`.
+pub struct IgnoredAny;
+
+/// Scan into a flat tape of offsets, and validate from that.
+pub struct Tape;
+
+/// As `Serde`, for what core sends a frontend.
+pub struct SerdeToClient;
+
+/// As `Serde`, for what a plugin sends core.
+pub struct SerdeFromPlugin;
+
+/// As `Serde`, for what core sends a plugin.
+pub struct SerdeToPlugin;
+
+/// As `FutureSerde`, for what core sends a plugin: straight from the string
+/// into rpc3's borrowing types, without a `Value` to take the id out of.
+pub struct FutureSerdeToPlugin;
+
+/// Returns all of the strategies, in the order they should be reported.
+///
+/// xi-core doesn't build for wasm, so the strategies that use it are
+/// missing there.
+pub fn all() -> Vec<Box<dyn Strategy>> {
+    let mut all: Vec<Box<dyn Strategy>> = Vec::new();
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        #[cfg(feature = "xi-core")]
+        all.push(Box::new(Borrow));
+        all.push(Box::new(Own));
+        all.push(Box::new(OwnDirect));
+        all.push(Box::new(OwnPeekId));
+        all.push(Box::new(ReferenceV1));
+        all.push(Box::new(ReferenceV2));
+    }
+    all.push(Box::new(Serde));
+    all.push(Box::new(ScanId));
+    all.push(Box::new(CachedParams::default()));
+    all.push(Box::new(FastPath));
+    all.push(Box::new(Internal));
+    all.push(Box::new(Reassemble));
+    #[cfg(feature = "serde_path_to_error")]
+    all.push(Box::new(SerdePath));
+    #[cfg(feature = "json5")]
+    all.push(Box::new(Json5));
+    all.push(Box::new(FutureSerde));
+    all.push(Box::new(FutureSerdeStream));
+    #[cfg(feature = "serde_path_to_error")]
+    all.push(Box::new(FutureSerdePath));
+    #[cfg(feature = "unbounded_depth")]
+    all.push(Box::new(FutureSerdeUnbounded));
+    all.push(Box::new(Hand));
+    #[cfg(feature = "unchecked")]
+    all.push(Box::new(Unchecked));
+    all.push(Box::new(Split));
+    #[cfg(not(target_arch = "wasm32"))]
+    all.push(Box::new(Scoped));
+    all.push(Box::new(Tape));
+    all
+}
+
+/// Returns the strategies that read a frontend's messages only as far as
+/// forwarding them needs.
+pub fn routers() -> Vec<Box<dyn Strategy>> {
+    vec![Box::new(IgnoredAny)]
+}
+
+/// Returns the strategies for messages sent in `direction`: `all` of them
+/// for a frontend's messages, and one or two for each of the others.
+pub fn for_direction(direction: Direction) -> Vec<Box<dyn Strategy>> {
+    match direction {
+        Direction::C2S => all(),
+        Direction::S2C => vec![Box::new(SerdeToClient)],
+        Direction::P2C => vec![Box::new(SerdeFromPlugin)],
+        Direction::C2P => vec![Box::new(SerdeToPlugin), Box::new(FutureSerdeToPlugin)],
+    }
+}
+
+/// Returns the strategy named `name`, if one exists, for any direction, or
+/// one of the `routers`.
+pub fn by_name(name: &str) -> Option<Box<dyn Strategy>> {
+    Direction::ALL.iter().flat_map(|&d| for_direction(d)).chain(routers())
+        .find(|s| s.name() == name)
+}
+
+/// Whether `json` is a batch: a JSON-RPC array of messages, on one line.
+///
+/// Some clients coalesce messages this way. Strategies that parse straight
+/// from the string need to know up front what they're looking at.
+#[cfg_attr(feature = "no-panic", no_panic)]
+pub fn is_batch(json: &str) -> bool {
+    json.trim_start().starts_with('[')
+}
+
+/// Calls `f` with each message in `val`: the elements of a batch, or `val`
+/// itself. As in JSON-RPC, an empty batch is an error.
+pub fn for_each_message<F>(val: Value, mut f: F) -> Result<(), ParseError>
+    where F: FnMut(Value) -> Result<(), ParseError>
+{
+    match val {
+        Value::Array(items) => {
+            if items.is_empty() {
+                return Err(ParseError::Envelope("empty batch".into()));
+            }
+            items.into_iter().try_for_each(f)
+        }
+        single => f(single),
+    }
+}
+
+/// Parses `json` directly as a batch of `T`, or a single `T`.
+fn from_str_batch<'a, T, F>(json: &'a str, mut f: F) -> Result<(), ParseError>
+    where T: Deserialize<'a>, F: FnMut(T) -> Result<(), ParseError>
+{
+    if is_batch(json) {
+        let batch = serde_json::from_str::<Vec<T>>(json)
+            .map_err(|e| ParseError::from_json(json, e))?;
+        if batch.is_empty() {
+            return Err(ParseError::Envelope("empty batch".into()));
+        }
+        batch.into_iter().try_for_each(f)
+    } else {
+        f(serde_json::from_str::<T>(json).map_err(|e| ParseError::from_json(json, e))?)
+    }
+}
+
+/// Calls `f` with the text of each message in `json`: the elements of a
+/// batch, or `json` itself.
+fn for_each_str<F>(json: &str, mut f: F) -> Result<(), ParseError>
+    where F: FnMut(&str) -> serde_json::Result<()>
+{
+    if !is_batch(json) {
+        return f(json).map_err(|e| ParseError::from_json(json, e));
+    }
+    let batch = serde_json::from_str::<Vec<&RawValue>>(json)
+        .map_err(|e| ParseError::from_json(json, e))?;
+    if batch.is_empty() {
+        return Err(ParseError::Envelope("empty batch".into()));
+    }
+    batch.iter().try_for_each(|msg| f(msg.get()).map_err(|e| ParseError::from_json(json, e)))
+}
+
+/// As `from_str_batch`, tracking the path to the field being deserialized.
+#[cfg(feature = "serde_path_to_error")]
+fn from_str_batch_tracked<'a, T, F>(json: &'a str, mut f: F) -> Result<(), ParseError>
+    where T: Deserialize<'a>, F: FnMut(T) -> Result<(), ParseError>
+{
+    let mut de = serde_json::Deserializer::from_str(json);
+    if is_batch(json) {
+        let batch = serde_path_to_error::deserialize::<_, Vec<T>>(&mut de)
+            .map_err(|e| ParseError::from_path(json, e))?;
+        de.end().map_err(|e| ParseError::from_json(json, e))?;
+        if batch.is_empty() {
+            return Err(ParseError::Envelope("empty batch".into()));
+        }
+        batch.into_iter().try_for_each(f)
+    } else {
+        let msg = serde_path_to_error::deserialize::<_, T>(&mut de)
+            .map_err(|e| ParseError::from_path(json, e))?;
+        de.end().map_err(|e| ParseError::from_json(json, e))?;
+        f(msg)
+    }
+}
+
+/// As `from_str_batch`, however deep `json` nests.
+#[cfg(feature = "unbounded_depth")]
+fn from_str_batch_unbounded<'a, T, F>(json: &'a str, mut f: F) -> Result<(), ParseError>
+    where T: Deserialize<'a>, F: FnMut(T) -> Result<(), ParseError>
+{
+    fn parse<'a, T: Deserialize<'a>>(json: &'a str) -> serde_json::Result<T> {
+        let mut de = serde_json::Deserializer::from_str(json);
+        de.disable_recursion_limit();
+        let parsed = T::deserialize(serde_stacker::Deserializer::new(&mut de))?;
+        de.end()?;
+        Ok(parsed)
+    }
+
+    if is_batch(json) {
+        let batch = parse::<Vec<T>>(json).map_err(|e| ParseError::from_json(json, e))?;
+        if batch.is_empty() {
+            return Err(ParseError::Envelope("empty batch".into()));
+        }
+        batch.into_iter().try_for_each(f)
+    } else {
+        f(parse::<T>(json).map_err(|e| ParseError::from_json(json, e))?)
+    }
+}
+
+/// Converts one message from a `Value` to `T`, and feeds it to `sink`.
+///
+/// The tape validator only knows a frontend's methods, so it can't classify
+/// errors in the others'; they're put down to the params.
+fn directed_from_value<T>(val: Value, sink: &mut Sink) -> Result<(), ParseError>
+    where T: DeserializeOwned + Consume
+{
+    let method = val.get("method").and_then(Value::as_str).unwrap_or_default().to_owned();
+    let msg = serde_json::from_value::<T>(val)
+        .map_err(|e| ParseError::BadParams { method, message: e.to_string() })?;
+    sink.consume(&msg);
+    Ok(())
+}
+
+#[cfg_attr(feature = "no-panic", no_panic)]
+pub fn dict_get_string<'a>(dict: &'a serde_json::Map<String, Value>, key: &str) -> Option<&'a str> {
+    dict.get(key).and_then(Value::as_str)
+}
+
+#[cfg_attr(feature = "no-panic", no_panic)]
+pub fn parse_rpc_request(json: &Value) -> Option<(Option<&Value>, &str, &Value)> {
+    json.as_object().and_then(|req| {
+        if let (Some(method), Some(params)) =
+            (dict_get_string(req, "method"), req.get("params")) {
+                let id = req.get("id");
+                Some((id, method, params))
+            }
+        else { None }
+    })
+}
+
+#[cfg(all(feature = "xi-core", not(target_arch = "wasm32")))]
+impl Strategy for Borrow {
+    fn name(&self) -> &'static str {
+        "borrow"
+    }
+
+    // a `Request` can't be fed to the sink, so it's kept from the optimizer
+    // with `black_box` instead
+    fn parse_into(&self, json: &str, _sink: &mut Sink) -> Result<(), ParseError> {
+        let val = serde_json::from_str::<Value>(json).map_err(|e| ParseError::from_json(json, e))?;
+        for_each_message(val, |val| {
+            let (_id, method, params) = parse_rpc_request(&val)
+                .ok_or_else(|| ParseError::Envelope("invalid RPC request".into()))?;
+            Request::from_json(method, params)
+                .map(|req| { black_box(req); })
+                .map_err(|e| ParseError::classify(json, format!("{:?}", e)))
+        })
+    }
+
+    fn checksums(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Strategy for Own {
+    fn name(&self) -> &'static str {
+        "own"
+    }
+
+    fn parse_into(&self, json: &str, _sink: &mut Sink) -> Result<(), ParseError> {
+        let val = serde_json::from_str::<Value>(json).map_err(|e| ParseError::from_json(json, e))?;
+        for_each_message(val, |mut val| {
+            let _id = val.as_object_mut().map(|obj| obj.remove("id"));
+            let rpc: RpcCall = serde_json::from_value(val)
+                .map_err(|e| ParseError::from_json(json, e))?;
+            Request::from_json(&rpc.method, &rpc.params)
+                .map(|req| { black_box(req); })
+                .map_err(|e| ParseError::classify(json, format!("{:?}", e)))
+        })
+    }
+
+    fn checksums(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Strategy for OwnDirect {
+    fn name(&self) -> &'static str {
+        "own_direct"
+    }
+
+    fn parse_into(&self, json: &str, _sink: &mut Sink) -> Result<(), ParseError> {
+        from_str_batch(json, |rpc: RpcCallWithId| {
+            Request::from_json(&rpc.method, &rpc.params)
+                .map(|req| { black_box(req); })
+                .map_err(|e| ParseError::classify(json, format!("{:?}", e)))
+        })
+    }
+
+    fn checksums(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Strategy for OwnPeekId {
+    fn name(&self) -> &'static str {
+        "own_peek_id"
+    }
+
+    fn parse_into(&self, json: &str, _sink: &mut Sink) -> Result<(), ParseError> {
+        let val = serde_json::from_str::<Value>(json).map_err(|e| ParseError::from_json(json, e))?;
+        let _id = val.get("id");
+        from_str_batch(json, |rpc: RpcCall| {
+            Request::from_json(&rpc.method, &rpc.params)
+                .map(|req| { black_box(req); })
+                .map_err(|e| ParseError::classify(json, format!("{:?}", e)))
+        })
+    }
+
+    fn checksums(&self) -> bool {
+        false
+    }
+}
+
+/// `Own`'s pipeline, with `from_json` in place of xi-core's parser.
+#[cfg(not(target_arch = "wasm32"))]
+fn own_with<F>(json: &str, from_json: F) -> Result<(), ParseError>
+    where F: Fn(&str, &Value) -> Result<(), String>
+{
+    let val = serde_json::from_str::<Value>(json).map_err(|e| ParseError::from_json(json, e))?;
+    for_each_message(val, |mut val| {
+        let _id = val.as_object_mut().map(|obj| obj.remove("id"));
+        let rpc: RpcCall = serde_json::from_value(val)
+            .map_err(|e| ParseError::from_json(json, e))?;
+        from_json(&rpc.method, &rpc.params).map_err(|e| ParseError::classify(json, e))
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Strategy for ReferenceV1 {
+    fn name(&self) -> &'static str {
+        "reference_v1"
+    }
+
+    fn parse_into(&self, json: &str, _sink: &mut Sink) -> Result<(), ParseError> {
+        own_with(json, |method, params| {
+            reference_v1::Request::from_json(method, params)
+                .map(|req| { black_box(req); })
+                .map_err(|e| format!("{:?}", e))
+        })
+    }
+
+    fn checksums(&self) -> bool {
+        false
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::partly_by_hand()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Strategy for ReferenceV2 {
+    fn name(&self) -> &'static str {
+        "reference_v2"
+    }
+
+    fn parse_into(&self, json: &str, _sink: &mut Sink) -> Result<(), ParseError> {
+        own_with(json, |method, params| {
+            reference_v2::Request::from_json(method, params)
+                .map(|req| { black_box(req); })
+                .map_err(|e| format!("{:?}", e))
+        })
+    }
+
+    fn checksums(&self) -> bool {
+        false
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::partly_by_hand()
+    }
+}
+
+/// Converts one message from a `Value` to the rpc2 types, as a request if
+/// it has an id, and a notification if not, and feeds it to `sink`.
+pub fn rpc2_from_value(mut val: Value, sink: &mut Sink) -> Result<(), serde_json::Error> {
+    let id = val.as_object_mut().and_then(|obj| obj.remove("id"));
+    if id.is_some() {
+        sink.consume(&serde_json::from_value::<rpc2::CoreRequest>(val)?);
+    } else {
+        sink.consume(&serde_json::from_value::<rpc2::CoreNotification>(val)?);
+    }
+    Ok(())
+}
+
+/// As `rpc2_from_value`, from a string, telling requests from notifications
+/// with `peek::has_id`.
+pub fn rpc2_from_str(json: &str, sink: &mut Sink) -> Result<(), serde_json::Error> {
+    if peek::has_id(json) {
+        sink.consume(&serde_json::from_str::<rpc2::CoreRequest>(json)?);
+    } else {
+        sink.consume(&serde_json::from_str::<rpc2::CoreNotification>(json)?);
+    }
+    Ok(())
+}
+
+/// As `rpc2_from_value`, tracking the path to the field being deserialized.
+#[cfg(feature = "serde_path_to_error")]
+pub fn rpc2_from_value_tracked(mut val: Value, sink: &mut Sink)
+    -> Result<(), serde_path_to_error::Error<serde_json::Error>>
+{
+    let id = val.as_object_mut().and_then(|obj| obj.remove("id"));
+    if id.is_some() {
+        sink.consume(&serde_path_to_error::deserialize::<_, rpc2::CoreRequest>(val)?);
+    } else {
+        sink.consume(&serde_path_to_error::deserialize::<_, rpc2::CoreNotification>(val)?);
+    }
+    Ok(())
+}
+
+impl Strategy for Serde {
+    fn name(&self) -> &'static str {
+        "serde"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let val = serde_json::from_str::<Value>(json).map_err(|e| ParseError::from_json(json, e))?;
+        for_each_message(val, |val| {
+            rpc2_from_value(val, sink).map_err(|e| ParseError::from_json(json, e))
+        })
+    }
+}
+
+impl Strategy for ScanId {
+    fn name(&self) -> &'static str {
+        "scan_id"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        for_each_str(json, |msg| rpc2_from_str(msg, sink))
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::partly_by_hand()
+    }
+}
+
+impl Strategy for CachedParams {
+    fn name(&self) -> &'static str {
+        "cached_params"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        for_each_str(json, |msg| self.parse_one(msg, sink))
+    }
+
+    fn prepare(&self) {
+        self.cache.prepare();
+    }
+
+    fn reset(&self) {
+        self.cache.reset();
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::partly_by_hand()
+    }
+}
+
+impl CachedParams {
+    /// Edit notifications go through the cache, and everything else is
+    /// parsed as `ScanId` would, reading the envelope again.
+    fn parse_one(&self, json: &str, sink: &mut Sink) -> Result<(), serde_json::Error> {
+        let env = rpc4::Envelope::parse(json)?;
+        if env.id.is_some() || env.method != "edit" {
+            return rpc2_from_str(json, sink);
+        }
+        sink.consume(&*self.cache.get().edit(env.params.get())?);
+        Ok(())
+    }
+}
+
+impl Strategy for FastPath {
+    fn name(&self) -> &'static str {
+        "fast_path"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        for_each_str(json, |msg| FastPath::parse_one(msg, sink))
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::by_hand()
+    }
+}
+
+impl FastPath {
+    fn parse_one(json: &str, sink: &mut Sink) -> Result<(), serde_json::Error> {
+        match fast_path::parse(json) {
+            Some(msg) => sink.consume(&msg),
+            None => sink.consume(&serde_json::from_str::<rpc5::CoreNotification>(json)?),
+        }
+        Ok(())
+    }
+}
+
+impl Strategy for Internal {
+    fn name(&self) -> &'static str {
+        "internal"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        for_each_str(json, |msg| Internal::parse_one(msg, sink))
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::partly_by_hand()
+    }
+}
+
+impl Internal {
+    /// Notifications other than edits, and requests, are parsed as `ScanId`
+    /// would, reading the envelope again.
+    fn parse_one(json: &str, sink: &mut Sink) -> Result<(), serde_json::Error> {
+        let env = rpc4::Envelope::parse(json)?;
+        if env.id.is_some() || env.method != "edit" {
+            return rpc2_from_str(json, sink);
+        }
+        sink.consume(&internal::Edit::from_params(env.params.get())?);
+        Ok(())
+    }
+}
+
+impl Strategy for Reassemble {
+    fn name(&self) -> &'static str {
+        "reassemble"
+    }
+
+    /// A batch's messages are reassembled as if they'd come one at a time.
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let mut reassembler = Reassembler::new();
+        let mut messages = 0;
+        for val in serde_json::Deserializer::from_str(json).into_iter::<Value>() {
+            let val = val.map_err(|e| ParseError::from_json(json, e))?;
+            for_each_message(val, |mut val| {
+                let id = val.as_object_mut().and_then(|obj| obj.remove("id"));
+                if id.is_some() {
+                    reassembler.flush(sink);
+                    sink.consume(&serde_json::from_value::<rpc2::CoreRequest>(val)
+                        .map_err(|e| ParseError::from_json(json, e))?);
+                } else {
+                    reassembler.push(serde_json::from_value(val)
+                        .map_err(|e| ParseError::from_json(json, e))?, sink);
+                }
+                Ok(())
+            })?;
+            messages += 1;
+        }
+        if messages == 0 {
+            return Err(ParseError::Frame("no message".into()));
+        }
+        reassembler.flush(sink);
+        Ok(())
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::partly_by_hand()
+    }
+}
+
+#[cfg(feature = "serde_path_to_error")]
+impl Strategy for SerdePath {
+    fn name(&self) -> &'static str {
+        "serde_path"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let val = serde_json::from_str::<Value>(json).map_err(|e| ParseError::from_json(json, e))?;
+        for_each_message(val, |val| {
+            rpc2_from_value_tracked(val, sink).map_err(|e| ParseError::from_path(json, e))
+        })
+    }
+}
+
+#[cfg(feature = "json5")]
+impl Strategy for Json5 {
+    fn name(&self) -> &'static str {
+        "json5"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let val = json5::from_str::<Value>(json).map_err(|e| ParseError::Frame(e.to_string()))?;
+        for_each_message(val, |val| rpc2_from_value(val, sink).map_err(|e| {
+            // the validator only understands JSON, so show it the input as a Value
+            match json5::from_str::<Value>(json) {
+                Ok(val) => ParseError::from_value(&val, e),
+                Err(_) => ParseError::Frame(e.to_string()),
+            }
+        }))
+    }
+}
+
+impl Strategy for FutureSerde {
+    fn name(&self) -> &'static str {
+        "future_serde"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        from_str_batch(json, |msg: rpc3::CoreNotification| {
+            sink.consume(&msg);
+            Ok(())
+        })
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::borrowing()
+    }
+}
+
+impl Strategy for FutureSerdeStream {
+    fn name(&self) -> &'static str {
+        "future_serde_stream"
+    }
+
+    /// A batch has to be the only thing in `json`.
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        if is_batch(json) {
+            return FutureSerde.parse_into(json, sink);
+        }
+        let mut messages = 0;
+        for msg in serde_json::Deserializer::from_str(json).into_iter::<rpc3::CoreNotification>() {
+            sink.consume(&msg.map_err(|e| ParseError::from_json(json, e))?);
+            messages += 1;
+        }
+        if messages == 0 {
+            return Err(ParseError::Frame("no message".into()));
+        }
+        Ok(())
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::borrowing()
+    }
+}
+
+#[cfg(feature = "serde_path_to_error")]
+impl Strategy for FutureSerdePath {
+    fn name(&self) -> &'static str {
+        "future_serde_path"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        from_str_batch_tracked(json, |msg: rpc3::CoreNotification| {
+            sink.consume(&msg);
+            Ok(())
+        })
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::borrowing()
+    }
+}
+
+#[cfg(feature = "unbounded_depth")]
+impl Strategy for FutureSerdeUnbounded {
+    fn name(&self) -> &'static str {
+        "future_serde_unbounded"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        from_str_batch_unbounded(json, |msg: rpc3::CoreNotification| {
+            sink.consume(&msg);
+            Ok(())
+        })
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::borrowing()
+    }
+}
+
+impl Strategy for Hand {
+    fn name(&self) -> &'static str {
+        "hand"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        from_str_batch(json, |msg: rpc5::CoreNotification| {
+            sink.consume(&msg);
+            Ok(())
+        })
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::by_hand()
+    }
+}
+
+#[cfg(feature = "unchecked")]
+impl Strategy for Unchecked {
+    fn name(&self) -> &'static str {
+        "unchecked"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let (method, params) = match unchecked::envelope(json) {
+            Some(envelope) => envelope,
+            None => return Hand.parse_into(json, sink),
+        };
+        let msg = rpc5::CoreNotification::from_params(method, params)
+            .map_err(|e| ParseError::from_json(json, e))?;
+        sink.consume(&msg);
+        Ok(())
+    }
+
+    fn traits(&self) -> Traits {
+        Traits { uses_unsafe: true, ..Traits::by_hand() }
+    }
+}
+
+impl Strategy for Split {
+    fn name(&self) -> &'static str {
+        "split"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        from_str_batch(json, |msg: split::Message| {
+            sink.consume(&msg);
+            Ok(())
+        })
+    }
+
+    fn traits(&self) -> Traits {
+        Traits { maintainability: Maintainability::Medium, ..Traits::borrowing() }
+    }
+}
+
+impl Strategy for IgnoredAny {
+    fn name(&self) -> &'static str {
+        "ignored_any"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        from_str_batch(json, |msg: route::Envelope| {
+            sink.consume(&msg);
+            Ok(())
+        })
+    }
+
+    /// Only the methods are hashed.
+    fn checksums(&self) -> bool {
+        false
+    }
+
+    fn traits(&self) -> Traits {
+        Traits { allocation_free: true, ..Traits::borrowing() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Strategy for Scoped {
+    fn name(&self) -> &'static str {
+        "scoped"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let mut msgs = Vec::new();
+        from_str_batch(json, |msg: rpc3::CoreNotification| {
+            msgs.push(msg);
+            Ok(())
+        })?;
+        sink.consume(&msgs);
+        scoped::dispatch_all(msgs);
+        Ok(())
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::borrowing()
+    }
+}
+
+impl Strategy for Tape {
+    fn name(&self) -> &'static str {
+        "tape"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let tape = tape::Tape::parse(json).map_err(|e| ParseError::Frame(e.to_string()))?;
+        let root = tape.root();
+        let mut message = |node: tape::Node| -> Result<(), ParseError> {
+            let msg = tape::Message::from_node(node)?;
+            msg.validate()?;
+            sink.consume(&msg);
+            Ok(())
+        };
+        if root.op() != tape::Op::Array {
+            return message(root);
+        }
+        if root.is_empty() {
+            return Err(ParseError::Envelope("empty batch".into()));
+        }
+        root.children().try_for_each(message)
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::by_hand()
+    }
+}
+
+impl Strategy for SerdeToClient {
+    fn name(&self) -> &'static str {
+        "serde_s2c"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let val = serde_json::from_str::<Value>(json).map_err(|e| ParseError::from_json(json, e))?;
+        directed_from_value::<rpc2::ClientNotification>(val, sink)
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::S2C
+    }
+}
+
+impl Strategy for SerdeFromPlugin {
+    fn name(&self) -> &'static str {
+        "serde_p2c"
+    }
+
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let val = serde_json::from_str::<Value>(json).map_err(|e| ParseError::from_json(json, e))?;
+        directed_from_value::<rpc2::PluginCoreNotification>(val, sink)
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::P2C
+    }
+}
+
+impl Strategy for SerdeToPlugin {
+    fn name(&self) -> &'static str {
+        "serde_c2p"
+    }
+
+    /// Core's updates are requests, and everything else it sends a plugin
+    /// notifications.
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let mut val = serde_json::from_str::<Value>(json)
+            .map_err(|e| ParseError::from_json(json, e))?;
+        if val.as_object_mut().and_then(|obj| obj.remove("id")).is_some() {
+            directed_from_value::<rpc2::HostRequest>(val, sink)
+        } else {
+            directed_from_value::<rpc2::HostNotification>(val, sink)
+        }
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::C2P
+    }
+}
+
+impl Strategy for FutureSerdeToPlugin {
+    fn name(&self) -> &'static str {
+        "future_serde_c2p"
+    }
+
+    /// Which type a message is has to be settled by peeking at its id
+    /// first; the id is then skipped like any other unknown field.
+    fn parse_into(&self, json: &str, sink: &mut Sink) -> Result<(), ParseError> {
+        let classify = |e| validate::classify(json, Direction::C2P, e);
+        if peek::has_id(json) {
+            sink.consume(&serde_json::from_str::<rpc3::HostRequest>(json).map_err(classify)?);
+        } else {
+            sink.consume(&serde_json::from_str::<rpc3::HostNotification>(json)
+                .map_err(classify)?);
+        }
+        Ok(())
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::C2P
+    }
+
+    fn traits(&self) -> Traits {
+        Traits::borrowing()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corpus;
+    use mutate::{self, Mutation};
+    use corpus::rng::XorShift;
+    use TEST_JSON;
+
+    #[test]
+    fn envelope_never_panics() {
+        let mut rng = XorShift::new(3);
+        for line in TEST_JSON.lines() {
+            for mutation in Mutation::all() {
+                for _ in 0..20 {
+                    let mutated = match mutation.apply(line, &mut rng) {
+                        Some(m) => m,
+                        None => break,
+                    };
+                    if let Ok(val) = serde_json::from_str::<Value>(&mutated) {
+                        // the point is that this returns at all
+                        let _ = parse_rpc_request(&val);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn batches() {
+        let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        let batches = corpus::batch(&lines, 4);
+        for strategy in all() {
+            let (mut singly, mut batched) = (Sink::new(), Sink::new());
+            for line in &lines {
+                assert_eq!(strategy.parse_into(line, &mut singly), Ok(()),
+                           "{}: {}", strategy.name(), line);
+            }
+            for batch in &batches {
+                assert_eq!(strategy.parse_into(batch, &mut batched), Ok(()),
+                           "{}: {}", strategy.name(), batch);
+            }
+            assert_eq!(singly, batched, "{}", strategy.name());
+            assert!(strategy.parse(" []").is_err(), "{}", strategy.name());
+            let bad = format!("[{},{{\"method\":\"frobnicate\",\"params\":{{}}}}]", lines[0]);
+            assert!(strategy.parse(&bad).is_err(), "{}", strategy.name());
+        }
+    }
+
+    #[test]
+    fn routers_forward_anything() {
+        let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        for strategy in routers() {
+            assert!(by_name(strategy.name()).is_some(), "{}", strategy.name());
+            for batch in corpus::batch(&lines, 4) {
+                assert_eq!(strategy.parse(&batch), Ok(()), "{}: {}", strategy.name(), batch);
+            }
+            assert_eq!(strategy.parse(r#"{"method":"frobnicate","params":{}}"#), Ok(()));
+            assert_eq!(strategy.parse("{").unwrap_err().kind(), "frame");
+        }
+    }
+
+    #[cfg(feature = "json5")]
+    #[test]
+    fn relaxed() {
+        for line in corpus::RELAXED_JSON.lines() {
+            assert_eq!(Json5.parse(line), Ok(()), "{}", line);
+            assert!(Serde.parse(line).is_err(), "{}", line);
+        }
+    }
+
+    #[cfg(feature = "serde_path_to_error")]
+    #[test]
+    fn paths_in_errors() {
+        let insert = r#"{"method":"edit","params":{"view_id":"v","method":"insert","params":{}}}"#;
+        for strategy in &[&SerdePath as &dyn Strategy, &FutureSerdePath] {
+            let err = strategy.parse(insert).unwrap_err();
+            assert_eq!(err.kind(), "bad_params");
+            assert!(err.message().starts_with("params"), "{}", err);
+        }
+        let batch = format!(r#"[{{"method":"client_started","params":{{}}}},{}]"#, insert);
+        let err = FutureSerdePath.parse(&batch).unwrap_err();
+        assert!(err.message().starts_with("[1]"), "{}", err);
+        assert_eq!(FutureSerdePath.parse(r#"{"method":"client_started","params":{}} }"#)
+                   .unwrap_err().kind(), "frame");
+    }
+
+    #[test]
+    fn directions() {
+        for &direction in &Direction::ALL {
+            for strategy in for_direction(direction) {
+                assert_eq!(strategy.direction(), direction, "{}", strategy.name());
+                assert_eq!(by_name(strategy.name()).map(|s| s.direction()), Some(direction));
+                // xi-core doesn't know all of a frontend's corpora, so
+                // those are left to the tests above
+                if direction == Direction::C2S {
+                    continue;
+                }
+                for corpus in direction.corpora() {
+                    for line in corpus.lines() {
+                        assert_eq!(strategy.parse(line), Ok(()), "{}: {}", strategy.name(), line);
+                    }
+                }
+            }
+        }
+        let err = SerdeToClient.parse(TEST_JSON.lines().next().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), "bad_params");
+    }
+
+    #[test]
+    fn to_plugin() {
+        let session = corpus::generate::plugin_session(500, 2);
+        let (mut owned, mut borrowed) = (Sink::new(), Sink::new());
+        for line in &session {
+            SerdeToPlugin.parse_into(line, &mut owned).unwrap();
+            FutureSerdeToPlugin.parse_into(line, &mut borrowed).unwrap();
+        }
+        assert_eq!(owned, borrowed);
+
+        let no_delta = r#"{"id":0,"method":"update","params":{"view_id":"view-id-1"}}"#;
+        for strategy in for_direction(Direction::C2P) {
+            let errors = [no_delta, r#"{"method":"frobnicate","params":{}}"#, "{"]
+                .iter().map(|json| strategy.parse(json).unwrap_err().kind()).collect::<Vec<_>>();
+            assert_eq!(errors, ["bad_params", "bad_params", "frame"], "{}", strategy.name());
+        }
+    }
+
+    #[test]
+    fn one_deserializer() {
+        let (mut streamed, mut singly) = (Sink::new(), Sink::new());
+        assert_eq!(FutureSerdeStream.parse_into(TEST_JSON, &mut streamed), Ok(()));
+        for line in TEST_JSON.lines() {
+            FutureSerde.parse_into(line, &mut singly).unwrap();
+        }
+        assert_eq!(streamed, singly);
+        assert_eq!(FutureSerdeStream.parse(" \n").unwrap_err().kind(), "frame");
+        let bad = format!("{}\n{{", TEST_JSON.lines().next().unwrap());
+        assert_eq!(FutureSerdeStream.parse(&bad).unwrap_err().kind(), "frame");
+    }
+
+    #[test]
+    fn errors_agree() {
+        let cases = [
+            ("{", "frame"),
+            (r#"{"params":{}}"#, "envelope"),
+            (r#"{"method":"frobnicate","params":{}}"#, "unknown_method"),
+            (r#"{"method":"edit","params":{"view_id":"v","method":"insert","params":{}}}"#,
+             "bad_params"),
+        ];
+        for strategy in all() {
+            for &(json, kind) in &cases {
+                let err = strategy.parse(json).unwrap_err();
+                assert_eq!(err.kind(), kind, "{}: {}: {}", strategy.name(), json, err);
+            }
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        use corpus::generate::{deep_nesting, ENVELOPE_DEPTH};
+
+        let strategies = || all().into_iter().chain(routers())
+            // json5's parser recurses with no limit, and isn't ours to fix;
+            // future_serde_unbounded has a test of its own below
+            .filter(|s| s.name() != "json5" && s.name() != "future_serde_unbounded");
+        for line in &deep_nesting(127 - ENVELOPE_DEPTH, 20, 3) {
+            for strategy in strategies() {
+                assert_eq!(strategy.parse(line), Ok(()), "{}", strategy.name());
+            }
+        }
+        // far deeper than the stack would allow a recursive parser: those
+        // that parse the params give up at serde_json's limit, and those
+        // that keep them raw skip over them without recursing
+        let deep = &deep_nesting(100_000, 2, 3)[1];
+        for strategy in strategies() {
+            if let Err(err) = strategy.parse(deep) {
+                assert_eq!(err.kind(), "frame", "{}: {}", strategy.name(), err);
+            }
+        }
+        assert!(Serde.parse(deep).unwrap_err().message().contains("recursion limit"));
+        assert_eq!(Hand.parse(deep), Ok(()));
+    }
+
+    #[cfg(feature = "unbounded_depth")]
+    #[test]
+    fn unbounded_depth() {
+        use corpus::generate::deep_nesting;
+
+        let (mut bounded, mut unbounded) = (Sink::new(), Sink::new());
+        for line in &deep_nesting(64, 20, 3) {
+            FutureSerde.parse_into(line, &mut bounded).unwrap();
+            FutureSerdeUnbounded.parse_into(line, &mut unbounded).unwrap();
+        }
+        assert_eq!(bounded, unbounded);
+        // past the limit, but not so far that dropping the params, which
+        // recurses, overflows the stack
+        for line in &deep_nesting(1000, 20, 3)[1..] {
+            assert_eq!(FutureSerdeUnbounded.parse(line), Ok(()));
+            assert_eq!(FutureSerde.parse(line).unwrap_err().kind(), "frame");
+        }
+        let batch = format!("[{}]", deep_nesting(1000, 2, 3)[1]);
+        assert_eq!(FutureSerdeUnbounded.parse(&batch), Ok(()));
+        assert_eq!(FutureSerdeUnbounded.parse(&batch[..batch.len() - 1]).unwrap_err().kind(),
+                   "frame");
+    }
+
+    #[test]
+    fn traits() {
+        for strategy in all().into_iter().chain(routers()) {
+            let traits = strategy.traits();
+            // copying a string out of the input allocates
+            assert!(traits.borrows || !traits.allocation_free, "{}", strategy.name());
+        }
+        assert_eq!(Serde.traits(), Traits::default());
+        assert!(Hand.traits().maintainability < FutureSerde.traits().maintainability);
+        assert!(by_name("unchecked").map_or(true, |s| s.traits().uses_unsafe));
+    }
+
+    // xi-core's parsing isn't ours to fix, so borrow and own are left out
+    #[test]
+    fn strategies_never_panic() {
+        let lines = TEST_JSON.lines().map(String::from).collect::<Vec<_>>();
+        let strategies: Vec<Box<dyn Strategy>> =
+            vec![Box::new(Serde), Box::new(FutureSerde), Box::new(Hand), Box::new(Tape)];
+        let report = mutate::run(&lines, &strategies, 20, 3);
+        for (name, tallies) in report.strategies.iter().zip(report.tallies.iter()) {
+            for (mutation, tally) in report.mutations.iter().zip(tallies.iter()) {
+                assert_eq!(tally.panicked, 0, "{} panicked on {}", name, mutation.name());
+            }
+        }
+    }
+}
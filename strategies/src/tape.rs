@@ -0,0 +1,673 @@
+//! A two-stage, simdjson-style parse, specialized to xi RPC.
+//!
+//! The first stage scans a message into a flat tape of entries, each an
+//! opcode plus offsets back into the original buffer. Nothing is unescaped
+//! and no tree is built. The second stage answers questions about the tape
+//! ("what is the method?", "what is params.chars?") by walking it, which is
+//! cheap because containers record where they end and can be skipped in a
+//! single step.
+
+use std::borrow::Cow;
+use std::char;
+use std::fmt;
+use std::str;
+
+use error::ParseError;
+
+/// Deeper than any real message; guards the recursive scanner.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Object,
+    Array,
+    String,
+    Number,
+    True,
+    False,
+    Null,
+}
+
+/// One entry on the tape.
+///
+/// For scalars, `start..end` is the byte range of the token; for strings this
+/// excludes the quotes. For containers, `start` is the byte offset of the
+/// opening bracket, and `end` is the tape index just past the container's
+/// last descendant. An object's children alternate between keys and values.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub op: Op,
+    pub start: u32,
+    pub end: u32,
+    /// For strings, whether the raw bytes contain escapes.
+    pub escaped: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapeError {
+    pub pos: usize,
+    pub msg: &'static str,
+}
+
+/// A scanned message.
+#[derive(Debug)]
+pub struct Tape<'a> {
+    json: &'a str,
+    entries: Vec<Entry>,
+}
+
+/// A reference to a value on a tape.
+#[derive(Debug, Clone, Copy)]
+pub struct Node<'t, 'a: 't> {
+    tape: &'t Tape<'a>,
+    idx: usize,
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    entries: Vec<Entry>,
+}
+
+impl fmt::Display for TapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.msg, self.pos)
+    }
+}
+
+impl<'a> Tape<'a> {
+    /// Scans `json`, which must contain exactly one JSON value.
+    pub fn parse(json: &'a str) -> Result<Tape<'a>, TapeError> {
+        if json.len() > u32::MAX as usize {
+            return Err(TapeError { pos: 0, msg: "message too large" });
+        }
+        let mut scanner = Scanner { bytes: json.as_bytes(), pos: 0, entries: Vec::new() };
+        scanner.value(0)?;
+        scanner.skip_ws();
+        if scanner.pos != json.len() {
+            return Err(scanner.error("trailing characters"));
+        }
+        Ok(Tape { json, entries: scanner.entries })
+    }
+
+    pub fn root<'t>(&'t self) -> Node<'t, 'a> {
+        Node { tape: self, idx: 0 }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+}
+
+impl<'t, 'a> Node<'t, 'a> {
+    fn entry(&self) -> Entry {
+        self.tape.entries[self.idx]
+    }
+
+    /// The tape index of the next sibling of this node.
+    fn next_idx(&self) -> usize {
+        match self.entry().op {
+            Op::Object | Op::Array => self.entry().end as usize,
+            _ => self.idx + 1,
+        }
+    }
+
+    pub fn op(&self) -> Op {
+        self.entry().op
+    }
+
+    /// The children of this container, or nothing if this is a scalar.
+    /// For objects, keys and values alternate.
+    pub fn children(&self) -> Children<'t, 'a> {
+        let entry = self.entry();
+        let (idx, end) = match entry.op {
+            Op::Object | Op::Array => (self.idx + 1, entry.end as usize),
+            _ => (0, 0),
+        };
+        Children { tape: self.tape, idx, end }
+    }
+
+    /// Looks up `key`, if this is an object.
+    pub fn get(&self, key: &str) -> Option<Node<'t, 'a>> {
+        if self.op() != Op::Object {
+            return None;
+        }
+        let mut children = self.children();
+        while let (Some(k), Some(v)) = (children.next(), children.next()) {
+            if k.str_eq(key) {
+                return Some(v);
+            }
+        }
+        None
+    }
+
+    /// The number of elements in an array, or members in an object.
+    pub fn len(&self) -> usize {
+        let n = self.children().count();
+        if self.op() == Op::Object { n / 2 } else { n }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The raw contents of a string, with any escapes left in place.
+    pub fn as_raw_str(&self) -> Option<&'a str> {
+        let entry = self.entry();
+        if entry.op != Op::String {
+            return None;
+        }
+        self.tape.json.get(entry.start as usize..entry.end as usize)
+    }
+
+    /// The contents of a string. Only allocates if there are escapes.
+    pub fn as_str(&self) -> Option<Cow<'a, str>> {
+        let raw = self.as_raw_str()?;
+        if self.entry().escaped {
+            unescape(raw).map(Cow::Owned)
+        } else {
+            Some(Cow::Borrowed(raw))
+        }
+    }
+
+    /// Compares a string with `s`, without allocating in the common case.
+    pub fn str_eq(&self, s: &str) -> bool {
+        if self.entry().escaped {
+            self.as_str().map(|c| c == s).unwrap_or(false)
+        } else {
+            self.as_raw_str() == Some(s)
+        }
+    }
+
+    /// The text of a number, as written.
+    pub fn as_raw_number(&self) -> Option<&'a str> {
+        let entry = self.entry();
+        if entry.op != Op::Number {
+            return None;
+        }
+        self.tape.json.get(entry.start as usize..entry.end as usize)
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_raw_number()?.parse().ok()
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.op() {
+            Op::True => Some(true),
+            Op::False => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.op() == Op::Null
+    }
+}
+
+/// Iterates the direct children of a container.
+pub struct Children<'t, 'a: 't> {
+    tape: &'t Tape<'a>,
+    idx: usize,
+    end: usize,
+}
+
+impl<'t, 'a> Iterator for Children<'t, 'a> {
+    type Item = Node<'t, 'a>;
+
+    fn next(&mut self) -> Option<Node<'t, 'a>> {
+        if self.idx >= self.end {
+            return None;
+        }
+        let node = Node { tape: self.tape, idx: self.idx };
+        self.idx = node.next_idx();
+        Some(node)
+    }
+}
+
+impl<'a> Scanner<'a> {
+    fn error(&self, msg: &'static str) -> TapeError {
+        TapeError { pos: self.pos, msg }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).cloned()
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn push(&mut self, op: Op, start: usize, end: usize, escaped: bool) {
+        self.entries.push(Entry { op, start: start as u32, end: end as u32, escaped });
+    }
+
+    fn value(&mut self, depth: usize) -> Result<(), TapeError> {
+        self.skip_ws();
+        match self.peek() {
+            Some(b'{') => self.container(Op::Object, b'}', depth),
+            Some(b'[') => self.container(Op::Array, b']', depth),
+            Some(b'"') => self.string(),
+            Some(b't') => self.literal(b"true", Op::True),
+            Some(b'f') => self.literal(b"false", Op::False),
+            Some(b'n') => self.literal(b"null", Op::Null),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            _ => Err(self.error("expected value")),
+        }
+    }
+
+    fn container(&mut self, op: Op, close: u8, depth: usize) -> Result<(), TapeError> {
+        if depth >= MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        let idx = self.entries.len();
+        let start = self.pos;
+        self.push(op, start, 0, false);
+        self.pos += 1;
+        self.skip_ws();
+
+        if self.peek() == Some(close) {
+            self.pos += 1;
+        } else {
+            loop {
+                if op == Op::Object {
+                    self.skip_ws();
+                    if self.peek() != Some(b'"') {
+                        return Err(self.error("expected key"));
+                    }
+                    self.string()?;
+                    self.skip_ws();
+                    if self.peek() != Some(b':') {
+                        return Err(self.error("expected ':'"));
+                    }
+                    self.pos += 1;
+                }
+                self.value(depth + 1)?;
+                self.skip_ws();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(c) if c == close => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.error("expected ',' or end of container")),
+                }
+            }
+        }
+
+        self.entries[idx].end = self.entries.len() as u32;
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<(), TapeError> {
+        // skip the opening quote
+        self.pos += 1;
+        let start = self.pos;
+        let mut escaped = false;
+        loop {
+            match self.peek() {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    escaped = true;
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(b'"') | Some(b'\\') | Some(b'/') | Some(b'b') | Some(b'f')
+                            | Some(b'n') | Some(b'r') | Some(b't') => self.pos += 1,
+                        Some(b'u') => {
+                            let hex = self.bytes.get(self.pos + 1..self.pos + 5);
+                            if !hex.map(|h| h.iter().all(u8::is_ascii_hexdigit)).unwrap_or(false) {
+                                return Err(self.error("invalid unicode escape"));
+                            }
+                            self.pos += 5;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                Some(b) if b < 0x20 => return Err(self.error("control character in string")),
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+        let end = self.pos;
+        self.pos += 1;
+        self.push(Op::String, start, end, escaped);
+        Ok(())
+    }
+
+    fn literal(&mut self, lit: &'static [u8], op: Op) -> Result<(), TapeError> {
+        if !self.bytes[self.pos..].starts_with(lit) {
+            return Err(self.error("invalid literal"));
+        }
+        let start = self.pos;
+        self.pos += lit.len();
+        self.push(op, start, self.pos, false);
+        Ok(())
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self) -> Result<(), TapeError> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                self.digits();
+            }
+            _ => return Err(self.error("invalid number")),
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if self.digits() == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+        self.push(Op::Number, start, self.pos, false);
+        Ok(())
+    }
+}
+
+/// Decodes the escapes in the raw contents of a JSON string.
+///
+/// Returns `None` if an escape is invalid, including unpaired surrogates.
+pub fn unescape(raw: &str) -> Option<String> {
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(idx) = rest.find('\\') {
+        out.push_str(&rest[..idx]);
+        let esc = *rest.as_bytes().get(idx + 1)?;
+        if !esc.is_ascii() {
+            return None;
+        }
+        rest = &rest[idx + 2..];
+        let c = match esc {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+                let hi = hex4(rest)?;
+                rest = &rest[4..];
+                if (0xD800..0xDC00).contains(&hi) {
+                    if !rest.starts_with("\\u") {
+                        return None;
+                    }
+                    let lo = hex4(&rest[2..])?;
+                    if !(0xDC00..0xE000).contains(&lo) {
+                        return None;
+                    }
+                    rest = &rest[6..];
+                    char::from_u32(0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00))?
+                } else {
+                    char::from_u32(hi)?
+                }
+            }
+            _ => return None,
+        };
+        out.push(c);
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+fn hex4(s: &str) -> Option<u32> {
+    let digits = s.get(..4)?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok()
+}
+
+// =============================================================================
+//  Dispatch
+// =============================================================================
+
+/// The envelope of an RPC, read off a tape.
+#[derive(Debug, Clone, Copy)]
+pub struct Message<'t, 'a: 't> {
+    pub id: Option<Node<'t, 'a>>,
+    pub method: Node<'t, 'a>,
+    pub params: Node<'t, 'a>,
+}
+
+/// The names of `GestureType`'s variants.
+pub const GESTURE_TYPES: &[&str] = &["point_select", "toggle_sel", "range_select", "line_select",
+    "word_select", "multi_line_select", "multi_word_select"];
+
+/// Edit methods that take no params.
+pub const PLAIN_EDIT_METHODS: &[&str] = &["delete_forward", "delete_backward",
+    "delete_word_forward", "delete_word_backward", "delete_to_end_of_paragraph",
+    "delete_to_beginning_of_line", "insert_newline", "insert_tab", "move_up",
+    "move_up_and_modify_selection", "move_down", "move_down_and_modify_selection", "move_left",
+    "move_left_and_modify_selection", "move_right", "move_right_and_modify_selection",
+    "move_word_left", "move_word_left_and_modify_selection", "move_word_right",
+    "move_word_right_and_modify_selection", "move_to_beginning_of_paragraph",
+    "move_to_end_of_paragraph", "move_to_left_end_of_line",
+    "move_to_left_end_of_line_and_modify_selection", "move_to_right_end_of_line",
+    "move_to_right_end_of_line_and_modify_selection", "move_to_beginning_of_document",
+    "move_to_beginning_of_document_and_modify_selection", "move_to_end_of_document",
+    "move_to_end_of_document_and_modify_selection", "scroll_page_up",
+    "page_up_and_modify_selection", "scroll_page_down", "page_down_and_modify_selection",
+    "select_all", "add_selection_above", "add_selection_below", "yank", "transpose", "undo",
+    "redo", "debug_rewrap", "debug_print_spans", "cut", "copy", "unmark_text"];
+
+impl<'t, 'a> Message<'t, 'a> {
+    /// Reads the envelope of the message on `tape`.
+    pub fn from_tape(tape: &'t Tape<'a>) -> Result<Message<'t, 'a>, ParseError> {
+        Message::from_node(tape.root())
+    }
+
+    /// Reads the envelope of the message at `node`, such as an element of a
+    /// batch.
+    pub fn from_node(node: Node<'t, 'a>) -> Result<Message<'t, 'a>, ParseError> {
+        let method = node.get("method").filter(|m| m.op() == Op::String)
+            .ok_or_else(|| ParseError::Envelope("missing method".into()))?;
+        let params = node.get("params")
+            .ok_or_else(|| ParseError::Envelope("missing params".into()))?;
+        Ok(Message { id: node.get("id"), method, params })
+    }
+
+    /// The method, with edits qualified by their own method, as in
+    /// "edit/insert".
+    pub fn qualified_method(&self) -> String {
+        let method = self.method.as_str().unwrap_or_default();
+        match self.params.get("method").and_then(|m| m.as_str()) {
+            Some(inner) if method == "edit" => format!("edit/{}", inner),
+            _ => method.into_owned(),
+        }
+    }
+
+    /// Checks that this is a known method, with the params it requires.
+    ///
+    /// This does the same work as deserializing into the rpc2 types, short
+    /// of constructing them.
+    pub fn validate(&self) -> Result<(), ParseError> {
+        if let Some(id) = self.id {
+            if !(id.is_null() || id.op() == Op::String || id.as_u64().is_some()) {
+                return Err(ParseError::Envelope(
+                    "id must be a non-negative integer, a string, or null".into()));
+            }
+        }
+        let method = self.method.as_str().unwrap_or_default();
+        let p = &self.params;
+        let ok = match method.as_ref() {
+            "client_started" => p.op() == Op::Object,
+            "set_theme" => is_str(p.get("theme_name")),
+            "new_view" => p.get("file_path").map(|f| f.is_null() || f.op() == Op::String)
+                .unwrap_or(true),
+            "close_view" => is_str(p.get("view_id")),
+            "save" => is_str(p.get("view_id")) && is_str(p.get("file_path")),
+            "plugin" => is_str(p.get("command")) && is_str(p.get("view_id")),
+            "edit" => return validate_edit(p),
+            other => return Err(unknown(other.to_owned())),
+        };
+        if ok { Ok(()) } else { Err(malformed(method.into_owned())) }
+    }
+}
+
+fn unknown(method: String) -> ParseError {
+    let message = format!("unknown method '{}'", method);
+    ParseError::UnknownMethod { method, message }
+}
+
+fn malformed(method: String) -> ParseError {
+    ParseError::BadParams { method, message: "malformed params".into() }
+}
+
+fn is_str(node: Option<Node>) -> bool {
+    node.map(|n| n.op() == Op::String).unwrap_or(false)
+}
+
+fn is_u64(node: Option<Node>) -> bool {
+    node.and_then(|n| n.as_u64()).is_some()
+}
+
+fn is_bool(node: Option<Node>) -> bool {
+    node.and_then(|n| n.as_bool()).is_some()
+}
+
+/// Whether `node` is an array of between `min` and `max` integers.
+fn is_u64_array(node: Option<Node>, min: usize, max: usize) -> bool {
+    node.map(|n| {
+        n.op() == Op::Array && n.children().all(|c| c.as_u64().is_some())
+            && n.len() >= min && n.len() <= max
+    }).unwrap_or(false)
+}
+
+fn validate_edit(edit: &Node) -> Result<(), ParseError> {
+    let missing = |field: &str| {
+        ParseError::BadParams { method: "edit".into(), message: format!("missing {}", field) }
+    };
+    if !is_str(edit.get("view_id")) {
+        return Err(missing("view_id"));
+    }
+    // protocol 2's optional undo group and annotation
+    let annotated = edit.get("undo_group").map(|g| g.is_null() || g.as_u64().is_some())
+        .unwrap_or(true)
+        && edit.get("annotation").map(|a| a.is_null() || a.op() == Op::String).unwrap_or(true);
+    if !annotated {
+        return Err(malformed("edit".into()));
+    }
+    let method = edit.get("method").and_then(|m| m.as_str()).ok_or_else(|| missing("method"))?;
+    let params = edit.get("params");
+    // absent params, or empty params, are equivalent for methods without them
+    let empty = params.map(|p| (p.op() == Op::Object || p.op() == Op::Array) && p.is_empty())
+        .unwrap_or(true);
+    let get = |key| params.and_then(|p| p.get(key));
+
+    let ok = match method.as_ref() {
+        "insert" => is_str(get("chars")),
+        "scroll" | "request_lines" => is_u64_array(params, 2, 2),
+        "click" | "drag" => is_u64_array(params, 3, 4),
+        "gesture" => is_u64(get("line")) && is_u64(get("column"))
+            && get("ty").map(|t| GESTURE_TYPES.iter().any(|g| t.str_eq(g))).unwrap_or(false),
+        "goto_line" => is_u64(get("line")),
+        "find_next" => is_bool(get("wrap_around")) && is_bool(get("allow_same")),
+        "find_previous" => is_bool(get("wrap_around")),
+        "find" => is_bool(get("case_sensitive"))
+            && get("chars").map(|c| c.is_null() || c.op() == Op::String).unwrap_or(true),
+        "set_marked_text" => is_str(get("chars")) && is_u64_array(get("selection"), 2, 2)
+            && get("replacement").map(|r| r.is_null() || is_u64_array(Some(r), 2, 2))
+                .unwrap_or(true),
+        other if PLAIN_EDIT_METHODS.contains(&other) => empty,
+        other => return Err(unknown(format!("edit/{}", other))),
+    };
+    if ok { Ok(()) } else { Err(malformed(format!("edit/{}", method))) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {ANNOTATED_JSON, GESTURE_JSON, IDS_JSON, IME_JSON, TEST_JSON};
+
+    #[test]
+    fn scans_values() {
+        let tape = Tape::parse(r#" {"a": [1, -2.5e3, true], "b\"": null, "c": {}} "#).unwrap();
+        let ops = tape.entries().iter().map(|e| e.op).collect::<Vec<_>>();
+        assert_eq!(ops, vec![Op::Object, Op::String, Op::Array, Op::Number, Op::Number,
+                             Op::True, Op::String, Op::Null, Op::String, Op::Object]);
+        let root = tape.root();
+        assert_eq!(root.len(), 3);
+        assert_eq!(root.get("a").unwrap().len(), 3);
+        assert!(root.get("b\"").unwrap().is_null());
+        assert!(root.get("c").unwrap().is_empty());
+        assert!(root.get("d").is_none());
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        for bad in &["", "{", "[1,]", r#"{"a" 1}"#, r#"{"a":1,}"#, "01", "1.", "-", "tru",
+                     r#""\x""#, r#""\u12""#, "\"a\nb\"", "[1] 2", "{1:2}"] {
+            assert!(Tape::parse(bad).is_err(), "{}", bad);
+        }
+        let deep = format!("{}{}", "[".repeat(MAX_DEPTH + 1), "]".repeat(MAX_DEPTH + 1));
+        assert!(Tape::parse(&deep).is_err());
+    }
+
+    #[test]
+    fn strings() {
+        let json = r#"["plain", "tab\tquote\"", "é😀", "\ud83d"]"#;
+        let tape = Tape::parse(json).unwrap();
+        let strs = tape.root().children().map(|n| n.as_str()).collect::<Vec<_>>();
+        assert_eq!(strs[0], Some(Cow::Borrowed("plain")));
+        assert_eq!(strs[1].as_ref().map(|s| s.as_ref()), Some("tab\tquote\""));
+        assert_eq!(strs[2].as_ref().map(|s| s.as_ref()), Some("\u{e9}\u{1F600}"));
+        // an unpaired surrogate scans fine, but can't be decoded
+        assert_eq!(strs[3], None);
+    }
+
+    #[test]
+    fn validates_corpus() {
+        let corpus = TEST_JSON.lines().chain(GESTURE_JSON.lines()).chain(IDS_JSON.lines())
+            .chain(IME_JSON.lines()).chain(ANNOTATED_JSON.lines());
+        for line in corpus {
+            let tape = Tape::parse(line).unwrap();
+            let msg = Message::from_tape(&tape).unwrap();
+            assert_eq!(msg.validate(), Ok(()), "{}", line);
+        }
+    }
+
+    #[test]
+    fn validation_errors() {
+        let bad = [
+            r#"{"method":"frobnicate","params":{}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"frobnicate","params":[]}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"insert","params":{}}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"scroll","params":[1]}}"#,
+            r#"{"method":"edit","params":{"method":"undo","params":[]}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"undo","params":[1]}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","undo_group":-1,"method":"undo"}}"#,
+            r#"{"method":"edit","params":{"view_id":"v","annotation":7,"method":"undo"}}"#,
+            r#"{"id":-1,"method":"new_view","params":{}}"#,
+            r#"{"id":1.5,"method":"new_view","params":{}}"#,
+            r#"{"id":[1],"method":"new_view","params":{}}"#,
+        ];
+        for line in bad.iter() {
+            let tape = Tape::parse(line).unwrap();
+            assert!(Message::from_tape(&tape).and_then(|m| m.validate()).is_err(), "{}", line);
+        }
+    }
+}
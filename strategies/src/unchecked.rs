@@ -0,0 +1,187 @@
+//! The envelope, read without bounds checks, as a ceiling for the rest.
+//!
+//! The other strategies check every index and validate every byte, and
+//! it's hard to say how much of their time that is. `envelope` finds the
+//! method and the params by skipping over the rest of the message, reading
+//! with `get_unchecked` and slicing the method without checking that it's
+//! UTF-8, and the params are then handed to rpc5 as they would have been had
+//! rpc5 read the envelope itself. It trusts the envelope to be well-formed:
+//! what's outside the method and params isn't validated, so it accepts
+//! lines that the others don't. Anything it can't read quickly, such as a
+//! method with escapes, or a batch, is left to `Hand`.
+//!
+//! Every index it reads is below the length it's checked against in the
+//! enclosing loop, and every slice it makes starts just after an ASCII byte
+//! and ends at one, or at the end, so that a slice of valid UTF-8 is valid
+//! UTF-8. Debug builds assert both, and the tests run it over mutated
+//! messages.
+
+use std::str;
+
+/// The method and the raw params of the message in `json`, or `None` if it
+/// isn't an object with both, or the method has escapes.
+pub fn envelope(json: &str) -> Option<(&str, &str)> {
+    let bytes = json.as_bytes();
+    let mut pos = skip_ws(bytes, 0);
+    if byte(bytes, pos)? != b'{' {
+        return None;
+    }
+    let (mut method, mut params) = (None, None);
+    pos += 1;
+    loop {
+        pos = skip_ws(bytes, pos);
+        if byte(bytes, pos)? != b'"' {
+            return None;
+        }
+        let key_end = string_end(bytes, pos + 1)?;
+        let key = slice(bytes, pos + 1, key_end);
+        pos = skip_ws(bytes, key_end + 1);
+        if byte(bytes, pos)? != b':' {
+            return None;
+        }
+        let start = skip_ws(bytes, pos + 1);
+        pos = value_end(bytes, start)?;
+        match key {
+            b"method" => {
+                // a string without escapes, so the quotes are its bounds
+                let raw = slice(bytes, start, pos);
+                if raw.len() < 2 || raw[0] != b'"' || raw.contains(&b'\\') {
+                    return None;
+                }
+                method = Some(as_str(slice(bytes, start + 1, pos - 1)));
+            }
+            b"params" => params = Some(as_str(slice(bytes, start, pos))),
+            _ => {}
+        }
+        pos = skip_ws(bytes, pos);
+        match byte(bytes, pos)? {
+            b',' => pos += 1,
+            b'}' => break,
+            _ => return None,
+        }
+    }
+    Some((method?, params?))
+}
+
+fn byte(bytes: &[u8], pos: usize) -> Option<u8> {
+    if pos < bytes.len() {
+        // checked just above
+        Some(unsafe { *bytes.get_unchecked(pos) })
+    } else {
+        None
+    }
+}
+
+fn slice(bytes: &[u8], start: usize, end: usize) -> &[u8] {
+    debug_assert!(start <= end && end <= bytes.len());
+    unsafe { bytes.get_unchecked(start..end) }
+}
+
+/// `bytes` are a slice of a `str` that starts and ends on char boundaries.
+fn as_str(bytes: &[u8]) -> &str {
+    debug_assert!(str::from_utf8(bytes).is_ok());
+    unsafe { str::from_utf8_unchecked(bytes) }
+}
+
+fn skip_ws(bytes: &[u8], mut pos: usize) -> usize {
+    while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = byte(bytes, pos) {
+        pos += 1;
+    }
+    pos
+}
+
+/// The index of the quote that ends the string starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut pos = start;
+    while pos < bytes.len() {
+        match unsafe { *bytes.get_unchecked(pos) } {
+            b'\\' => pos += 2,
+            b'"' => return Some(pos),
+            _ => pos += 1,
+        }
+    }
+    None
+}
+
+/// The index just past the value starting at `start`. Containers are
+/// skipped by counting brackets outside of strings; nothing in them is
+/// validated.
+fn value_end(bytes: &[u8], start: usize) -> Option<usize> {
+    match byte(bytes, start)? {
+        b'"' => string_end(bytes, start + 1).map(|end| end + 1),
+        b'{' | b'[' => {
+            let mut depth = 0usize;
+            let mut pos = start;
+            while pos < bytes.len() {
+                match unsafe { *bytes.get_unchecked(pos) } {
+                    b'"' => pos = string_end(bytes, pos + 1)?,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(pos + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+            None
+        }
+        _ => {
+            let mut pos = start;
+            while let Some(b) = byte(bytes, pos) {
+                match b {
+                    b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r' => break,
+                    _ => pos += 1,
+                }
+            }
+            Some(pos)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::*;
+    use corpus::rng::XorShift;
+    use mutate::Mutation;
+    use tape::{Message, Tape};
+    use {GESTURE_JSON, IDS_JSON, TEST_JSON};
+
+    #[test]
+    fn agrees_with_tape() {
+        for line in TEST_JSON.lines().chain(GESTURE_JSON.lines()).chain(IDS_JSON.lines()) {
+            let tape = Tape::parse(line).unwrap();
+            let msg = Message::from_tape(&tape).unwrap();
+            let (method, params) = envelope(line).unwrap();
+            assert_eq!(Some(method), msg.method.as_raw_str(), "{}", line);
+            let expected = serde_json::from_str::<Value>(line).unwrap()["params"].take();
+            assert_eq!(serde_json::from_str::<Value>(params).unwrap(), expected, "{}", line);
+        }
+        assert_eq!(envelope(r#"{"method":"a\"b","params":{}}"#), None);
+        assert_eq!(envelope(r#"[{"method":"a","params":{}}]"#), None);
+        assert_eq!(envelope(r#" { "params" : [1, "]"] , "method" : "a" } "#),
+                   Some(("a", r#"[1, "]"]"#)));
+    }
+
+    #[test]
+    fn mutations_stay_in_bounds() {
+        let mut rng = XorShift::new(5);
+        for line in TEST_JSON.lines() {
+            for mutation in Mutation::all() {
+                for _ in 0..20 {
+                    if let Some(mutated) = mutation.apply(line, &mut rng) {
+                        // the debug assertions are the test
+                        let _ = envelope(&mutated);
+                    }
+                }
+            }
+        }
+        for cut in 0..TEST_JSON.lines().next().unwrap().len() {
+            let _ = envelope(&TEST_JSON.lines().next().unwrap()[..cut]);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! A SIMD-assisted unescaper for the contents of JSON strings.
+//!
+//! Large pastes arrive as `Insert.chars`, which is mostly long runs of plain
+//! text between escapes (`\n`, `\"`, `\/`). Finding the next backslash 16
+//! bytes at a time lets those runs be copied in bulk.
+
+/// Decodes the escapes in the raw contents of a JSON string.
+///
+/// This is equivalent to `tape::unescape`; it differs only in how it looks
+/// for backslashes.
+pub fn unescape(raw: &str) -> Option<String> {
+    let bytes = raw.as_bytes();
+    let mut out = String::with_capacity(raw.len());
+    let mut pos = 0;
+    while let Some(idx) = find_either(bytes, pos, b'\\', b'\\') {
+        // escapes are all ASCII, so these are always char boundaries
+        out.push_str(raw.get(pos..idx)?);
+        let (c, len) = decode_escape(bytes.get(idx + 1..)?)?;
+        out.push(c);
+        pos = idx + 1 + len;
+    }
+    out.push_str(raw.get(pos..)?);
+    Some(out)
+}
+
+/// Returns the index of the quote closing the JSON string that starts with
+/// the quote at `json[0]`.
+///
+/// This is all the work a parser has to do for a string it leaves escaped
+/// until it's used.
+pub fn string_end(json: &[u8]) -> Option<usize> {
+    let mut pos = 1;
+    loop {
+        let idx = find_either(json, pos, b'"', b'\\')?;
+        if json[idx] == b'"' {
+            return Some(idx);
+        }
+        pos = idx + 2;
+    }
+}
+
+/// Decodes the escape following a backslash, returning the char and the
+/// number of bytes consumed.
+fn decode_escape(bytes: &[u8]) -> Option<(char, usize)> {
+    let c = match *bytes.first()? {
+        b'"' => '"',
+        b'\\' => '\\',
+        b'/' => '/',
+        b'b' => '\u{8}',
+        b'f' => '\u{c}',
+        b'n' => '\n',
+        b'r' => '\r',
+        b't' => '\t',
+        b'u' => {
+            let hi = hex4(bytes.get(1..5)?)?;
+            if !(0xD800..0xDC00).contains(&hi) {
+                return char::from_u32(hi).map(|c| (c, 5));
+            }
+            if bytes.get(5..7) != Some(&b"\\u"[..]) {
+                return None;
+            }
+            let lo = hex4(bytes.get(7..11)?)?;
+            if !(0xDC00..0xE000).contains(&lo) {
+                return None;
+            }
+            return char::from_u32(0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00))
+                .map(|c| (c, 11));
+        }
+        _ => return None,
+    };
+    Some((c, 1))
+}
+
+fn hex4(digits: &[u8]) -> Option<u32> {
+    digits.iter().try_fold(0, |acc, &b| char::from(b).to_digit(16).map(|d| acc << 4 | d))
+}
+
+/// Finds the first `a` or `b` in `bytes` at or after `from`.
+#[cfg(target_arch = "x86_64")]
+fn find_either(bytes: &[u8], from: usize, a: u8, b: u8) -> Option<usize> {
+    use std::arch::x86_64::*;
+
+    let mut i = from;
+    // safe: SSE2 is part of the x86_64 baseline, and every load is unaligned
+    // and in bounds
+    unsafe {
+        let a = _mm_set1_epi8(a as i8);
+        let b = _mm_set1_epi8(b as i8);
+        while i + 16 <= bytes.len() {
+            let chunk = _mm_loadu_si128(bytes.as_ptr().add(i) as *const __m128i);
+            let hits = _mm_or_si128(_mm_cmpeq_epi8(chunk, a), _mm_cmpeq_epi8(chunk, b));
+            let mask = _mm_movemask_epi8(hits);
+            if mask != 0 {
+                return Some(i + mask.trailing_zeros() as usize);
+            }
+            i += 16;
+        }
+    }
+    bytes.get(i..)?.iter().position(|&c| c == a || c == b).map(|p| i + p)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn find_either(bytes: &[u8], from: usize, a: u8, b: u8) -> Option<usize> {
+    bytes.get(from..)?.iter().position(|&c| c == a || c == b).map(|p| from + p)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+    use tape;
+
+    #[test]
+    fn matches_serde() {
+        let long_run = "x".repeat(40);
+        let inputs = [
+            String::new(),
+            "plain".to_owned(),
+            r#"\/\/ Copyright\n\"quoted\"\t"#.to_owned(),
+            format!(r#"{}\n{}\\"#, long_run, long_run),
+            // escapes straddling the 16 byte chunks
+            format!(r#"{}\n{}é"#, &long_run[..15], &long_run[..14]),
+            "é😀 unescaped".to_owned(),
+        ];
+        for raw in inputs.iter() {
+            let expected = serde_json::from_str::<String>(&format!("\"{}\"", raw)).unwrap();
+            assert_eq!(unescape(raw).as_ref(), Some(&expected), "{}", raw);
+            assert_eq!(tape::unescape(raw).as_ref(), Some(&expected), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for raw in &[r#"\"#, r#"\x"#, r#"\u12"#, r#"\u12g4"#, r#"\ud83d"#, r#"\ud83dA"#,
+                     "\\é"] {
+            assert_eq!(unescape(raw), None, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn ends() {
+        let long_run = "y".repeat(40);
+        for &(json, end) in &[(r#""""#, Some(1)), (r#""ab" , "#, Some(3)),
+                              (r#""a\"b\\" x"#, Some(7)), (r#""\""#, None), (r#""abc"#, None)] {
+            assert_eq!(string_end(json.as_bytes()), end, "{}", json);
+        }
+        let json = format!(r#""{}\"{}" "#, long_run, long_run);
+        assert_eq!(string_end(json.as_bytes()), Some(json.len() - 2));
+    }
+}
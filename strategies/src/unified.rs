@@ -0,0 +1,134 @@
+//! One enum for everything a frontend sends core.
+//!
+//! rpc2 has two, `CoreNotification` and `CoreRequest`, and the serde
+//! strategy has to find out whether a message has an id before it knows
+//! which of them to parse it into. Here the methods of both are variants of
+//! one `CoreMessage`, so every message is parsed the same way, and its id,
+//! if it has one, is kept beside it in an `Envelope`. Whether a message
+//! should have had an id is checked afterwards, from the variant it turned
+//! out to be.
+//!
+//! The catch is edits, which are notifications or requests depending on
+//! their inner method, so that `Edit` has to look at the method before it
+//! can pick which of rpc2's edit enums to parse. The `unified` benches show
+//! whether that costs more than the branch on the id saves.
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde_json::{self, Value};
+
+use rpc2::{EditCommand, EditNotification, EditRequest, EmptyStruct, PluginNotification, RpcId,
+           ViewIdentifier};
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum CoreMessage {
+    Edit(EditCommand<Edit>),
+    Plugin(PluginNotification),
+    CloseView { view_id: ViewIdentifier },
+    Save { view_id: ViewIdentifier, file_path: String },
+    SetTheme { theme_name: String },
+    ClientStarted(EmptyStruct),
+    NewView { file_path: Option<String> },
+}
+
+/// An edit, of either kind.
+#[derive(Debug, PartialEq)]
+pub enum Edit {
+    Notification(EditNotification),
+    Request(EditRequest),
+}
+
+/// The edit methods that are requests.
+const REQUEST_METHODS: &[&str] = &["cut", "copy", "find"];
+
+impl<'de> Deserialize<'de> for Edit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        // EditCommand hands its inner edit over as a `Value`, so this is a
+        // second walk over the edit's params
+        let v = Value::deserialize(deserializer)?;
+        let is_request = v.get("method").and_then(Value::as_str)
+            .map_or(false, |m| REQUEST_METHODS.contains(&m));
+        if is_request {
+            EditRequest::deserialize(v).map(Edit::Request).map_err(de::Error::custom)
+        } else {
+            EditNotification::deserialize(v).map(Edit::Notification).map_err(de::Error::custom)
+        }
+    }
+}
+
+impl CoreMessage {
+    /// Whether the message is answered, and so has to have an id.
+    pub fn is_request(&self) -> bool {
+        match *self {
+            CoreMessage::NewView { .. } => true,
+            CoreMessage::Edit(ref edit) => match edit.cmd {
+                Edit::Request(_) => true,
+                Edit::Notification(_) => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// A message, and its id if it's a request.
+#[derive(Debug, PartialEq)]
+pub struct Envelope {
+    pub id: Option<RpcId>,
+    pub message: CoreMessage,
+}
+
+/// Parses one message from a `Value`, without branching on its id first.
+/// A request without an id, or a notification with one, is an error, as it
+/// is for rpc2.
+pub fn from_value(mut val: Value) -> Result<Envelope, serde_json::Error> {
+    let id = match val.as_object_mut().and_then(|obj| obj.remove("id")) {
+        Some(id) => Some(serde_json::from_value::<RpcId>(id)?),
+        None => None,
+    };
+    let message = serde_json::from_value::<CoreMessage>(val)?;
+    if id.is_some() != message.is_request() {
+        let expected = if message.is_request() { "a request" } else { "a notification" };
+        return Err(de::Error::custom(format!("{} {} an id", expected,
+                                             if id.is_some() { "with" } else { "without" })));
+    }
+    Ok(Envelope { id, message })
+}
+
+#[cfg(test)]
+mod tests {
+    use corpus::{ANNOTATED_JSON, IDS_JSON, TEST_JSON};
+    use sink::Sink;
+    use strategy::rpc2_from_value;
+
+    use super::*;
+
+    #[test]
+    fn same_as_split() {
+        for corpus in &[TEST_JSON, IDS_JSON, ANNOTATED_JSON] {
+            let (mut split, mut unified) = (Sink::new(), Sink::new());
+            for line in corpus.lines() {
+                let val = serde_json::from_str::<Value>(line).unwrap();
+                rpc2_from_value(val.clone(), &mut split).unwrap();
+                unified.consume(&from_value(val).unwrap().message);
+            }
+            assert_eq!(split, unified);
+        }
+    }
+
+    #[test]
+    fn checks_ids() {
+        let copy = r#"{"method":"edit","params":{"view_id":"view-id-1","method":"copy"}}"#;
+        let close = r#"{"id":1,"method":"close_view","params":{"view_id":"view-id-1"}}"#;
+        for bad in &[copy, close] {
+            assert!(from_value(serde_json::from_str(bad).unwrap()).is_err(), "{}", bad);
+        }
+        let find = r#"{"id":null,"method":"edit","params":{"view_id":"view-id-1","method":"find",
+                       "params":{"chars":null,"case_sensitive":true}}}"#;
+        let envelope = from_value(serde_json::from_str(find).unwrap()).unwrap();
+        assert_eq!(envelope.id, Some(RpcId::Null));
+        assert!(envelope.message.is_request());
+    }
+}
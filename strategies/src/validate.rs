@@ -0,0 +1,235 @@
+//! Checking a corpus before it's benchmarked.
+//!
+//! A line that isn't a message is an error for every strategy, and a run
+//! over a corpus with many of them times error paths rather than parsing,
+//! without saying much more than how many there were. `check` reads a
+//! corpus a line at a time and reports each line that isn't JSON, or that is
+//! but isn't a message of the corpus's direction as rpc2's types have it,
+//! with the line and column where serde_json gave up, and the text around
+//! them. Lines are counted in the file, blank ones included, although the
+//! blank ones are skipped, as `corpus::read_lines` skips them.
+//!
+//! serde_json often gives up on a message with the wrong params only at the
+//! end of the params, or of the message, since which variant of an enum it
+//! is can't be settled until both its tag and its content have been read.
+//! The column points there, and the error says what was wrong.
+
+use std::fmt;
+use std::io::{self, BufRead};
+
+use serde::de::DeserializeOwned;
+use serde_json::{self, value::RawValue, Value};
+
+use corpus::Direction;
+
+use error::ParseError;
+use peek;
+use rpc2;
+use strategy;
+
+/// How much of the line to show either side of the column.
+const CONTEXT: usize = 24;
+
+/// A line that isn't a message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invalid {
+    /// The line, counting from 1.
+    pub line: usize,
+    /// The byte of the line where the parser gave up, counting from 1.
+    pub column: usize,
+    pub error: ParseError,
+    /// The text around the column, cut short at either end if it was long.
+    pub fragment: String,
+    /// The number of characters of `fragment` before the column.
+    pub caret: usize,
+}
+
+/// The result of checking a corpus.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The number of lines, blank ones included.
+    pub lines: usize,
+    /// The number of lines that weren't blank.
+    pub messages: usize,
+    pub invalid: Vec<Invalid>,
+}
+
+/// Checks each line that `reader` reads as a message sent in `direction`.
+pub fn check<R: BufRead>(reader: R, direction: Direction) -> io::Result<Report> {
+    let mut report = Report::default();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        report.lines += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        report.messages += 1;
+        if let Err((column, error)) = check_line(&line, direction) {
+            let (fragment, caret) = fragment(&line, column);
+            report.invalid.push(Invalid { line: idx + 1, column, error, fragment, caret });
+        }
+    }
+    Ok(report)
+}
+
+/// Checks that `json` is a message sent in `direction`, or a batch of them,
+/// returning the column where it isn't.
+pub fn check_line(json: &str, direction: Direction) -> Result<(), (usize, ParseError)> {
+    let located = |json: &str, offset: usize, err: serde_json::Error| {
+        // serde_json's column is 0 when it doesn't know where
+        let column = offset + err.column().max(1);
+        (column, classify(json, direction, err))
+    };
+    if !strategy::is_batch(json) {
+        return check_message(json, direction).map_err(|e| located(json, 0, e));
+    }
+    let batch = serde_json::from_str::<Vec<&RawValue>>(json).map_err(|e| located(json, 0, e))?;
+    if batch.is_empty() {
+        return Err((1, ParseError::Envelope("empty batch".into())));
+    }
+    for msg in batch {
+        // each message is a slice of the line
+        let offset = msg.get().as_ptr() as usize - json.as_ptr() as usize;
+        check_message(msg.get(), direction).map_err(|e| located(msg.get(), offset, e))?;
+    }
+    Ok(())
+}
+
+fn check_message(json: &str, direction: Direction) -> serde_json::Result<()> {
+    fn parse<T: DeserializeOwned>(json: &str) -> serde_json::Result<()> {
+        serde_json::from_str::<T>(json).map(|_| ())
+    }
+
+    let request = peek::has_id(json);
+    match direction {
+        Direction::C2S if request => parse::<rpc2::CoreRequest>(json),
+        Direction::C2S => parse::<rpc2::CoreNotification>(json),
+        Direction::S2C => parse::<rpc2::ClientNotification>(json),
+        Direction::P2C => parse::<rpc2::PluginCoreNotification>(json),
+        Direction::C2P if request => parse::<rpc2::HostRequest>(json),
+        Direction::C2P => parse::<rpc2::HostNotification>(json),
+    }
+}
+
+/// The tape validator that tells what kind of error it was only knows
+/// what frontends send, so the other directions' errors are put down to
+/// the params, as the strategies for them do.
+pub fn classify(json: &str, direction: Direction, err: serde_json::Error) -> ParseError {
+    use serde_json::error::Category;
+    match (direction, err.classify()) {
+        (Direction::C2S, _) | (_, Category::Syntax) | (_, Category::Eof) | (_, Category::Io) => {
+            ParseError::from_json(json, err)
+        }
+        (_, Category::Data) => {
+            let method = serde_json::from_str::<Value>(json).ok()
+                .and_then(|val| val.get("method").and_then(Value::as_str).map(String::from))
+                .unwrap_or_default();
+            ParseError::BadParams { method, message: err.to_string() }
+        }
+    }
+}
+
+/// Up to `CONTEXT` bytes of `line` either side of `column`, on character
+/// boundaries, and the number of characters before the column.
+fn fragment(line: &str, column: usize) -> (String, usize) {
+    let at = column.saturating_sub(1).min(line.len());
+    let mut start = at.saturating_sub(CONTEXT);
+    while !line.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (at + CONTEXT).min(line.len());
+    while !line.is_char_boundary(end) {
+        end += 1;
+    }
+    let mut at = at;
+    while !line.is_char_boundary(at) {
+        at -= 1;
+    }
+    let mut fragment = String::new();
+    if start > 0 {
+        fragment.push('…');
+    }
+    fragment.push_str(&line[start..end]);
+    if end < line.len() {
+        fragment.push('…');
+    }
+    (fragment, (start > 0) as usize + line[start..at].chars().count())
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}:{}: {}", self.line, self.column, self.error)?;
+        writeln!(f, "    {}", self.fragment)?;
+        write!(f, "    {:>width$}", "^", width = self.caret + 1)
+    }
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.invalid.is_empty()
+    }
+
+    /// Prints up to `max` of the invalid lines, and how many there were.
+    pub fn print(&self, max: usize) {
+        for invalid in self.invalid.iter().take(max) {
+            println!("{}\n", invalid);
+        }
+        if self.invalid.len() > max {
+            println!("... and {} more\n", self.invalid.len() - max);
+        }
+        println!("{} of {} messages invalid", self.invalid.len(), self.messages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corpus::{self, TEST_JSON, TO_CLIENT_JSON};
+
+    #[test]
+    fn built_in() {
+        let report = check(TEST_JSON.as_bytes(), Direction::C2S).unwrap();
+        assert!(report.is_valid(), "{:?}", report.invalid);
+        assert_eq!(report.messages, corpus::read_lines(TEST_JSON.as_bytes()).unwrap().len());
+        assert!(check(TO_CLIENT_JSON.as_bytes(), Direction::S2C).unwrap().is_valid());
+        // the wrong direction
+        assert!(!check(TO_CLIENT_JSON.as_bytes(), Direction::C2S).unwrap().is_valid());
+    }
+
+    #[test]
+    fn locations() {
+        let corpus = [
+            r#"{"method":"client_started","params":{}}"#,
+            "",
+            r#"{"method":"set_theme","params":{"theme_name":"InspiredGitHub"}"#,
+            r#"[{"method":"client_started","params":{}},{"method":"frobnicate","params":{}}]"#,
+            r#"{"method":"edit","params":{"view_id":"v","method":"scroll","params":[1]}}"#,
+        ].join("\n");
+        let report = check(corpus.as_bytes(), Direction::C2S).unwrap();
+        assert_eq!((report.lines, report.messages), (5, 4));
+        let found = report.invalid.iter().map(|i| (i.line, i.error.kind())).collect::<Vec<_>>();
+        assert_eq!(found, [(3, "frame"), (4, "unknown_method"), (5, "bad_params")]);
+
+        // in the batch, the column is in the second message, and the caret
+        // under it
+        let batch = &report.invalid[1];
+        let line = corpus.lines().nth(3).unwrap();
+        assert!(batch.column > line.find("frobnicate").unwrap());
+        assert!(batch.fragment.starts_with('…'));
+        let under = batch.fragment.chars().nth(batch.caret).unwrap();
+        assert_eq!(under, line.as_bytes()[batch.column - 1] as char);
+        let shown = format!("4:{}: unknown method 'frobnicate'", batch.column);
+        assert!(batch.to_string().starts_with(&shown), "{}", batch);
+    }
+
+    #[test]
+    fn fragments() {
+        assert_eq!(fragment("{}", 2), ("{}".to_owned(), 1));
+        let line = format!("{}é{}", "a".repeat(30), "b".repeat(30));
+        let (text, caret) = fragment(&line, 32);
+        assert_eq!(text.chars().nth(caret), Some('é'));
+        assert!(text.starts_with('…') && text.ends_with('…'));
+        // past the end, as at an unexpected EOF
+        assert_eq!(fragment("{", 2), ("{".to_owned(), 1));
+    }
+}
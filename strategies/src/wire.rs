@@ -0,0 +1,391 @@
+//! How the modules' serializers disagree about what a message looks like.
+//!
+//! rpc2 writes an edit by turning its command into a `Value` and inserting
+//! the view id and protocol 2's fields, so an edit's keys come out sorted;
+//! `EditCommand::flattened` writes them in the order it has them, and rpc3's
+//! derive in declaration order, without the view id of the edits that don't
+//! keep one. A parser doesn't care, but anything comparing bytes does: a
+//! cache keyed by a message's text, or a recorded session replayed against
+//! another core. `run` writes each notification out again with every
+//! module, and reports where each output differs from rpc2's, structurally:
+//! members in another order, missing or extra, nulls left out or written,
+//! numbers and escapes written another way, and values that aren't the same
+//! at all. Requests are skipped, as not every module can write one.
+//!
+//! Compared canonically instead (see `canonical`), order, numbers and
+//! escapes no longer count, and what's left is where the modules disagree
+//! about the message itself.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use serde_json;
+
+use canonical;
+use peek;
+use rpc2;
+use rpc3;
+use rpc4;
+use tape::{Node, Op, Tape};
+
+/// A module's serializer.
+#[derive(Clone, Copy)]
+pub struct Writer {
+    pub name: &'static str,
+    write: fn(&str) -> serde_json::Result<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// The same members, in another order.
+    Order,
+    Missing,
+    Extra,
+    /// A member that's null in the reference, left out here.
+    MissingNull,
+    /// A null member here, that the reference leaves out.
+    ExtraNull,
+    /// The same number, written another way, such as `1.0` for `1`.
+    Number,
+    /// The same string, escaped another way, such as `\/` for `/`.
+    Escape,
+    /// A different value, or a different type.
+    Value,
+}
+
+pub const KINDS: [Kind; 8] = [
+    Kind::Order, Kind::Missing, Kind::Extra, Kind::MissingNull, Kind::ExtraNull, Kind::Number,
+    Kind::Escape, Kind::Value,
+];
+
+/// One way an output differs from the reference's. `path` is where, as in
+/// `$.params.view_id`; `expected` and `found` are the reference's and this
+/// output's sides of it, empty if there's nothing there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub path: String,
+    pub kind: Kind,
+    pub expected: String,
+    pub found: String,
+}
+
+/// How each writer's output differs from the reference's.
+#[derive(Debug)]
+pub struct Report {
+    pub reference: &'static str,
+    pub writers: Vec<&'static str>,
+    /// Notifications compared.
+    pub messages: usize,
+    /// Indexed by writer, with the index of the line each was found on.
+    pub differences: Vec<Vec<(usize, Difference)>>,
+    /// Lines a writer couldn't parse, or write, with the writer and why.
+    pub errors: Vec<(usize, &'static str, String)>,
+}
+
+/// The writers, rpc2's first, as the reference.
+pub fn writers() -> Vec<Writer> {
+    vec![
+        Writer { name: "rpc2", write: write_rpc2 },
+        Writer { name: "rpc2_flattened", write: write_rpc2_flattened },
+        Writer { name: "rpc3", write: write_rpc3 },
+        Writer { name: "rpc4", write: write_rpc4 },
+    ]
+}
+
+fn write_rpc2(json: &str) -> serde_json::Result<String> {
+    serde_json::to_string(&serde_json::from_str::<rpc2::CoreNotification>(json)?)
+}
+
+fn write_rpc2_flattened(json: &str) -> serde_json::Result<String> {
+    #[derive(Serialize)]
+    struct Flat<'a> {
+        method: &'static str,
+        params: rpc2::FlatEditCommand<'a, rpc2::EditNotification>,
+    }
+
+    match serde_json::from_str::<rpc2::CoreNotification>(json)? {
+        rpc2::CoreNotification::Edit(edit) => {
+            serde_json::to_string(&Flat { method: "edit", params: edit.flattened() })
+        }
+        other => serde_json::to_string(&other),
+    }
+}
+
+fn write_rpc3(json: &str) -> serde_json::Result<String> {
+    serde_json::to_string(&serde_json::from_str::<rpc3::CoreNotification>(json)?)
+}
+
+fn write_rpc4(json: &str) -> serde_json::Result<String> {
+    serde_json::to_string(&rpc4::Envelope::parse(json)?)
+}
+
+/// Writes each notification in `lines` with every writer, and compares the
+/// outputs with the reference's, rewritten canonically first if `canonical`
+/// is set.
+pub fn run(lines: &[String], canonical: bool) -> Report {
+    let writers = writers();
+    let (reference, others) = writers.split_first().expect("no writers");
+    let mut report = Report {
+        reference: reference.name,
+        writers: others.iter().map(|w| w.name).collect(),
+        messages: 0,
+        differences: vec![Vec::new(); others.len()],
+        errors: Vec::new(),
+    };
+    for (idx, line) in lines.iter().enumerate() {
+        if peek::has_id(line) {
+            continue;
+        }
+        report.messages += 1;
+        let expected = match (reference.write)(line) {
+            Ok(json) => json,
+            Err(e) => {
+                report.errors.push((idx, reference.name, e.to_string()));
+                continue;
+            }
+        };
+        for (writer, differences) in others.iter().zip(report.differences.iter_mut()) {
+            let found = match (writer.write)(line) {
+                Ok(json) => json,
+                Err(e) => {
+                    report.errors.push((idx, writer.name, e.to_string()));
+                    continue;
+                }
+            };
+            let diffs = if canonical {
+                canonical::canonicalize(&expected)
+                    .and_then(|e| Ok((e, canonical::canonicalize(&found)?)))
+                    .map_err(|e| e.to_string())
+                    .and_then(|(e, f)| diff(&e, &f))
+            } else {
+                diff(&expected, &found)
+            };
+            match diffs {
+                Ok(diffs) => differences.extend(diffs.into_iter().map(|d| (idx, d))),
+                Err(e) => report.errors.push((idx, writer.name, e)),
+            }
+        }
+    }
+    report
+}
+
+/// The differences between two JSON texts.
+pub fn diff(expected: &str, found: &str) -> Result<Vec<Difference>, String> {
+    let expected = Tape::parse(expected).map_err(|e| e.to_string())?;
+    let found = Tape::parse(found).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    diff_nodes(expected.root(), found.root(), "$", &mut out);
+    Ok(out)
+}
+
+fn diff_nodes(expected: Node, found: Node, path: &str, out: &mut Vec<Difference>) {
+    let difference = |kind, expected: &Node, found: &Node| Difference {
+        path: path.to_owned(),
+        kind,
+        expected: describe(expected),
+        found: describe(found),
+    };
+    match (expected.op(), found.op()) {
+        (Op::Object, Op::Object) => diff_objects(expected, found, path, out),
+        (Op::Array, Op::Array) => {
+            if expected.len() != found.len() {
+                out.push(difference(Kind::Value, &expected, &found));
+            }
+            for (idx, (e, f)) in expected.children().zip(found.children()).enumerate() {
+                diff_nodes(e, f, &format!("{}[{}]", path, idx), out);
+            }
+        }
+        (Op::String, Op::String) => {
+            if expected.as_str() != found.as_str() {
+                out.push(difference(Kind::Value, &expected, &found));
+            } else if expected.as_raw_str() != found.as_raw_str() {
+                out.push(difference(Kind::Escape, &expected, &found));
+            }
+        }
+        (Op::Number, Op::Number) => {
+            let (e, f) = (expected.as_raw_number(), found.as_raw_number());
+            if e != f {
+                let parse = |n: Option<&str>| n.and_then(|n| n.parse::<f64>().ok());
+                let kind = if parse(e) == parse(f) { Kind::Number } else { Kind::Value };
+                out.push(difference(kind, &expected, &found));
+            }
+        }
+        (e, f) if e == f => (),
+        _ => out.push(difference(Kind::Value, &expected, &found)),
+    }
+}
+
+fn diff_objects(expected: Node, found: Node, path: &str, out: &mut Vec<Difference>) {
+    let (expected, found) = (members(expected), members(found));
+    for &(ref key, ref value) in &expected {
+        if !has(&found, key) {
+            let kind = if value.is_null() { Kind::MissingNull } else { Kind::Missing };
+            out.push(Difference {
+                path: format!("{}.{}", path, key),
+                kind,
+                expected: describe(value),
+                found: String::new(),
+            });
+        }
+    }
+    for &(ref key, ref value) in &found {
+        if !has(&expected, key) {
+            let kind = if value.is_null() { Kind::ExtraNull } else { Kind::Extra };
+            out.push(Difference {
+                path: format!("{}.{}", path, key),
+                kind,
+                expected: String::new(),
+                found: describe(value),
+            });
+        }
+    }
+
+    let (expected_order, found_order) = (shared(&expected, &found), shared(&found, &expected));
+    if expected_order != found_order {
+        out.push(Difference {
+            path: path.to_owned(),
+            kind: Kind::Order,
+            expected: expected_order.join(","),
+            found: found_order.join(","),
+        });
+    }
+    for &(ref key, e) in &expected {
+        if let Some(&(_, f)) = found.iter().find(|m| m.0 == *key) {
+            diff_nodes(e, f, &format!("{}.{}", path, key), out);
+        }
+    }
+}
+
+fn members<'t, 'a>(object: Node<'t, 'a>) -> Vec<(Cow<'a, str>, Node<'t, 'a>)> {
+    let mut children = object.children();
+    let mut members = Vec::new();
+    while let (Some(key), Some(value)) = (children.next(), children.next()) {
+        members.push((key.as_str().unwrap_or_default(), value));
+    }
+    members
+}
+
+fn has(members: &[(Cow<str>, Node)], key: &str) -> bool {
+    members.iter().any(|m| m.0 == key)
+}
+
+/// The keys of `members` that `other` has too, in order.
+fn shared<'m>(members: &'m [(Cow<str>, Node)], other: &[(Cow<str>, Node)]) -> Vec<&'m str> {
+    members.iter().map(|m| &*m.0).filter(|key| has(other, key)).collect()
+}
+
+/// A short description of a value: scalars as written, containers by size.
+fn describe(node: &Node) -> String {
+    match node.op() {
+        Op::Object => format!("an object of {}", node.len()),
+        Op::Array => format!("an array of {}", node.len()),
+        Op::String => format!("\"{}\"", node.as_raw_str().unwrap_or_default()),
+        Op::Number => node.as_raw_number().unwrap_or_default().to_owned(),
+        Op::True => "true".into(),
+        Op::False => "false".into(),
+        Op::Null => "null".into(),
+    }
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Kind::Order => "order",
+            Kind::Missing => "missing",
+            Kind::Extra => "extra",
+            Kind::MissingNull => "missing_null",
+            Kind::ExtraNull => "extra_null",
+            Kind::Number => "number",
+            Kind::Escape => "escape",
+            Kind::Value => "value",
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.path, self.kind.name())?;
+        match (self.expected.is_empty(), self.found.is_empty()) {
+            (false, false) => write!(f, ": {} -> {}", self.expected, self.found),
+            (false, true) => write!(f, ": {}", self.expected),
+            (true, false) => write!(f, ": {}", self.found),
+            (true, true) => Ok(()),
+        }
+    }
+}
+
+impl Report {
+    /// Prints how many differences of each kind each writer has, and up to
+    /// `examples` of each.
+    pub fn print(&self, lines: &[String], examples: usize) {
+        println!("{} notifications, compared with {}", self.messages, self.reference);
+        for (writer, differences) in self.writers.iter().zip(self.differences.iter()) {
+            let mut messages = differences.iter().map(|d| d.0).collect::<Vec<_>>();
+            messages.dedup();
+            println!("\n{}: {} differences, in {} notifications",
+                     writer, differences.len(), messages.len());
+            for kind in &KINDS {
+                let of_kind = differences.iter().filter(|d| d.1.kind == *kind)
+                    .collect::<Vec<_>>();
+                if of_kind.is_empty() {
+                    continue;
+                }
+                println!("    {:<14}{}", kind.name(), of_kind.len());
+                for &&(idx, ref difference) in of_kind.iter().take(examples) {
+                    println!("        line {}: {}", idx + 1, difference);
+                }
+            }
+        }
+        for &(idx, writer, ref e) in &self.errors {
+            println!("\n{} failed on line {}: {}\n    {}", writer, idx + 1, e, lines[idx]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corpus::generate::Preset;
+    use corpus::{ANNOTATED_JSON, TEST_JSON};
+
+    #[test]
+    fn differences() {
+        let diffs = diff(r#"{"a":1,"b":null,"c":[1,"\/"],"d":"x"}"#,
+                         r#"{"c":[1.0,"/",3],"a":1,"d":"y","e":null}"#).unwrap();
+        let kinds = diffs.iter().map(|d| (d.path.as_str(), d.kind)).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![("$.b", Kind::MissingNull),
+                               ("$.e", Kind::ExtraNull),
+                               ("$", Kind::Order),
+                               ("$.c", Kind::Value),
+                               ("$.c[0]", Kind::Number),
+                               ("$.c[1]", Kind::Escape),
+                               ("$.d", Kind::Value)]);
+        assert_eq!(diffs[2].to_string(), "$ order: a,c,d -> c,a,d");
+        assert!(diff("[]", "[]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn modules() {
+        let mut lines = TEST_JSON.lines().chain(ANNOTATED_JSON.lines()).map(String::from)
+            .collect::<Vec<_>>();
+        lines.extend(Preset::CursorBurst.generate(200, 1));
+        let report = run(&lines, false);
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        let differences = |writer| {
+            let idx = report.writers.iter().position(|w| *w == writer).unwrap();
+            &report.differences[idx]
+        };
+        // the same object, in another order
+        assert!(differences("rpc2_flattened").iter().all(|d| d.1.kind == Kind::Order));
+        // move_up and the like, without a view id
+        assert!(differences("rpc3").iter()
+                .any(|d| d.1.path == "$.params.view_id" && d.1.kind == Kind::Missing));
+        // the params as they came in
+        assert!(differences("rpc4").iter().any(|d| d.1.kind == Kind::Order));
+
+        let report = run(&lines, true);
+        assert!(report.differences.iter().flat_map(|d| d.iter())
+                .all(|d| d.1.kind != Kind::Order && d.1.kind != Kind::Escape));
+        assert!(report.differences[0].is_empty());
+    }
+}